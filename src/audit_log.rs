@@ -0,0 +1,111 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::types::ReservationID;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use sui_types::base_types::SuiAddress;
+use sui_types::digests::TransactionDigest;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// A transaction the sponsor signed.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub sponsor: SuiAddress,
+    pub transaction_digest: TransactionDigest,
+    pub gas_budget: u64,
+    /// When the transaction was signed, in milliseconds since the epoch.
+    pub timestamp_ms: u64,
+    pub reservation_id: ReservationID,
+}
+
+/// Durable record of every sponsor signature, for compliance. Unlike the tracing logs, every
+/// entry must be recorded: a transaction whose entry cannot be recorded is not submitted.
+#[async_trait::async_trait]
+pub trait AuditLog: Send + Sync {
+    async fn record(&self, entry: AuditLogEntry) -> anyhow::Result<()>;
+}
+
+/// Records nothing.
+pub struct NoopAuditLog;
+
+#[async_trait::async_trait]
+impl AuditLog for NoopAuditLog {
+    async fn record(&self, _entry: AuditLogEntry) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends each entry to a file as a line of JSON, and syncs the file before returning so that
+/// recorded entries survive a crash.
+pub struct FileAuditLog {
+    file: Mutex<File>,
+}
+
+impl FileAuditLog {
+    /// Opens the file for appending, creating it if needed. Existing entries are kept.
+    pub fn new(path: &Path) -> anyhow::Result<Arc<Self>> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Arc::new(Self {
+            file: Mutex::new(File::from_std(file)),
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLog for FileAuditLog {
+    async fn record(&self, entry: AuditLogEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().await;
+        file.write_all(&line).await?;
+        file.sync_data().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(reservation_id: ReservationID) -> AuditLogEntry {
+        AuditLogEntry {
+            sponsor: SuiAddress::random_for_testing_only(),
+            transaction_digest: TransactionDigest::random(),
+            gas_budget: 1000,
+            timestamp_ms: 1,
+            reservation_id,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let entries = vec![entry(1), entry(2)];
+        let audit_log = FileAuditLog::new(&path).unwrap();
+        for entry in &entries {
+            audit_log.record(entry.clone()).await.unwrap();
+        }
+        // Reopening the log appends to the existing entries.
+        let reopened = FileAuditLog::new(&path).unwrap();
+        let third = entry(3);
+        reopened.record(third.clone()).await.unwrap();
+
+        let recorded: Vec<AuditLogEntry> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            recorded,
+            vec![entries[0].clone(), entries[1].clone(), third]
+        );
+    }
+}
@@ -64,7 +64,7 @@ impl BenchmarkMode {
                     let now = Instant::now();
                     let budget = rng.gen_range(1_000_000u64..100_000_000u64);
                     let result = client
-                        .reserve_gas(sponsor, budget, reserve_duration_sec)
+                        .reserve_gas(sponsor, budget, reserve_duration_sec, None)
                         .await;
                     let (sponsor, reservation_id, gas_coins) = match result {
                         Ok(r) => r,
@@ -6,7 +6,10 @@ use std::path::PathBuf;
 use sui_config::Config;
 use sui_gas_station::benchmarks::kms_stress::run_kms_stress_test;
 use sui_gas_station::benchmarks::BenchmarkMode;
-use sui_gas_station::config::{GasPoolStorageConfig, GasStationConfig, TxSignerConfig};
+use sui_gas_station::config::{
+    default_coin_selection_top_k, CoinSelectionStrategy, GasPoolStorageConfig, GasStationConfig,
+    TxSignerConfig,
+};
 use sui_gas_station::rpc::client::GasPoolRpcClient;
 use sui_types::crypto::get_account_key_pair;
 
@@ -77,6 +80,12 @@ pub enum CliCommand {
         #[clap(long, help = "Full URL of the station RPC server")]
         station_rpc_url: String,
     },
+    /// Only checks the bearer secret and the signer, without reserving gas from the pool, so it
+    /// can be run often.
+    CheckStationSignerHealth {
+        #[clap(long, help = "Full URL of the station RPC server")]
+        station_rpc_url: String,
+    },
     GetStationVersion {
         #[clap(long, help = "Full URL of the station RPC server")]
         station_rpc_url: String,
@@ -120,6 +129,9 @@ impl ToolCommand {
                     signer_config,
                     gas_pool_config: GasPoolStorageConfig::Redis {
                         redis_url: "redis://127.0.0.1".to_string(),
+                        coin_selection_strategy: CoinSelectionStrategy::default(),
+                        coin_selection_top_k: default_coin_selection_top_k(),
+                        max_pool_coins_per_sponsor: None,
                     },
                     ..Default::default()
                 };
@@ -143,6 +155,18 @@ impl ToolCommand {
                         }
                     }
                 }
+                CliCommand::CheckStationSignerHealth { station_rpc_url } => {
+                    let station_client = GasPoolRpcClient::new(station_rpc_url);
+                    match station_client.debug_signer_health_check().await {
+                        Err(e) => {
+                            eprintln!("Station signer is not healthy: {}", e);
+                            std::process::exit(1);
+                        }
+                        Ok(_) => {
+                            println!("Station signer is healthy");
+                        }
+                    }
+                }
                 CliCommand::GetStationVersion { station_rpc_url } => {
                     let station_client = GasPoolRpcClient::new(station_rpc_url);
                     let version = station_client.version().await.unwrap();
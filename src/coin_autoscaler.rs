@@ -0,0 +1,516 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::CoinAutoscalerConfig;
+use crate::metrics::GasPoolCoreMetrics;
+use crate::retry_forever;
+use crate::storage::{Storage, DEFAULT_ADD_COINS_BATCH_SIZE};
+use crate::sui_client::SuiClient;
+use crate::tx_signer::TxSigner;
+use crate::types::{ExecutedTransaction, GasCoin};
+use std::sync::Arc;
+use std::time::Duration;
+use sui_json_rpc_types::{SuiTransactionBlockEffectsAPI, SuiTransactionBlockResponseOptions};
+use sui_types::base_types::SuiAddress;
+use sui_types::programmable_transaction_builder::ProgrammableTransactionBuilder;
+use sui_types::transaction::{Argument, Transaction, TransactionData};
+use tap::TapFallible;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// Coins reserved by the autoscaler are given back to the pool automatically after this long,
+/// in case the autoscaler crashes in the middle of an action.
+const ACTION_RESERVATION_DURATION_MS: u64 = 60 * 1000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AutoscalerAction {
+    /// Split coins into `split_count` coins of roughly the target balance.
+    Split { split_count: u64 },
+    /// Merge `merge_count` coins into a single one.
+    Merge { merge_count: u64 },
+}
+
+impl AutoscalerAction {
+    /// The value reported through the `autoscaler_last_action` metric.
+    fn metric_value(action: Option<Self>) -> i64 {
+        match action {
+            None => 0,
+            Some(AutoscalerAction::Split { .. }) => 1,
+            Some(AutoscalerAction::Merge { .. }) => 2,
+        }
+    }
+
+    /// Decides what to do given the current number of available coins of a sponsor.
+    /// Returns None if the coin count is within the tolerated range of the target.
+    pub fn decide(config: &CoinAutoscalerConfig, coin_count: u64) -> Option<Self> {
+        let target = config.target_coin_count;
+        let tolerance = target * config.tolerance_percent / 100;
+        if coin_count + tolerance < target {
+            let missing = target - coin_count;
+            // Plus one since the coin being split is kept as one of the resulting coins.
+            let split_count = missing.min(config.max_coins_per_action) + 1;
+            Some(AutoscalerAction::Split { split_count })
+        } else if coin_count > target + tolerance {
+            let excess = coin_count - target;
+            // Plus one since the excess coins are merged into one of the coins.
+            let merge_count = (excess + 1).min(config.max_coins_per_action);
+            Some(AutoscalerAction::Merge { merge_count })
+        } else {
+            None
+        }
+    }
+}
+
+/// Periodically compares the number of available coins of each sponsor against a target, and
+/// splits or merges coins to converge toward it. It takes at most one action per sponsor per
+/// interval, and the tolerated range around the target avoids flipping between split and merge.
+/// It only ever operates on coins it reserved from the pool itself, so coins that are reserved by
+/// clients or in the middle of an execution are never touched.
+pub struct CoinAutoscaler {
+    _task_handle: JoinHandle<()>,
+    // This is always Some. It is None only after the drop method is called.
+    cancel_sender: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl Drop for CoinAutoscaler {
+    fn drop(&mut self) {
+        if let Some(cancel_sender) = self.cancel_sender.take() {
+            let _ = cancel_sender.send(());
+        }
+    }
+}
+
+impl CoinAutoscaler {
+    pub fn start(
+        sui_client: SuiClient,
+        storage: Arc<dyn Storage>,
+        signer: Arc<dyn TxSigner>,
+        config: CoinAutoscalerConfig,
+        metrics: Arc<GasPoolCoreMetrics>,
+    ) -> Self {
+        let (cancel_sender, cancel_receiver) = tokio::sync::oneshot::channel();
+        let env = AutoscalerEnv {
+            sui_client,
+            storage,
+            signer,
+            config,
+            metrics,
+        };
+        let _task_handle = tokio::spawn(env.run(cancel_receiver));
+        Self {
+            _task_handle,
+            cancel_sender: Some(cancel_sender),
+        }
+    }
+}
+
+struct AutoscalerEnv {
+    sui_client: SuiClient,
+    storage: Arc<dyn Storage>,
+    signer: Arc<dyn TxSigner>,
+    config: CoinAutoscalerConfig,
+    metrics: Arc<GasPoolCoreMetrics>,
+}
+
+impl AutoscalerEnv {
+    async fn run(self, mut cancel_receiver: tokio::sync::oneshot::Receiver<()>) {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(self.config.interval_sec)) => {}
+                _ = &mut cancel_receiver => {
+                    info!("Coin autoscaler task is cancelled");
+                    break;
+                }
+            }
+            for sponsor in self.signer.get_addresses() {
+                if let Err(err) = self.run_once(sponsor).await {
+                    error!(?sponsor, "Coin autoscaler failed: {:?}", err);
+                }
+            }
+        }
+    }
+
+    async fn run_once(&self, sponsor: SuiAddress) -> anyhow::Result<()> {
+        let coin_count = self.storage.get_available_coin_count(sponsor).await? as u64;
+        let total_balance = self.storage.get_available_coin_total_balance(sponsor).await;
+        let sponsor_str = sponsor.to_string();
+        self.metrics
+            .autoscaler_target_coin_count
+            .with_label_values(&[&sponsor_str])
+            .set(self.config.target_coin_count as i64);
+        self.metrics
+            .autoscaler_current_coin_count
+            .with_label_values(&[&sponsor_str])
+            .set(coin_count as i64);
+        self.metrics
+            .autoscaler_average_coin_balance
+            .with_label_values(&[&sponsor_str])
+            .set(total_balance.checked_div(coin_count).unwrap_or_default() as i64);
+
+        let action = AutoscalerAction::decide(&self.config, coin_count);
+        self.metrics
+            .autoscaler_last_action
+            .with_label_values(&[&sponsor_str])
+            .set(AutoscalerAction::metric_value(action));
+        let Some(action) = action else {
+            debug!(?sponsor, "Coin count {} is within target range", coin_count);
+            return Ok(());
+        };
+        info!(
+            ?sponsor,
+            "Coin count is {} while the target is {}. Taking action: {:?}",
+            coin_count,
+            self.config.target_coin_count,
+            action
+        );
+        let split_count = match action {
+            AutoscalerAction::Split { split_count } => split_count,
+            AutoscalerAction::Merge { merge_count } => {
+                let average_balance = total_balance.checked_div(coin_count).unwrap_or_default();
+                return self
+                    .merge_in_batches(sponsor, merge_count, average_balance)
+                    .await;
+            }
+        };
+        let (reservation_id, coins) = self
+            .storage
+            .reserve_gas_coins(
+                sponsor,
+                self.config.split_coin_balance() * split_count,
+                ACTION_RESERVATION_DURATION_MS,
+            )
+            .await?;
+        self.storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await?;
+        let (updated_coins, _) = self.execute_action(sponsor, action, &coins).await;
+        self.release_coins(updated_coins).await;
+        Ok(())
+    }
+
+    /// Merges `merge_count` coins into one, in transactions of at most `merge_batch_size` coins.
+    /// The coin each batch merges into is carried over as the first coin of the next batch, so
+    /// that the whole run still ends up with a single coin. A failed batch stops the run, and
+    /// its coins go back to the pool as they are on chain.
+    async fn merge_in_batches(
+        &self,
+        sponsor: SuiAddress,
+        merge_count: u64,
+        average_balance: u64,
+    ) -> anyhow::Result<()> {
+        let sponsor_str = sponsor.to_string();
+        let batches = merge_batches(merge_count, self.config.merge_batch_size);
+        let mut remaining = merge_count;
+        let mut carried_coin: Option<GasCoin> = None;
+        for new_coin_count in batches {
+            self.metrics
+                .autoscaler_coins_remaining_to_merge
+                .with_label_values(&[&sponsor_str])
+                .set(remaining as i64);
+            let reserved = self
+                .storage
+                .reserve_gas_coins_with_max_coins(
+                    sponsor,
+                    average_balance * new_coin_count,
+                    new_coin_count as usize,
+                    ACTION_RESERVATION_DURATION_MS,
+                )
+                .await;
+            let (reservation_id, new_coins) = match reserved {
+                Ok(reserved) => reserved,
+                Err(err) => {
+                    self.release_coins(carried_coin.into_iter().collect()).await;
+                    return Err(err);
+                }
+            };
+            if let Err(err) = self
+                .storage
+                .ready_for_execution(sponsor, reservation_id)
+                .await
+            {
+                // The coins are still part of the reservation, which expires on its own.
+                self.release_coins(carried_coin.into_iter().collect()).await;
+                return Err(err);
+            }
+            remaining = remaining.saturating_sub(new_coins.len() as u64);
+            let coins: Vec<_> = carried_coin.take().into_iter().chain(new_coins).collect();
+            let (updated_coins, succeeded) = self
+                .execute_action(
+                    sponsor,
+                    AutoscalerAction::Merge {
+                        merge_count: coins.len() as u64,
+                    },
+                    &coins,
+                )
+                .await;
+            if !succeeded || updated_coins.len() != 1 {
+                self.release_coins(updated_coins).await;
+                break;
+            }
+            carried_coin = updated_coins.into_iter().next();
+        }
+        self.release_coins(carried_coin.into_iter().collect()).await;
+        self.metrics
+            .autoscaler_coins_remaining_to_merge
+            .with_label_values(&[&sponsor_str])
+            .set(0);
+        Ok(())
+    }
+
+    async fn release_coins(&self, coins: Vec<GasCoin>) {
+        if coins.is_empty() {
+            return;
+        }
+        // Like in the gas pool core, the coins must be released regardless of the outcome,
+        // since ready_for_execution took them out of the auto-release mechanism.
+        for batch in coins.chunks(DEFAULT_ADD_COINS_BATCH_SIZE) {
+            let not_added = retry_forever!(async {
+                self.storage
+                    .add_new_coins(batch.to_vec())
+                    .await
+                    .tap_err(|err| error!("Failed to call add_new_coins on storage: {:?}", err))
+            })
+            .unwrap();
+            // A split can create more coins than the pool has room for. The storage counts them
+            // in num_coins_over_max_pool_size, but they are no longer tracked by the pool.
+            if !not_added.is_empty() {
+                warn!(
+                    "Pool is full, left out {} coins released by the autoscaler: {:?}",
+                    not_added.len(),
+                    not_added
+                );
+            }
+        }
+    }
+
+    /// Executes the split or merge with the given coins as gas payment, and returns the coins
+    /// that should be put back into the pool, along with whether the action succeeded.
+    async fn execute_action(
+        &self,
+        sponsor: SuiAddress,
+        action: AutoscalerAction,
+        coins: &[GasCoin],
+    ) -> (Vec<GasCoin>, bool) {
+        let total_balance: u64 = coins.iter().map(|c| c.balance).sum();
+        let gas_cost_per_object = self
+            .sui_client
+            .calibrate_gas_cost_per_object(sponsor, &coins[0])
+            .await;
+        let (pt, new_coin_amounts, budget) = match action {
+            AutoscalerAction::Split { split_count } => {
+                let amounts = split_amounts(
+                    total_balance,
+                    self.config.split_coin_balance(),
+                    gas_cost_per_object,
+                    split_count,
+                );
+                if amounts.is_empty() {
+                    return (coins.to_vec(), false);
+                }
+                let pt = self.sui_client.construct_coin_split_amounts_pt(
+                    Argument::GasCoin,
+                    &amounts,
+                    sponsor,
+                );
+                // The coin being split is one of the resulting coins too.
+                let budget = gas_cost_per_object * (amounts.len() as u64 + 1);
+                (pt, amounts, budget)
+            }
+            AutoscalerAction::Merge { .. } => {
+                if coins.len() < 2 {
+                    return (coins.to_vec(), false);
+                }
+                // Gas smashing merges all the payment coins into the first one, so an empty
+                // transaction is enough.
+                (
+                    ProgrammableTransactionBuilder::new().finish(),
+                    vec![],
+                    gas_cost_per_object * 2,
+                )
+            }
+        };
+        let rgp = self.sui_client.get_reference_gas_price().await;
+        let tx_data = TransactionData::new_programmable(
+            sponsor,
+            coins.iter().map(|c| c.object_ref).collect(),
+            pt,
+            budget,
+            rgp,
+        );
+        let result = match self.signer.sign_transaction(&tx_data).await {
+            Ok(sig) => {
+                let tx = Transaction::from_generic_sig_data(tx_data, vec![sig]);
+                self.sui_client
+                    .execute_transaction(
+                        tx,
+                        None,
+                        SuiTransactionBlockResponseOptions::new().with_effects(),
+                        3,
+                    )
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(ExecutedTransaction { effects, .. }) if effects.status().is_ok() => {
+                // All the split coins have the same amount, so their order does not matter.
+                let mut updated_coins: Vec<_> = effects
+                    .created()
+                    .iter()
+                    .zip(&new_coin_amounts)
+                    .map(|(created, amount)| GasCoin {
+                        owner: sponsor,
+                        object_ref: created.reference.to_object_ref(),
+                        balance: *amount,
+                    })
+                    .collect();
+                let remaining_coin_balance = (total_balance - new_coin_amounts.iter().sum::<u64>())
+                    as i64
+                    - effects.gas_cost_summary().net_gas_usage();
+                updated_coins.push(GasCoin {
+                    owner: sponsor,
+                    object_ref: effects.gas_object().reference.to_object_ref(),
+                    balance: remaining_coin_balance as u64,
+                });
+                self.metrics
+                    .num_autoscaler_actions
+                    .with_label_values(&[
+                        &sponsor.to_string(),
+                        match action {
+                            AutoscalerAction::Split { .. } => "split",
+                            AutoscalerAction::Merge { .. } => "merge",
+                        },
+                    ])
+                    .inc();
+                info!(
+                    ?sponsor,
+                    "Autoscaler turned {} coins into {} coins",
+                    coins.len(),
+                    updated_coins.len()
+                );
+                (updated_coins, true)
+            }
+            other => {
+                error!(?sponsor, "Autoscaler action failed: {:?}", other);
+                let latest_coins = self
+                    .sui_client
+                    .get_latest_gas_objects(coins.iter().map(|c| c.object_ref.0))
+                    .await
+                    .into_values()
+                    .flatten()
+                    .collect();
+                (latest_coins, false)
+            }
+        }
+    }
+}
+
+/// The amounts of the coins split off a balance of `total_balance`: as many coins of
+/// `coin_balance` as it can fund, up to `split_count` resulting coins in total. The coin being
+/// split is one of them and keeps the rest, which after paying for the split is never less than
+/// `coin_balance` either. Empty if the balance cannot fund at least two such coins.
+fn split_amounts(
+    total_balance: u64,
+    coin_balance: u64,
+    gas_cost_per_object: u64,
+    split_count: u64,
+) -> Vec<u64> {
+    let split_count = total_balance
+        .checked_div(coin_balance + gas_cost_per_object)
+        .unwrap_or(0)
+        .min(split_count);
+    if split_count < 2 {
+        return vec![];
+    }
+    vec![coin_balance; split_count as usize - 1]
+}
+
+/// How many new coins each batch of a merge takes from the pool. Every batch after the first
+/// also merges the coin the previous batch merged into, so it takes one fewer new coin.
+fn merge_batches(merge_count: u64, batch_size: u64) -> Vec<u64> {
+    // A batch needs room for at least one new coin besides the carried one.
+    let batch_size = batch_size.max(2);
+    let mut batches = vec![];
+    let mut remaining = merge_count;
+    while remaining > 0 {
+        let capacity = if batches.is_empty() {
+            batch_size
+        } else {
+            batch_size - 1
+        };
+        let count = remaining.min(capacity);
+        batches.push(count);
+        remaining -= count;
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_batches, split_amounts, AutoscalerAction};
+    use crate::config::CoinAutoscalerConfig;
+
+    #[test]
+    fn test_autoscaler_decision() {
+        let config = CoinAutoscalerConfig {
+            target_coin_count: 1000,
+            tolerance_percent: 10,
+            max_coins_per_action: 200,
+            ..Default::default()
+        };
+        assert_eq!(AutoscalerAction::decide(&config, 1000), None);
+        assert_eq!(AutoscalerAction::decide(&config, 900), None);
+        assert_eq!(AutoscalerAction::decide(&config, 1100), None);
+        assert_eq!(
+            AutoscalerAction::decide(&config, 850),
+            Some(AutoscalerAction::Split { split_count: 151 })
+        );
+        assert_eq!(
+            AutoscalerAction::decide(&config, 0),
+            Some(AutoscalerAction::Split { split_count: 201 })
+        );
+        assert_eq!(
+            AutoscalerAction::decide(&config, 1150),
+            Some(AutoscalerAction::Merge { merge_count: 151 })
+        );
+        assert_eq!(
+            AutoscalerAction::decide(&config, 5000),
+            Some(AutoscalerAction::Merge { merge_count: 200 })
+        );
+    }
+
+    #[test]
+    fn test_merge_batches() {
+        assert_eq!(merge_batches(0, 100), Vec::<u64>::new());
+        assert_eq!(merge_batches(50, 100), vec![50]);
+        assert_eq!(merge_batches(100, 100), vec![100]);
+        // The coin merged into by a batch takes a slot in the next one.
+        assert_eq!(merge_batches(101, 100), vec![100, 1]);
+        assert_eq!(merge_batches(10, 4), vec![4, 3, 3]);
+        assert_eq!(merge_batches(3, 1), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_split_amounts() {
+        let config = CoinAutoscalerConfig {
+            target_coin_balance: 1000,
+            split_headroom_percent: 20,
+            ..Default::default()
+        };
+        let coin_balance = config.split_coin_balance();
+        assert_eq!(coin_balance, 1200);
+
+        // 10 coins of 1200 plus 10 per object fit in 12100, 11 do not.
+        let amounts = split_amounts(12_100, coin_balance, 10, 100);
+        assert_eq!(amounts, vec![1200; 9]);
+        // The coin being split keeps at least the target size after paying for the split.
+        let remaining = 12_100 - amounts.iter().sum::<u64>() - 10 * 10;
+        assert!(remaining >= coin_balance);
+
+        // The coin count is capped regardless of the balance, without shrinking the coins.
+        assert_eq!(split_amounts(1_000_000, coin_balance, 10, 5), vec![1200; 4]);
+        // Too small to produce two coins of the target size.
+        assert!(split_amounts(2_000, coin_balance, 10, 100).is_empty());
+        assert!(split_amounts(0, 0, 0, 100).is_empty());
+    }
+}
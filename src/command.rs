@@ -1,18 +1,23 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::coin_autoscaler::CoinAutoscaler;
 use crate::config::GasStationConfig;
 use crate::gas_pool::gas_pool_core::GasPoolContainer;
 use crate::gas_pool_initializer::GasPoolInitializer;
 use crate::metrics::{GasPoolCoreMetrics, GasPoolRpcMetrics, StorageMetrics};
+use crate::onchain_reconciler::OnChainReconciler;
+use crate::reservation_watchdog::ReservationWatchdog;
 use crate::rpc::GasPoolServer;
 use crate::storage::connect_storage;
 use crate::sui_client::SuiClient;
 use clap::*;
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
+use std::time::Duration;
 use sui_config::Config;
-use tracing::info;
+use sui_types::parse_sui_type_tag;
+use tracing::{error, info};
 
 #[derive(Parser)]
 #[command(
@@ -34,18 +39,27 @@ impl Command {
             gas_pool_config,
             fullnode_url,
             fullnode_basic_auth,
+            fullnode_headers,
+            fullnode_max_concurrent_object_fetches,
+            fullnode_max_object_fetch_attempts,
+            fullnode_connect_timeout_sec,
+            gas_coin_type,
             rpc_host_ip,
             rpc_port,
             metrics_port,
             coin_init_config,
             daily_gas_usage_cap,
+            core_config,
+            onchain_anchor_config,
+            coin_autoscaler_config,
+            stuck_reservation_watchdog_config,
         } = config;
 
         let metric_address = SocketAddr::new(IpAddr::V4(rpc_host_ip), metrics_port);
         let registry_service = mysten_metrics::start_prometheus_server(metric_address);
         let prometheus_registry = registry_service.default_registry();
         let telemetry_config = telemetry_subscribers::TelemetryConfig::new()
-            .with_log_level("off,sui_gas_station=debug")
+            .with_log_level("off,sui_gas_station=debug,gas_pool=debug")
             .with_env()
             .with_prom_registry(&prometheus_registry);
         let _guard = telemetry_config.init();
@@ -56,7 +70,22 @@ impl Command {
         let sponsor_addresses = signer.get_addresses();
         info!("Sponsor addresses: {:?}", sponsor_addresses);
         let storage = connect_storage(&gas_pool_config, sponsor_addresses, storage_metrics).await;
-        let sui_client = SuiClient::new(&fullnode_url, fullnode_basic_auth).await;
+        let mut sui_client = SuiClient::new_with_retries(
+            &fullnode_url,
+            fullnode_basic_auth,
+            fullnode_headers,
+            Duration::from_secs(fullnode_connect_timeout_sec),
+        )
+        .await
+        .expect("Unable to connect to the fullnode")
+        .with_max_concurrent_object_fetches(fullnode_max_concurrent_object_fetches)
+        .with_max_object_fetch_attempts(fullnode_max_object_fetch_attempts);
+        if let Some(gas_coin_type) = gas_coin_type {
+            info!("Gas coin type: {}", gas_coin_type);
+            sui_client = sui_client.with_gas_coin_type(
+                parse_sui_type_tag(&gas_coin_type).expect("Invalid gas coin type"),
+            );
+        }
         let _coin_init_task = if let Some(coin_init_config) = coin_init_config {
             let task = GasPoolInitializer::start(
                 sui_client.clone(),
@@ -71,11 +100,37 @@ impl Command {
         };
 
         let core_metrics = GasPoolCoreMetrics::new(&prometheus_registry);
+        let _onchain_reconciler = onchain_anchor_config.map(|onchain_anchor_config| {
+            OnChainReconciler::start(
+                sui_client.clone(),
+                storage.clone(),
+                onchain_anchor_config,
+                core_metrics.clone(),
+            )
+        });
+        let _coin_autoscaler = coin_autoscaler_config.map(|coin_autoscaler_config| {
+            CoinAutoscaler::start(
+                sui_client.clone(),
+                storage.clone(),
+                signer.clone(),
+                coin_autoscaler_config,
+                core_metrics.clone(),
+            )
+        });
+        let _reservation_watchdog = stuck_reservation_watchdog_config.map(|watchdog_config| {
+            ReservationWatchdog::start(
+                storage.clone(),
+                signer.get_addresses(),
+                watchdog_config,
+                core_metrics.clone(),
+            )
+        });
         let container = GasPoolContainer::new(
             signer,
             storage,
             sui_client,
             daily_gas_usage_cap,
+            core_config,
             core_metrics,
         )
         .await;
@@ -88,6 +143,12 @@ impl Command {
             rpc_metrics,
         )
         .await;
-        server.handle.await.unwrap();
+        tokio::select! {
+            result = server.handle => result.unwrap(),
+            _ = tokio::signal::ctrl_c() => info!("Received ctrl-c, shutting down"),
+        }
+        if let Err(err) = container.shutdown().await {
+            error!("Failed to record the final pool snapshot: {:?}", err);
+        }
     }
 }
@@ -1,14 +1,28 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::tx_signer::{SidecarTxSigner, TestTxSigner, TxSigner};
-use serde::{Deserialize, Serialize};
+use crate::audit_log::{AuditLog, FileAuditLog, NoopAuditLog};
+use crate::gas_estimator::{
+    DevInspectGasEstimator, GasEstimator, HistoricalAverageGasEstimator, StaticGasEstimator,
+};
+use crate::storage::{DEFAULT_ADD_COINS_BATCH_SIZE, MAX_GAS_PER_QUERY};
+use crate::sui_client::SuiClient;
+use crate::tx_signer::{
+    MultiSigKeypairSigner, MultiSigMember, MultiSigSigner, SidecarTxSigner, TestTxSigner, TxSigner,
+};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use serde_with::serde_as;
+use std::collections::{BTreeMap, BTreeSet};
 use std::net::Ipv4Addr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use sui_config::Config;
-use sui_types::crypto::{get_account_key_pair, SuiKeyPair};
+use sui_types::base_types::{ObjectID, SuiAddress};
+use sui_types::crypto::{get_account_key_pair, EncodeDecodeBase64, PublicKey, SuiKeyPair};
 use sui_types::gas_coin::MIST_PER_OCT;
+use sui_types::multisig::{ThresholdUnit, WeightUnit};
+use sui_types::quorum_driver_types::ExecuteTransactionRequestType;
 
 pub const DEFAULT_RPC_PORT: u16 = 9527;
 pub const DEFAULT_METRICS_PORT: u16 = 9184;
@@ -16,6 +30,41 @@ pub const DEFAULT_METRICS_PORT: u16 = 9184;
 pub const DEFAULT_INIT_COIN_BALANCE: u64 = MIST_PER_OCT / 10;
 // 24 hours.
 const DEFAULT_COIN_POOL_REFRESH_INTERVAL_SEC: u64 = 60 * 60 * 24;
+// 1 hour.
+const DEFAULT_EXECUTION_RESULT_TTL_SEC: u64 = 60 * 60;
+// 10 seconds.
+const DEFAULT_STORAGE_TIMEOUT_MS: u64 = 10 * 1000;
+// Well above what legitimate transactions need, while bounding validation and execution effort.
+const DEFAULT_MAX_TRANSACTION_COMPLEXITY: u64 = 2048;
+// The maximum transaction size of the Sui protocol, 128 KiB.
+const DEFAULT_MAX_TRANSACTION_SIZE_BYTES: usize = 128 * 1024;
+const DEFAULT_MAX_RESERVATION_METADATA_BYTES: usize = 1024;
+// 10 minutes.
+const DEFAULT_ONCHAIN_RECONCILE_INTERVAL_SEC: u64 = 60 * 10;
+// 5 minutes.
+const DEFAULT_AUTOSCALER_INTERVAL_SEC: u64 = 60 * 5;
+// 1 minute.
+const DEFAULT_STUCK_RESERVATION_CHECK_INTERVAL_SEC: u64 = 60;
+// 10 seconds.
+const DEFAULT_MULTISIG_SIGN_TIMEOUT_MS: u64 = 10 * 1000;
+const DEFAULT_SIGN_ATTEMPT_TIMEOUT_MS: u64 = 10 * 1000;
+// 1 minute.
+const DEFAULT_COIN_COUNT_RECONCILE_INTERVAL_SEC: u64 = 60;
+const DEFAULT_RESERVATION_READ_CACHE_CAPACITY: usize = 10_000;
+const DEFAULT_WARM_RESERVATIONS_PER_BUCKET: usize = 4;
+// 1 second.
+const DEFAULT_MIN_RESERVATION_DURATION_MS: u64 = 1000;
+// 5 minutes.
+const DEFAULT_COIN_COOLDOWN_MS: u64 = 5 * 60 * 1000;
+// 30 seconds.
+const DEFAULT_RESERVATION_HEARTBEAT_INTERVAL_MS: u64 = 30 * 1000;
+// 5 seconds.
+const DEFAULT_READY_FOR_EXECUTION_MAX_ATTEMPTS: usize = 3;
+const DEFAULT_ONCHAIN_COIN_FALLBACK_TIMEOUT_MS: u64 = 5 * 1000;
+pub const DEFAULT_FULLNODE_MAX_CONCURRENT_OBJECT_FETCHES: usize = 20;
+pub const DEFAULT_FULLNODE_MAX_OBJECT_FETCH_ATTEMPTS: usize = 5;
+// 1 minute.
+pub const DEFAULT_FULLNODE_CONNECT_TIMEOUT_SEC: u64 = 60;
 pub const DEFAULT_DAILY_GAS_USAGE_CAP: u64 = 1500 * MIST_PER_OCT;
 
 // Use 127.0.0.1 for tests to avoid OS complaining about permissions.
@@ -38,9 +87,39 @@ pub struct GasStationConfig {
     /// (username, password).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fullnode_basic_auth: Option<(String, String)>,
+    /// Extra HTTP headers sent with every request to the fullnode, e.g. an API key or a client
+    /// identifier required by a gated provider. Headers set by the SDK cannot be overridden.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fullnode_headers: BTreeMap<String, String>,
+    /// Maximum number of object fetch requests sent to the fullnode at the same time when
+    /// reading the latest state of many gas coins.
+    #[serde(default = "default_fullnode_max_concurrent_object_fetches")]
+    pub fullnode_max_concurrent_object_fetches: usize,
+    /// How many times a batch of objects is requested again when the fullnode returns an
+    /// incomplete response, before the missing objects are treated as gone.
+    #[serde(default = "default_fullnode_max_object_fetch_attempts")]
+    pub fullnode_max_object_fetch_attempts: usize,
+    /// How long to keep trying to connect to the fullnode at startup before giving up, in
+    /// seconds. Attempts are retried with an exponential backoff.
+    #[serde(default = "default_fullnode_connect_timeout_sec")]
+    pub fullnode_connect_timeout_sec: u64,
+    /// The type of the coins that make up the pool, e.g. `0x2::oct::OCT`. Only coins of this
+    /// type are picked up from the sponsor accounts. Defaults to the native gas coin.
+    /// A process runs a single pool, and the storage keys are not namespaced by coin type, so
+    /// pools of different coin types need their own sponsor addresses or their own storage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_coin_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub coin_init_config: Option<CoinInitConfig>,
     pub daily_gas_usage_cap: u64,
+    #[serde(default)]
+    pub core_config: GasPoolCoreConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub onchain_anchor_config: Option<OnChainAnchorConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coin_autoscaler_config: Option<CoinAutoscalerConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stuck_reservation_watchdog_config: Option<StuckReservationWatchdogConfig>,
 }
 
 impl Config for GasStationConfig {}
@@ -55,33 +134,243 @@ impl Default for GasStationConfig {
             gas_pool_config: GasPoolStorageConfig::default(),
             fullnode_url: "http://localhost:9000".to_string(),
             fullnode_basic_auth: None,
+            fullnode_headers: BTreeMap::new(),
+            fullnode_max_concurrent_object_fetches: DEFAULT_FULLNODE_MAX_CONCURRENT_OBJECT_FETCHES,
+            fullnode_max_object_fetch_attempts: DEFAULT_FULLNODE_MAX_OBJECT_FETCH_ATTEMPTS,
+            fullnode_connect_timeout_sec: DEFAULT_FULLNODE_CONNECT_TIMEOUT_SEC,
+            gas_coin_type: None,
             coin_init_config: Some(CoinInitConfig::default()),
             daily_gas_usage_cap: DEFAULT_DAILY_GAS_USAGE_CAP,
+            core_config: GasPoolCoreConfig::default(),
+            onchain_anchor_config: None,
+            coin_autoscaler_config: None,
+            stuck_reservation_watchdog_config: None,
         }
     }
 }
 
+pub fn default_fullnode_max_concurrent_object_fetches() -> usize {
+    DEFAULT_FULLNODE_MAX_CONCURRENT_OBJECT_FETCHES
+}
+
+pub fn default_fullnode_max_object_fetch_attempts() -> usize {
+    DEFAULT_FULLNODE_MAX_OBJECT_FETCH_ATTEMPTS
+}
+
+pub fn default_fullnode_connect_timeout_sec() -> u64 {
+    DEFAULT_FULLNODE_CONNECT_TIMEOUT_SEC
+}
+
 #[serde_as]
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum GasPoolStorageConfig {
-    Redis { redis_url: String },
+    Redis {
+        redis_url: String,
+        #[serde(default)]
+        coin_selection_strategy: CoinSelectionStrategy,
+        /// Number of candidate coins to randomly choose from when reserving, to spread concurrent
+        /// reservations across different coins. 1 means always picking the first candidate.
+        #[serde(default = "default_coin_selection_top_k")]
+        coin_selection_top_k: u64,
+        /// When set, the pool holds at most this many available coins per sponsor. Coins added
+        /// beyond it, by the initializer, imports, splits or releases, are left on chain.
+        #[serde(default)]
+        max_pool_coins_per_sponsor: Option<u64>,
+    },
+}
+
+pub fn default_coin_selection_top_k() -> u64 {
+    1
 }
 
 impl Default for GasPoolStorageConfig {
     fn default() -> Self {
         Self::Redis {
             redis_url: "redis://127.0.0.1:6379".to_string(),
+            coin_selection_strategy: CoinSelectionStrategy::default(),
+            coin_selection_top_k: default_coin_selection_top_k(),
+            max_pool_coins_per_sponsor: None,
+        }
+    }
+}
+
+/// How the storage picks coins from the available pool to cover a reservation budget.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CoinSelectionStrategy {
+    /// Take coins from the front of the queue until the budget is covered.
+    #[default]
+    Fifo,
+    /// Prefer a single coin that covers the whole budget, so that fewer coins get smashed
+    /// during execution. Falls back to Fifo when no such coin is found.
+    PreferSingleCoin,
+    /// Prefer the coins that were reserved the longest time ago, so that usage, and version
+    /// churn, is spread evenly over the pool rather than concentrated on a few hot coins. Falls
+    /// back to Fifo when they do not cover the budget.
+    LeastRecentlyUsed,
+}
+
+/// How fresh the objects read from the fullnode need to be.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReadConsistency {
+    /// Use whatever the fullnode returns, which may lag behind consensus.
+    #[default]
+    BestEffort,
+    /// Wait until the fullnode has caught up with the expected object versions before reading.
+    /// This avoids stale reads at the cost of latency.
+    Strong,
+}
+
+/// What the gas price checks do when the reference gas price cannot be read from the fullnode
+/// and no previously read value is known either.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReferenceGasPriceFallback {
+    /// Reject the transaction, since its gas price cannot be checked.
+    #[default]
+    FailClosed,
+    /// Only check the gas price against the configured floor.
+    FailOpen,
+}
+
+/// When the gas coin of an executed transaction goes back to the pool. Releasing it right away
+/// can hand it to the next reservation before the fullnode serves its new version, so that the
+/// next transaction fails on a stale version.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PostExecutionRelease {
+    /// Release the coin as soon as the effects are in.
+    #[default]
+    Immediate,
+    /// Wait a fixed time before releasing the coin.
+    Delay { delay_ms: u64 },
+    /// Wait until the fullnode serves the new version of the coin, for at most `timeout_ms`,
+    /// after which the coin is released anyway.
+    WaitForVersion { timeout_ms: u64 },
+}
+
+/// What happens to the reserved coins a transaction does not pay with. They move to execution
+/// along with the rest of the reservation, so they stay out of the pool unless released again.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnusedReservedCoins {
+    /// Release them back to the pool at their latest on-chain state, since they were not used
+    /// as gas.
+    #[default]
+    Release,
+    /// Assume they were smashed into the gas coin, and leave them out of the pool until they
+    /// are imported again.
+    AssumeSmashed,
+}
+
+/// How the gas a transaction uses is estimated before it is executed.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GasEstimatorConfig {
+    /// Dev-inspect the transaction on the fullnode.
+    #[default]
+    DevInspect,
+    /// A fixed estimate per transaction category, i.e. the Move functions the transaction
+    /// calls, e.g. "0x2::coin::split". Other transactions are estimated at `default_budget`.
+    Static {
+        budgets: BTreeMap<String, u64>,
+        default_budget: u64,
+    },
+    /// The average gas used by the recent executions of the same transaction category, once it
+    /// has been executed `min_samples` times. Dev-inspect is used until then.
+    HistoricalAverage { min_samples: usize },
+}
+
+impl GasEstimatorConfig {
+    pub fn new_estimator(&self, sui_client: SuiClient) -> Arc<dyn GasEstimator> {
+        match self {
+            GasEstimatorConfig::DevInspect => DevInspectGasEstimator::new(sui_client),
+            GasEstimatorConfig::Static {
+                budgets,
+                default_budget,
+            } => StaticGasEstimator::new(budgets.clone(), *default_budget),
+            GasEstimatorConfig::HistoricalAverage { min_samples } => {
+                HistoricalAverageGasEstimator::new(
+                    DevInspectGasEstimator::new(sui_client),
+                    *min_samples,
+                )
+            }
         }
     }
 }
 
+/// Where the audit record of every sponsor signature goes.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditLogConfig {
+    /// No audit record is kept.
+    #[default]
+    None,
+    /// Each signature is appended to the file as a line of JSON.
+    File { path: PathBuf },
+}
+
+impl AuditLogConfig {
+    pub fn new_audit_log(&self) -> anyhow::Result<Arc<dyn AuditLog>> {
+        Ok(match self {
+            AuditLogConfig::None => Arc::new(NoopAuditLog),
+            AuditLogConfig::File { path } => FileAuditLog::new(path)?,
+        })
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum TxSignerConfig {
-    Local { keypair: Vec<SuiKeyPair> },
-    Sidecar { sidecar_url: String },
+    Local {
+        keypair: Vec<SuiKeyPair>,
+    },
+    Sidecar {
+        sidecar_url: String,
+    },
+    /// Sponsors with a multisig address, whose members each sign with their own signer.
+    MultiSig {
+        members: Vec<MultiSigMemberConfig>,
+        threshold: ThresholdUnit,
+        /// How long to wait for each member to sign, in milliseconds.
+        #[serde(default = "default_multisig_sign_timeout_ms")]
+        sign_timeout_ms: u64,
+    },
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MultiSigMemberConfig {
+    /// A local signer takes exactly one keypair, that of the member.
+    #[serde(deserialize_with = "deserialize_multisig_member_signer")]
+    pub signer: TxSignerConfig,
+    /// Base64 encoding of the flag byte followed by the public key bytes.
+    pub public_key: String,
+    pub weight: WeightUnit,
+}
+
+pub fn default_multisig_sign_timeout_ms() -> u64 {
+    DEFAULT_MULTISIG_SIGN_TIMEOUT_MS
+}
+
+fn deserialize_multisig_member_signer<'de, D>(deserializer: D) -> Result<TxSignerConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let signer = TxSignerConfig::deserialize(deserializer)?;
+    if let TxSignerConfig::Local { keypair } = &signer {
+        if keypair.len() != 1 {
+            return Err(de::Error::custom(format!(
+                "A local multisig member takes exactly one keypair, got {}",
+                keypair.len()
+            )));
+        }
+    }
+    Ok(signer)
 }
 
 impl Default for TxSignerConfig {
@@ -98,6 +387,36 @@ impl TxSignerConfig {
         match self {
             TxSignerConfig::Local { keypair } => TestTxSigner::new(keypair),
             TxSignerConfig::Sidecar { sidecar_url } => SidecarTxSigner::new(sidecar_url).await,
+            TxSignerConfig::MultiSig {
+                members,
+                threshold,
+                sign_timeout_ms,
+            } => {
+                let mut multisig_members = vec![];
+                for member in members {
+                    let public_key = PublicKey::decode_base64(&member.public_key)
+                        .unwrap_or_else(|err| panic!("Invalid multisig public key: {}", err));
+                    let signer: Arc<dyn TxSigner> = match member.signer {
+                        // A local key signs for the multisig address, not for its own. Loading
+                        // the config made sure there is exactly one.
+                        TxSignerConfig::Local { mut keypair } => {
+                            MultiSigKeypairSigner::new(keypair.remove(0))
+                        }
+                        signer => Box::pin(signer.new_signer()).await,
+                    };
+                    multisig_members.push(MultiSigMember {
+                        signer,
+                        public_key,
+                        weight: member.weight,
+                    });
+                }
+                MultiSigSigner::new(
+                    multisig_members,
+                    threshold,
+                    Duration::from_millis(sign_timeout_ms),
+                )
+                .unwrap()
+            }
         }
     }
 }
@@ -112,6 +431,10 @@ pub struct CoinInitConfig {
     /// requires initialization, i.e. splitting into smaller coins and add them to the gas pool.
     /// This is in seconds.
     pub refresh_interval_sec: u64,
+    /// Coins of the listed sponsors below the given balance, in MIST, are never imported into
+    /// the pool. Other sponsors import every coin when the pool is first initialized.
+    #[serde(default)]
+    pub min_import_balances: BTreeMap<SuiAddress, u64>,
 }
 
 impl Default for CoinInitConfig {
@@ -119,6 +442,343 @@ impl Default for CoinInitConfig {
         CoinInitConfig {
             target_init_balance: DEFAULT_INIT_COIN_BALANCE,
             refresh_interval_sec: DEFAULT_COIN_POOL_REFRESH_INTERVAL_SEC,
+            min_import_balances: BTreeMap::new(),
+        }
+    }
+}
+
+/// Tunes how the gas pool core handles reservations and transaction executions.
+/// Every field has a default, so that the whole section can be omitted from the config file.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct GasPoolCoreConfig {
+    /// How long the summary of an executed transaction is kept around for lookup by digest,
+    /// in seconds.
+    pub execution_result_ttl_sec: u64,
+    /// Sponsors listed here have every transaction dev-inspected before execution, and rejected
+    /// if the estimated gas used exceeds the given cost, in MIST.
+    pub max_estimated_gas_cost: BTreeMap<SuiAddress, u64>,
+    /// Sponsors listed here reject transactions whose gas price is below the given floor or the
+    /// reference gas price, whichever is higher, in MIST.
+    pub min_gas_price: BTreeMap<SuiAddress, u64>,
+    /// Sponsors listed here reject transactions whose gas price is below the reference gas price
+    /// or above the given percentage of it, e.g. 150 to only sponsor gas prices between the
+    /// reference gas price and one and a half times it.
+    pub max_gas_price_percent: BTreeMap<SuiAddress, u64>,
+    /// What `min-gas-price` and `max-gas-price-percent` checks do when the reference gas price
+    /// is unavailable. The last
+    /// reference gas price read successfully is used when there is one, so this only applies
+    /// until the first successful read.
+    pub reference_gas_price_fallback: ReferenceGasPriceFallback,
+    /// Consistency of the gas coin reads made before execution to derive the new balance.
+    pub object_read_consistency: ReadConsistency,
+    /// How long reservation and ready-for-execution calls wait for the storage before failing
+    /// with a storage timeout error, in milliseconds.
+    pub storage_timeout_ms: u64,
+    /// Transactions whose number of commands plus number of command arguments exceeds this are
+    /// rejected before execution.
+    pub max_transaction_complexity: u64,
+    /// Transactions whose BCS serialized transaction data is larger than this, in bytes, are
+    /// rejected before they are signed.
+    pub max_transaction_size_bytes: usize,
+    /// The request type used to execute transactions of the listed sponsors when the caller
+    /// does not specify one. Other sponsors use `WaitForEffectsCert`.
+    pub execute_request_types: BTreeMap<SuiAddress, ExecuteTransactionRequestType>,
+    /// When set, reservation budgets are rounded up to a multiple of this, in MIST, so that
+    /// reserved balances fall into predictable buckets. 0 means no rounding.
+    pub reservation_budget_granularity: u64,
+    /// Whether to check the coins of every persisted reservation against the chain when the
+    /// pool starts, dropping the coins that no longer exist or belong to the sponsor.
+    pub reconcile_reservations_at_startup: bool,
+    /// Whether executions whose effects report a gas object outside of the payment coins fail.
+    /// Either way such a gas object is never added to the pool, and the payment coins are read
+    /// from the chain instead.
+    pub fail_on_unexpected_gas_object: bool,
+    /// Whether `reserve_gas` reads the latest version of the reserved coins from the fullnode
+    /// before returning them, trading a fullnode round trip for never handing out stale versions.
+    pub refresh_reserved_coin_versions: bool,
+    /// When set, new reservations must be confirmed with `confirm_reservation` within this many
+    /// milliseconds. Until then they cannot be executed, and unconfirmed reservations expire
+    /// after this instead of their full duration, so that clients that crash right after
+    /// reserving do not hold coins for long.
+    pub reservation_confirmation_ttl_ms: Option<u64>,
+    /// The maximum number of coins written to the storage at once, when releasing or importing
+    /// many coins.
+    pub add_coins_batch_size: usize,
+    /// Whether transactions that publish or upgrade packages are rejected, for pools that only
+    /// sponsor calls to existing packages.
+    pub reject_publish_and_upgrade: bool,
+    /// Whether reservations of multiple coins also cover the gas it costs to smash the coins
+    /// into one during execution, so that smashing does not eat into the requested budget.
+    pub reserve_smash_headroom: bool,
+    /// Budgets, in MIST, for which reservations are made ahead of time and handed out on
+    /// request, to cut the latency of reserving common budget sizes. Each budget is served by
+    /// the smallest of these that covers it. Empty disables the warm cache.
+    pub warm_budget_buckets: Vec<u64>,
+    /// How many reservations are kept ready for each sponsor and warm budget bucket.
+    pub warm_reservations_per_bucket: usize,
+    /// Caps the number of concurrent executions of the listed sponsors, so that one sponsor's
+    /// traffic cannot monopolize the signer or the fullnode.
+    pub max_concurrent_executions_per_sponsor: BTreeMap<SuiAddress, usize>,
+    /// How long an execution waits for a sponsor at its concurrency limit before failing as
+    /// busy, in milliseconds. 0 fails right away.
+    pub sponsor_busy_wait_ms: u64,
+    /// When set, only these of the signer's addresses can be used as sponsors, e.g. to retire an
+    /// address without removing its key. All of the signer's addresses are active otherwise.
+    pub active_sponsors: Option<BTreeSet<SuiAddress>>,
+    /// Where the final pool snapshot is written on shutdown, as JSON. It is only logged
+    /// otherwise.
+    pub shutdown_snapshot_path: Option<PathBuf>,
+    /// Whether coins that cannot be read back after their reservation expires are checked
+    /// again, one by one, before being dropped from the pool. Coins that are really gone are
+    /// logged and counted, while coins that only failed to be read are read again.
+    pub recheck_missing_coins_on_expiry: bool,
+    /// Shorter reservation durations are raised to this, in milliseconds, since such
+    /// reservations would mostly expire before the client gets to execute them.
+    pub min_reservation_duration_ms: u64,
+    /// How many failed executions within `coin_cooldown_ms` put a coin on cooldown, during
+    /// which new reservations avoid it. 0 disables the cooldown.
+    pub coin_cooldown_failure_threshold: usize,
+    /// How long a coin stays on cooldown after its failures, in milliseconds.
+    pub coin_cooldown_ms: u64,
+    /// Whether executions first read the balance of the payment coins from the fullnode, and
+    /// fail early when it no longer covers the gas budget, e.g. because the coins were spent
+    /// outside of the pool. This costs a fullnode round trip per execution.
+    pub verify_reserved_balance_before_execution: bool,
+    /// How the gas of transactions is estimated for `max-estimated-gas-cost`.
+    pub gas_estimator: GasEstimatorConfig,
+    /// When the gas coin of an executed transaction is released back to the pool. Waiting
+    /// delays the response of the execution by as much.
+    pub post_execution_release: PostExecutionRelease,
+    /// What happens to the reserved coins that an executed transaction does not pay with.
+    pub unused_reserved_coins: UnusedReservedCoins,
+    /// How long the result of an execution is kept in memory after it finishes, in
+    /// milliseconds, and returned to identical submissions instead of executing them again.
+    /// 0 only shares the result with submissions made while the execution is in progress.
+    pub execution_dedup_window_ms: u64,
+    /// Multiplies the net gas usage of each transaction category before it is charged against
+    /// the daily gas usage cap, e.g. 2.0 charges twice the gas actually used. Categories are
+    /// the Move functions a transaction calls, as used by the gas estimator, or "builtin".
+    /// Unlisted categories are charged their raw usage.
+    pub gas_usage_cap_weights: BTreeMap<String, f64>,
+    /// When set, a reservation that fails for lack of coins looks up the coins the sponsor owns
+    /// on chain, imports those the pool does not know about, and is retried once. The lookup
+    /// runs at most once per this many milliseconds for each sponsor.
+    pub onchain_coin_fallback_interval_ms: Option<u64>,
+    /// How long the on-chain coin lookup may take before the reservation fails as it would
+    /// have without it, in milliseconds.
+    pub onchain_coin_fallback_timeout_ms: u64,
+    /// Where the audit record of every transaction the sponsor signs goes. Transactions whose
+    /// record cannot be written are not executed.
+    pub audit_log: AuditLogConfig,
+    /// The maximum size of the metadata a reservation can be made with, in bytes.
+    pub max_reservation_metadata_bytes: usize,
+    /// When set, a gas coin whose balance exceeds this after an execution, in MIST, is split
+    /// into coins of about half of it before going back to the pool, instead of going back as
+    /// one coin that keeps growing as more coins get smashed into it.
+    pub split_gas_coins_above: Option<u64>,
+    /// How long a heartbeat keeps a reservation from expiring, in milliseconds. A reservation
+    /// made for this long is reclaimed once its client misses a heartbeat.
+    pub reservation_heartbeat_interval_ms: u64,
+    /// When set, a gas coin whose balance is below this after an execution, in MIST, goes to the
+    /// sponsor's dust bin instead of back to the pool, so that reservations stop picking coins
+    /// that can barely pay for anything. Dust coins are kept track of to be merged later.
+    pub dust_coin_threshold: Option<u64>,
+    /// How many times moving the coins of a reservation to execution is tried against the
+    /// storage before the execution fails. Independent of the signing and execution retries.
+    pub ready_for_execution_max_attempts: usize,
+    /// How long each attempt to sign a transaction waits for the signer, in milliseconds, so
+    /// that a hung remote signer fails the attempt instead of blocking the execution.
+    pub sign_attempt_timeout_ms: u64,
+    /// How often the cached available coin count of each sponsor is reset to the count in
+    /// storage, in seconds. In between, it is only adjusted by the reservations and releases
+    /// made through this pool.
+    pub coin_count_reconcile_interval_sec: u64,
+    /// How long the expiration time, coins and metadata read for a reservation are served from
+    /// memory instead of the storage, in milliseconds. 0 disables the cache. Changes made through
+    /// this server show right away, and an expiration time that has passed is never served.
+    pub reservation_read_cache_ttl_ms: u64,
+    /// How many reservations the reservation read cache holds at most, dropping the least
+    /// recently read one to make room.
+    pub reservation_read_cache_capacity: usize,
+}
+
+impl Default for GasPoolCoreConfig {
+    fn default() -> Self {
+        GasPoolCoreConfig {
+            execution_result_ttl_sec: DEFAULT_EXECUTION_RESULT_TTL_SEC,
+            max_estimated_gas_cost: BTreeMap::new(),
+            min_gas_price: BTreeMap::new(),
+            max_gas_price_percent: BTreeMap::new(),
+            reference_gas_price_fallback: ReferenceGasPriceFallback::default(),
+            object_read_consistency: ReadConsistency::default(),
+            storage_timeout_ms: DEFAULT_STORAGE_TIMEOUT_MS,
+            max_transaction_complexity: DEFAULT_MAX_TRANSACTION_COMPLEXITY,
+            max_transaction_size_bytes: DEFAULT_MAX_TRANSACTION_SIZE_BYTES,
+            execute_request_types: BTreeMap::new(),
+            reservation_budget_granularity: 0,
+            reconcile_reservations_at_startup: false,
+            fail_on_unexpected_gas_object: false,
+            refresh_reserved_coin_versions: false,
+            reservation_confirmation_ttl_ms: None,
+            add_coins_batch_size: DEFAULT_ADD_COINS_BATCH_SIZE,
+            reject_publish_and_upgrade: false,
+            reserve_smash_headroom: false,
+            warm_budget_buckets: vec![],
+            warm_reservations_per_bucket: DEFAULT_WARM_RESERVATIONS_PER_BUCKET,
+            max_concurrent_executions_per_sponsor: BTreeMap::new(),
+            sponsor_busy_wait_ms: 0,
+            active_sponsors: None,
+            shutdown_snapshot_path: None,
+            recheck_missing_coins_on_expiry: false,
+            min_reservation_duration_ms: DEFAULT_MIN_RESERVATION_DURATION_MS,
+            coin_cooldown_failure_threshold: 0,
+            coin_cooldown_ms: DEFAULT_COIN_COOLDOWN_MS,
+            verify_reserved_balance_before_execution: false,
+            gas_estimator: GasEstimatorConfig::default(),
+            post_execution_release: PostExecutionRelease::default(),
+            unused_reserved_coins: UnusedReservedCoins::default(),
+            execution_dedup_window_ms: 0,
+            gas_usage_cap_weights: BTreeMap::new(),
+            onchain_coin_fallback_interval_ms: None,
+            onchain_coin_fallback_timeout_ms: DEFAULT_ONCHAIN_COIN_FALLBACK_TIMEOUT_MS,
+            audit_log: AuditLogConfig::default(),
+            max_reservation_metadata_bytes: DEFAULT_MAX_RESERVATION_METADATA_BYTES,
+            split_gas_coins_above: None,
+            reservation_heartbeat_interval_ms: DEFAULT_RESERVATION_HEARTBEAT_INTERVAL_MS,
+            dust_coin_threshold: None,
+            ready_for_execution_max_attempts: DEFAULT_READY_FOR_EXECUTION_MAX_ATTEMPTS,
+            sign_attempt_timeout_ms: DEFAULT_SIGN_ATTEMPT_TIMEOUT_MS,
+            coin_count_reconcile_interval_sec: DEFAULT_COIN_COUNT_RECONCILE_INTERVAL_SEC,
+            reservation_read_cache_ttl_ms: 0,
+            reservation_read_cache_capacity: DEFAULT_RESERVATION_READ_CACHE_CAPACITY,
+        }
+    }
+}
+
+/// Anchors the pool state of each sponsor to a Move object the sponsor owns on chain.
+/// The off-chain storage remains the primary store; the anchor objects are only used to
+/// periodically detect divergence between the two.
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OnChainAnchorConfig {
+    /// Maps each sponsor address to the object ID of its anchor object.
+    pub anchor_objects: BTreeMap<SuiAddress, ObjectID>,
+    /// How often do we compare the off-chain storage against the anchor objects, in seconds.
+    pub reconcile_interval_sec: u64,
+}
+
+impl Default for OnChainAnchorConfig {
+    fn default() -> Self {
+        OnChainAnchorConfig {
+            anchor_objects: BTreeMap::new(),
+            reconcile_interval_sec: DEFAULT_ONCHAIN_RECONCILE_INTERVAL_SEC,
+        }
+    }
+}
+
+/// Periodically looks for reservations whose coins have been in flight for too long, i.e. whose
+/// execution hung or whose process crashed after it took the coins out of the pool.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StuckReservationWatchdogConfig {
+    /// How long the coins of a reservation may stay in flight before it counts as stuck, in
+    /// seconds.
+    pub min_age_sec: u64,
+    /// How often do we look for stuck reservations, in seconds.
+    pub check_interval_sec: u64,
+}
+
+impl Default for StuckReservationWatchdogConfig {
+    fn default() -> Self {
+        StuckReservationWatchdogConfig {
+            min_age_sec: 10 * 60,
+            check_interval_sec: DEFAULT_STUCK_RESERVATION_CHECK_INTERVAL_SEC,
+        }
+    }
+}
+
+/// Keeps the number of available coins of each sponsor close to a target, by periodically
+/// splitting or merging coins in the pool.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CoinAutoscalerConfig {
+    /// Number of available coins we aim to keep for each sponsor.
+    pub target_coin_count: u64,
+    /// Typical gas budget a coin created by a split should cover on its own, in MIST.
+    pub target_coin_balance: u64,
+    /// Extra balance given to each coin created by a split on top of `target_coin_balance`,
+    /// in percent of it.
+    #[serde(default)]
+    pub split_headroom_percent: u64,
+    /// How far the coin count may drift from the target before we act, in percent of the target.
+    pub tolerance_percent: u64,
+    /// Maximum number of coins created or merged away in a single action.
+    pub max_coins_per_action: u64,
+    /// How often do we check the coin count, in seconds.
+    /// At most one action is taken per sponsor in each interval.
+    pub interval_sec: u64,
+    /// Maximum number of coins merged by a single transaction. Larger merges take several
+    /// transactions, each merging into the coin of the previous one.
+    #[serde(default = "default_merge_batch_size")]
+    pub merge_batch_size: u64,
+}
+
+impl CoinAutoscalerConfig {
+    /// Balance of each coin created by a split: the target balance plus the headroom.
+    pub fn split_coin_balance(&self) -> u64 {
+        self.target_coin_balance * (100 + self.split_headroom_percent) / 100
+    }
+}
+
+pub fn default_merge_batch_size() -> u64 {
+    MAX_GAS_PER_QUERY as u64
+}
+
+impl Default for CoinAutoscalerConfig {
+    fn default() -> Self {
+        CoinAutoscalerConfig {
+            target_coin_count: 1000,
+            target_coin_balance: DEFAULT_INIT_COIN_BALANCE,
+            split_headroom_percent: 0,
+            tolerance_percent: 10,
+            max_coins_per_action: 200,
+            interval_sec: DEFAULT_AUTOSCALER_INTERVAL_SEC,
+            merge_batch_size: default_merge_batch_size(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::MultiSigMemberConfig;
+    use sui_types::crypto::{get_account_key_pair, EncodeDecodeBase64, SuiKeyPair};
+
+    #[test]
+    fn test_multisig_member_local_keypairs() {
+        let keypairs: Vec<SuiKeyPair> = (0..2).map(|_| get_account_key_pair().1.into()).collect();
+        let member = |keypairs: &[SuiKeyPair]| {
+            serde_json::from_value::<MultiSigMemberConfig>(serde_json::json!({
+                "signer": {
+                    "local": {
+                        "keypair": keypairs
+                            .iter()
+                            .map(|keypair| keypair.encode_base64())
+                            .collect::<Vec<_>>(),
+                    },
+                },
+                "public-key": keypairs[0].public().encode_base64(),
+                "weight": 1,
+            }))
+        };
+        assert!(member(&keypairs[..1]).is_ok());
+        let err = member(&keypairs).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("A local multisig member takes exactly one keypair, got 2"));
+    }
+}
@@ -1,6 +1,8 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::time::Duration;
+
 #[macro_export]
 macro_rules! retry_with_max_attempts {
     ($func:expr, $max_attempts:expr) => {{
@@ -10,6 +12,14 @@ macro_rules! retry_with_max_attempts {
             .map(tokio_retry::strategy::jitter);
         tokio_retry::Retry::spawn(retry_strategy, || $func).await
     }};
+    // Only the errors for which `$retry_if` returns true are retried.
+    ($func:expr, $max_attempts:expr, $retry_if:expr) => {{
+        let retry_strategy = tokio_retry::strategy::ExponentialBackoff::from_millis(50)
+            .max_delay(std::time::Duration::from_secs(1))
+            .take($max_attempts)
+            .map(tokio_retry::strategy::jitter);
+        tokio_retry::RetryIf::spawn(retry_strategy, || $func, $retry_if).await
+    }};
 }
 
 #[cfg(not(test))]
@@ -28,3 +38,147 @@ macro_rules! retry_forever {
         $func.await
     }};
 }
+
+/// Failures of the gas pool that callers need to tell apart from other errors, e.g. to report
+/// them in metrics. They are returned wrapped in an anyhow::Error, and can be recovered from it
+/// through `downcast_ref`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GasPoolError {
+    /// The pool does not have enough available coins to cover the requested budget. Carries the
+    /// total balance and number of the coins that were available, so that the caller can tell
+    /// whether a smaller budget would go through, and how long until the next reservation
+    /// expires and gives its coins back, if there is any.
+    InsufficientCoins {
+        available_balance: u64,
+        available_coin_count: u64,
+        retry_after: Option<Duration>,
+    },
+    /// The pool has enough balance, but not within the number of coins the caller allows.
+    TooFragmented,
+    /// The daily gas usage cap has been reached. It resets after `retry_after`.
+    DailyCapExceeded { retry_after: Duration },
+    /// An operator froze all new reservations.
+    ReservationsFrozen,
+    /// The storage did not respond within the configured timeout.
+    StorageTimeout,
+    /// The sponsor already runs as many concurrent executions as it is allowed to.
+    SponsorBusy,
+    /// The transaction pays with coins that are not part of the reservation it names, e.g.
+    /// coins reserved through another server sharing the storage.
+    PaymentNotReserved,
+}
+
+impl std::fmt::Display for GasPoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GasPoolError::InsufficientCoins {
+                available_balance,
+                available_coin_count,
+                retry_after,
+            } => {
+                write!(
+                    f,
+                    "Unable to reserve gas coins for the given budget. The pool has {} coins \
+                    available with a total balance of {}",
+                    available_coin_count, available_balance
+                )?;
+                match retry_after {
+                    Some(retry_after) => write!(f, ", retry after {:?}", retry_after),
+                    None => Ok(()),
+                }
+            }
+            GasPoolError::TooFragmented => write!(
+                f,
+                "The pool is too fragmented to cover the budget within the given number of coins"
+            ),
+            GasPoolError::DailyCapExceeded { retry_after } => write!(
+                f,
+                "Gas usage exceeds daily cap, retry after {:?}",
+                retry_after
+            ),
+            GasPoolError::ReservationsFrozen => write!(f, "Reservations are frozen"),
+            GasPoolError::StorageTimeout => write!(f, "Storage timeout"),
+            GasPoolError::SponsorBusy => {
+                write!(f, "Sponsor is busy with too many concurrent executions")
+            }
+            GasPoolError::PaymentNotReserved => write!(
+                f,
+                "Transaction pays with gas coins that are not part of the reservation"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GasPoolError {}
+
+impl GasPoolError {
+    /// Returns the gas pool error wrapped in the given error, if there is one.
+    pub fn from_anyhow(err: &anyhow::Error) -> Option<Self> {
+        err.downcast_ref::<GasPoolError>().copied()
+    }
+
+    /// How long the caller should wait before trying again, for the failures that are expected
+    /// to clear up on their own after a known time.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            GasPoolError::InsufficientCoins { retry_after, .. } => *retry_after,
+            GasPoolError::DailyCapExceeded { retry_after } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+/// Reasons why `check_transaction_validity` rejects a transaction, so that callers can tell them
+/// apart, e.g. to report a transaction the client needs to fix as a bad request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ValidationError {
+    /// A command uses the gas coin, which may only pay for gas.
+    GasCoinMisused,
+    /// The transaction has more commands and arguments than the pool accepts.
+    TooComplex {
+        complexity: u64,
+        max_complexity: u64,
+    },
+    /// The transaction publishes a package, which the pool is configured not to sponsor.
+    PublishDisallowed,
+    /// The transaction upgrades a package, which the pool is configured not to sponsor.
+    UpgradeDisallowed,
+    /// The BCS serialized transaction data is larger than the pool accepts, in bytes.
+    TooLarge { size: usize, max_size: usize },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::GasCoinMisused => write!(f, "Gas coin can only be used to pay gas"),
+            ValidationError::TooComplex {
+                complexity,
+                max_complexity,
+            } => write!(
+                f,
+                "Transaction complexity {} exceeds the limit of {}",
+                complexity, max_complexity
+            ),
+            ValidationError::PublishDisallowed => {
+                write!(f, "Publishing packages is not sponsored by this gas pool")
+            }
+            ValidationError::UpgradeDisallowed => {
+                write!(f, "Upgrading packages is not sponsored by this gas pool")
+            }
+            ValidationError::TooLarge { size, max_size } => write!(
+                f,
+                "Transaction data size {} bytes exceeds the limit of {} bytes",
+                size, max_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl ValidationError {
+    /// Returns the validation error wrapped in the given error, if there is one.
+    pub fn from_anyhow(err: &anyhow::Error) -> Option<Self> {
+        err.downcast_ref::<ValidationError>().copied()
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::sui_client::SuiClient;
+use itertools::Itertools;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use sui_types::transaction::{Command, TransactionData, TransactionDataAPI, TransactionKind};
+
+/// How many of the most recent executions of a category the historical average is taken over.
+const HISTORY_WINDOW: usize = 100;
+
+/// Estimates the gas a transaction uses (computation plus storage cost, without rebate) before
+/// it is executed.
+#[async_trait::async_trait]
+pub trait GasEstimator: Send + Sync {
+    async fn estimate(&self, tx_data: &TransactionData) -> anyhow::Result<u64>;
+    /// Called with the gas used by every executed transaction, for estimators that learn from
+    /// past executions.
+    fn record_execution(&self, _tx_kind: &TransactionKind, _gas_used: u64) {}
+}
+
+/// Groups transactions that are expected to use about the same gas: the Move functions they
+/// call, in order, e.g. "0x2::coin::split". Transactions without Move calls are all "builtin".
+pub fn transaction_category(tx_kind: &TransactionKind) -> String {
+    let calls = tx_kind
+        .iter_commands()
+        .filter_map(|command| match command {
+            Command::MoveCall(call) => Some(format!(
+                "{}::{}::{}",
+                call.package, call.module, call.function
+            )),
+            _ => None,
+        })
+        .join(",");
+    if calls.is_empty() {
+        "builtin".to_string()
+    } else {
+        calls
+    }
+}
+
+/// Dev-inspects the transaction on the fullnode.
+pub struct DevInspectGasEstimator {
+    sui_client: SuiClient,
+}
+
+impl DevInspectGasEstimator {
+    pub fn new(sui_client: SuiClient) -> Arc<Self> {
+        Arc::new(Self { sui_client })
+    }
+}
+
+#[async_trait::async_trait]
+impl GasEstimator for DevInspectGasEstimator {
+    async fn estimate(&self, tx_data: &TransactionData) -> anyhow::Result<u64> {
+        self.sui_client.estimate_gas_used(tx_data).await
+    }
+}
+
+/// Uses a fixed estimate per transaction category, without any round trip.
+pub struct StaticGasEstimator {
+    budgets: BTreeMap<String, u64>,
+    default_budget: u64,
+}
+
+impl StaticGasEstimator {
+    pub fn new(budgets: BTreeMap<String, u64>, default_budget: u64) -> Arc<Self> {
+        Arc::new(Self {
+            budgets,
+            default_budget,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl GasEstimator for StaticGasEstimator {
+    async fn estimate(&self, tx_data: &TransactionData) -> anyhow::Result<u64> {
+        let category = transaction_category(tx_data.kind());
+        Ok(self
+            .budgets
+            .get(&category)
+            .copied()
+            .unwrap_or(self.default_budget))
+    }
+}
+
+/// Averages the gas used by the recent executions of the same transaction category. Categories
+/// with fewer than `min_samples` executions are estimated by the fallback instead.
+pub struct HistoricalAverageGasEstimator {
+    fallback: Arc<dyn GasEstimator>,
+    min_samples: usize,
+    history: Mutex<HashMap<String, VecDeque<u64>>>,
+}
+
+impl HistoricalAverageGasEstimator {
+    pub fn new(fallback: Arc<dyn GasEstimator>, min_samples: usize) -> Arc<Self> {
+        Arc::new(Self {
+            fallback,
+            min_samples,
+            history: Default::default(),
+        })
+    }
+
+    fn average(&self, category: &str) -> Option<u64> {
+        let history = self.history.lock();
+        let samples = history.get(category)?;
+        if samples.is_empty() || samples.len() < self.min_samples {
+            return None;
+        }
+        let total: u128 = samples.iter().map(|gas_used| *gas_used as u128).sum();
+        Some((total / samples.len() as u128) as u64)
+    }
+}
+
+#[async_trait::async_trait]
+impl GasEstimator for HistoricalAverageGasEstimator {
+    async fn estimate(&self, tx_data: &TransactionData) -> anyhow::Result<u64> {
+        match self.average(&transaction_category(tx_data.kind())) {
+            Some(average) => Ok(average),
+            None => self.fallback.estimate(tx_data).await,
+        }
+    }
+
+    fn record_execution(&self, tx_kind: &TransactionKind, gas_used: u64) {
+        let mut history = self.history.lock();
+        let samples = history.entry(transaction_category(tx_kind)).or_default();
+        samples.push_back(gas_used);
+        if samples.len() > HISTORY_WINDOW {
+            samples.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sui_types::base_types::{random_object_ref, SuiAddress};
+    use sui_types::programmable_transaction_builder::ProgrammableTransactionBuilder;
+    use sui_types::Identifier;
+
+    fn move_call_tx(function: &str) -> TransactionData {
+        let mut builder = ProgrammableTransactionBuilder::new();
+        builder.programmable_move_call(
+            sui_types::SUI_FRAMEWORK_PACKAGE_ID,
+            Identifier::new("coin").unwrap(),
+            Identifier::new(function).unwrap(),
+            vec![],
+            vec![],
+        );
+        TransactionData::new_programmable(
+            SuiAddress::ZERO,
+            vec![random_object_ref()],
+            builder.finish(),
+            1000,
+            1,
+        )
+    }
+
+    #[test]
+    fn test_transaction_category() {
+        assert_eq!(
+            transaction_category(move_call_tx("split").kind()),
+            format!("{}::coin::split", sui_types::SUI_FRAMEWORK_PACKAGE_ID)
+        );
+        let kind = TransactionKind::programmable(ProgrammableTransactionBuilder::new().finish());
+        assert_eq!(transaction_category(&kind), "builtin");
+    }
+
+    #[tokio::test]
+    async fn test_static_gas_estimator() {
+        let split = move_call_tx("split");
+        let budgets = BTreeMap::from([(transaction_category(split.kind()), 500)]);
+        let estimator = StaticGasEstimator::new(budgets, 100);
+        assert_eq!(estimator.estimate(&split).await.unwrap(), 500);
+        assert_eq!(
+            estimator.estimate(&move_call_tx("join")).await.unwrap(),
+            100
+        );
+    }
+
+    #[tokio::test]
+    async fn test_historical_average_gas_estimator() {
+        let split = move_call_tx("split");
+        let estimator =
+            HistoricalAverageGasEstimator::new(StaticGasEstimator::new(BTreeMap::new(), 100), 2);
+        estimator.record_execution(split.kind(), 300);
+        // Not enough samples yet.
+        assert_eq!(estimator.estimate(&split).await.unwrap(), 100);
+        estimator.record_execution(split.kind(), 500);
+        assert_eq!(estimator.estimate(&split).await.unwrap(), 400);
+        // Other categories are not affected.
+        assert_eq!(
+            estimator.estimate(&move_call_tx("join")).await.unwrap(),
+            100
+        );
+
+        for _ in 0..HISTORY_WINDOW {
+            estimator.record_execution(split.kind(), 1000);
+        }
+        assert_eq!(estimator.estimate(&split).await.unwrap(), 1000);
+    }
+}
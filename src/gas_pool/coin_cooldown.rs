@@ -0,0 +1,107 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use sui_types::base_types::ObjectID;
+
+/// Keeps track of the coins that recently took part in failed executions. A coin that failed
+/// `threshold` times within `cooldown` is cooling down: reservations avoid it until its oldest
+/// recent failure is older than `cooldown`, after which it is picked as usual again.
+pub struct CoinCooldown {
+    threshold: usize,
+    cooldown: Duration,
+    failures: Mutex<HashMap<ObjectID, VecDeque<Instant>>>,
+}
+
+impl CoinCooldown {
+    pub fn new(threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            failures: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold > 0
+    }
+
+    pub fn record_failures(&self, object_ids: &[ObjectID]) {
+        if !self.is_enabled() {
+            return;
+        }
+        let now = Instant::now();
+        let mut failures = self.failures.lock();
+        for object_id in object_ids {
+            let coin_failures = failures.entry(*object_id).or_default();
+            coin_failures.push_back(now);
+            // Only the most recent failures matter to tell whether the coin is cooling down.
+            if coin_failures.len() > self.threshold {
+                coin_failures.pop_front();
+            }
+        }
+    }
+
+    pub fn is_cooling_down(&self, object_id: &ObjectID) -> bool {
+        self.failures
+            .lock()
+            .get(object_id)
+            .is_some_and(|coin_failures| self.is_cooling_down_impl(coin_failures))
+    }
+
+    /// The coins that are currently cooling down. Coins whose failures are all too old to
+    /// matter are forgotten along the way.
+    pub fn cooling_down_coins(&self) -> Vec<ObjectID> {
+        let mut failures = self.failures.lock();
+        failures.retain(|_, coin_failures| {
+            coin_failures
+                .back()
+                .is_some_and(|last| last.elapsed() < self.cooldown)
+        });
+        failures
+            .iter()
+            .filter(|(_, coin_failures)| self.is_cooling_down_impl(coin_failures))
+            .map(|(object_id, _)| *object_id)
+            .collect()
+    }
+
+    fn is_cooling_down_impl(&self, coin_failures: &VecDeque<Instant>) -> bool {
+        coin_failures.len() >= self.threshold
+            && coin_failures
+                .front()
+                .is_some_and(|first| first.elapsed() < self.cooldown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coin_cooldown() {
+        let coin = ObjectID::random();
+        let other_coin = ObjectID::random();
+        let cooldown = CoinCooldown::new(2, Duration::from_secs(60));
+        cooldown.record_failures(&[coin, other_coin]);
+        assert!(!cooldown.is_cooling_down(&coin));
+        cooldown.record_failures(&[coin]);
+        assert!(cooldown.is_cooling_down(&coin));
+        assert!(!cooldown.is_cooling_down(&other_coin));
+        assert_eq!(cooldown.cooling_down_coins(), vec![coin]);
+    }
+
+    #[test]
+    fn test_coin_cooldown_expires() {
+        let coin = ObjectID::random();
+        let cooldown = CoinCooldown::new(1, Duration::ZERO);
+        cooldown.record_failures(&[coin]);
+        assert!(!cooldown.is_cooling_down(&coin));
+        assert!(cooldown.cooling_down_coins().is_empty());
+
+        let disabled = CoinCooldown::new(0, Duration::from_secs(60));
+        disabled.record_failures(&[coin]);
+        assert!(!disabled.is_cooling_down(&coin));
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use sui_types::base_types::SuiAddress;
+
+/// An eventually consistent count of the available coins of each sponsor, for callers that poll
+/// it too often to query the storage every time. The pool adjusts it as it reserves and releases
+/// coins, and periodically resets it to the count in storage, which also makes up for changes
+/// made to the storage by others.
+pub struct CoinCountCache {
+    counts: HashMap<SuiAddress, AtomicI64>,
+}
+
+impl CoinCountCache {
+    pub fn new(sponsors: impl IntoIterator<Item = SuiAddress>) -> Self {
+        Self {
+            counts: sponsors
+                .into_iter()
+                .map(|sponsor| (sponsor, AtomicI64::new(0)))
+                .collect(),
+        }
+    }
+
+    /// Adds `delta` to the count of the sponsor. Unknown sponsors are ignored.
+    pub fn add(&self, sponsor: SuiAddress, delta: i64) {
+        if let Some(count) = self.counts.get(&sponsor) {
+            count.fetch_add(delta, Ordering::Relaxed);
+        }
+    }
+
+    /// The cached count of the sponsor, None for a sponsor of another pool. A count that drifted
+    /// below zero between two reconciliations reads as zero.
+    pub fn get(&self, sponsor: SuiAddress) -> Option<u64> {
+        self.counts
+            .get(&sponsor)
+            .map(|count| count.load(Ordering::Relaxed).max(0) as u64)
+    }
+
+    /// Resets the count of the sponsor to the authoritative one, and returns how far off the
+    /// cached count was.
+    pub fn reconcile(&self, sponsor: SuiAddress, authoritative: u64) -> i64 {
+        self.counts
+            .get(&sponsor)
+            .map(|count| count.swap(authoritative as i64, Ordering::Relaxed) - authoritative as i64)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoinCountCache;
+    use sui_types::base_types::SuiAddress;
+
+    #[test]
+    fn test_coin_count_cache() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let cache = CoinCountCache::new([sponsor]);
+        assert_eq!(cache.get(sponsor), Some(0));
+        assert_eq!(cache.get(SuiAddress::random_for_testing_only()), None);
+        cache.add(sponsor, 10);
+        cache.add(sponsor, -3);
+        assert_eq!(cache.get(sponsor), Some(7));
+        // The storage had two coins the cache did not see.
+        assert_eq!(cache.reconcile(sponsor, 9), -2);
+        assert_eq!(cache.get(sponsor), Some(9));
+        cache.add(sponsor, -12);
+        assert_eq!(cache.get(sponsor), Some(0));
+        assert_eq!(cache.reconcile(sponsor, 0), -3);
+    }
+}
@@ -1,19 +1,36 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::audit_log::{AuditLog, AuditLogEntry};
+use crate::config::{
+    GasPoolCoreConfig, PostExecutionRelease, ReadConsistency, ReferenceGasPriceFallback,
+    UnusedReservedCoins,
+};
+use crate::errors::{GasPoolError, ValidationError};
+use crate::gas_estimator::GasEstimator;
 use crate::metrics::GasPoolCoreMetrics;
-use crate::storage::Storage;
-use crate::sui_client::SuiClient;
+use crate::storage::{InstrumentedStorage, Storage, MAX_GAS_PER_QUERY};
+use crate::sui_client::{default_execute_response_options, SuiClient};
 use crate::tx_signer::TxSigner;
-use crate::types::{GasCoin, ReservationID};
+use crate::types::{
+    ActiveReservation, BalancePercentiles, CoinLookup, ExecutedTransaction, ExecutionResultSummary,
+    ExpirationTimeMs, GasCoin, GasUsageWindowStatus, PoolSnapshot, ReservationID,
+};
 use crate::{retry_forever, retry_with_max_attempts};
-use anyhow::bail;
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use futures_util::StreamExt;
+use itertools::{Either, Itertools};
+use parking_lot::{Mutex, RwLock};
+use prometheus::IntGauge;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use sui_json_rpc_types::{
-    SuiTransactionBlockEffects, SuiTransactionBlockEffectsAPI, SuiTransactionBlockEvents,
-};
-use sui_types::base_types::{ObjectID, ObjectRef, SuiAddress};
+use std::time::{Duration, Instant, SystemTime};
+use sui_json_rpc_types::{SuiTransactionBlockEffectsAPI, SuiTransactionBlockResponseOptions};
+use sui_types::base_types::{ObjectID, ObjectRef, SequenceNumber, SuiAddress};
+use sui_types::digests::{ObjectDigest, TransactionDigest};
 use sui_types::gas_coin::MIST_PER_OCT;
 use sui_types::programmable_transaction_builder::ProgrammableTransactionBuilder;
 use sui_types::quorum_driver_types::ExecuteTransactionRequestType;
@@ -21,27 +38,181 @@ use sui_types::signature::GenericSignature;
 use sui_types::transaction::{
     Argument, Command, Transaction, TransactionData, TransactionDataAPI, TransactionKind,
 };
+use sui_types::TypeTag;
 use tap::TapFallible;
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
+use super::coin_cooldown::CoinCooldown;
+use super::coin_count_cache::CoinCountCache;
 use super::gas_usage_cap::GasUsageCap;
+use super::reservation_read_cache::ReservationReadCache;
+use super::warm_reservation_cache::{WarmReservation, WarmReservationCache};
+
+/// What the startup reconciliation does with a persisted reservation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ReservationCheck {
+    /// All of its coins are still owned by the sponsor.
+    Keep,
+    /// Some of its coins are gone, and are dropped from the reservation.
+    Refresh(Vec<ObjectID>),
+    /// None of its coins are left, so the reservation is ended.
+    Expire,
+}
+
+/// The optional parameters of `GasPool::reserve_gas`.
+#[derive(Clone, Debug, Default)]
+pub struct ReserveGasOptions {
+    /// Bounds how many coins are returned, e.g. when the transaction can only take a few gas
+    /// payment objects.
+    pub max_coins: Option<usize>,
+    /// Groups the reservation with others for `list_reservations_by_tag` and
+    /// `cancel_reservations_by_tag`.
+    pub tag: Option<String>,
+    /// Kept as is for `get_reservation_metadata`, for the reservation duration plus
+    /// `execution_result_ttl_sec`. At most `max_reservation_metadata_bytes` long.
+    pub metadata: Option<Vec<u8>>,
+    /// A lease reservation expires after at most `reservation_heartbeat_interval_ms`, whatever
+    /// its duration, unless `heartbeat_reservation` keeps it alive, so that its coins go back to
+    /// the pool soon after its client stops sending heartbeats.
+    pub lease: bool,
+}
 
+/// Tracing target of the reservation and execution flow, tunable separately via RUST_LOG.
+const LOG_TARGET: &str = "gas_pool::core";
 const EXPIRATION_JOB_INTERVAL: Duration = Duration::from_secs(1);
+const SCHEDULED_EXECUTION_JOB_INTERVAL: Duration = Duration::from_secs(1);
+/// How many times a reservation is retried to make room for the smash headroom. The headroom
+/// only grows with the coin count, so this rarely takes more than two.
+const MAX_SMASH_HEADROOM_ATTEMPTS: usize = 3;
+/// How many times the existence of an expired coin that could not be read is checked, before
+/// giving up on telling whether it still exists.
+const MISSING_COIN_CHECK_MAX_ATTEMPTS: usize = 3;
+/// How many times a reservation is made again to avoid the coins on cooldown.
+const MAX_COIN_COOLDOWN_ATTEMPTS: usize = 3;
+/// How long an oversized gas coin is held for the coin unlock task to split it.
+const OVERSIZED_GAS_COIN_HOLD_DURATION: Duration = Duration::from_secs(10 * 60);
+// 10 mins.
+pub const MAX_DURATION_S: u64 = 10 * 60;
+/// How long an on-chain balance read for a sponsor is reused.
+const ONCHAIN_BALANCE_CACHE_TTL: Duration = Duration::from_secs(5);
+const WARM_CACHE_JOB_INTERVAL: Duration = Duration::from_secs(1);
+/// How long reservations of the warm cache are made for. They are released once they reach
+/// half of that age, so that a reservation handed out never had a chance to expire.
+const WARM_RESERVATION_DURATION: Duration = Duration::from_secs(60 * 10);
 
 pub struct GasPoolContainer {
     inner: Arc<GasPool>,
     _coin_unlocker_task: JoinHandle<()>,
-    // This is always Some. It is None only after the drop method is called.
+    _scheduled_execution_task: JoinHandle<()>,
+    /// Only started when the warm cache is enabled.
+    _warm_cache_task: Option<JoinHandle<()>>,
+    // These are always Some. They are None only after the drop method is called.
     cancel_sender: Option<tokio::sync::oneshot::Sender<()>>,
+    scheduler_cancel_sender: Option<tokio::sync::oneshot::Sender<()>>,
+    warm_cache_cancel_sender: Option<tokio::sync::oneshot::Sender<()>>,
 }
 
 pub struct GasPool {
     signer: Arc<dyn TxSigner>,
-    gas_pool_store: Arc<dyn Storage>,
+    /// Behind a lock so that `replace_storage` can point the running pool at another storage.
+    /// Always read through `store`, which does not hold the lock across storage calls.
+    gas_pool_store: RwLock<Arc<dyn Storage>>,
     sui_client: SuiClient,
     metrics: Arc<GasPoolCoreMetrics>,
     gas_usage_cap: Arc<GasUsageCap>,
+    config: GasPoolCoreConfig,
+    /// Executions that are currently in progress, or finished within the last
+    /// `execution_dedup_window_ms`, keyed by transaction digest.
+    /// A concurrent execution of the same digest waits on the receiver for the first result
+    /// instead of running the reservation accounting a second time.
+    in_flight_executions: Mutex<HashMap<TransactionDigest, InFlightExecution>>,
+    /// Set by operators to stop handing out coins, e.g. during an incident or a migration.
+    reservations_frozen: AtomicBool,
+    /// The calibrated gas cost per coin of each sponsor, used to estimate the gas it costs to
+    /// smash the coins of a reservation. Calibrated on first use.
+    smash_gas_cost_per_coin: Mutex<HashMap<SuiAddress, u64>>,
+    warm_cache: WarmReservationCache,
+    coin_cooldown: CoinCooldown,
+    gas_estimator: Arc<dyn GasEstimator>,
+    audit_log: Arc<dyn AuditLog>,
+    /// Bounds the concurrent executions of the sponsors that have a limit configured.
+    sponsor_execution_permits: HashMap<SuiAddress, Arc<Semaphore>>,
+    /// When the on-chain coins of each sponsor were last looked up for a reservation that ran
+    /// out of coins, to rate limit the lookups.
+    last_onchain_coin_fallback: Mutex<HashMap<SuiAddress, Instant>>,
+    /// Gas coins that exceeded `split_gas_coins_above` after an execution, each held under its
+    /// own reservation until the coin unlock task splits it. A coin whose reservation expires
+    /// first, e.g. because this server restarted, goes back to the pool as it is.
+    oversized_gas_coins: Mutex<Vec<(ReservationID, GasCoin)>>,
+    /// The duration each reservation made through this server was requested with, and when it
+    /// was made, until it is executed or its duration is over.
+    reservation_timings: Mutex<HashMap<(SuiAddress, ReservationID), (Duration, Instant)>>,
+    /// The last on-chain balance read for each sponsor, and when it was read.
+    onchain_balances: Mutex<HashMap<SuiAddress, (u64, Instant)>>,
+    /// The available coin count of each sponsor, kept up to date without querying the storage.
+    coin_counts: CoinCountCache,
+    /// When `coin_counts` was last reconciled with the storage.
+    last_coin_count_reconcile: Mutex<Option<Instant>>,
+    /// Recent reads of the expiration time and metadata of reservations.
+    reservation_reads: ReservationReadCache,
+}
+
+/// The result of an execution, shared with concurrent executions of the same digest.
+/// The error is stored as a string since anyhow::Error cannot be cloned.
+type SharedExecutionResult = Option<Result<ExecutedTransaction, String>>;
+
+struct InFlightExecution {
+    receiver: watch::Receiver<SharedExecutionResult>,
+    /// Set once the result is in, when results are kept for the dedup window.
+    finished_at: Option<Instant>,
+}
+
+impl InFlightExecution {
+    fn is_live(&self, dedup_window: Duration) -> bool {
+        self.finished_at
+            .is_none_or(|finished_at| finished_at.elapsed() < dedup_window)
+    }
+}
+
+/// Removes the digest from the in-flight executions once the execution finishes,
+/// including when the execution future is dropped before completing. Finished executions
+/// are kept for the dedup window instead, so that a retry arriving right after the first
+/// execution still gets its result.
+struct InFlightGuard<'a> {
+    in_flight_executions: &'a Mutex<HashMap<TransactionDigest, InFlightExecution>>,
+    digest: TransactionDigest,
+    dedup_window: Duration,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut in_flight_executions = self.in_flight_executions.lock();
+        match in_flight_executions.get_mut(&self.digest) {
+            Some(execution)
+                if !self.dedup_window.is_zero() && execution.receiver.borrow().is_some() =>
+            {
+                execution.finished_at = Some(Instant::now());
+            }
+            _ => {
+                in_flight_executions.remove(&self.digest);
+            }
+        }
+    }
+}
+
+/// One of the execution permits of a sponsor. Keeps the in-flight gauge of the sponsor up to
+/// date until it is dropped.
+pub(crate) struct SponsorExecutionPermit {
+    _permit: OwnedSemaphorePermit,
+    in_flight: IntGauge,
+}
+
+impl Drop for SponsorExecutionPermit {
+    fn drop(&mut self) {
+        self.in_flight.dec();
+    }
 }
 
 impl GasPool {
@@ -51,30 +222,405 @@ impl GasPool {
         sui_client: SuiClient,
         metrics: Arc<GasPoolCoreMetrics>,
         gas_usage_cap: Arc<GasUsageCap>,
+        config: GasPoolCoreConfig,
     ) -> Arc<Self> {
+        let gas_estimator = config.gas_estimator.new_estimator(sui_client.clone());
+        let audit_log = config
+            .audit_log
+            .new_audit_log()
+            .expect("Failed to open the audit log");
+        let coin_counts = CoinCountCache::new(signer.get_addresses());
         let pool = Self {
             signer,
-            gas_pool_store,
+            gas_pool_store: RwLock::new(gas_pool_store),
             sui_client,
             metrics,
             gas_usage_cap,
+            in_flight_executions: Default::default(),
+            reservations_frozen: AtomicBool::new(false),
+            smash_gas_cost_per_coin: Default::default(),
+            warm_cache: WarmReservationCache::new(
+                config.warm_budget_buckets.clone(),
+                config.warm_reservations_per_bucket,
+            ),
+            gas_estimator,
+            audit_log,
+            coin_cooldown: CoinCooldown::new(
+                config.coin_cooldown_failure_threshold,
+                Duration::from_millis(config.coin_cooldown_ms),
+            ),
+            sponsor_execution_permits: config
+                .max_concurrent_executions_per_sponsor
+                .iter()
+                .map(|(sponsor, limit)| (*sponsor, Arc::new(Semaphore::new(*limit))))
+                .collect(),
+            last_onchain_coin_fallback: Default::default(),
+            oversized_gas_coins: Default::default(),
+            reservation_timings: Default::default(),
+            onchain_balances: Default::default(),
+            coin_counts,
+            last_coin_count_reconcile: Default::default(),
+            reservation_reads: ReservationReadCache::new(
+                config.reservation_read_cache_capacity,
+                Duration::from_millis(config.reservation_read_cache_ttl_ms),
+            ),
+            config,
         };
+        pool.metrics
+            .daily_gas_usage_cap
+            .set(pool.gas_usage_cap.daily_cap() as i64);
         Arc::new(pool)
     }
 
+    /// The storage currently in use. Callers hold on to it for as long as one storage call, so
+    /// that `replace_storage` takes effect on the next call.
+    fn store(&self) -> Arc<dyn Storage> {
+        self.gas_pool_store.read().clone()
+    }
+
+    /// Changes the daily gas usage cap without a restart. The usage so far today is kept, so
+    /// lowering the cap below it stops new reservations right away.
+    pub fn set_daily_gas_cap(&self, daily_cap: u64) {
+        self.gas_usage_cap.set_daily_cap(daily_cap);
+        self.metrics.daily_gas_usage_cap.set(daily_cap as i64);
+        info!(target: LOG_TARGET, "Daily gas usage cap set to {}", daily_cap);
+    }
+
+    pub fn get_daily_gas_cap(&self) -> u64 {
+        self.gas_usage_cap.daily_cap()
+    }
+
+    /// Freezes or unfreezes new reservations. While frozen, reservations fail immediately,
+    /// but existing reservations can still be executed so that in-flight work drains.
+    pub fn set_reservations_frozen(&self, frozen: bool) {
+        self.reservations_frozen.store(frozen, Ordering::Relaxed);
+        self.metrics.reservations_frozen.set(frozen as i64);
+        info!(target: LOG_TARGET, "Reservations frozen: {}", frozen);
+    }
+
+    pub fn are_reservations_frozen(&self) -> bool {
+        self.reservations_frozen.load(Ordering::Relaxed)
+    }
+
+    /// The sponsors currently in service: the signer's addresses, restricted to the
+    /// `active_sponsors` allowlist when one is configured.
+    pub fn get_active_sponsors(&self) -> Vec<SuiAddress> {
+        let addresses = self.signer.get_addresses();
+        match &self.config.active_sponsors {
+            Some(active_sponsors) => addresses
+                .into_iter()
+                .filter(|address| active_sponsors.contains(address))
+                .collect(),
+            None => addresses,
+        }
+    }
+
+    fn is_active_sponsor(&self, sponsor: &SuiAddress) -> bool {
+        self.signer.is_valid_address(sponsor)
+            && self
+                .config
+                .active_sponsors
+                .as_ref()
+                .is_none_or(|active_sponsors| active_sponsors.contains(sponsor))
+    }
+
+    fn check_sponsor_active(&self, sponsor: SuiAddress) -> anyhow::Result<()> {
+        if !self.is_active_sponsor(&sponsor) {
+            self.record_reserve_outcome(sponsor, "inactive_sponsor");
+            bail!("Sponsor {:?} is not active", sponsor);
+        }
+        Ok(())
+    }
+
+    fn check_reservations_not_frozen(&self, sponsor: SuiAddress) -> anyhow::Result<()> {
+        if self.are_reservations_frozen() {
+            self.record_reserve_outcome(sponsor, "frozen");
+            return Err(GasPoolError::ReservationsFrozen.into());
+        }
+        Ok(())
+    }
+
+    /// Reserves gas coins covering `gas_budget`, see `ReserveGasOptions` for the options.
+    /// The returned object refs are complete, with the version and digest the pool last saw for
+    /// each coin, i.e. as of its last execution through the pool. They are read again from the
+    /// fullnode first when `refresh_reserved_coin_versions` is set.
     pub async fn reserve_gas(
         &self,
         sponsor_address: Option<SuiAddress>,
         gas_budget: u64,
         duration: Duration,
+        options: ReserveGasOptions,
     ) -> anyhow::Result<(SuiAddress, ReservationID, Vec<ObjectRef>)> {
-        let sponsor_address = sponsor_address.unwrap_or(self.signer.get_addresses()[0]);
-        let cur_time = std::time::Instant::now();
-        self.gas_usage_cap.check_usage().await?;
-        let (reservation_id, gas_coins) = self
-            .gas_pool_store
-            .reserve_gas_coins(sponsor_address, gas_budget, duration.as_millis() as u64)
+        let (sponsor, reservation_id, gas_coins, _smash_headroom) = self
+            .reserve_gas_with_headroom(sponsor_address, gas_budget, duration, options)
+            .await?;
+        Ok((sponsor, reservation_id, gas_coins))
+    }
+
+    /// Same as `reserve_gas`, but reserves until an absolute `deadline` instead of for a
+    /// duration. The deadline must be in the future and at most `MAX_DURATION_S` away; it is
+    /// turned into a duration against this server's clock, so clock skew with the caller shifts
+    /// the actual expiration.
+    pub async fn reserve_gas_until(
+        &self,
+        sponsor_address: Option<SuiAddress>,
+        gas_budget: u64,
+        deadline: SystemTime,
+        max_coins: Option<usize>,
+        tag: Option<String>,
+        metadata: Option<Vec<u8>>,
+    ) -> anyhow::Result<(SuiAddress, ReservationID, Vec<ObjectRef>)> {
+        let duration = deadline
+            .duration_since(SystemTime::now())
+            .ok()
+            .filter(|duration| !duration.is_zero())
+            .ok_or_else(|| anyhow!("Reservation deadline {:?} is in the past", deadline))?;
+        if duration > Duration::from_secs(MAX_DURATION_S) {
+            bail!(
+                "Reservation deadline {:?} is more than {} seconds away",
+                deadline,
+                MAX_DURATION_S
+            );
+        }
+        self.reserve_gas(
+            sponsor_address,
+            gas_budget,
+            duration,
+            ReserveGasOptions {
+                max_coins,
+                tag,
+                metadata,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Imports the coins the sponsor owns on chain that the pool does not know about, e.g. coins
+    /// sent to the sponsor after the pool was initialized, when `onchain_coin_fallback_interval_ms`
+    /// is set and the sponsor was not looked up within that interval. Returns how many coins
+    /// were imported; failures and timeouts are only logged, since the reservation then fails
+    /// as it would have anyway.
+    async fn import_untracked_onchain_coins(&self, sponsor: SuiAddress) -> usize {
+        let Some(interval_ms) = self.config.onchain_coin_fallback_interval_ms else {
+            return 0;
+        };
+        {
+            let mut last_lookups = self.last_onchain_coin_fallback.lock();
+            let now = Instant::now();
+            if last_lookups.get(&sponsor).is_some_and(|last_lookup| {
+                now.duration_since(*last_lookup) < Duration::from_millis(interval_ms)
+            }) {
+                return 0;
+            }
+            last_lookups.insert(sponsor, now);
+        }
+        // Coins are imported one page of the fullnode response at a time, so the pages imported
+        // before a failure or a timeout stay imported and are counted.
+        let imported = AtomicUsize::new(0);
+        let import = async {
+            // Empty coins cannot pay for anything.
+            let mut pages = std::pin::pin!(self
+                .sui_client
+                .stream_owned_sui_coins_above_balance_threshold(sponsor, 1));
+            while let Some(onchain_coins) = pages.next().await {
+                let mut untracked_coins = vec![];
+                for coin in onchain_coins {
+                    if self.store().find_coin(coin.object_ref.0).await?.is_none() {
+                        untracked_coins.push(coin);
+                    }
+                }
+                let count = untracked_coins.len();
+                let not_added = self
+                    .store()
+                    .add_new_coins_in_batches(untracked_coins, self.config.add_coins_batch_size)
+                    .await?;
+                let page_imported = count - not_added.len();
+                self.record_coin_count_change(sponsor, page_imported as i64);
+                imported.fetch_add(page_imported, Ordering::Relaxed);
+            }
+            anyhow::Ok(())
+        };
+        let timeout = Duration::from_millis(self.config.onchain_coin_fallback_timeout_ms);
+        let result = tokio::time::timeout(timeout, import).await;
+        let imported = imported.into_inner();
+        self.metrics
+            .num_onchain_fallback_coins_imported
+            .with_label_values(&[&sponsor.to_string()])
+            .inc_by(imported as u64);
+        match result {
+            Ok(Ok(())) => {
+                info!(
+                    target: LOG_TARGET,
+                    ?sponsor,
+                    "Pool ran out of coins, imported {} coins found on chain", imported
+                );
+            }
+            Ok(Err(err)) => {
+                error!(
+                    target: LOG_TARGET,
+                    ?sponsor,
+                    "Failed to import the coins found on chain, imported {} of them: {:?}",
+                    imported,
+                    err
+                );
+            }
+            Err(_) => {
+                warn!(
+                    target: LOG_TARGET,
+                    ?sponsor,
+                    "Timed out looking up the coins found on chain, imported {} of them",
+                    imported
+                );
+            }
+        }
+        imported
+    }
+
+    /// Same as `reserve_gas`, but reserves a single coin that covers the whole budget, failing
+    /// if no coin does. The transaction then pays with this coin alone, so its ID stays the
+    /// same after the transaction and callers can keep referring to it.
+    pub async fn reserve_single_gas_coin(
+        &self,
+        sponsor_address: Option<SuiAddress>,
+        gas_budget: u64,
+        duration: Duration,
+        tag: Option<String>,
+        metadata: Option<Vec<u8>>,
+    ) -> anyhow::Result<(SuiAddress, ReservationID, ObjectRef)> {
+        let (sponsor, reservation_id, gas_coins) = self
+            .reserve_gas(
+                sponsor_address,
+                gas_budget,
+                duration,
+                ReserveGasOptions {
+                    max_coins: Some(1),
+                    tag,
+                    metadata,
+                    ..Default::default()
+                },
+            )
             .await?;
+        Ok((sponsor, reservation_id, gas_coins[0]))
+    }
+
+    /// Same as `reserve_gas`, but also returns the headroom reserved on top of the budget to pay
+    /// for smashing the coins together, which is 0 unless `reserve_smash_headroom` is set.
+    pub async fn reserve_gas_with_headroom(
+        &self,
+        sponsor_address: Option<SuiAddress>,
+        gas_budget: u64,
+        duration: Duration,
+        options: ReserveGasOptions,
+    ) -> anyhow::Result<(SuiAddress, ReservationID, Vec<ObjectRef>, u64)> {
+        let ReserveGasOptions {
+            max_coins,
+            tag,
+            metadata,
+            lease,
+        } = options;
+        let sponsor_address = match sponsor_address {
+            Some(sponsor_address) => sponsor_address,
+            None => *self
+                .get_active_sponsors()
+                .first()
+                .ok_or_else(|| anyhow!("No sponsor is active"))?,
+        };
+        self.check_sponsor_active(sponsor_address)?;
+        if let Some(metadata) = &metadata {
+            if metadata.len() > self.config.max_reservation_metadata_bytes {
+                self.record_reserve_outcome(sponsor_address, "other_error");
+                bail!(
+                    "Reservation metadata of {} bytes exceeds the limit of {} bytes",
+                    metadata.len(),
+                    self.config.max_reservation_metadata_bytes
+                );
+            }
+        }
+        self.check_reservations_not_frozen(sponsor_address)?;
+        let mut duration = self.clamp_reservation_duration(duration);
+        if lease {
+            duration = duration.min(Duration::from_millis(
+                self.config.reservation_heartbeat_interval_ms,
+            ));
+        }
+        let cur_time = std::time::Instant::now();
+        self.gas_usage_cap
+            .check_usage()
+            .await
+            .tap_err(|err| self.record_reserve_failure(sponsor_address, err))?;
+        let target_budget =
+            Self::round_up_budget(gas_budget, self.config.reservation_budget_granularity);
+        let reserve = || {
+            self.reserve_gas_coins_with_headroom(
+                sponsor_address,
+                target_budget,
+                max_coins.unwrap_or(MAX_GAS_PER_QUERY),
+                duration.as_millis() as u64,
+            )
+        };
+        let mut result = reserve().await;
+        let out_of_coins = matches!(
+            result.as_ref().map_err(GasPoolError::from_anyhow),
+            Err(Some(GasPoolError::InsufficientCoins { .. }))
+        );
+        if out_of_coins && self.import_untracked_onchain_coins(sponsor_address).await > 0 {
+            result = reserve().await;
+        }
+        let (reservation_id, gas_coins, smash_headroom) =
+            result.tap_err(|err| self.record_reserve_failure(sponsor_address, err))?;
+        // Coins recorded without a digest could not be used to build a transaction, so they are
+        // read again whatever the config says.
+        let gas_coins = if self.config.refresh_reserved_coin_versions
+            || gas_coins
+                .iter()
+                .any(|coin| coin.object_ref.2 == ObjectDigest::MIN)
+        {
+            self.refresh_reserved_coins(
+                sponsor_address,
+                reservation_id,
+                target_budget.saturating_add(smash_headroom),
+                gas_coins,
+            )
+            .await
+            .tap_err(|err| self.record_reserve_failure(sponsor_address, err))?
+        } else {
+            gas_coins
+        };
+        let finished = async {
+            self.hold_reservation_unconfirmed(sponsor_address, reservation_id)
+                .await?;
+            if let Some(tag) = tag {
+                self.with_storage_timeout(self.store().tag_reservation(
+                    sponsor_address,
+                    reservation_id,
+                    &tag,
+                ))
+                .await?;
+            }
+            if let Some(metadata) = metadata {
+                let ttl = duration + Duration::from_secs(self.config.execution_result_ttl_sec);
+                self.with_storage_timeout(self.store().set_reservation_metadata(
+                    sponsor_address,
+                    reservation_id,
+                    &metadata,
+                    ttl.as_millis() as u64,
+                ))
+                .await?;
+            }
+            anyhow::Ok(())
+        };
+        if let Err(err) = finished.await {
+            self.record_reserve_failure(sponsor_address, &err);
+            self.release_failed_reservation(sponsor_address, reservation_id, gas_coins)
+                .await;
+            return Err(err);
+        }
+        self.reservation_timings.lock().insert(
+            (sponsor_address, reservation_id),
+            (duration, Instant::now()),
+        );
+        self.record_reserve_outcome(sponsor_address, "success");
         let elapsed = cur_time.elapsed().as_millis();
         self.metrics.reserve_gas_latency_ms.observe(elapsed as u64);
         self.metrics
@@ -84,167 +630,1945 @@ impl GasPool {
             sponsor_address,
             reservation_id,
             gas_coins.into_iter().map(|c| c.object_ref).collect(),
+            smash_headroom,
         ))
     }
 
-    pub async fn execute_transaction(
+    /// Reserves coins covering the budget plus, when `reserve_smash_headroom` is set, the gas
+    /// it costs to smash them into one during execution. That cost depends on how many coins
+    /// are picked, so a reservation that turns out short is released and retried with the
+    /// headroom of its coin count. Returns the headroom that was reserved.
+    async fn reserve_gas_coins_with_headroom(
         &self,
-        reservation_id: ReservationID,
-        tx_data: TransactionData,
-        request_type: Option<ExecuteTransactionRequestType>,
-        user_sig: GenericSignature,
-    ) -> anyhow::Result<(
-        Option<u64>,
-        SuiTransactionBlockEffects,
-        Option<SuiTransactionBlockEvents>,
-    )> {
-        let sponsor = tx_data.gas_data().owner;
-        if !self.signer.is_valid_address(&sponsor) {
-            bail!("Sponsor {:?} is not registered", sponsor);
-        };
-        Self::check_transaction_validity(&tx_data)?;
-        let payment: Vec<_> = tx_data
-            .gas_data()
-            .payment
-            .iter()
-            .map(|oref| oref.0)
-            .collect();
-        let payment_count = payment.len();
-        debug!(
-            ?reservation_id,
-            "Payment coins in transaction: {:?}", payment
-        );
-        self.gas_pool_store
-            .ready_for_execution(sponsor, reservation_id)
-            .await?;
-        debug!(?reservation_id, "Reservation is ready for execution");
+        sponsor: SuiAddress,
+        target_budget: u64,
+        max_coins: usize,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<(ReservationID, Vec<GasCoin>, u64)> {
+        let mut headroom = 0;
+        for _ in 0..MAX_SMASH_HEADROOM_ATTEMPTS {
+            let budget = target_budget.saturating_add(headroom);
+            let warm_reservation = self
+                .take_warm_reservation(sponsor, budget, max_coins, reserved_duration_ms)
+                .await;
+            let (reservation_id, gas_coins) = match warm_reservation {
+                Some(reservation) => (reservation.reservation_id, reservation.gas_coins),
+                None => {
+                    self.reserve_gas_coins_avoiding_cooldown(
+                        sponsor,
+                        budget,
+                        max_coins,
+                        reserved_duration_ms,
+                    )
+                    .await?
+                }
+            };
+            if !self.config.reserve_smash_headroom {
+                return Ok((reservation_id, gas_coins, 0));
+            }
+            let cost_per_coin = self.smash_gas_cost_per_coin(sponsor, &gas_coins).await;
+            let needed_headroom = Self::smash_headroom(cost_per_coin, gas_coins.len());
+            let total_balance: u64 = gas_coins.iter().map(|c| c.balance).sum();
+            if total_balance >= target_budget.saturating_add(needed_headroom) {
+                return Ok((reservation_id, gas_coins, needed_headroom));
+            }
+            let coin_ids = gas_coins.iter().map(|c| c.object_ref.0).collect();
+            self.release_coins_from_reservation(sponsor, reservation_id, coin_ids, gas_coins)
+                .await?;
+            headroom = needed_headroom;
+        }
+        Err(self.insufficient_coins(sponsor).await.into())
+    }
 
-        // To avoid read-after-write inconsistency, we apply a trick here to calculate the
-        // new balance of the gas coin after the transaction.
-        // We first query the total balance prior to transaction execution, then execute the
-        // transaction, and finally derive the new gas coin balance using the gas usage from effects.
-        let total_gas_coin_balance = self.get_total_gas_coin_balance(payment.clone()).await;
-        debug!(
-            ?reservation_id,
-            "Total gas coin balance prior to execution: {}", total_gas_coin_balance,
-        );
-        let response = self
-            .execute_transaction_impl(reservation_id, tx_data, request_type, user_sig)
-            .await;
-        let updated_coins = match &response {
-            Ok((_, effects, _)) => {
-                let new_gas_coin = effects.gas_object().reference.to_object_ref();
-                let new_balance =
-                    total_gas_coin_balance as i64 - effects.gas_cost_summary().net_gas_usage();
-                debug!(
-                    ?reservation_id,
-                    "New gas coin balance after execution: {}", new_balance,
-                );
-                #[cfg(test)]
-                {
-                    self.sui_client.wait_for_object(new_gas_coin).await;
-                    assert_eq!(
-                        self.get_total_gas_coin_balance(payment).await,
-                        new_balance as u64
+    /// The error for a budget the available coins of the sponsor do not cover, with what is
+    /// available at this point.
+    async fn insufficient_coins(&self, sponsor: SuiAddress) -> GasPoolError {
+        let store = self.store();
+        GasPoolError::InsufficientCoins {
+            available_balance: store.get_available_coin_total_balance(sponsor).await,
+            available_coin_count: store
+                .get_available_coin_count(sponsor)
+                .await
+                .unwrap_or_default() as u64,
+            retry_after: store
+                .get_next_reservation_expiry(sponsor)
+                .await
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Reserves coins from the storage, trying again when the reservation picked a coin that is
+    /// on cooldown. Releasing the reservation moves its coins to the back of the queue, so the
+    /// next attempt starts from other coins. Coins on cooldown are only avoided, not excluded:
+    /// the last attempt is kept even if it still holds one of them.
+    async fn reserve_gas_coins_avoiding_cooldown(
+        &self,
+        sponsor: SuiAddress,
+        budget: u64,
+        max_coins: usize,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<(ReservationID, Vec<GasCoin>)> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let (reservation_id, gas_coins) = self
+                .with_storage_timeout(self.store().reserve_gas_coins_with_max_coins(
+                    sponsor,
+                    budget,
+                    max_coins,
+                    reserved_duration_ms,
+                ))
+                .await?;
+            self.record_coin_count_change(sponsor, -(gas_coins.len() as i64));
+            if !self.coin_cooldown.is_enabled()
+                || attempt >= MAX_COIN_COOLDOWN_ATTEMPTS
+                || !gas_coins
+                    .iter()
+                    .any(|c| self.coin_cooldown.is_cooling_down(&c.object_ref.0))
+            {
+                return Ok((reservation_id, gas_coins));
+            }
+            debug!(
+                target: LOG_TARGET,
+                ?reservation_id,
+                "Reservation picked a coin on cooldown, reserving again"
+            );
+            let coin_ids = gas_coins.iter().map(|c| c.object_ref.0).collect();
+            self.release_coins_from_reservation(sponsor, reservation_id, coin_ids, gas_coins)
+                .await?;
+        }
+    }
+
+    /// The coins that new reservations currently avoid because of their recent execution
+    /// failures.
+    pub fn get_cooling_down_coins(&self) -> Vec<ObjectID> {
+        self.coin_cooldown.cooling_down_coins()
+    }
+
+    /// Hands out a reservation of the warm cache that covers the budget, with the requested
+    /// duration. Cached reservations that no longer exist in the storage are skipped.
+    async fn take_warm_reservation(
+        &self,
+        sponsor: SuiAddress,
+        budget: u64,
+        max_coins: usize,
+        reserved_duration_ms: u64,
+    ) -> Option<WarmReservation> {
+        if !self.warm_cache.is_enabled() || self.warm_cache.bucket_for(budget).is_none() {
+            return None;
+        }
+        let cur_time = Instant::now();
+        while let Some(reservation) = self.warm_cache.take(sponsor, budget, max_coins) {
+            let reservation_id = reservation.reservation_id;
+            match self
+                .with_storage_timeout(self.store().reset_reservation_expiration(
+                    sponsor,
+                    reservation_id,
+                    reserved_duration_ms,
+                ))
+                .await
+            {
+                Ok(()) => {
+                    self.record_warm_cache_outcome(sponsor, "hit");
+                    self.metrics
+                        .warm_cache_reserve_latency_ms
+                        .observe(cur_time.elapsed().as_millis() as u64);
+                    return Some(reservation);
+                }
+                Err(err) => {
+                    debug!(
+                        target: LOG_TARGET,
+                        ?reservation_id,
+                        "Dropping warm reservation: {:?}", err
                     );
+                    // Its coins would otherwise stay reserved until it expires, if it did not
+                    // already.
+                    self.release_failed_reservation(sponsor, reservation_id, reservation.gas_coins)
+                        .await;
                 }
-                vec![GasCoin {
-                    owner: sponsor,
-                    object_ref: new_gas_coin,
-                    balance: new_balance as u64,
-                }]
             }
-            Err(_) => {
+        }
+        self.record_warm_cache_outcome(sponsor, "miss");
+        None
+    }
+
+    fn record_warm_cache_outcome(&self, sponsor: SuiAddress, outcome: &str) {
+        self.metrics
+            .num_warm_cache_reservations
+            .with_label_values(&[&sponsor.to_string(), outcome])
+            .inc();
+    }
+
+    /// Releases the warm reservations that are getting old, and tops up every bucket.
+    async fn refill_warm_cache(&self) {
+        for (sponsor, reservation) in self.warm_cache.take_stale(WARM_RESERVATION_DURATION / 2) {
+            let coin_ids = reservation
+                .gas_coins
+                .iter()
+                .map(|c| c.object_ref.0)
+                .collect();
+            // The coins were never handed out, so they go back to the pool as they are.
+            if let Err(err) = self
+                .release_coins_from_reservation(
+                    sponsor,
+                    reservation.reservation_id,
+                    coin_ids,
+                    reservation.gas_coins,
+                )
+                .await
+            {
                 debug!(
-                    ?reservation_id,
-                    "Querying latest gas state since transaction failed"
+                    target: LOG_TARGET,
+                    "Failed to release stale warm reservation: {:?}", err
                 );
-                self.sui_client
-                    .get_latest_gas_objects(payment)
-                    .await
-                    .into_values()
-                    .flatten()
-                    .collect()
             }
+        }
+        if self.are_reservations_frozen() {
+            return;
+        }
+        for sponsor in self.get_active_sponsors() {
+            for bucket in self.warm_cache.buckets() {
+                for _ in 0..self.warm_cache.missing(sponsor, *bucket) {
+                    match self
+                        .store()
+                        .reserve_gas_coins(
+                            sponsor,
+                            *bucket,
+                            WARM_RESERVATION_DURATION.as_millis() as u64,
+                        )
+                        .await
+                    {
+                        Ok((reservation_id, gas_coins)) => {
+                            self.record_coin_count_change(sponsor, -(gas_coins.len() as i64));
+                            self.warm_cache.insert(
+                                sponsor,
+                                *bucket,
+                                WarmReservation::new(reservation_id, gas_coins),
+                            )
+                        }
+                        Err(err) => {
+                            debug!(
+                                target: LOG_TARGET,
+                                ?sponsor,
+                                "Failed to refill warm bucket {}: {:?}", bucket, err
+                            );
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The gas it costs to smash `coin_count` coins into one.
+    pub(crate) fn smash_headroom(cost_per_coin: u64, coin_count: usize) -> u64 {
+        cost_per_coin.saturating_mul(coin_count.saturating_sub(1) as u64)
+    }
+
+    async fn smash_gas_cost_per_coin(&self, sponsor: SuiAddress, gas_coins: &[GasCoin]) -> u64 {
+        if let Some(cost) = self.smash_gas_cost_per_coin.lock().get(&sponsor) {
+            return *cost;
+        }
+        let Some(gas_coin) = gas_coins.first() else {
+            return 0;
         };
-        let smashed_coin_count = payment_count - updated_coins.len();
-        // Regardless of whether the transaction succeeded, we need to release the coins.
-        // Otherwise, we lose track of them. This is because `ready_for_execution` already takes
-        // the coins out of the pool and will not be covered by the auto-release mechanism.
-        self.release_gas_coins(updated_coins).await;
-        if smashed_coin_count > 0 {
+        let cost = self
+            .sui_client
+            .calibrate_gas_cost_per_object(sponsor, gas_coin)
+            .await;
+        info!(
+            target: LOG_TARGET,
+            ?sponsor,
+            "Calibrated smash gas cost per coin: {}", cost
+        );
+        self.smash_gas_cost_per_coin.lock().insert(sponsor, cost);
+        cost
+    }
+
+    /// Replaces the reserved coins with their latest state on chain, so that clients never build
+    /// transactions with a stale coin version. Coins that are gone are dropped from the
+    /// reservation, and if the rest no longer covers the budget, the whole reservation is
+    /// released and the reservation fails as if the pool did not have enough coins.
+    /// The storage only records the IDs of reserved coins, so there is nothing to update there;
+    /// their latest state is read again after execution anyway.
+    async fn refresh_reserved_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        target_budget: u64,
+        gas_coins: Vec<GasCoin>,
+    ) -> anyhow::Result<Vec<GasCoin>> {
+        let mut latest_coins = self
+            .sui_client
+            .get_latest_gas_objects(gas_coins.iter().map(|c| c.object_ref.0))
+            .await;
+        let mut refreshed_coins = vec![];
+        let mut gone = vec![];
+        let mut stale_count = 0;
+        for coin in gas_coins {
+            match latest_coins
+                .remove(&coin.object_ref.0)
+                .flatten()
+                .filter(|c| c.owner == sponsor)
+            {
+                Some(latest_coin) => {
+                    if latest_coin.object_ref != coin.object_ref {
+                        stale_count += 1;
+                    }
+                    refreshed_coins.push(latest_coin);
+                }
+                None => gone.push(coin.object_ref.0),
+            }
+        }
+        self.metrics
+            .num_refreshed_stale_gas_coins
+            .with_label_values(&[&sponsor.to_string()])
+            .inc_by(stale_count);
+        if !gone.is_empty() {
             info!(
+                target: LOG_TARGET,
                 ?reservation_id,
-                "Smashed {:?} coins after transaction execution", smashed_coin_count
+                "Dropping coins {:?} that are gone from the reservation", gone
             );
-            self.metrics
-                .num_smashed_gas_coins
-                .with_label_values(&[&sponsor.to_string()])
-                .inc_by(smashed_coin_count as u64);
+            self.release_coins_from_reservation(sponsor, reservation_id, gone, vec![])
+                .await?;
+        }
+        let total_balance: u64 = refreshed_coins.iter().map(|c| c.balance).sum();
+        if total_balance < target_budget {
+            let coin_ids = refreshed_coins.iter().map(|c| c.object_ref.0).collect();
+            self.release_coins_from_reservation(sponsor, reservation_id, coin_ids, refreshed_coins)
+                .await?;
+            return Err(self.insufficient_coins(sponsor).await.into());
         }
-        info!(?reservation_id, "Transaction execution finished");
+        Ok(refreshed_coins)
+    }
 
-        response
+    /// Raises durations below `min_reservation_duration_ms` to it.
+    fn clamp_reservation_duration(&self, duration: Duration) -> Duration {
+        duration.max(Duration::from_millis(
+            self.config.min_reservation_duration_ms,
+        ))
     }
 
-    async fn execute_transaction_impl(
+    /// Rounds the budget up to the next multiple of `granularity`. The result is never below
+    /// the budget: if rounding up overflows, the budget becomes u64::MAX, which no pool covers.
+    pub(crate) fn round_up_budget(budget: u64, granularity: u64) -> u64 {
+        if granularity <= 1 {
+            return budget;
+        }
+        budget.div_ceil(granularity).saturating_mul(granularity)
+    }
+
+    /// Reserves exactly the given gas coins, failing if any of them is already reserved or not
+    /// in the pool. This gives callers that track coin state themselves full control over the
+    /// gas payment.
+    pub async fn reserve_specific_coins(
         &self,
-        reservation_id: ReservationID,
-        tx_data: TransactionData,
-        request_type: Option<ExecuteTransactionRequestType>,
-        user_sig: GenericSignature,
-    ) -> anyhow::Result<(
-        Option<u64>,
-        SuiTransactionBlockEffects,
-        Option<SuiTransactionBlockEvents>,
-    )> {
-        let sponsor = tx_data.gas_data().owner;
+        sponsor_address: SuiAddress,
+        coin_ids: Vec<ObjectID>,
+        duration: Duration,
+    ) -> anyhow::Result<(SuiAddress, ReservationID, Vec<ObjectRef>)> {
+        self.check_sponsor_active(sponsor_address)?;
+        self.check_reservations_not_frozen(sponsor_address)?;
+        let duration = self.clamp_reservation_duration(duration);
         let cur_time = std::time::Instant::now();
-        let sponsor_sig = retry_with_max_attempts!(
-            async {
-                self.signer
-                    .sign_transaction(&tx_data)
-                    .await
-                    .tap_err(|err| error!("Failed to sign transaction: {:?}", err))
-            },
+        self.gas_usage_cap
+            .check_usage()
+            .await
+            .tap_err(|err| self.record_reserve_failure(sponsor_address, err))?;
+        let (reservation_id, gas_coins) = self
+            .with_storage_timeout(self.store().reserve_specific_coins(
+                sponsor_address,
+                coin_ids,
+                duration.as_millis() as u64,
+            ))
+            .await
+            .tap_err(|err| self.record_reserve_failure(sponsor_address, err))?;
+        self.record_coin_count_change(sponsor_address, -(gas_coins.len() as i64));
+        if let Err(err) = self
+            .hold_reservation_unconfirmed(sponsor_address, reservation_id)
+            .await
+        {
+            self.record_reserve_failure(sponsor_address, &err);
+            self.release_failed_reservation(sponsor_address, reservation_id, gas_coins)
+                .await;
+            return Err(err);
+        }
+        self.record_reserve_outcome(sponsor_address, "success");
+        let elapsed = cur_time.elapsed().as_millis();
+        self.metrics.reserve_gas_latency_ms.observe(elapsed as u64);
+        self.metrics
+            .reserved_gas_coin_count_per_request
+            .observe(gas_coins.len() as u64);
+        Ok((
+            sponsor_address,
+            reservation_id,
+            gas_coins.into_iter().map(|c| c.object_ref).collect(),
+        ))
+    }
+
+    /// Gives the coins back to the pool when a reservation fails after its coins were reserved.
+    /// The caller never learns the reservation ID, so they would otherwise stay reserved until
+    /// the reservation expires.
+    async fn release_failed_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        gas_coins: Vec<GasCoin>,
+    ) {
+        let coin_ids = gas_coins.iter().map(|c| c.object_ref.0).collect();
+        if let Err(err) = self
+            .release_coins_from_reservation(sponsor, reservation_id, coin_ids, gas_coins)
+            .await
+        {
+            error!(
+                target: LOG_TARGET,
+                ?reservation_id,
+                "Failed to release the coins of a failed reservation: {:?}", err
+            );
+        }
+    }
+
+    /// Makes a new reservation tentative when confirmations are required.
+    async fn hold_reservation_unconfirmed(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<()> {
+        let Some(ttl_ms) = self.config.reservation_confirmation_ttl_ms else {
+            return Ok(());
+        };
+        self.with_storage_timeout(self.store().hold_reservation_unconfirmed(
+            sponsor,
+            reservation_id,
+            ttl_ms,
+        ))
+        .await
+    }
+
+    /// Confirms a reservation, which is required before it can be executed when
+    /// `reservation_confirmation_ttl_ms` is set. Confirming twice is fine, but confirming a
+    /// reservation that already expired fails.
+    pub async fn confirm_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<()> {
+        let result = self
+            .with_storage_timeout(self.store().confirm_reservation(sponsor, reservation_id))
+            .await;
+        self.reservation_reads.invalidate(sponsor, reservation_id);
+        result
+    }
+
+    /// Keeps a reservation alive for another `reservation_heartbeat_interval_ms`, for clients
+    /// that hold it for as long as they run rather than for a fixed duration. Without further
+    /// heartbeats, the expiration task reclaims its coins once that interval passes. Fails if
+    /// the reservation already expired or is not confirmed yet.
+    pub async fn heartbeat_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<()> {
+        let result = self
+            .with_storage_timeout(self.store().heartbeat_reservation(
+                sponsor,
+                reservation_id,
+                self.config.reservation_heartbeat_interval_ms,
+            ))
+            .await;
+        self.reservation_reads.invalidate(sponsor, reservation_id);
+        result
+    }
+
+    /// Returns the metadata the reservation was made with, if any. It outlives the reservation
+    /// by `execution_result_ttl_sec`, so it can still be read once the reservation is executed.
+    pub async fn get_reservation_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        if !self.signer.is_valid_address(&sponsor) {
+            bail!("Sponsor {:?} is not registered", sponsor);
+        }
+        if let Some(metadata) = self.reservation_reads.get_metadata(sponsor, reservation_id) {
+            return Ok(metadata);
+        }
+        let generation = self.reservation_reads.generation(sponsor, reservation_id);
+        let metadata = self
+            .store()
+            .get_reservation_metadata(sponsor, reservation_id)
+            .await?;
+        self.reservation_reads
+            .put_metadata(sponsor, reservation_id, metadata.clone(), generation);
+        Ok(metadata)
+    }
+
+    /// Returns when the reservation expires, in milliseconds since the epoch, or None if it
+    /// already expired or its coins are already used for execution.
+    pub async fn get_reservation_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<ExpirationTimeMs>> {
+        if !self.signer.is_valid_address(&sponsor) {
+            bail!("Sponsor {:?} is not registered", sponsor);
+        }
+        if let Some(expiration) = self
+            .reservation_reads
+            .get_expiration(sponsor, reservation_id)
+        {
+            return Ok(expiration);
+        }
+        let generation = self.reservation_reads.generation(sponsor, reservation_id);
+        let expiration = self
+            .store()
+            .get_reservation_expiration(sponsor, reservation_id)
+            .await?;
+        self.reservation_reads
+            .put_expiration(sponsor, reservation_id, expiration, generation);
+        Ok(expiration)
+    }
+
+    /// Returns the coins the reservation still holds, or None if it already expired or its
+    /// coins are already used for execution.
+    pub async fn get_reservation_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<Vec<ObjectID>>> {
+        if !self.signer.is_valid_address(&sponsor) {
+            bail!("Sponsor {:?} is not registered", sponsor);
+        }
+        if let Some(coins) = self.reservation_reads.get_coins(sponsor, reservation_id) {
+            return Ok(coins);
+        }
+        let generation = self.reservation_reads.generation(sponsor, reservation_id);
+        let coins = self
+            .store()
+            .get_reservation_coins(sponsor, reservation_id)
+            .await?;
+        // The coins of a reservation that expired stay there until the expiration task returns
+        // them to the pool.
+        let expiration = match coins {
+            Some(_) => {
+                self.store()
+                    .get_reservation_expiration(sponsor, reservation_id)
+                    .await?
+            }
+            None => None,
+        };
+        let now_ms = Utc::now().timestamp_millis() as u64;
+        let coins = coins.filter(|_| expiration.is_some_and(|expiration| expiration > now_ms));
+        self.reservation_reads
+            .put_expiration(sponsor, reservation_id, expiration, generation);
+        self.reservation_reads
+            .put_coins(sponsor, reservation_id, coins.clone(), generation);
+        Ok(coins)
+    }
+
+    /// Returns the reservations of every sponsor with the given tag that still hold their coins.
+    pub async fn list_reservations_by_tag(
+        &self,
+        tag: &str,
+    ) -> anyhow::Result<Vec<(SuiAddress, ReservationID)>> {
+        let mut reservations = vec![];
+        for sponsor in self.signer.get_addresses() {
+            let reservation_ids = self.store().get_reservations_by_tag(sponsor, tag).await?;
+            reservations.extend(reservation_ids.into_iter().map(|id| (sponsor, id)));
+        }
+        Ok(reservations)
+    }
+
+    /// Cancels the reservations of every sponsor with the given tag, returning their coins to the
+    /// pool, and returns the cancelled reservations. Reservations that already started executing
+    /// are not cancelled.
+    pub async fn cancel_reservations_by_tag(
+        &self,
+        tag: &str,
+    ) -> anyhow::Result<Vec<(SuiAddress, ReservationID)>> {
+        let mut cancelled = vec![];
+        for sponsor in self.signer.get_addresses() {
+            let reservation_ids = self
+                .store()
+                .cancel_reservations_by_tag(sponsor, tag)
+                .await?;
+            cancelled.extend(reservation_ids.into_iter().map(|id| (sponsor, id)));
+        }
+        for (sponsor, reservation_id) in &cancelled {
+            self.reservation_reads.invalidate(*sponsor, *reservation_id);
+        }
+        if !cancelled.is_empty() {
+            info!(
+                target: LOG_TARGET,
+                "Cancelled reservations {:?} with tag {:?}", cancelled, tag
+            );
+            // The reservations are expired now, so there is no need to wait for the coin unlock
+            // task to return their coins.
+            self.release_expired_coins().await;
+        }
+        Ok(cancelled)
+    }
+
+    /// Executes the transaction and releases its gas coins back to the pool.
+    /// If the same transaction is already being executed, e.g. because a client retried after
+    /// a timeout, this waits for and returns the result of that execution instead. So does a
+    /// transaction that finished executing within `execution_dedup_window_ms`.
+    pub async fn execute_transaction(
+        &self,
+        reservation_id: ReservationID,
+        tx_data: TransactionData,
+        request_type: Option<ExecuteTransactionRequestType>,
+        user_sig: GenericSignature,
+    ) -> anyhow::Result<ExecutedTransaction> {
+        self.execute_transaction_with_options(
+            reservation_id,
+            tx_data,
+            request_type,
+            default_execute_response_options(),
+            user_sig,
+        )
+        .await
+    }
+
+    /// Same as `execute_transaction`, but asks the fullnode for the parts of the response given
+    /// in `response_options` instead of the effects and events. A caller that waits for the
+    /// same transaction already being executed gets the response of that execution, with
+    /// whatever options it was asked with.
+    pub async fn execute_transaction_with_options(
+        &self,
+        reservation_id: ReservationID,
+        tx_data: TransactionData,
+        request_type: Option<ExecuteTransactionRequestType>,
+        response_options: SuiTransactionBlockResponseOptions,
+        user_sig: GenericSignature,
+    ) -> anyhow::Result<ExecutedTransaction> {
+        let digest = tx_data.digest();
+        let dedup_window = Duration::from_millis(self.config.execution_dedup_window_ms);
+        let in_flight = {
+            let mut in_flight_executions = self.in_flight_executions.lock();
+            in_flight_executions.retain(|_, execution| execution.is_live(dedup_window));
+            match in_flight_executions.get(&digest) {
+                Some(execution) => Err(execution.receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    in_flight_executions.insert(
+                        digest,
+                        InFlightExecution {
+                            receiver,
+                            finished_at: None,
+                        },
+                    );
+                    Ok(sender)
+                }
+            }
+        };
+        let sender = match in_flight {
+            Ok(sender) => sender,
+            Err(mut receiver) => {
+                info!(
+                    target: LOG_TARGET,
+                    ?reservation_id,
+                    "Transaction {:?} is already being executed, waiting for its result",
+                    digest
+                );
+                self.record_execute_outcome(tx_data.gas_data().owner, "deduplicated");
+                let result = receiver
+                    .wait_for(Option::is_some)
+                    .await
+                    .map_err(|_| anyhow!("Concurrent execution of {:?} was aborted", digest))?
+                    .clone()
+                    .unwrap();
+                return result.map_err(|err| anyhow!(err));
+            }
+        };
+        let _guard = InFlightGuard {
+            in_flight_executions: &self.in_flight_executions,
+            digest,
+            dedup_window,
+        };
+        let response = self
+            .execute_and_release(
+                reservation_id,
+                tx_data,
+                request_type,
+                response_options,
+                user_sig,
+            )
+            .await;
+        sender.send_replace(Some(
+            response
+                .as_ref()
+                .map(Clone::clone)
+                .map_err(|err| format!("{:#}", err)),
+        ));
+        response
+    }
+
+    async fn execute_and_release(
+        &self,
+        reservation_id: ReservationID,
+        tx_data: TransactionData,
+        request_type: Option<ExecuteTransactionRequestType>,
+        response_options: SuiTransactionBlockResponseOptions,
+        user_sig: GenericSignature,
+    ) -> anyhow::Result<ExecutedTransaction> {
+        let sponsor = tx_data.gas_data().owner;
+        if !self.signer.is_valid_address(&sponsor) {
+            self.record_execute_outcome(sponsor, "unknown_sponsor");
+            bail!("Sponsor {:?} is not registered", sponsor);
+        };
+        if !self.is_active_sponsor(&sponsor) {
+            self.record_execute_outcome(sponsor, "inactive_sponsor");
+            bail!("Sponsor {:?} is not active", sponsor);
+        }
+        Self::check_transaction_size(&tx_data, self.config.max_transaction_size_bytes)
+            .tap_err(|_| self.record_execute_outcome(sponsor, "validity_rejected"))?;
+        Self::check_transaction_validity(
+            &tx_data,
+            self.config.max_transaction_complexity,
+            self.config.reject_publish_and_upgrade,
+        )
+        .tap_err(|_| self.record_execute_outcome(sponsor, "validity_rejected"))?;
+        self.check_gas_price_limits(&tx_data)
+            .await
+            .tap_err(|_| self.record_execute_outcome(sponsor, "gas_price_rejected"))?;
+        self.check_estimated_gas_cost(&tx_data)
+            .await
+            .tap_err(|_| self.record_execute_outcome(sponsor, "gas_cost_rejected"))?;
+        let _permit = self
+            .acquire_execution_permit(sponsor)
+            .await
+            .tap_err(|_| self.record_execute_outcome(sponsor, "sponsor_busy"))?;
+        // Checked before the coins move to execution, so that a rejected transaction leaves its
+        // reservation as it was.
+        self.check_reserved_balance(&tx_data)
+            .await
+            .tap_err(|_| self.record_execute_outcome(sponsor, "insufficient_balance"))?;
+        let payment: Vec<_> = tx_data
+            .gas_data()
+            .payment
+            .iter()
+            .map(|oref| oref.0)
+            .collect();
+        let payment_count = payment.len();
+        debug!(
+            target: LOG_TARGET,
+            ?reservation_id,
+            "Payment coins in transaction: {:?}", payment
+        );
+        // Every payment coin must belong to the named reservation, since only those coins are
+        // held for this transaction. Otherwise it could pay with coins reserved by another
+        // server sharing the storage, and release them into the pool while still in use there.
+        let reserved_coins = self
+            .ready_for_execution_with_retries(sponsor, reservation_id, &payment, tx_data.digest())
+            .await
+            .tap_err(|err| {
+                self.record_execute_outcome(
+                    sponsor,
+                    match GasPoolError::from_anyhow(err) {
+                        Some(GasPoolError::StorageTimeout) => "storage_timeout",
+                        Some(GasPoolError::PaymentNotReserved) => "payment_not_reserved",
+                        _ => "reservation_invalid",
+                    },
+                )
+            })?;
+        debug!(target: LOG_TARGET, ?reservation_id, "Reservation is ready for execution");
+        let unused_coins: Vec<_> = reserved_coins
+            .into_iter()
+            .filter(|id| !payment.contains(id))
+            .collect();
+        self.observe_reservation_duration_used(sponsor, reservation_id);
+
+        // To avoid read-after-write inconsistency, we apply a trick here to calculate the
+        // new balance of the gas coin after the transaction.
+        // We first query the total balance prior to transaction execution, then execute the
+        // transaction, and finally derive the new gas coin balance using the gas usage from effects.
+        let total_gas_coin_balance = self
+            .get_total_gas_coin_balance(
+                tx_data.gas_data().payment.clone(),
+                self.config.object_read_consistency,
+            )
+            .await;
+        debug!(
+            target: LOG_TARGET,
+            ?reservation_id,
+            "Total gas coin balance prior to execution: {}", total_gas_coin_balance,
+        );
+        let request_type = Self::resolve_request_type(&self.config, sponsor, request_type);
+        let tx_kind = tx_data.kind().clone();
+        let response = self
+            .execute_transaction_impl(
+                reservation_id,
+                tx_data,
+                request_type,
+                response_options,
+                user_sig,
+            )
+            .await;
+        if let Ok(result) = &response {
+            self.save_execution_result(ExecutionResultSummary::new(reservation_id, result))
+                .await;
+            self.gas_estimator
+                .record_execution(&tx_kind, result.effects.gas_cost_summary().gas_used());
+        }
+        self.record_execute_outcome(
+            sponsor,
+            match &response {
+                Ok(result) if result.effects.status().is_ok() => "success",
+                Ok(_) => "transaction_failed",
+                Err(_) => "execution_failed",
+            },
+        );
+        if response.is_err() {
+            self.coin_cooldown.record_failures(&payment);
+        }
+        // The gas object in the effects is added back to the pool, so it must be one of the
+        // payment coins. Otherwise the pool could end up tracking a coin it never owned.
+        let unexpected_gas_object = match &response {
+            Ok(result) => Self::check_gas_object(&payment, result.effects.gas_object().object_id())
+                .tap_err(|err| {
+                    error!(target: LOG_TARGET, ?reservation_id, "{:?}", err);
+                    self.metrics
+                        .num_unexpected_gas_objects
+                        .with_label_values(&[&sponsor.to_string()])
+                        .inc();
+                })
+                .err(),
+            Err(_) => None,
+        };
+        let mut updated_coins = match &response {
+            Ok(ExecutedTransaction { effects, .. }) if unexpected_gas_object.is_none() => {
+                let new_gas_coin = effects.gas_object().reference.to_object_ref();
+                let net_gas_usage = effects.gas_cost_summary().net_gas_usage();
+                if net_gas_usage < 0 {
+                    // Storage rebates exceeded the gas cost, so the gas coin ends up with more
+                    // balance than before the transaction.
+                    debug!(
+                        target: LOG_TARGET,
+                        ?reservation_id,
+                        "Transaction resulted in a net rebate of {}", -net_gas_usage,
+                    );
+                    self.metrics
+                        .num_net_rebate_transactions
+                        .with_label_values(&[&sponsor.to_string()])
+                        .inc();
+                }
+                let new_balance =
+                    Self::balance_after_execution(total_gas_coin_balance, net_gas_usage)
+                        .unwrap_or_else(|| {
+                            self.metrics.invariant_violation(format!(
+                                "Net gas usage {} exceeds the gas coin balance {}",
+                                net_gas_usage, total_gas_coin_balance
+                            ));
+                            0
+                        });
+                debug!(
+                    target: LOG_TARGET,
+                    ?reservation_id,
+                    "New gas coin balance after execution: {}", new_balance,
+                );
+                #[cfg(test)]
+                {
+                    self.sui_client.wait_for_object(new_gas_coin).await;
+                    assert_eq!(
+                        self.get_total_gas_coin_balance(
+                            vec![new_gas_coin],
+                            ReadConsistency::BestEffort
+                        )
+                        .await,
+                        new_balance
+                    );
+                }
+                self.wait_before_release(sponsor, reservation_id, new_gas_coin)
+                    .await;
+                vec![GasCoin {
+                    owner: sponsor,
+                    object_ref: new_gas_coin,
+                    balance: new_balance,
+                }]
+            }
+            _ => {
+                debug!(
+                    target: LOG_TARGET,
+                    ?reservation_id,
+                    "Querying latest gas state since the effects cannot be used"
+                );
+                self.sui_client
+                    .get_latest_gas_objects(payment)
+                    .await
+                    .into_values()
+                    .flatten()
+                    .collect()
+            }
+        };
+        let smashed_coin_count = payment_count - updated_coins.len();
+        updated_coins.extend(
+            self.unused_reserved_coins(sponsor, reservation_id, unused_coins)
+                .await,
+        );
+        // Regardless of whether the transaction succeeded, we need to release the coins.
+        // Otherwise, we lose track of them. This is because `ready_for_execution` already takes
+        // the coins out of the pool and will not be covered by the auto-release mechanism.
+        let updated_coins = self.hold_oversized_gas_coins(updated_coins).await;
+        let released_coin_count = updated_coins.len();
+        self.release_gas_coins(updated_coins).await;
+        self.metrics
+            .num_released_gas_coins_after_execution
+            .with_label_values(&[&sponsor.to_string()])
+            .inc_by(released_coin_count as u64);
+        self.release_sponsor_objects(sponsor, reservation_id, &response)
+            .await;
+        if smashed_coin_count > 0 {
+            info!(
+                target: LOG_TARGET,
+                ?reservation_id,
+                "Smashed {:?} coins after transaction execution", smashed_coin_count
+            );
+            self.metrics
+                .num_smashed_gas_coins
+                .with_label_values(&[&sponsor.to_string()])
+                .inc_by(smashed_coin_count as u64);
+        }
+        info!(target: LOG_TARGET, ?reservation_id, "Transaction execution finished");
+        if let Some(err) = unexpected_gas_object {
+            if self.config.fail_on_unexpected_gas_object {
+                return Err(err);
+            }
+        }
+
+        response
+    }
+
+    /// Fails if the gas object reported by the effects is not one of the payment coins.
+    pub(crate) fn check_gas_object(
+        payment: &[ObjectID],
+        gas_object: ObjectID,
+    ) -> anyhow::Result<()> {
+        if !payment.contains(&gas_object) {
+            bail!(
+                "Gas object {:?} in effects is not one of the payment coins {:?}",
+                gas_object,
+                payment
+            );
+        }
+        Ok(())
+    }
+
+    /// The request type given by the caller takes precedence over the one configured for the
+    /// sponsor. When neither is set, the fullnode client falls back to `WaitForEffectsCert`.
+    pub(crate) fn resolve_request_type(
+        config: &GasPoolCoreConfig,
+        sponsor: SuiAddress,
+        request_type: Option<ExecuteTransactionRequestType>,
+    ) -> Option<ExecuteTransactionRequestType> {
+        request_type.or_else(|| config.execute_request_types.get(&sponsor).cloned())
+    }
+
+    /// Derives the balance of the gas coin after execution from its balance before execution.
+    /// `net_gas_usage` is negative when storage rebates exceed the gas cost, in which case the
+    /// balance increases. Returns None if the result would be negative, which is impossible
+    /// unless the balance before execution was wrong.
+    pub(crate) fn balance_after_execution(balance_before: u64, net_gas_usage: i64) -> Option<u64> {
+        let new_balance = balance_before as i128 - net_gas_usage as i128;
+        u64::try_from(new_balance).ok()
+    }
+
+    /// Signs the transaction, failing if the signer does not answer within `timeout`.
+    /// `on_timeout` is called when it does not.
+    pub(crate) async fn sign_with_timeout(
+        signer: &dyn TxSigner,
+        tx_data: &TransactionData,
+        timeout: Duration,
+        on_timeout: impl FnOnce(),
+    ) -> anyhow::Result<GenericSignature> {
+        match tokio::time::timeout(timeout, signer.sign_transaction(tx_data)).await {
+            Ok(result) => result,
+            Err(_) => {
+                on_timeout();
+                bail!("Signer did not answer within {:?}", timeout)
+            }
+        }
+    }
+
+    async fn execute_transaction_impl(
+        &self,
+        reservation_id: ReservationID,
+        tx_data: TransactionData,
+        request_type: Option<ExecuteTransactionRequestType>,
+        response_options: SuiTransactionBlockResponseOptions,
+        user_sig: GenericSignature,
+    ) -> anyhow::Result<ExecutedTransaction> {
+        let sponsor = tx_data.gas_data().owner;
+        let cur_time = std::time::Instant::now();
+        let sign_timeout = Duration::from_millis(self.config.sign_attempt_timeout_ms);
+        let sponsor_sig = retry_with_max_attempts!(
+            async {
+                Self::sign_with_timeout(self.signer.as_ref(), &tx_data, sign_timeout, || {
+                    self.metrics
+                        .num_signer_timeouts
+                        .with_label_values(&[&sponsor.to_string()])
+                        .inc()
+                })
+                .await
+                .tap_err(|err| error!(target: LOG_TARGET, "Failed to sign transaction: {:?}", err))
+            },
             3
         )?;
         let elapsed = cur_time.elapsed().as_millis();
         self.metrics
             .transaction_signing_latency_ms
             .observe(elapsed as u64);
-        debug!(?reservation_id, "Transaction signed by sponsor");
+        debug!(target: LOG_TARGET, ?reservation_id, "Transaction signed by sponsor");
+        self.audit_log
+            .record(AuditLogEntry {
+                sponsor,
+                transaction_digest: tx_data.digest(),
+                gas_budget: tx_data.gas_data().budget,
+                timestamp_ms: Utc::now().timestamp_millis() as u64,
+                reservation_id,
+            })
+            .await
+            .tap_err(|err| {
+                error!(
+                    target: LOG_TARGET,
+                    ?reservation_id,
+                    "Failed to record the signature in the audit log: {:?}", err
+                )
+            })?;
+
+        let gas_usage_weight = self.gas_usage_cap_weight(tx_data.kind());
+        let tx = Transaction::from_generic_sig_data(tx_data, vec![sponsor_sig, user_sig]);
+        let cur_time = std::time::Instant::now();
+        let response = self
+            .sui_client
+            .execute_transaction(tx, request_type, response_options, 3)
+            .await?;
+        debug!(target: LOG_TARGET, ?reservation_id, "Transaction executed");
+        let elapsed = cur_time.elapsed().as_millis();
+        self.metrics
+            .transaction_execution_latency_ms
+            .observe(elapsed as u64);
+        let net_gas_usage = response.effects.gas_cost_summary().net_gas_usage();
+        let new_daily_usage = self
+            .gas_usage_cap
+            .update_usage(net_gas_usage, gas_usage_weight)
+            .await;
+        self.metrics
+            .daily_gas_usage
+            .with_label_values(&[&sponsor.to_string()])
+            .set(new_daily_usage);
+        Ok(response)
+    }
+
+    /// How much the gas usage of a transaction of this kind counts against the daily cap,
+    /// relative to the gas actually used.
+    fn gas_usage_cap_weight(&self, tx_kind: &TransactionKind) -> f64 {
+        if self.config.gas_usage_cap_weights.is_empty() {
+            return 1.0;
+        }
+        let category = crate::gas_estimator::transaction_category(tx_kind);
+        self.config
+            .gas_usage_cap_weights
+            .get(&category)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Moves the coins of the reservation to in flight and returns them, retrying transient
+    /// storage errors up to `ready_for_execution_max_attempts` attempts in total. The storage
+    /// returns the same coins to a retry of the same transaction, so an attempt that went
+    /// through but whose response was lost is recovered by the next one.
+    async fn ready_for_execution_with_retries(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        payment: &[ObjectID],
+        transaction_digest: TransactionDigest,
+    ) -> anyhow::Result<Vec<ObjectID>> {
+        let result = retry_with_max_attempts!(
+            async {
+                self.with_storage_timeout(self.store().ready_for_execution_with_payment(
+                    sponsor,
+                    reservation_id,
+                    payment,
+                    transaction_digest,
+                ))
+                .await
+                .tap_err(|err| {
+                    warn!(
+                        target: LOG_TARGET,
+                        ?reservation_id,
+                        "Failed to get the reservation ready for execution: {:?}", err
+                    )
+                })
+            },
+            self.config
+                .ready_for_execution_max_attempts
+                .saturating_sub(1),
+            |err: &anyhow::Error| {
+                GasPoolError::from_anyhow(err) != Some(GasPoolError::PaymentNotReserved)
+            }
+        );
+        self.reservation_reads.invalidate(sponsor, reservation_id);
+        result
+    }
+
+    /// Fails with a storage timeout error if the storage call does not finish in time, so that
+    /// a hung storage backend does not block the request indefinitely.
+    async fn with_storage_timeout<T>(
+        &self,
+        storage_call: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        let timeout = Duration::from_millis(self.config.storage_timeout_ms);
+        match tokio::time::timeout(timeout, storage_call).await {
+            Ok(result) => result,
+            Err(_) => {
+                error!(
+                    target: LOG_TARGET,
+                    "Storage call did not finish within {:?}", timeout
+                );
+                Err(GasPoolError::StorageTimeout.into())
+            }
+        }
+    }
+
+    /// Takes one of the execution permits of the sponsor, if its concurrent executions are
+    /// limited. When all permits are taken, this waits up to `sponsor_busy_wait_ms` for one
+    /// before failing with `GasPoolError::SponsorBusy`.
+    pub(crate) async fn acquire_execution_permit(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<SponsorExecutionPermit>> {
+        let Some(semaphore) = self.sponsor_execution_permits.get(&sponsor) else {
+            return Ok(None);
+        };
+        let wait = Duration::from_millis(self.config.sponsor_busy_wait_ms);
+        let permit = match tokio::time::timeout(wait, semaphore.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => permit,
+            // The semaphore is never closed, so this can only be the timeout.
+            _ => return Err(GasPoolError::SponsorBusy.into()),
+        };
+        let in_flight = self
+            .metrics
+            .sponsor_in_flight_executions
+            .with_label_values(&[&sponsor.to_string()]);
+        in_flight.inc();
+        Ok(Some(SponsorExecutionPermit {
+            _permit: permit,
+            in_flight,
+        }))
+    }
+
+    /// Sponsors are only used as metric labels if they are registered, so that arbitrary
+    /// addresses sent by clients cannot blow up the metric cardinality.
+    fn sponsor_metric_label(&self, sponsor: SuiAddress) -> String {
+        if self.signer.is_valid_address(&sponsor) {
+            sponsor.to_string()
+        } else {
+            "unknown".to_string()
+        }
+    }
+
+    fn record_reserve_outcome(&self, sponsor: SuiAddress, outcome: &str) {
+        self.metrics
+            .reserve_gas_total
+            .with_label_values(&[&self.sponsor_metric_label(sponsor), outcome])
+            .inc();
+    }
+
+    fn record_reserve_failure(&self, sponsor: SuiAddress, err: &anyhow::Error) {
+        let outcome = match GasPoolError::from_anyhow(err) {
+            Some(GasPoolError::InsufficientCoins { .. }) => "pool_empty",
+            Some(GasPoolError::TooFragmented) => "too_fragmented",
+            Some(GasPoolError::DailyCapExceeded { .. }) => "cap_exceeded",
+            Some(GasPoolError::ReservationsFrozen) => "frozen",
+            Some(GasPoolError::StorageTimeout) => "storage_timeout",
+            Some(GasPoolError::SponsorBusy) | Some(GasPoolError::PaymentNotReserved) | None => {
+                "other_error"
+            }
+        };
+        self.record_reserve_outcome(sponsor, outcome);
+    }
+
+    fn record_execute_outcome(&self, sponsor: SuiAddress, outcome: &str) {
+        self.metrics
+            .execute_transaction_total
+            .with_label_values(&[&self.sponsor_metric_label(sponsor), outcome])
+            .inc();
+    }
+
+    #[cfg(test)]
+    pub fn metrics_for_testing(&self) -> &GasPoolCoreMetrics {
+        &self.metrics
+    }
+
+    /// Persists the execution result so that it can be looked up by digest later.
+    /// Failing to do so should not fail the execution, hence we only log the error.
+    async fn save_execution_result(&self, summary: ExecutionResultSummary) {
+        if let Err(err) = self
+            .store()
+            .save_execution_result(&summary, self.config.execution_result_ttl_sec)
+            .await
+        {
+            error!(
+                target: LOG_TARGET,
+                "Failed to persist execution result of {:?}: {:?}", summary.digest, err
+            );
+        }
+    }
+
+    /// Returns the summary of a transaction previously executed through the pool, if it is
+    /// still retained.
+    pub async fn get_execution_result(
+        &self,
+        digest: TransactionDigest,
+    ) -> anyhow::Result<Option<ExecutionResultSummary>> {
+        self.store().get_execution_result(digest).await
+    }
+
+    /// Stores a signed transaction to be executed with the given reservation at `execute_at`.
+    /// Fails if the reservation would expire before then. Scheduled executions are persisted in
+    /// the storage, so they still run if the server restarts in the meantime.
+    pub async fn schedule_execution(
+        &self,
+        reservation_id: ReservationID,
+        tx_data: TransactionData,
+        user_sig: GenericSignature,
+        execute_at: Instant,
+    ) -> anyhow::Result<()> {
+        let sponsor = tx_data.gas_data().owner;
+        if !self.signer.is_valid_address(&sponsor) {
+            bail!("Sponsor {:?} is not registered", sponsor);
+        }
+        Self::check_transaction_size(&tx_data, self.config.max_transaction_size_bytes)?;
+        Self::check_transaction_validity(
+            &tx_data,
+            self.config.max_transaction_complexity,
+            self.config.reject_publish_and_upgrade,
+        )?;
+        // Instants cannot be persisted, so the execution time is stored as a wall clock time.
+        let execute_at_ms = Utc::now().timestamp_millis() as u64
+            + execute_at
+                .saturating_duration_since(Instant::now())
+                .as_millis() as u64;
+        let Some(expiration_time) = self
+            .store()
+            .get_reservation_expiration(sponsor, reservation_id)
+            .await?
+        else {
+            bail!("Reservation {} no longer exists", reservation_id);
+        };
+        if expiration_time < execute_at_ms {
+            bail!(
+                "Reservation {} expires before the scheduled execution time",
+                reservation_id
+            );
+        }
+        self.store()
+            .save_scheduled_execution(&ScheduledExecution {
+                sponsor,
+                reservation_id,
+                tx_data,
+                user_sig,
+                execute_at_ms,
+            })
+            .await?;
+        info!(
+            target: LOG_TARGET,
+            ?reservation_id,
+            "Scheduled execution at {}", execute_at_ms
+        );
+        Ok(())
+    }
+
+    /// Returns the scheduled executions of all sponsors that have not been submitted yet.
+    pub async fn get_scheduled_executions(&self) -> anyhow::Result<Vec<ScheduledExecution>> {
+        let mut executions = vec![];
+        for sponsor in self.signer.get_addresses() {
+            executions.extend(self.store().get_scheduled_executions(sponsor).await?);
+        }
+        Ok(executions)
+    }
+
+    /// Submits every scheduled execution that is due. Each one is removed from the storage
+    /// before it is submitted, so it runs at most once even if several servers share the storage.
+    async fn run_due_scheduled_executions(self: &Arc<Self>) -> anyhow::Result<()> {
+        let now = Utc::now().timestamp_millis() as u64;
+        for execution in self.get_scheduled_executions().await? {
+            if execution.execute_at_ms > now {
+                continue;
+            }
+            let ScheduledExecution {
+                sponsor,
+                reservation_id,
+                tx_data,
+                user_sig,
+                ..
+            } = execution;
+            if !self
+                .store()
+                .remove_scheduled_execution(sponsor, reservation_id)
+                .await?
+            {
+                continue;
+            }
+            let pool = self.clone();
+            tokio::spawn(async move {
+                match pool
+                    .execute_transaction(reservation_id, tx_data, None, user_sig)
+                    .await
+                {
+                    Ok(result) => info!(
+                        target: LOG_TARGET,
+                        ?reservation_id,
+                        "Scheduled execution completed: {:?}",
+                        result.digest
+                    ),
+                    Err(err) => error!(
+                        target: LOG_TARGET,
+                        ?reservation_id,
+                        "Scheduled execution failed: {:?}",
+                        err
+                    ),
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Releases only the given coins of a reservation back to the pool, keeping the rest of the
+    /// coins reserved under the same reservation ID. Fails if any of the coins does not belong
+    /// to the reservation.
+    pub async fn release_reservation_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        coin_ids: Vec<ObjectID>,
+    ) -> anyhow::Result<()> {
+        if !self.signer.is_valid_address(&sponsor) {
+            bail!("Sponsor {:?} is not registered", sponsor);
+        }
+        // Only coins that still exist and are still owned by the sponsor go back to the pool.
+        let latest_coins: Vec<_> = self
+            .sui_client
+            .get_latest_gas_objects(coin_ids.clone())
+            .await
+            .into_values()
+            .flatten()
+            .filter(|coin| coin.owner == sponsor)
+            .collect();
+        debug!(
+            target: LOG_TARGET,
+            ?reservation_id,
+            "Releasing coins {:?} out of the reservation", coin_ids
+        );
+        self.release_coins_from_reservation(sponsor, reservation_id, coin_ids, latest_coins)
+            .await
+    }
+
+    /// Adds objects owned by the sponsor, other than gas coins, to the pool so that they can be
+    /// reserved as transaction inputs. Objects that do not exist, are not owned by the sponsor
+    /// or are gas coins are skipped. Returns the number of objects added.
+    /// Note that the chain only accepts sponsor-owned objects as inputs of transactions that
+    /// the sponsor also sends, so these are meant for transactions built by the sponsor.
+    pub async fn add_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        object_ids: Vec<ObjectID>,
+    ) -> anyhow::Result<usize> {
+        if !self.signer.is_valid_address(&sponsor) {
+            bail!("Sponsor {:?} is not registered", sponsor);
+        }
+        let object_refs = self
+            .sui_client
+            .get_latest_owned_object_refs(sponsor, object_ids)
+            .await;
+        let count = object_refs.len();
+        self.store()
+            .add_sponsor_objects(sponsor, object_refs)
+            .await?;
+        Ok(count)
+    }
+
+    /// Reserves sponsor objects alongside the gas coins of an existing reservation, and returns
+    /// their versions to build the transaction with. The objects are locked with the
+    /// reservation so they are never handed out twice, and are released once the transaction
+    /// is executed or the reservation expires.
+    pub async fn reserve_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        object_ids: Vec<ObjectID>,
+    ) -> anyhow::Result<Vec<ObjectRef>> {
+        self.check_reservations_not_frozen(sponsor)?;
+        self.with_storage_timeout(self.store().reserve_sponsor_objects(
+            sponsor,
+            reservation_id,
+            object_ids,
+        ))
+        .await
+    }
+
+    /// Puts the sponsor objects used by an executed reservation back to the pool with their new
+    /// versions. Objects the transaction deleted, wrapped or transferred away are dropped.
+    async fn release_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        response: &anyhow::Result<ExecutedTransaction>,
+    ) {
+        let object_ids = match self
+            .store()
+            .get_in_flight_sponsor_objects(sponsor, reservation_id)
+            .await
+        {
+            Ok(object_ids) if object_ids.is_empty() => return,
+            Ok(object_ids) => object_ids,
+            Err(err) => {
+                error!(
+                    target: LOG_TARGET,
+                    ?reservation_id,
+                    "Failed to get in-flight sponsor objects: {:?}", err
+                );
+                return;
+            }
+        };
+        let latest_objects = match response {
+            Ok(ExecutedTransaction { effects, .. }) => effects
+                .mutated()
+                .iter()
+                .filter(|obj| {
+                    object_ids.contains(&obj.object_id())
+                        && obj.owner.get_address_owner_address().ok() == Some(sponsor)
+                })
+                .map(|obj| obj.reference.to_object_ref())
+                .collect(),
+            // The transaction may or may not have been executed, so the chain is the only
+            // source of truth for the latest versions.
+            Err(_) => {
+                self.sui_client
+                    .get_latest_owned_object_refs(sponsor, object_ids.clone())
+                    .await
+            }
+        };
+        debug!(
+            target: LOG_TARGET,
+            ?reservation_id,
+            "Releasing {} of {} sponsor objects after execution",
+            latest_objects.len(),
+            object_ids.len()
+        );
+        if let Err(err) = self
+            .store()
+            .release_sponsor_objects(sponsor, reservation_id, latest_objects)
+            .await
+        {
+            error!(
+                target: LOG_TARGET,
+                ?reservation_id,
+                "Failed to release sponsor objects: {:?}", err
+            );
+        }
+    }
+
+    /// Checks the coins of every persisted reservation against the chain, since they may have
+    /// changed while no server was running. Reservations whose coins are all still owned by the
+    /// sponsor are kept, coins that are gone are dropped from their reservation, and
+    /// reservations left without coins are ended. The storage only records the IDs of reserved
+    /// coins, so a coin that is still owned by the sponsor is kept even if its version changed;
+    /// the execution path reads the latest balance anyway.
+    pub async fn reconcile_reservations(&self) -> anyhow::Result<()> {
+        for sponsor in self.signer.get_addresses() {
+            let snapshot = self.store().export_sponsor_snapshot(sponsor).await?;
+            let latest_coins: HashMap<_, _> = self
+                .sui_client
+                .get_latest_gas_objects(
+                    snapshot
+                        .reservations
+                        .iter()
+                        .flat_map(|r| r.object_ids.iter().cloned()),
+                )
+                .await
+                .into_iter()
+                .filter_map(|(id, coin)| coin.filter(|c| c.owner == sponsor).map(|c| (id, c)))
+                .collect();
+            for reservation in snapshot.reservations {
+                let reservation_id = reservation.reservation_id;
+                let (outcome, gone) =
+                    match Self::check_reservation(&reservation.object_ids, &latest_coins) {
+                        ReservationCheck::Keep => ("kept", vec![]),
+                        ReservationCheck::Refresh(gone) => ("refreshed", gone),
+                        ReservationCheck::Expire => ("expired", reservation.object_ids),
+                    };
+                if !gone.is_empty() {
+                    info!(
+                        target: LOG_TARGET,
+                        ?reservation_id,
+                        "Dropping coins {:?} that are gone from the reservation", gone
+                    );
+                    // The reservation may have been executed or expired in the meantime, in which
+                    // case there is nothing left to fix.
+                    if let Err(err) = self
+                        .release_coins_from_reservation(sponsor, reservation_id, gone, vec![])
+                        .await
+                    {
+                        debug!(
+                            target: LOG_TARGET,
+                            ?reservation_id,
+                            "Skipping reconciliation of reservation: {:?}", err
+                        );
+                        continue;
+                    }
+                }
+                self.metrics
+                    .num_reconciled_reservations
+                    .with_label_values(&[&sponsor.to_string(), outcome])
+                    .inc();
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_reservation(
+        object_ids: &[ObjectID],
+        latest_coins: &HashMap<ObjectID, GasCoin>,
+    ) -> ReservationCheck {
+        let gone: Vec<_> = object_ids
+            .iter()
+            .filter(|id| !latest_coins.contains_key(id))
+            .cloned()
+            .collect();
+        if gone.is_empty() {
+            ReservationCheck::Keep
+        } else if gone.len() == object_ids.len() {
+            ReservationCheck::Expire
+        } else {
+            ReservationCheck::Refresh(gone)
+        }
+    }
+
+    /// Looks up which sponsor a coin belongs to and what state it is in, for debugging.
+    pub async fn find_coin(&self, object_id: ObjectID) -> anyhow::Result<Option<CoinLookup>> {
+        self.store().find_coin(object_id).await
+    }
+
+    /// The oldest reservation of the sponsor whose coins are still reserved. An old one usually
+    /// means a client that reserved gas and never executed, tying up the coins until it expires.
+    pub async fn oldest_active_reservation(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<ActiveReservation>> {
+        let reservation = self.store().get_oldest_active_reservation(sponsor).await?;
+        self.metrics
+            .oldest_active_reservation_age_ms
+            .with_label_values(&[&self.sponsor_metric_label(sponsor)])
+            .set(
+                reservation
+                    .as_ref()
+                    .map_or(0, |reservation| reservation.age().as_millis() as i64),
+            );
+        Ok(reservation)
+    }
+
+    /// Dumps the available coins and active reservations of every sponsor. Coins that are in
+    /// the middle of an execution are not included, so reservations should be frozen and
+    /// in-flight executions drained first for the snapshot to cover the whole pool.
+    pub async fn export_snapshot(&self) -> anyhow::Result<PoolSnapshot> {
+        let mut sponsors = vec![];
+        for sponsor in self.signer.get_addresses() {
+            sponsors.push(self.store().export_sponsor_snapshot(sponsor).await?);
+        }
+        Ok(PoolSnapshot { sponsors })
+    }
+
+    /// Logs the state of the pool at shutdown, per sponsor, and writes the full snapshot to
+    /// `shutdown_snapshot_path` when it is set, in the same format as `export_snapshot`.
+    /// Executions still in flight are only counted, since their coins are not in the snapshot.
+    pub async fn record_final_snapshot(&self) -> anyhow::Result<PoolSnapshot> {
+        let snapshot = self.export_snapshot().await?;
+        let in_flight_executions = self
+            .in_flight_executions
+            .lock()
+            .values()
+            .filter(|execution| execution.finished_at.is_none())
+            .count();
+        for sponsor_snapshot in &snapshot.sponsors {
+            info!(
+                target: LOG_TARGET,
+                sponsor = ?sponsor_snapshot.sponsor,
+                "Final pool state: {} available coins with total balance {}, {} reserved coins in {} reservations",
+                sponsor_snapshot.available_coins.len(),
+                sponsor_snapshot
+                    .available_coins
+                    .iter()
+                    .map(|c| c.balance)
+                    .sum::<u64>(),
+                sponsor_snapshot
+                    .reservations
+                    .iter()
+                    .map(|r| r.object_ids.len())
+                    .sum::<usize>(),
+                sponsor_snapshot.reservations.len()
+            );
+        }
+        info!(
+            target: LOG_TARGET,
+            "Final pool state: {} executions in flight", in_flight_executions
+        );
+        if let Some(path) = &self.config.shutdown_snapshot_path {
+            tokio::fs::write(path, serde_json::to_string(&snapshot)?).await?;
+            info!(target: LOG_TARGET, "Wrote final pool snapshot to {:?}", path);
+        }
+        Ok(snapshot)
+    }
+
+    /// Loads a snapshot into a fresh storage. Every coin is re-read from the chain first, and
+    /// coins that no longer exist or are no longer owned by the sponsor are dropped. Available
+    /// coins are imported with their latest balance, up to the maximum pool size, and
+    /// reservations keep their original ID and expiration time.
+    pub async fn import_snapshot(&self, snapshot: PoolSnapshot) -> anyhow::Result<()> {
+        let result = self.import_snapshot_into(&self.store(), snapshot).await;
+        self.reconcile_coin_counts().await;
+        result
+    }
+
+    /// Points the running pool at a new storage, e.g. to migrate between storage backends
+    /// without downtime. New reservations fail as frozen while the snapshot is imported into
+    /// `new_store`, as with `import_snapshot`, and are resumed once the pool uses the new
+    /// storage. If the import fails, the pool keeps the current storage.
+    ///
+    /// The caller is responsible for the following, as none of it can be checked here:
+    /// - The snapshot is exported from the current storage after freezing reservations and
+    ///   draining in-flight executions, and no execution starts until this returns. Otherwise
+    ///   coins reserved or released in between are lost to the pool, or an execution releases
+    ///   its coins to the storage that did not mark them as in flight.
+    /// - `new_store` is empty and manages the same sponsors as the pool.
+    /// - Other components handed the storage directly, such as the coin autoscaler, the coin
+    ///   initializer and the on-chain reconciler, and other servers sharing the old storage,
+    ///   keep using the old storage until they are restarted against the new one.
+    pub async fn replace_storage(
+        &self,
+        new_store: Arc<dyn Storage>,
+        snapshot: PoolSnapshot,
+    ) -> anyhow::Result<()> {
+        let new_store: Arc<dyn Storage> = InstrumentedStorage::new(new_store, self.metrics.clone());
+        self.set_reservations_frozen(true);
+        let result = self.import_snapshot_into(&new_store, snapshot).await;
+        if result.is_ok() {
+            *self.gas_pool_store.write() = new_store;
+            self.reservation_reads.clear();
+            info!(target: LOG_TARGET, "Switched the pool to the new storage");
+            self.reconcile_coin_counts().await;
+        }
+        self.set_reservations_frozen(false);
+        result
+    }
+
+    async fn import_snapshot_into(
+        &self,
+        store: &Arc<dyn Storage>,
+        snapshot: PoolSnapshot,
+    ) -> anyhow::Result<()> {
+        for sponsor_snapshot in &snapshot.sponsors {
+            if !self.signer.is_valid_address(&sponsor_snapshot.sponsor) {
+                bail!(
+                    "Sponsor {:?} in the snapshot is not registered",
+                    sponsor_snapshot.sponsor
+                );
+            }
+        }
+        for sponsor_snapshot in snapshot.sponsors {
+            let sponsor = sponsor_snapshot.sponsor;
+            let all_ids = sponsor_snapshot
+                .available_coins
+                .iter()
+                .map(|coin| coin.object_ref.0)
+                .chain(
+                    sponsor_snapshot
+                        .reservations
+                        .iter()
+                        .flat_map(|r| r.object_ids.iter().cloned()),
+                );
+            let latest_coins: HashMap<_, _> = self
+                .sui_client
+                .get_latest_gas_objects(all_ids)
+                .await
+                .into_iter()
+                .filter_map(|(id, coin)| coin.filter(|c| c.owner == sponsor).map(|c| (id, c)))
+                .collect();
+
+            for mut reservation in sponsor_snapshot.reservations {
+                reservation
+                    .object_ids
+                    .retain(|id| latest_coins.contains_key(id));
+                if reservation.object_ids.is_empty() {
+                    continue;
+                }
+                store.import_reservation(sponsor, &reservation).await?;
+            }
+            let available_coins: Vec<_> = sponsor_snapshot
+                .available_coins
+                .iter()
+                .filter_map(|coin| latest_coins.get(&coin.object_ref.0).cloned())
+                .collect();
+            let dropped = sponsor_snapshot.available_coins.len() - available_coins.len();
+            info!(
+                target: LOG_TARGET,
+                ?sponsor,
+                "Importing {} available coins, dropped {} that are no longer valid",
+                available_coins.len(),
+                dropped
+            );
+            let not_added = store
+                .add_new_coins_in_batches(available_coins, self.config.add_coins_batch_size)
+                .await?;
+            if !not_added.is_empty() {
+                warn!(
+                    target: LOG_TARGET,
+                    ?sponsor,
+                    "{} imported coins were left on chain since the pool is at its maximum size",
+                    not_added.len()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_total_gas_coin_balance(
+        &self,
+        gas_coins: Vec<ObjectRef>,
+        consistency: ReadConsistency,
+    ) -> u64 {
+        let latest = self
+            .sui_client
+            .get_latest_gas_objects_with_consistency(gas_coins, consistency)
+            .await;
+        latest
+            .into_values()
+            .flatten()
+            .map(|coin| coin.balance)
+            .sum()
+    }
+
+    /// Returns how much gas has been used against each usage cap window. The cap is shared by all
+    /// sponsors of the pool, so the sponsor only needs to be one of them.
+    pub async fn get_usage_status(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Vec<GasUsageWindowStatus>> {
+        if !self.signer.is_valid_address(&sponsor) {
+            bail!("Sponsor {:?} is not registered", sponsor);
+        }
+        Ok(self.gas_usage_cap.usage_status().await)
+    }
+
+    /// Runs every check `execute_transaction` applies to a transaction before executing it, so
+    /// that clients can find out whether it would be rejected before reserving gas for it.
+    /// This never touches the storage; it only dev-inspects the transaction if the sponsor has a
+    /// maximum estimated gas cost configured.
+    pub async fn validate_transaction(&self, tx_data: &TransactionData) -> anyhow::Result<()> {
+        let sponsor = tx_data.gas_data().owner;
+        if !self.signer.is_valid_address(&sponsor) {
+            bail!("Sponsor {:?} is not registered", sponsor);
+        }
+        Self::check_transaction_size(tx_data, self.config.max_transaction_size_bytes)?;
+        Self::check_transaction_validity(
+            tx_data,
+            self.config.max_transaction_complexity,
+            self.config.reject_publish_and_upgrade,
+        )?;
+        self.check_gas_price_limits(tx_data).await?;
+        self.check_estimated_gas_cost(tx_data).await
+    }
+
+    /// Rejects the transaction if `verify_reserved_balance_before_execution` is set and its
+    /// payment coins no longer hold enough balance to cover its gas budget.
+    async fn check_reserved_balance(&self, tx_data: &TransactionData) -> anyhow::Result<()> {
+        if !self.config.verify_reserved_balance_before_execution {
+            return Ok(());
+        }
+        let budget = tx_data.gas_data().budget;
+        let balance = self
+            .get_total_gas_coin_balance(
+                tx_data.gas_data().payment.clone(),
+                self.config.object_read_consistency,
+            )
+            .await;
+        if balance < budget {
+            bail!(
+                "Payment coins only hold {} MIST, which does not cover the gas budget of {}",
+                balance,
+                budget
+            );
+        }
+        Ok(())
+    }
+
+    /// Rejects the transaction if the sponsor has a gas price floor or ceiling configured and the
+    /// gas price is outside of them, or below the reference gas price.
+    async fn check_gas_price_limits(&self, tx_data: &TransactionData) -> anyhow::Result<()> {
+        let sponsor = tx_data.gas_data().owner;
+        let floor = self.config.min_gas_price.get(&sponsor);
+        let max_percent = self.config.max_gas_price_percent.get(&sponsor);
+        if floor.is_none() && max_percent.is_none() {
+            return Ok(());
+        }
+        let reference_gas_price = Self::resolve_reference_gas_price(
+            self.sui_client.get_reference_gas_price_or_cached().await,
+            self.config.reference_gas_price_fallback,
+        )?;
+        Self::check_gas_price(
+            tx_data.gas_price(),
+            reference_gas_price,
+            floor.copied().unwrap_or_default(),
+        )?;
+        match max_percent {
+            Some(max_percent) => {
+                Self::check_max_gas_price(tx_data.gas_price(), reference_gas_price, *max_percent)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Applies the fallback policy to a failed read of the reference gas price. Failing open
+    /// returns 0, which leaves only the floor to check against.
+    pub(crate) fn resolve_reference_gas_price(
+        reference_gas_price: anyhow::Result<u64>,
+        fallback: ReferenceGasPriceFallback,
+    ) -> anyhow::Result<u64> {
+        match (reference_gas_price, fallback) {
+            (Ok(reference_gas_price), _) => Ok(reference_gas_price),
+            (Err(err), ReferenceGasPriceFallback::FailClosed) => Err(err.context(
+                "Cannot check the gas price since the reference gas price is unavailable",
+            )),
+            (Err(err), ReferenceGasPriceFallback::FailOpen) => {
+                warn!(
+                    target: LOG_TARGET,
+                    "Reference gas price is unavailable, only checking the gas price floor: {:?}",
+                    err
+                );
+                Ok(0)
+            }
+        }
+    }
+
+    pub(crate) fn check_gas_price(
+        gas_price: u64,
+        reference_gas_price: u64,
+        floor: u64,
+    ) -> anyhow::Result<()> {
+        let min_gas_price = reference_gas_price.max(floor);
+        if gas_price < min_gas_price {
+            bail!(
+                "Gas price {} is below the minimum of {} (reference gas price {}, floor {})",
+                gas_price,
+                min_gas_price,
+                reference_gas_price,
+                floor
+            );
+        }
+        Ok(())
+    }
+
+    /// A reference gas price of 0 means it is unavailable and the check fails open, in which case
+    /// there is no ceiling to check against.
+    pub(crate) fn check_max_gas_price(
+        gas_price: u64,
+        reference_gas_price: u64,
+        max_percent: u64,
+    ) -> anyhow::Result<()> {
+        if reference_gas_price == 0 {
+            return Ok(());
+        }
+        let max_gas_price = reference_gas_price.saturating_mul(max_percent) / 100;
+        if gas_price > max_gas_price {
+            bail!(
+                "Gas price {} is above the maximum of {} ({}% of the reference gas price {})",
+                gas_price,
+                max_gas_price,
+                max_percent,
+                reference_gas_price
+            );
+        }
+        Ok(())
+    }
 
-        let tx = Transaction::from_generic_sig_data(tx_data, vec![sponsor_sig, user_sig]);
-        let cur_time = std::time::Instant::now();
-        let response = self
-            .sui_client
-            .execute_transaction(tx, request_type, 3)
-            .await?;
-        debug!(?reservation_id, "Transaction executed");
-        let elapsed = cur_time.elapsed().as_millis();
-        self.metrics
-            .transaction_execution_latency_ms
-            .observe(elapsed as u64);
-        let net_gas_usage = response.1.gas_cost_summary().net_gas_usage();
-        let new_daily_usage = self.gas_usage_cap.update_usage(net_gas_usage).await;
-        self.metrics
-            .daily_gas_usage
-            .with_label_values(&[&sponsor.to_string()])
-            .set(new_daily_usage);
-        Ok(response)
+    /// Rejects the transaction if the sponsor has a maximum gas cost configured and the
+    /// dev-inspected gas usage exceeds it. This costs an extra round trip to the fullnode,
+    /// hence it only applies to sponsors that opted in.
+    async fn check_estimated_gas_cost(&self, tx_data: &TransactionData) -> anyhow::Result<()> {
+        let sponsor = tx_data.gas_data().owner;
+        let Some(max_gas_cost) = self.config.max_estimated_gas_cost.get(&sponsor) else {
+            return Ok(());
+        };
+        let estimated_gas_cost = self.gas_estimator.estimate(tx_data).await?;
+        debug!(
+            target: LOG_TARGET,
+            "Estimated gas cost of transaction: {}", estimated_gas_cost
+        );
+        if estimated_gas_cost > *max_gas_cost {
+            self.metrics
+                .num_rejected_by_estimated_gas_cost
+                .with_label_values(&[&sponsor.to_string()])
+                .inc();
+            bail!(
+                "Estimated gas cost {} exceeds the maximum of {}",
+                estimated_gas_cost,
+                max_gas_cost
+            );
+        }
+        Ok(())
     }
 
-    async fn get_total_gas_coin_balance(&self, gas_coins: Vec<ObjectID>) -> u64 {
-        let latest = self.sui_client.get_latest_gas_objects(gas_coins).await;
-        latest
-            .into_values()
-            .flatten()
-            .map(|coin| coin.balance)
+    /// The number of commands of the transaction plus the number of arguments they reference.
+    /// Arguments can refer to the results of earlier commands, so this bounds the size of the whole
+    /// command graph rather than the number of inputs alone.
+    pub(crate) fn transaction_complexity(tx_data: &TransactionData) -> u64 {
+        tx_data
+            .kind()
+            .iter_commands()
+            .map(|command| {
+                let arg_count = match command {
+                    Command::MoveCall(call) => call.arguments.len(),
+                    Command::TransferObjects(args, _) => args.len() + 1,
+                    Command::SplitCoins(_, amounts) => amounts.len() + 1,
+                    Command::MergeCoins(_, args) => args.len() + 1,
+                    Command::Publish(_, _) => 0,
+                    Command::MakeMoveVec(_, args) => args.len(),
+                    Command::Upgrade(_, _, _, _) => 1,
+                };
+                1 + arg_count as u64
+            })
             .sum()
     }
 
-    fn check_transaction_validity(tx_data: &TransactionData) -> anyhow::Result<()> {
+    /// Rejects transactions whose BCS serialized data is larger than `max_size` bytes, which are
+    /// expensive to sign and submit, and may exceed the limits of the fullnode.
+    pub(crate) fn check_transaction_size(
+        tx_data: &TransactionData,
+        max_size: usize,
+    ) -> Result<(), ValidationError> {
+        // Serializing transaction data cannot fail, as it only holds serializable types.
+        let size = bcs::serialized_size(tx_data).expect("Transaction data is serializable");
+        if size > max_size {
+            return Err(ValidationError::TooLarge { size, max_size });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_transaction_validity(
+        tx_data: &TransactionData,
+        max_complexity: u64,
+        reject_publish_and_upgrade: bool,
+    ) -> Result<(), ValidationError> {
+        let complexity = Self::transaction_complexity(tx_data);
+        if complexity > max_complexity {
+            return Err(ValidationError::TooComplex {
+                complexity,
+                max_complexity,
+            });
+        }
         let mut all_args = vec![];
         for command in tx_data.kind().iter_commands() {
             match command {
@@ -261,32 +2585,506 @@ impl GasPool {
                     all_args.push(arg);
                     all_args.extend(args.iter());
                 }
-                Command::Publish(_, _) => {}
+                Command::Publish(_, _) => {
+                    if reject_publish_and_upgrade {
+                        return Err(ValidationError::PublishDisallowed);
+                    }
+                }
                 Command::MakeMoveVec(_, args) => {
                     all_args.extend(args.iter());
                 }
-                Command::Upgrade(_, _, _, _) => {}
+                Command::Upgrade(_, _, _, _) => {
+                    if reject_publish_and_upgrade {
+                        return Err(ValidationError::UpgradeDisallowed);
+                    }
+                }
             };
         }
         let uses_gas = all_args
             .into_iter()
             .any(|arg| matches!(*arg, Argument::GasCoin));
         if uses_gas {
-            bail!("Gas coin can only be used to pay gas")
+            return Err(ValidationError::GasCoinMisused);
         };
         Ok(())
     }
 
-    /// Release gas coins back to the gas pool, by adding them to the storage.
-    async fn release_gas_coins(&self, gas_coins: Vec<GasCoin>) {
-        debug!("Trying to release gas coins: {:?}", gas_coins);
-        retry_forever!(async {
-            self.gas_pool_store
-                .add_new_coins(gas_coins.clone())
+    /// Holds the gas coin of an executed transaction back according to `post_execution_release`,
+    /// so that it is not reserved again before the fullnode serves its new version.
+    async fn wait_before_release(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        new_gas_coin: ObjectRef,
+    ) {
+        let start = Instant::now();
+        match self.config.post_execution_release {
+            PostExecutionRelease::Immediate => return,
+            PostExecutionRelease::Delay { delay_ms } => {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            }
+            PostExecutionRelease::WaitForVersion { timeout_ms } => {
+                if tokio::time::timeout(
+                    Duration::from_millis(timeout_ms),
+                    self.sui_client.wait_for_object_version(new_gas_coin),
+                )
                 .await
-                .tap_err(|err| error!("Failed to call update_gas_coins on storage: {:?}", err))
-        })
-        .unwrap();
+                .is_err()
+                {
+                    info!(
+                        target: LOG_TARGET,
+                        ?reservation_id,
+                        "Releasing gas coin {:?} before the fullnode caught up with it",
+                        new_gas_coin
+                    );
+                    self.metrics
+                        .num_post_execution_release_timeouts
+                        .with_label_values(&[&sponsor.to_string()])
+                        .inc();
+                }
+            }
+        }
+        self.metrics
+            .post_execution_release_wait_ms
+            .observe(start.elapsed().as_millis() as u64);
+    }
+
+    /// The reserved coins that an executed transaction did not pay with, at their latest state,
+    /// to be released along with its gas coin. None of them under
+    /// `UnusedReservedCoins::AssumeSmashed`.
+    async fn unused_reserved_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        unused_coins: Vec<ObjectID>,
+    ) -> Vec<GasCoin> {
+        if unused_coins.is_empty() {
+            return vec![];
+        }
+        match self.config.unused_reserved_coins {
+            UnusedReservedCoins::Release => {
+                debug!(
+                    target: LOG_TARGET,
+                    ?reservation_id,
+                    "Releasing reserved coins the transaction did not pay with: {:?}",
+                    unused_coins
+                );
+                // A coin the transaction used as a regular input may have been transferred away.
+                self.sui_client
+                    .get_latest_gas_objects(unused_coins)
+                    .await
+                    .into_values()
+                    .flatten()
+                    .filter(|coin| coin.owner == sponsor)
+                    .collect()
+            }
+            UnusedReservedCoins::AssumeSmashed => {
+                debug!(
+                    target: LOG_TARGET,
+                    ?reservation_id,
+                    "Assuming the reserved coins the transaction did not pay with were smashed: {:?}",
+                    unused_coins
+                );
+                vec![]
+            }
+        }
+    }
+
+    /// Takes the coins above `split_gas_coins_above` out of the coins about to be released, and
+    /// holds each of them under its own reservation until the coin unlock task splits it. Returns
+    /// the other coins, along with the oversized coins that could not be held.
+    async fn hold_oversized_gas_coins(&self, gas_coins: Vec<GasCoin>) -> Vec<GasCoin> {
+        let Some(threshold) = self.config.split_gas_coins_above else {
+            return gas_coins;
+        };
+        let (oversized, mut rest): (Vec<_>, Vec<_>) = gas_coins
+            .into_iter()
+            .partition(|coin| coin.balance > threshold);
+        for coin in oversized {
+            let held = self
+                .with_storage_timeout(self.store().hold_coins(
+                    coin.owner,
+                    vec![coin.object_ref.0],
+                    OVERSIZED_GAS_COIN_HOLD_DURATION.as_millis() as u64,
+                ))
+                .await;
+            match held {
+                Ok(reservation_id) => {
+                    debug!(
+                        target: LOG_TARGET,
+                        ?reservation_id,
+                        "Holding back oversized gas coin to split it: {:?}",
+                        coin
+                    );
+                    self.oversized_gas_coins.lock().push((reservation_id, coin));
+                }
+                Err(err) => {
+                    error!(
+                        target: LOG_TARGET,
+                        "Failed to hold oversized gas coin {:?}, releasing it as it is: {:?}",
+                        coin.object_ref.0,
+                        err
+                    );
+                    rest.push(coin);
+                }
+            }
+        }
+        rest
+    }
+
+    /// Splits the held oversized gas coins and releases the resulting coins to the pool. A coin
+    /// that fails to be split is released as it is on chain, so that it is never lost. Split
+    /// coins that do not fit under `max_pool_coins_per_sponsor` are left out of the pool.
+    async fn split_oversized_gas_coins(&self) {
+        let Some(threshold) = self.config.split_gas_coins_above else {
+            return;
+        };
+        let held = std::mem::take(&mut *self.oversized_gas_coins.lock());
+        for (reservation_id, coin) in held {
+            let sponsor = coin.owner;
+            let coins = match self.split_gas_coin(&coin, threshold).await {
+                Ok(coins) => {
+                    info!(
+                        target: LOG_TARGET,
+                        ?sponsor,
+                        "Split oversized gas coin {:?} of balance {} into {} coins",
+                        coin.object_ref.0,
+                        coin.balance,
+                        coins.len()
+                    );
+                    self.metrics
+                        .num_oversized_gas_coin_splits
+                        .with_label_values(&[&sponsor.to_string()])
+                        .inc();
+                    coins
+                }
+                Err(err) => {
+                    error!(
+                        target: LOG_TARGET,
+                        ?sponsor,
+                        "Failed to split oversized gas coin {:?}: {:?}", coin.object_ref.0, err
+                    );
+                    self.sui_client
+                        .get_latest_gas_objects([coin.object_ref.0])
+                        .await
+                        .into_values()
+                        .flatten()
+                        .collect()
+                }
+            };
+            // Only the held coin goes back through its reservation. The coins split from it are
+            // new to the pool, so they are added like any other coin, within the coin cap.
+            let (held_coin, split_coins): (Vec<_>, Vec<_>) = coins
+                .into_iter()
+                .partition(|split_coin| split_coin.object_ref.0 == coin.object_ref.0);
+            if let Err(err) = self
+                .release_coins_from_reservation(
+                    sponsor,
+                    reservation_id,
+                    vec![coin.object_ref.0],
+                    held_coin,
+                )
+                .await
+            {
+                // The held coin goes back to the pool when its reservation expires, or already
+                // did.
+                error!(
+                    target: LOG_TARGET,
+                    ?sponsor,
+                    ?reservation_id,
+                    "Failed to release oversized gas coin {:?}: {:?}",
+                    coin.object_ref.0,
+                    err
+                );
+            }
+            self.release_gas_coins(split_coins).await;
+        }
+    }
+
+    /// Reports how much of its requested duration the reservation used up before its execution.
+    /// Only reservations made through this server within their requested duration are known.
+    fn observe_reservation_duration_used(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) {
+        let timing = self
+            .reservation_timings
+            .lock()
+            .remove(&(sponsor, reservation_id));
+        if let Some((requested, reserved_at)) = timing {
+            self.metrics
+                .reservation_duration_used_pct
+                .with_label_values(&[&self.sponsor_metric_label(sponsor)])
+                .observe(Self::duration_used_pct(reserved_at.elapsed(), requested));
+        }
+    }
+
+    /// Drops the timings of reservations whose requested duration is over. They expired, or
+    /// were kept alive by heartbeats, which makes their requested duration meaningless.
+    fn prune_reservation_timings(&self) {
+        self.reservation_timings
+            .lock()
+            .retain(|_, (requested, reserved_at)| reserved_at.elapsed() <= *requested);
+    }
+
+    /// `elapsed` as a percentage of `requested`, rounded down.
+    pub(crate) fn duration_used_pct(elapsed: Duration, requested: Duration) -> u64 {
+        if requested.is_zero() {
+            return 100;
+        }
+        (elapsed.as_millis() * 100 / requested.as_millis()) as u64
+    }
+
+    /// How many coins a gas coin is split into so that each is about half of the threshold,
+    /// after paying for the split. At most 500, to stay well within the transaction limits.
+    pub(crate) fn oversized_coin_split_count(
+        balance: u64,
+        threshold: u64,
+        gas_cost_per_object: u64,
+    ) -> u64 {
+        const MAX_SPLIT_COUNT: u64 = 500;
+        balance
+            .checked_div(threshold / 2 + gas_cost_per_object)
+            .unwrap_or(0)
+            .min(MAX_SPLIT_COUNT)
+    }
+
+    async fn split_gas_coin(&self, coin: &GasCoin, threshold: u64) -> anyhow::Result<Vec<GasCoin>> {
+        let sponsor = coin.owner;
+        let gas_cost_per_object = self
+            .sui_client
+            .calibrate_gas_cost_per_object(sponsor, coin)
+            .await;
+        let split_count =
+            Self::oversized_coin_split_count(coin.balance, threshold, gas_cost_per_object);
+        if split_count < 2 {
+            bail!("Balance {} is too small to split", coin.balance);
+        }
+        let Some(budget) = gas_cost_per_object
+            .checked_mul(split_count)
+            .filter(|budget| *budget < coin.balance)
+        else {
+            bail!(
+                "Balance {} cannot pay for splitting into {} coins",
+                coin.balance,
+                split_count
+            );
+        };
+        let tx_data = TransactionData::new_programmable(
+            sponsor,
+            vec![coin.object_ref],
+            self.sui_client
+                .construct_coin_split_pt(Argument::GasCoin, split_count),
+            budget,
+            self.sui_client.get_reference_gas_price().await,
+        );
+        let sig = self.signer.sign_transaction(&tx_data).await?;
+        let tx = Transaction::from_generic_sig_data(tx_data, vec![sig]);
+        let effects = self
+            .sui_client
+            .execute_transaction(
+                tx,
+                None,
+                SuiTransactionBlockResponseOptions::new().with_effects(),
+                3,
+            )
+            .await?
+            .effects;
+        if !effects.status().is_ok() {
+            bail!("Split transaction failed: {:?}", effects.status());
+        }
+        // Same accounting as the autoscaler splits: the budget is taken out of the coin before
+        // it is divided, and the gas coin keeps the remainder minus the actual gas used. Neither
+        // can underflow since the budget is less than the balance.
+        let new_coin_balance = (coin.balance - budget) / split_count;
+        let mut new_coins: Vec<_> = effects
+            .created()
+            .iter()
+            .map(|created| GasCoin {
+                owner: sponsor,
+                object_ref: created.reference.to_object_ref(),
+                balance: new_coin_balance,
+            })
+            .collect();
+        let remainder = coin.balance - new_coin_balance * (split_count - 1);
+        let net_gas_usage = effects.gas_cost_summary().net_gas_usage();
+        let remaining_balance = Self::balance_after_execution(remainder, net_gas_usage)
+            .unwrap_or_else(|| {
+                self.metrics.invariant_violation(format!(
+                    "Net gas usage {} of a split exceeds the gas coin balance {}",
+                    net_gas_usage, remainder
+                ));
+                0
+            });
+        new_coins.push(GasCoin {
+            owner: sponsor,
+            object_ref: effects.gas_object().reference.to_object_ref(),
+            balance: remaining_balance,
+        });
+        Ok(new_coins)
+    }
+
+    /// Release gas coins back to the gas pool, by adding them to the storage in bounded batches.
+    async fn release_gas_coins(&self, gas_coins: Vec<GasCoin>) {
+        debug!(target: LOG_TARGET, "Trying to release gas coins: {:?}", gas_coins);
+        let (dust_coins, gas_coins): (Vec<_>, Vec<_>) =
+            gas_coins.into_iter().partition(|coin| self.is_dust(coin));
+        if !dust_coins.is_empty() {
+            debug!(target: LOG_TARGET, "Moving gas coins to the dust bin: {:?}", dust_coins);
+            retry_forever!(async {
+                self.store()
+                    .add_dust_coins(dust_coins.clone())
+                    .await
+                    .tap_err(|err| {
+                        error!(
+                            target: LOG_TARGET,
+                            "Failed to call add_dust_coins on storage: {:?}", err
+                        )
+                    })
+            })
+            .unwrap();
+        }
+        for batch in gas_coins.chunks(self.config.add_coins_batch_size.max(1)) {
+            let not_added = retry_forever!(async {
+                self.store()
+                    .add_new_coins(batch.to_vec())
+                    .await
+                    .tap_err(|err| {
+                        error!(
+                            target: LOG_TARGET,
+                            "Failed to call update_gas_coins on storage: {:?}", err
+                        )
+                    })
+            })
+            .unwrap();
+            if !not_added.is_empty() {
+                warn!(
+                    target: LOG_TARGET,
+                    "Pool is full, left out {} released gas coins: {:?}",
+                    not_added.len(),
+                    not_added
+                );
+            }
+            let mut added: HashMap<SuiAddress, i64> = HashMap::new();
+            for coin in batch {
+                *added.entry(coin.owner).or_default() += 1;
+            }
+            for coin in &not_added {
+                *added.entry(coin.owner).or_default() -= 1;
+            }
+            for (sponsor, count) in added {
+                self.record_coin_count_change(sponsor, count);
+            }
+        }
+    }
+
+    /// Takes coins out of a reservation, putting `latest_coins` back into the pool, and counts
+    /// them in the cached coin count of the sponsor.
+    async fn release_coins_from_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        coin_ids: Vec<ObjectID>,
+        latest_coins: Vec<GasCoin>,
+    ) -> anyhow::Result<()> {
+        let released = latest_coins.len() as i64;
+        let result = self
+            .store()
+            .release_reservation_coins(sponsor, reservation_id, coin_ids, latest_coins)
+            .await;
+        self.reservation_reads.invalidate(sponsor, reservation_id);
+        result?;
+        self.record_coin_count_change(sponsor, released);
+        Ok(())
+    }
+
+    fn record_coin_count_change(&self, sponsor: SuiAddress, delta: i64) {
+        if delta == 0 {
+            return;
+        }
+        self.coin_counts.add(sponsor, delta);
+        if let Some(count) = self.coin_counts.get(sponsor) {
+            self.metrics
+                .cached_available_coin_count
+                .with_label_values(&[&sponsor.to_string()])
+                .set(count as i64);
+        }
+    }
+
+    /// Resets the cached available coin count of every sponsor to the count in storage, which
+    /// also picks up the changes made to the storage other than through this pool.
+    pub async fn reconcile_coin_counts(&self) {
+        for sponsor in self.signer.get_addresses() {
+            let count = match self.store().get_available_coin_count(sponsor).await {
+                Ok(count) => count as u64,
+                Err(err) => {
+                    error!(
+                        target: LOG_TARGET,
+                        ?sponsor,
+                        "Failed to read the available coin count to reconcile: {:?}",
+                        err
+                    );
+                    continue;
+                }
+            };
+            let drift = self.coin_counts.reconcile(sponsor, count);
+            if drift != 0 {
+                debug!(
+                    target: LOG_TARGET,
+                    ?sponsor,
+                    "Cached available coin count was off by {}", drift
+                );
+            }
+            let sponsor_str = sponsor.to_string();
+            self.metrics
+                .coin_count_cache_drift
+                .with_label_values(&[&sponsor_str])
+                .set(drift);
+            self.metrics
+                .cached_available_coin_count
+                .with_label_values(&[&sponsor_str])
+                .set(count as i64);
+        }
+        *self.last_coin_count_reconcile.lock() = Some(Instant::now());
+    }
+
+    async fn reconcile_coin_counts_if_due(&self) {
+        let interval = Duration::from_secs(self.config.coin_count_reconcile_interval_sec);
+        let due = self
+            .last_coin_count_reconcile
+            .lock()
+            .is_none_or(|last| last.elapsed() >= interval);
+        if due {
+            self.reconcile_coin_counts().await;
+        }
+    }
+
+    /// The total balance of the gas coins the sponsor owns on chain, including those that are
+    /// not in the pool. Reads are cached for a few seconds.
+    pub async fn get_sponsor_onchain_balance(&self, sponsor: SuiAddress) -> anyhow::Result<u64> {
+        if let Some((balance, read_at)) = self.onchain_balances.lock().get(&sponsor) {
+            if read_at.elapsed() < ONCHAIN_BALANCE_CACHE_TTL {
+                return Ok(*balance);
+            }
+        }
+        let balance = self.sui_client.get_total_balance(sponsor).await?;
+        self.onchain_balances
+            .lock()
+            .insert(sponsor, (balance, Instant::now()));
+        Ok(balance)
+    }
+
+    fn is_dust(&self, coin: &GasCoin) -> bool {
+        self.config
+            .dust_coin_threshold
+            .is_some_and(|threshold| coin.balance < threshold)
+    }
+
+    /// The coins of the sponsor that were released with too little balance to go back to the
+    /// pool, waiting to be merged.
+    pub async fn get_dust_coins(&self, sponsor: SuiAddress) -> anyhow::Result<Vec<GasCoin>> {
+        self.store().get_dust_coins(sponsor).await
     }
 
     /// Performs an end-to-end flow of reserving gas, signing a transaction, and releasing the gas coins.
@@ -294,51 +3092,193 @@ impl GasPool {
         let gas_budget = MIST_PER_OCT / 10;
         let sponsor = self.signer.get_addresses()[0];
         let (_address, _reservation_id, gas_coins) = self
-            .reserve_gas(Some(sponsor), gas_budget, Duration::from_secs(3))
+            .reserve_gas(
+                Some(sponsor),
+                gas_budget,
+                Duration::from_secs(3),
+                ReserveGasOptions::default(),
+            )
             .await?;
+        self.sign_health_check_transaction(sponsor, gas_coins).await
+    }
+
+    /// A lighter version of `debug_check_health` that only checks that the signer can sign for
+    /// every sponsor. It pays with a made-up gas coin instead of reserving one, so it can be
+    /// called often without tying up coins of the pool or touching the storage.
+    pub async fn debug_check_signer_health(&self) -> anyhow::Result<()> {
+        for sponsor in self.signer.get_addresses() {
+            let synthetic_coin = (ObjectID::ZERO, SequenceNumber::new(), ObjectDigest::MIN);
+            self.sign_health_check_transaction(sponsor, vec![synthetic_coin])
+                .await
+                .map_err(|err| anyhow!("Failed to sign for sponsor {:?}: {:?}", sponsor, err))?;
+        }
+        Ok(())
+    }
+
+    async fn sign_health_check_transaction(
+        &self,
+        sponsor: SuiAddress,
+        gas_coins: Vec<ObjectRef>,
+    ) -> anyhow::Result<()> {
         let tx_kind = TransactionKind::ProgrammableTransaction(
             ProgrammableTransactionBuilder::new().finish(),
         );
         // Since we just want to check the health of the signer, we don't need to actually execute the transaction.
-        let tx_data = TransactionData::new_with_gas_coins(
-            tx_kind,
-            SuiAddress::default(),
-            gas_coins,
-            gas_budget,
-            0,
-        );
+        let tx_data =
+            TransactionData::new_with_gas_coins(tx_kind, sponsor, gas_coins, MIST_PER_OCT / 10, 0);
         self.signer.sign_transaction(&tx_data).await?;
         Ok(())
     }
 
+    /// Expires the reservations that are past their expiration time, and returns their coins to
+    /// the pool.
+    async fn release_expired_coins(&self) {
+        let expire_results = self.store().expire_coins().await;
+        let unlocked_coins = expire_results.unwrap_or_else(|err| {
+            error!(
+                target: LOG_TARGET,
+                "Failed to call expire_coins to the storage: {:?}", err
+            );
+            vec![]
+        });
+        if !unlocked_coins.is_empty() {
+            debug!(target: LOG_TARGET, "Coins that are expired: {:?}", unlocked_coins);
+            let (mut latest_coins, missing_coins): (Vec<_>, Vec<_>) = self
+                .sui_client
+                .get_latest_gas_objects(unlocked_coins.clone())
+                .await
+                .into_iter()
+                .partition_map(|(id, coin)| match coin {
+                    Some(coin) => Either::Left(coin),
+                    None => Either::Right(id),
+                });
+            if self.config.recheck_missing_coins_on_expiry && !missing_coins.is_empty() {
+                latest_coins.extend(self.recheck_missing_coins(missing_coins).await);
+            }
+            let count = latest_coins.len();
+            self.release_gas_coins(latest_coins).await;
+            info!(target: LOG_TARGET, "Released {:?} coins after expiration", count);
+        }
+    }
+
+    /// Refreshes the age of the oldest active reservation of every sponsor, so that it can be
+    /// alerted on.
+    async fn update_oldest_active_reservation_ages(&self) {
+        for sponsor in self.signer.get_addresses() {
+            if let Err(err) = self.oldest_active_reservation(sponsor).await {
+                error!(
+                    target: LOG_TARGET,
+                    ?sponsor,
+                    "Failed to get the oldest active reservation: {:?}", err
+                );
+            }
+        }
+    }
+
+    /// Checks again the expired coins that could not be read from the fullnode, and returns the
+    /// ones that turn out to still exist. Coins that no longer exist were spent or deleted
+    /// outside of the pool, and are counted before being dropped.
+    async fn recheck_missing_coins(&self, object_ids: Vec<ObjectID>) -> Vec<GasCoin> {
+        let checks = object_ids.iter().map(|id| async move {
+            retry_with_max_attempts!(
+                self.sui_client.object_exists(*id),
+                MISSING_COIN_CHECK_MAX_ATTEMPTS
+            )
+        });
+        let mut reread = vec![];
+        for (id, result) in object_ids
+            .iter()
+            .zip(futures_util::future::join_all(checks).await)
+        {
+            match result {
+                Ok(false) => {
+                    warn!(
+                        target: LOG_TARGET,
+                        "Expired coin {:?} no longer exists, dropping it from the pool", id
+                    );
+                    self.metrics.coins_vanished_on_expiry.inc();
+                }
+                Ok(true) => reread.push(*id),
+                Err(err) => {
+                    warn!(
+                        target: LOG_TARGET,
+                        "Failed to check whether expired coin {:?} exists: {:?}", id, err
+                    );
+                    reread.push(*id);
+                }
+            }
+        }
+        if reread.is_empty() {
+            return vec![];
+        }
+        let latest_coins = self.sui_client.get_latest_gas_objects(reread).await;
+        for (id, coin) in &latest_coins {
+            if coin.is_none() {
+                error!(
+                    target: LOG_TARGET,
+                    "Expired coin {:?} could not be read again, dropping it from the pool", id
+                );
+            }
+        }
+        latest_coins.into_values().flatten().collect()
+    }
+
     async fn start_coin_unlock_task(
         self: Arc<Self>,
         mut cancel_receiver: tokio::sync::oneshot::Receiver<()>,
     ) -> JoinHandle<()> {
         tokio::task::spawn(async move {
             loop {
-                let expire_results = self.gas_pool_store.expire_coins().await;
-                let unlocked_coins = expire_results.unwrap_or_else(|err| {
-                    error!("Failed to call expire_coins to the storage: {:?}", err);
-                    vec![]
-                });
-                if !unlocked_coins.is_empty() {
-                    debug!("Coins that are expired: {:?}", unlocked_coins);
-                    let latest_coins: Vec<_> = self
-                        .sui_client
-                        .get_latest_gas_objects(unlocked_coins.clone())
-                        .await
-                        .into_values()
-                        .flatten()
-                        .collect();
-                    let count = latest_coins.len();
-                    self.release_gas_coins(latest_coins).await;
-                    info!("Released {:?} coins after expiration", count);
-                }
+                self.release_expired_coins().await;
+                self.prune_reservation_timings();
+                self.update_oldest_active_reservation_ages().await;
+                self.split_oversized_gas_coins().await;
+                self.reconcile_coin_counts_if_due().await;
                 tokio::select! {
                     _ = tokio::time::sleep(EXPIRATION_JOB_INTERVAL) => {}
                     _ = &mut cancel_receiver => {
-                        info!("Coin unlocker task is cancelled");
+                        info!(target: LOG_TARGET, "Coin unlocker task is cancelled");
+                        break;
+                    }
+                }
+            }
+        })
+    }
+
+    async fn start_warm_cache_task(
+        self: Arc<Self>,
+        mut cancel_receiver: tokio::sync::oneshot::Receiver<()>,
+    ) -> JoinHandle<()> {
+        tokio::task::spawn(async move {
+            loop {
+                self.refill_warm_cache().await;
+                tokio::select! {
+                    _ = tokio::time::sleep(WARM_CACHE_JOB_INTERVAL) => {}
+                    _ = &mut cancel_receiver => {
+                        info!(target: LOG_TARGET, "Warm cache task is cancelled");
+                        break;
+                    }
+                }
+            }
+        })
+    }
+
+    async fn start_scheduled_execution_task(
+        self: Arc<Self>,
+        mut cancel_receiver: tokio::sync::oneshot::Receiver<()>,
+    ) -> JoinHandle<()> {
+        tokio::task::spawn(async move {
+            loop {
+                if let Err(err) = self.run_due_scheduled_executions().await {
+                    error!(
+                        target: LOG_TARGET,
+                        "Failed to run scheduled executions: {:?}", err
+                    );
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(SCHEDULED_EXECUTION_JOB_INTERVAL) => {}
+                    _ = &mut cancel_receiver => {
+                        info!(target: LOG_TARGET, "Scheduled execution task is cancelled");
                         break;
                     }
                 }
@@ -346,11 +3286,35 @@ impl GasPool {
         })
     }
 
-    pub async fn query_pool_available_coin_count(&self, sponsor: SuiAddress) -> usize {
-        self.gas_pool_store
-            .get_available_coin_count(sponsor)
+    /// How the balances of the available coins of the sponsor are distributed, or None if it has
+    /// no available coins.
+    pub async fn get_balance_percentiles(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<BalancePercentiles>> {
+        self.with_storage_timeout(self.store().get_balance_percentiles(sponsor))
             .await
-            .unwrap()
+    }
+
+    /// The number of available coins of the sponsor, as read from the storage.
+    pub async fn query_pool_available_coin_count(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<usize> {
+        self.store().get_available_coin_count(sponsor).await
+    }
+
+    /// The number of available coins of the sponsor as kept track of by this pool, which is
+    /// cheap enough to poll but may be off by the changes made since the last reconciliation
+    /// with the storage. None if the sponsor is not one of the pool.
+    pub fn cached_available_coin_count(&self, sponsor: SuiAddress) -> Option<u64> {
+        self.coin_counts.get(sponsor)
+    }
+
+    /// The type of the coins this pool is made of. Pools of different coin types are kept apart
+    /// by their sponsor addresses, so a process can run one pool per coin type side by side.
+    pub fn gas_coin_type(&self) -> &TypeTag {
+        self.sui_client.gas_coin_type()
     }
 
     pub fn support_address(&self) -> Vec<SuiAddress> {
@@ -364,33 +3328,101 @@ impl GasPoolContainer {
         gas_pool_store: Arc<dyn Storage>,
         sui_client: SuiClient,
         gas_usage_daily_cap: u64,
+        config: GasPoolCoreConfig,
         metrics: Arc<GasPoolCoreMetrics>,
     ) -> Self {
+        let reconcile_reservations = config.reconcile_reservations_at_startup;
+        let gas_pool_store = InstrumentedStorage::new(gas_pool_store, metrics.clone());
         let inner = GasPool::new(
             signer,
             gas_pool_store,
             sui_client,
             metrics,
             Arc::new(GasUsageCap::new(gas_usage_daily_cap)),
+            config,
         )
         .await;
+        // Done before the background tasks start, so that they only see reconciled reservations.
+        if reconcile_reservations {
+            if let Err(err) = inner.reconcile_reservations().await {
+                error!(target: LOG_TARGET, "Failed to reconcile reservations: {:?}", err);
+            }
+        }
         let (cancel_sender, cancel_receiver) = tokio::sync::oneshot::channel();
         let _coin_unlocker_task = inner.clone().start_coin_unlock_task(cancel_receiver).await;
+        let (scheduler_cancel_sender, scheduler_cancel_receiver) = tokio::sync::oneshot::channel();
+        let _scheduled_execution_task = inner
+            .clone()
+            .start_scheduled_execution_task(scheduler_cancel_receiver)
+            .await;
+        let (warm_cache_cancel_sender, warm_cache_cancel_receiver) =
+            tokio::sync::oneshot::channel();
+        let _warm_cache_task = if inner.warm_cache.is_enabled() {
+            Some(
+                inner
+                    .clone()
+                    .start_warm_cache_task(warm_cache_cancel_receiver)
+                    .await,
+            )
+        } else {
+            None
+        };
 
         Self {
             inner,
             _coin_unlocker_task,
+            _scheduled_execution_task,
+            _warm_cache_task,
             cancel_sender: Some(cancel_sender),
+            scheduler_cancel_sender: Some(scheduler_cancel_sender),
+            warm_cache_cancel_sender: Some(warm_cache_cancel_sender),
         }
     }
 
     pub fn get_gas_pool_arc(&self) -> Arc<GasPool> {
         self.inner.clone()
     }
+
+    /// Stops the background tasks and records the final state of the pool. Unlike dropping the
+    /// container, this waits for the tasks to stop, so that the final snapshot is not changed by
+    /// them anymore.
+    pub async fn shutdown(mut self) -> anyhow::Result<PoolSnapshot> {
+        let cancel_senders = [
+            self.cancel_sender.take(),
+            self.scheduler_cancel_sender.take(),
+            self.warm_cache_cancel_sender.take(),
+        ];
+        for cancel_sender in cancel_senders.into_iter().flatten() {
+            let _ = cancel_sender.send(());
+        }
+        let _ = (&mut self._coin_unlocker_task).await;
+        let _ = (&mut self._scheduled_execution_task).await;
+        if let Some(task) = &mut self._warm_cache_task {
+            let _ = task.await;
+        }
+        self.inner.record_final_snapshot().await
+    }
+
+    /// Stops the coin unlocker task as if it had exited on its own.
+    #[cfg(test)]
+    pub async fn abort_coin_unlock_task_for_testing(&mut self) {
+        self._coin_unlocker_task.abort();
+        let _ = (&mut self._coin_unlocker_task).await;
+    }
 }
 
 impl Drop for GasPoolContainer {
     fn drop(&mut self) {
-        self.cancel_sender.take().unwrap().send(()).unwrap();
+        // Sending fails if the task has already exited, in which case there is nothing to cancel.
+        // Never panic here, since that would abort the process if we are already unwinding.
+        if let Some(cancel_sender) = self.cancel_sender.take() {
+            let _ = cancel_sender.send(());
+        }
+        if let Some(cancel_sender) = self.scheduler_cancel_sender.take() {
+            let _ = cancel_sender.send(());
+        }
+        if let Some(cancel_sender) = self.warm_cache_cancel_sender.take() {
+            let _ = cancel_sender.send(());
+        }
     }
 }
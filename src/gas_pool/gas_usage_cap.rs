@@ -1,61 +1,121 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::bail;
-use chrono::{Local, NaiveDate};
-use tokio::sync::RwLock;
+use crate::errors::GasPoolError;
+use crate::types::GasUsageWindowStatus;
+use chrono::{Datelike, Local, NaiveDate};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
 
-pub struct GasUsageCap {
-    daily_cap: u64,
-    inner: RwLock<GasUsageCapInner>,
-}
+/// Marks the current day while a thread is starting a new one.
+const STARTING_NEW_DAY: i64 = i64::MIN;
 
-struct GasUsageCapInner {
-    cur_daily_usage: i64,
-    cur_date: NaiveDate,
+/// Daily gas usage, updated on every execution. It is kept in atomics rather than behind a lock
+/// so that concurrent executions do not wait on each other.
+pub struct GasUsageCap {
+    daily_cap: AtomicU64,
+    /// The day the usage is counted for, in days since the Common Era, or `STARTING_NEW_DAY`
+    /// while the usage is being reset for a new day.
+    cur_day: AtomicI64,
+    cur_daily_usage: AtomicI64,
 }
 
 impl GasUsageCap {
     pub fn new(daily_cap: u64) -> Self {
         Self {
-            daily_cap,
-            inner: RwLock::new(GasUsageCapInner {
-                cur_daily_usage: 0,
-                cur_date: Local::now().date_naive(),
-            }),
+            daily_cap: AtomicU64::new(daily_cap),
+            cur_day: AtomicI64::new(Self::day_number(Local::now().date_naive())),
+            cur_daily_usage: AtomicI64::new(0),
         }
     }
 
     pub async fn check_usage(&self) -> anyhow::Result<()> {
-        self.reset_date_maybe().await;
-        let cur_daily_usage = self.inner.read().await.cur_daily_usage;
-        if cur_daily_usage >= self.daily_cap as i64 {
-            bail!("Gas usage exceeds daily cap");
+        self.reset_date_maybe();
+        let cur_daily_usage = self.cur_daily_usage.load(Ordering::Acquire);
+        if cur_daily_usage >= self.daily_cap() as i64 {
+            return Err(GasPoolError::DailyCapExceeded {
+                retry_after: Self::resets_in(),
+            }
+            .into());
         }
         Ok(())
     }
 
+    pub fn daily_cap(&self) -> u64 {
+        self.daily_cap.load(Ordering::Relaxed)
+    }
+
+    /// Changes the daily cap. The usage so far is kept and checked against the new cap.
+    pub fn set_daily_cap(&self, daily_cap: u64) {
+        self.daily_cap.store(daily_cap, Ordering::Relaxed);
+    }
+
     /// Update daily usage and returns the new current usage.
-    pub async fn update_usage(&self, usage: i64) -> i64 {
-        self.reset_date_maybe().await;
-        let mut inner = self.inner.write().await;
-        inner.cur_daily_usage += usage;
-        inner.cur_daily_usage
+    /// The usage is multiplied by `weight` first, rounded to the nearest MIST, so that some
+    /// transactions count more or less than their actual gas against the cap.
+    pub async fn update_usage(&self, usage: i64, weight: f64) -> i64 {
+        self.reset_date_maybe();
+        let charged = (usage as f64 * weight).round() as i64;
+        self.cur_daily_usage.fetch_add(charged, Ordering::AcqRel) + charged
     }
 
-    async fn reset_date_maybe(&self) {
-        let today = Local::now().date_naive();
-        let cur_date = self.inner.read().await.cur_date;
-        if cur_date != today {
-            self.inner.write().await.reset_date(today);
+    /// Returns the usage of each window against its cap. There is only the daily window for now.
+    pub async fn usage_status(&self) -> Vec<GasUsageWindowStatus> {
+        self.reset_date_maybe();
+        vec![GasUsageWindowStatus {
+            window: "daily".to_string(),
+            usage: self.cur_daily_usage.load(Ordering::Acquire),
+            cap: self.daily_cap(),
+            resets_in_ms: Self::resets_in().as_millis() as u64,
+        }]
+    }
+
+    /// How long until the daily window resets, at the next local midnight. If that time does not
+    /// exist locally, e.g. due to a DST change, the window is reported as resetting now.
+    fn resets_in() -> Duration {
+        Local::now()
+            .date_naive()
+            .succ_opt()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .and_then(|time| time.and_local_timezone(Local).earliest())
+            .and_then(|time| (time - Local::now()).to_std().ok())
+            .unwrap_or_default()
+    }
+
+    /// Resets the usage when the day changed. Exactly one caller wins the switch to the new day
+    /// and resets the usage, while the others wait for it to finish, so that no usage of the new
+    /// day is added before the reset and lost. Usage added right before the switch by a caller
+    /// that saw the old day is counted in the new day.
+    fn reset_date_maybe(&self) {
+        let today = Self::day_number(Local::now().date_naive());
+        loop {
+            let cur_day = self.cur_day.load(Ordering::Acquire);
+            if cur_day == today {
+                return;
+            }
+            if cur_day == STARTING_NEW_DAY {
+                std::thread::yield_now();
+                continue;
+            }
+            if self
+                .cur_day
+                .compare_exchange(
+                    cur_day,
+                    STARTING_NEW_DAY,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_ok()
+            {
+                self.cur_daily_usage.store(0, Ordering::Release);
+                self.cur_day.store(today, Ordering::Release);
+                return;
+            }
         }
     }
-}
 
-impl GasUsageCapInner {
-    fn reset_date(&mut self, new_date: NaiveDate) {
-        self.cur_date = new_date;
-        self.cur_daily_usage = 0;
+    fn day_number(date: NaiveDate) -> i64 {
+        date.num_days_from_ce() as i64
     }
 }
 
@@ -67,21 +127,94 @@ mod tests {
     async fn test_gas_usage_cap() {
         let cap = GasUsageCap::new(100);
         assert!(cap.check_usage().await.is_ok());
-        cap.update_usage(50).await;
+        cap.update_usage(50, 1.0).await;
         assert!(cap.check_usage().await.is_ok());
-        cap.update_usage(49).await;
+        cap.update_usage(49, 1.0).await;
         assert!(cap.check_usage().await.is_ok());
-        cap.update_usage(1).await;
+        cap.update_usage(1, 1.0).await;
+        let err = cap.check_usage().await.unwrap_err();
+        // The cap resets at the next local midnight, at most a day away give or take a DST change.
+        let Some(GasPoolError::DailyCapExceeded { retry_after }) = GasPoolError::from_anyhow(&err)
+        else {
+            panic!("Unexpected error: {:?}", err);
+        };
+        assert!(retry_after <= Duration::from_secs(25 * 3600));
+    }
+
+    #[tokio::test]
+    async fn test_weighted_gas_usage() {
+        let cap = GasUsageCap::new(100);
+        assert_eq!(cap.update_usage(20, 2.0).await, 40);
+        assert_eq!(cap.update_usage(20, 0.5).await, 50);
+        // Rounded to the nearest MIST.
+        assert_eq!(cap.update_usage(3, 1.5).await, 55);
+        // Storage rebates are weighted like any other usage.
+        assert_eq!(cap.update_usage(-10, 2.0).await, 35);
+        assert!(cap.check_usage().await.is_ok());
+        cap.update_usage(40, 2.0).await;
+        assert!(cap.check_usage().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_daily_cap() {
+        let cap = GasUsageCap::new(100);
+        cap.update_usage(60, 1.0).await;
+        cap.set_daily_cap(50);
+        assert_eq!(cap.daily_cap(), 50);
         assert!(cap.check_usage().await.is_err());
+        cap.set_daily_cap(200);
+        assert!(cap.check_usage().await.is_ok());
+        assert_eq!(cap.usage_status().await[0].usage, 60);
+    }
+
+    #[tokio::test]
+    async fn test_gas_usage_status() {
+        let cap = GasUsageCap::new(100);
+        cap.update_usage(30, 1.0).await;
+        let status = cap.usage_status().await;
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].window, "daily");
+        assert_eq!(status[0].usage, 30);
+        assert_eq!(status[0].cap, 100);
+        assert!(status[0].resets_in_ms <= 25 * 3600 * 1000);
     }
 
     #[tokio::test]
     async fn test_gas_usage_cap_reset() {
         let today = Local::now().date_naive();
         let cap = GasUsageCap::new(100);
-        cap.update_usage(100).await;
+        cap.update_usage(100, 1.0).await;
         assert!(cap.check_usage().await.is_err());
-        cap.inner.write().await.cur_date = today + chrono::Duration::days(1);
+        cap.cur_day.store(
+            GasUsageCap::day_number(today - chrono::Duration::days(1)),
+            Ordering::Release,
+        );
         assert!(cap.check_usage().await.is_ok());
+        assert_eq!(cap.usage_status().await[0].usage, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_gas_usage_updates() {
+        let cap = std::sync::Arc::new(GasUsageCap::new(u64::MAX));
+        // Start on the previous day, so that the updates also race to reset the usage.
+        let yesterday = Local::now().date_naive() - chrono::Duration::days(1);
+        cap.cur_day
+            .store(GasUsageCap::day_number(yesterday), Ordering::Release);
+        cap.cur_daily_usage.store(1_000_000, Ordering::Release);
+        let tasks: Vec<_> = (0..64)
+            .map(|_| {
+                let cap = cap.clone();
+                tokio::spawn(async move {
+                    for _ in 0..1000 {
+                        cap.update_usage(3, 1.0).await;
+                        cap.update_usage(-1, 1.0).await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(cap.usage_status().await[0].usage, 64 * 1000 * 2);
     }
 }
@@ -0,0 +1,332 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::types::{ExpirationTimeMs, ReservationID};
+use chrono::Utc;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use sui_types::base_types::{ObjectID, SuiAddress};
+
+type ReservationKey = (SuiAddress, ReservationID);
+
+/// Caches the expiration time, coins and metadata read for recent reservations for `ttl`, for
+/// clients that read them back many times. At most `capacity` reservations are kept, dropping
+/// the least recently used one to make room. The pool invalidates a reservation whenever it
+/// changes it, and neither an expiration time that has passed nor the coins of a reservation
+/// past it are ever served, so the cache does not report a reservation as active after it
+/// expired. Changes made by other servers sharing the storage can still take up to `ttl` to show.
+pub struct ReservationReadCache {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<ReservationKey, CachedReservation>,
+    /// The reservations by when they were last used, the least recently used first.
+    recency: BTreeMap<u64, ReservationKey>,
+    /// Source of both the recency of the entries and their generations, so that an entry
+    /// dropped and cached again never gets a generation it had before.
+    ticks: u64,
+}
+
+impl Inner {
+    fn tick(&mut self) -> u64 {
+        self.ticks += 1;
+        self.ticks
+    }
+
+    /// Marks the reservation as the most recently used one.
+    fn touch(&mut self, key: ReservationKey) -> Option<&mut CachedReservation> {
+        let tick = self.tick();
+        let cached = self.entries.get_mut(&key)?;
+        self.recency.remove(&cached.last_used);
+        cached.last_used = tick;
+        self.recency.insert(tick, key);
+        Some(cached)
+    }
+}
+
+struct CachedReservation {
+    /// Bumped whenever the reservation is invalidated, so that a read that started before is
+    /// not cached.
+    generation: u64,
+    last_used: u64,
+    expiration: Option<(Option<ExpirationTimeMs>, Instant)>,
+    coins: Option<(Option<Vec<ObjectID>>, Instant)>,
+    metadata: Option<(Option<Vec<u8>>, Instant)>,
+}
+
+impl ReservationReadCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0 && !self.ttl.is_zero()
+    }
+
+    /// The cached expiration time of the reservation, None if it is not cached. A cached
+    /// expiration time that has already passed is not served.
+    pub fn get_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> Option<Option<ExpirationTimeMs>> {
+        let mut inner = self.inner.lock();
+        let cached = inner.touch((sponsor, reservation_id))?;
+        self.fresh_expiration(cached)
+    }
+
+    /// The cached coins of the reservation, None if they are not cached. The coins of a
+    /// reservation are only served along with a cached expiration time that has not passed yet.
+    pub fn get_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> Option<Option<Vec<ObjectID>>> {
+        let mut inner = self.inner.lock();
+        let cached = inner.touch((sponsor, reservation_id))?;
+        let (coins, read_at) = cached.coins.as_ref()?;
+        if read_at.elapsed() > self.ttl {
+            return None;
+        }
+        if coins.is_some() && !matches!(self.fresh_expiration(cached), Some(Some(_))) {
+            return None;
+        }
+        Some(coins.clone())
+    }
+
+    /// The cached metadata of the reservation, None if it is not cached.
+    pub fn get_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> Option<Option<Vec<u8>>> {
+        let mut inner = self.inner.lock();
+        let (metadata, read_at) = inner.touch((sponsor, reservation_id))?.metadata.as_ref()?;
+        (read_at.elapsed() <= self.ttl).then(|| metadata.clone())
+    }
+
+    /// To be read before reading the reservation from the storage, and passed along with what
+    /// was read to cache it.
+    pub fn generation(&self, sponsor: SuiAddress, reservation_id: ReservationID) -> u64 {
+        if !self.is_enabled() {
+            return 0;
+        }
+        let key = (sponsor, reservation_id);
+        let mut inner = self.inner.lock();
+        if let Some(cached) = inner.touch(key) {
+            return cached.generation;
+        }
+        if inner.entries.len() >= self.capacity {
+            if let Some((_, least_recently_used)) = inner.recency.pop_first() {
+                inner.entries.remove(&least_recently_used);
+            }
+        }
+        let tick = inner.tick();
+        inner.entries.insert(
+            key,
+            CachedReservation {
+                generation: tick,
+                last_used: tick,
+                expiration: None,
+                coins: None,
+                metadata: None,
+            },
+        );
+        inner.recency.insert(tick, key);
+        tick
+    }
+
+    /// Caches the expiration time read from the storage, unless the reservation may have
+    /// changed since the read started at `generation`.
+    pub fn put_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        expiration: Option<ExpirationTimeMs>,
+        generation: u64,
+    ) {
+        self.update(sponsor, reservation_id, generation, |cached| {
+            cached.expiration = Some((expiration, Instant::now()));
+        });
+    }
+
+    /// Same as `put_expiration`, for the coins.
+    pub fn put_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        coins: Option<Vec<ObjectID>>,
+        generation: u64,
+    ) {
+        self.update(sponsor, reservation_id, generation, |cached| {
+            cached.coins = Some((coins, Instant::now()));
+        });
+    }
+
+    /// Same as `put_expiration`, for the metadata.
+    pub fn put_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        metadata: Option<Vec<u8>>,
+        generation: u64,
+    ) {
+        self.update(sponsor, reservation_id, generation, |cached| {
+            cached.metadata = Some((metadata, Instant::now()));
+        });
+    }
+
+    /// Forgets what was read for the reservation, so that the next reads go to the storage.
+    pub fn invalidate(&self, sponsor: SuiAddress, reservation_id: ReservationID) {
+        let mut inner = self.inner.lock();
+        let tick = inner.tick();
+        // A reservation that is not cached has no read in flight that could still be cached.
+        if let Some(cached) = inner.entries.get_mut(&(sponsor, reservation_id)) {
+            cached.generation = tick;
+            cached.expiration = None;
+            cached.coins = None;
+            cached.metadata = None;
+        }
+    }
+
+    /// Forgets everything that was read, e.g. when the pool switches to another storage.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock();
+        inner.entries.clear();
+        inner.recency.clear();
+    }
+
+    fn fresh_expiration(&self, cached: &CachedReservation) -> Option<Option<ExpirationTimeMs>> {
+        let now_ms = Utc::now().timestamp_millis() as u64;
+        let (expiration, read_at) = cached.expiration?;
+        if read_at.elapsed() > self.ttl || expiration.is_some_and(|expiration| expiration <= now_ms)
+        {
+            return None;
+        }
+        Some(expiration)
+    }
+
+    fn update(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        generation: u64,
+        update: impl FnOnce(&mut CachedReservation),
+    ) {
+        let mut inner = self.inner.lock();
+        // The entry is gone if it was dropped to make room since the read started, in which case
+        // the reservation may have changed without the cache knowing.
+        if let Some(cached) = inner.entries.get_mut(&(sponsor, reservation_id)) {
+            if cached.generation == generation {
+                update(cached);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReservationReadCache;
+    use chrono::Utc;
+    use std::time::Duration;
+    use sui_types::base_types::{ObjectID, SuiAddress};
+
+    #[test]
+    fn test_reservation_read_cache() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let cache = ReservationReadCache::new(2, Duration::from_secs(60));
+        let expiration = Utc::now().timestamp_millis() as u64 + 60_000;
+        assert_eq!(cache.get_expiration(sponsor, 1), None);
+        let generation = cache.generation(sponsor, 1);
+        cache.put_expiration(sponsor, 1, Some(expiration), generation);
+        cache.put_metadata(sponsor, 1, Some(b"order".to_vec()), generation);
+        assert_eq!(cache.get_expiration(sponsor, 1), Some(Some(expiration)));
+        assert_eq!(
+            cache.get_metadata(sponsor, 1),
+            Some(Some(b"order".to_vec()))
+        );
+
+        // A read that started before the reservation changed is not cached.
+        let generation = cache.generation(sponsor, 1);
+        cache.invalidate(sponsor, 1);
+        assert_eq!(cache.get_expiration(sponsor, 1), None);
+        assert_eq!(cache.get_metadata(sponsor, 1), None);
+        cache.put_expiration(sponsor, 1, Some(expiration), generation);
+        assert_eq!(cache.get_expiration(sponsor, 1), None);
+
+        // Changes to other reservations do not get in the way.
+        let generation = cache.generation(sponsor, 1);
+        cache.invalidate(sponsor, 2);
+        cache.put_expiration(sponsor, 1, Some(expiration), generation);
+        assert_eq!(cache.get_expiration(sponsor, 1), Some(Some(expiration)));
+
+        // A reservation that is gone stays gone.
+        let generation = cache.generation(sponsor, 2);
+        cache.put_expiration(sponsor, 2, None, generation);
+        assert_eq!(cache.get_expiration(sponsor, 2), Some(None));
+
+        // The least recently used reservation makes room for a new one, and a read that started
+        // before its entry was dropped is not cached.
+        assert!(cache.get_expiration(sponsor, 1).is_some());
+        let generation = cache.generation(sponsor, 3);
+        cache.put_expiration(sponsor, 3, Some(expiration), generation);
+        assert_eq!(cache.get_expiration(sponsor, 2), None);
+        assert_eq!(cache.get_expiration(sponsor, 1), Some(Some(expiration)));
+        assert_eq!(cache.get_expiration(sponsor, 3), Some(Some(expiration)));
+        let generation = cache.generation(sponsor, 1);
+        cache.generation(sponsor, 4);
+        cache.generation(sponsor, 5);
+        cache.put_expiration(sponsor, 1, Some(expiration), generation);
+        cache.generation(sponsor, 1);
+        assert_eq!(cache.get_expiration(sponsor, 1), None);
+    }
+
+    #[test]
+    fn test_reservation_read_cache_coins() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let cache = ReservationReadCache::new(10, Duration::from_secs(60));
+        let coins = vec![ObjectID::random(), ObjectID::random()];
+        let expiration = Utc::now().timestamp_millis() as u64 + 60_000;
+
+        // The coins are only served along with an expiration time that has not passed.
+        let generation = cache.generation(sponsor, 1);
+        cache.put_coins(sponsor, 1, Some(coins.clone()), generation);
+        assert_eq!(cache.get_coins(sponsor, 1), None);
+        cache.put_expiration(sponsor, 1, Some(expiration), generation);
+        assert_eq!(cache.get_coins(sponsor, 1), Some(Some(coins.clone())));
+        let generation = cache.generation(sponsor, 2);
+        cache.put_coins(sponsor, 2, Some(coins.clone()), generation);
+        cache.put_expiration(sponsor, 2, Some(expiration - 120_000), generation);
+        assert_eq!(cache.get_coins(sponsor, 2), None);
+
+        // Executing the reservation invalidates its coins, which are gone for good.
+        cache.invalidate(sponsor, 1);
+        assert_eq!(cache.get_coins(sponsor, 1), None);
+        let generation = cache.generation(sponsor, 1);
+        cache.put_coins(sponsor, 1, None, generation);
+        assert_eq!(cache.get_coins(sponsor, 1), Some(None));
+    }
+
+    #[test]
+    fn test_reservation_read_cache_ttl() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let cache = ReservationReadCache::new(10, Duration::from_millis(10));
+        cache.put_metadata(sponsor, 1, None, cache.generation(sponsor, 1));
+        assert_eq!(cache.get_metadata(sponsor, 1), Some(None));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get_metadata(sponsor, 1), None);
+
+        let disabled = ReservationReadCache::new(0, Duration::from_secs(60));
+        disabled.put_metadata(sponsor, 1, None, disabled.generation(sponsor, 1));
+        assert_eq!(disabled.get_metadata(sponsor, 1), None);
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::types::{GasCoin, ReservationID};
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use sui_types::base_types::SuiAddress;
+
+/// A reservation made ahead of time, waiting to be handed out.
+pub struct WarmReservation {
+    pub reservation_id: ReservationID,
+    pub gas_coins: Vec<GasCoin>,
+    created_at: Instant,
+}
+
+impl WarmReservation {
+    pub fn new(reservation_id: ReservationID, gas_coins: Vec<GasCoin>) -> Self {
+        Self {
+            reservation_id,
+            gas_coins,
+            created_at: Instant::now(),
+        }
+    }
+}
+
+/// Reservations made ahead of time for a few common budgets, so that reserving one of these
+/// budgets does not need to wait for the storage to pick coins.
+/// Every budget is served by the smallest bucket that covers it. The cached reservations are
+/// regular reservations in the storage, so no other path can reserve their coins; they only
+/// need to be handed out before they expire.
+pub struct WarmReservationCache {
+    /// Sorted in increasing order.
+    buckets: Vec<u64>,
+    size_per_bucket: usize,
+    reservations: Mutex<HashMap<(SuiAddress, u64), VecDeque<WarmReservation>>>,
+}
+
+impl WarmReservationCache {
+    pub fn new(mut buckets: Vec<u64>, size_per_bucket: usize) -> Self {
+        buckets.sort();
+        buckets.dedup();
+        Self {
+            buckets,
+            size_per_bucket,
+            reservations: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.buckets.is_empty() && self.size_per_bucket > 0
+    }
+
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+
+    /// The smallest bucket that covers the budget.
+    pub fn bucket_for(&self, budget: u64) -> Option<u64> {
+        self.buckets.iter().find(|b| **b >= budget).copied()
+    }
+
+    /// Takes the oldest cached reservation of the bucket that covers the budget, as long as it
+    /// does not have more than `max_coins` coins.
+    pub fn take(
+        &self,
+        sponsor: SuiAddress,
+        budget: u64,
+        max_coins: usize,
+    ) -> Option<WarmReservation> {
+        let bucket = self.bucket_for(budget)?;
+        let mut reservations = self.reservations.lock();
+        let queue = reservations.get_mut(&(sponsor, bucket))?;
+        let index = queue.iter().position(|r| r.gas_coins.len() <= max_coins)?;
+        queue.remove(index)
+    }
+
+    /// How many reservations the bucket is missing to be full.
+    pub fn missing(&self, sponsor: SuiAddress, bucket: u64) -> usize {
+        let reservations = self.reservations.lock();
+        let len = reservations
+            .get(&(sponsor, bucket))
+            .map(|queue| queue.len())
+            .unwrap_or_default();
+        self.size_per_bucket.saturating_sub(len)
+    }
+
+    pub fn insert(&self, sponsor: SuiAddress, bucket: u64, reservation: WarmReservation) {
+        self.reservations
+            .lock()
+            .entry((sponsor, bucket))
+            .or_default()
+            .push_back(reservation);
+    }
+
+    /// Removes and returns the reservations older than `max_age`, so that they can be released
+    /// before they expire.
+    pub fn take_stale(&self, max_age: Duration) -> Vec<(SuiAddress, WarmReservation)> {
+        let mut stale = vec![];
+        for ((sponsor, _), queue) in self.reservations.lock().iter_mut() {
+            while queue
+                .front()
+                .is_some_and(|r| r.created_at.elapsed() >= max_age)
+            {
+                stale.push((*sponsor, queue.pop_front().unwrap()));
+            }
+        }
+        stale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sui_types::base_types::random_object_ref;
+
+    fn warm_reservation(reservation_id: ReservationID, coin_count: usize) -> WarmReservation {
+        let gas_coins = (0..coin_count)
+            .map(|_| GasCoin {
+                owner: SuiAddress::ZERO,
+                object_ref: random_object_ref(),
+                balance: 1,
+            })
+            .collect();
+        WarmReservation::new(reservation_id, gas_coins)
+    }
+
+    #[test]
+    fn test_warm_reservation_cache() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let cache = WarmReservationCache::new(vec![1000, 10, 100], 2);
+        assert_eq!(cache.bucket_for(1), Some(10));
+        assert_eq!(cache.bucket_for(100), Some(100));
+        assert_eq!(cache.bucket_for(101), Some(1000));
+        assert_eq!(cache.bucket_for(1001), None);
+
+        assert_eq!(cache.missing(sponsor, 100), 2);
+        cache.insert(sponsor, 100, warm_reservation(1, 3));
+        cache.insert(sponsor, 100, warm_reservation(2, 1));
+        assert_eq!(cache.missing(sponsor, 100), 0);
+
+        // Nothing cached for the bucket of this budget.
+        assert!(cache.take(sponsor, 5, 256).is_none());
+        // The oldest reservation has too many coins.
+        assert_eq!(cache.take(sponsor, 50, 2).unwrap().reservation_id, 2);
+        assert_eq!(cache.take(sponsor, 50, 256).unwrap().reservation_id, 1);
+        assert!(cache.take(sponsor, 50, 256).is_none());
+    }
+
+    #[test]
+    fn test_warm_reservation_cache_stale() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let cache = WarmReservationCache::new(vec![100], 2);
+        cache.insert(sponsor, 100, warm_reservation(1, 1));
+        assert!(cache.take_stale(Duration::from_secs(60)).is_empty());
+        let stale = cache.take_stale(Duration::ZERO);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].0, sponsor);
+        assert_eq!(cache.missing(sponsor, 100), 2);
+    }
+}
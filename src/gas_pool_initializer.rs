@@ -3,21 +3,21 @@
 
 use crate::config::CoinInitConfig;
 use crate::retry_forever;
-use crate::storage::Storage;
+use crate::storage::{Storage, DEFAULT_ADD_COINS_BATCH_SIZE};
 use crate::sui_client::SuiClient;
 use crate::tx_signer::TxSigner;
-use crate::types::GasCoin;
+use crate::types::{ExecutedTransaction, GasCoin};
+use futures_util::StreamExt;
 use parking_lot::Mutex;
 use std::cmp::min;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::str::FromStr;
 use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 use std::time::Duration;
-use sui_json_rpc_types::SuiTransactionBlockEffectsAPI;
+use sui_json_rpc_types::{SuiTransactionBlockEffectsAPI, SuiTransactionBlockResponseOptions};
 use sui_types::base_types::SuiAddress;
 use sui_types::coin::{PAY_MODULE_NAME, PAY_SPLIT_N_FUNC_NAME};
-use sui_types::gas_coin::GAS;
 use sui_types::programmable_transaction_builder::ProgrammableTransactionBuilder;
 use sui_types::transaction::{Argument, Transaction, TransactionData};
 use sui_types::SUI_FRAMEWORK_PACKAGE_ID;
@@ -89,7 +89,7 @@ impl CoinSplitEnv {
                 SUI_FRAMEWORK_PACKAGE_ID,
                 PAY_MODULE_NAME.into(),
                 PAY_SPLIT_N_FUNC_NAME.into(),
-                vec![GAS::type_tag()],
+                vec![self.sui_client.gas_coin_type().clone()],
                 vec![Argument::GasCoin, pure_arg],
             );
             let pt = pt_builder.finish();
@@ -114,10 +114,15 @@ impl CoinSplitEnv {
             );
             let result = self
                 .sui_client
-                .execute_transaction(tx.clone(), None, 10)
+                .execute_transaction(
+                    tx.clone(),
+                    None,
+                    SuiTransactionBlockResponseOptions::new().with_effects(),
+                    10,
+                )
                 .await;
             match result {
-                Ok((_, effects, _)) => {
+                Ok(ExecutedTransaction { effects, .. }) => {
                     assert!(
                         effects.status().is_ok(),
                         "Transaction failed. This should never happen. Tx: {:?}, effects: {:?}",
@@ -175,7 +180,9 @@ pub struct GasPoolInitializer {
 
 impl Drop for GasPoolInitializer {
     fn drop(&mut self) {
-        self.cancel_sender.take().unwrap().send(()).unwrap();
+        if let Some(cancel_sender) = self.cancel_sender.take() {
+            let _ = cancel_sender.send(());
+        }
     }
 }
 
@@ -193,6 +200,7 @@ impl GasPoolInitializer {
                 &storage,
                 RunMode::Init,
                 coin_init_config.target_init_balance,
+                &coin_init_config.min_import_balances,
                 &signer,
             )
             .await;
@@ -232,6 +240,7 @@ impl GasPoolInitializer {
                 &storage,
                 RunMode::Refresh,
                 coin_init_config.target_init_balance,
+                &coin_init_config.min_import_balances,
                 &signer,
             )
             .await;
@@ -243,6 +252,7 @@ impl GasPoolInitializer {
         storage: &Arc<dyn Storage>,
         mode: RunMode,
         target_init_coin_balance: u64,
+        min_import_balances: &BTreeMap<SuiAddress, u64>,
         signer: &Arc<dyn TxSigner>,
     ) {
         let init_lock_vec = storage
@@ -253,12 +263,9 @@ impl GasPoolInitializer {
         let mut handles = vec![];
 
         let start = Instant::now();
-        let balance_threshold = if matches!(mode, RunMode::Init) {
+        if matches!(mode, RunMode::Init) {
             info!("The pool has never been initialized. Initializing it for the first time");
-            0
-        } else {
-            target_init_coin_balance * NEW_COIN_BALANCE_FACTOR_THRESHOLD
-        };
+        }
 
         for (sponsor, init_lock) in init_lock_vec {
             if !init_lock {
@@ -273,41 +280,69 @@ impl GasPoolInitializer {
                 let signer = signer.clone();
 
                 let sponsor = SuiAddress::from_str(&sponsor).unwrap();
+                let balance_threshold = Self::balance_threshold(
+                    &mode,
+                    target_init_coin_balance,
+                    min_import_balances.get(&sponsor).copied(),
+                );
 
                 handles.push(tokio::spawn(async move {
-                    let coins = sui_client
-                        .get_all_owned_sui_coins_above_balance_threshold(sponsor, balance_threshold)
-                        .await;
-                    if coins.is_empty() {
+                    // The coins are split and added one page of the fullnode response at a time,
+                    // so that they are never all held in memory.
+                    let mut pages = std::pin::pin!(sui_client
+                        .stream_owned_sui_coins_above_balance_threshold(
+                            sponsor,
+                            balance_threshold
+                        ));
+                    let mut split_env: Option<CoinSplitEnv> = None;
+                    // The coins split off are owned by the sponsor too, and can show up in the
+                    // pages that come next.
+                    let mut split_coin_ids = HashSet::new();
+                    while let Some(coins) = pages.next().await {
+                        let coins: Vec<_> = coins
+                            .into_iter()
+                            .filter(|coin| !split_coin_ids.contains(&coin.object_ref.0))
+                            .collect();
+                        if coins.is_empty() {
+                            continue;
+                        }
+                        let env = match split_env.clone() {
+                            Some(env) => env,
+                            None => {
+                                let rgp = sui_client.get_reference_gas_price().await;
+                                let gas_cost_per_object = sui_client
+                                    .calibrate_gas_cost_per_object(sponsor, &coins[0])
+                                    .await;
+                                info!("Calibrated gas cost per object: {:?}", gas_cost_per_object);
+                                split_env
+                                    .insert(CoinSplitEnv {
+                                        target_init_coin_balance,
+                                        gas_cost_per_object,
+                                        signer: signer.clone(),
+                                        sponsor_address: sponsor,
+                                        sui_client: sui_client.clone(),
+                                        task_queue: Default::default(),
+                                        total_coin_count: Default::default(),
+                                        rgp,
+                                    })
+                                    .clone()
+                            }
+                        };
+                        env.total_coin_count
+                            .fetch_add(coins.len(), std::sync::atomic::Ordering::Relaxed);
+                        let result = Self::split_gas_coins(coins, env).await;
+                        split_coin_ids.extend(result.iter().map(|coin| coin.object_ref.0));
+                        storage
+                            .add_new_coins_in_batches(result, DEFAULT_ADD_COINS_BATCH_SIZE)
+                            .await
+                            .unwrap();
+                    }
+                    if split_env.is_none() {
                         info!(
                         "No coins with balance above {} found. Skipping new coin initialization",
                         balance_threshold
                     );
                         storage.release_init_lock().await.unwrap();
-                        return;
-                    }
-                    let total_coin_count = Arc::new(AtomicUsize::new(coins.len()));
-                    let rgp = sui_client.get_reference_gas_price().await;
-                    let gas_cost_per_object = sui_client
-                        .calibrate_gas_cost_per_object(sponsor, &coins[0])
-                        .await;
-                    info!("Calibrated gas cost per object: {:?}", gas_cost_per_object);
-                    let result = Self::split_gas_coins(
-                        coins,
-                        CoinSplitEnv {
-                            target_init_coin_balance,
-                            gas_cost_per_object,
-                            signer: signer.clone(),
-                            sponsor_address: sponsor,
-                            sui_client: sui_client.clone(),
-                            task_queue: Default::default(),
-                            total_coin_count,
-                            rgp,
-                        },
-                    )
-                    .await;
-                    for chunk in result.chunks(5000) {
-                        storage.add_new_coins(chunk.to_vec()).await.unwrap();
                     }
                 }));
             }
@@ -326,6 +361,21 @@ impl GasPoolInitializer {
         );
     }
 
+    /// The smallest balance of the coins looked up on chain. At initialization every coin is
+    /// imported, while later runs only pick up coins large enough to be new funding. A
+    /// sponsor's minimum import balance raises either.
+    fn balance_threshold(
+        mode: &RunMode,
+        target_init_coin_balance: u64,
+        min_import_balance: Option<u64>,
+    ) -> u64 {
+        let threshold = match mode {
+            RunMode::Init => 0,
+            RunMode::Refresh => target_init_coin_balance * NEW_COIN_BALANCE_FACTOR_THRESHOLD,
+        };
+        threshold.max(min_import_balance.unwrap_or(0))
+    }
+
     async fn split_gas_coins(coins: Vec<GasCoin>, env: CoinSplitEnv) -> Vec<GasCoin> {
         let total_balance: u64 = coins.iter().map(|c| c.balance).sum();
         info!(
@@ -359,10 +409,13 @@ impl GasPoolInitializer {
 #[cfg(test)]
 mod tests {
     use crate::config::CoinInitConfig;
-    use crate::gas_pool_initializer::{GasPoolInitializer, NEW_COIN_BALANCE_FACTOR_THRESHOLD};
+    use crate::gas_pool_initializer::{
+        GasPoolInitializer, RunMode, NEW_COIN_BALANCE_FACTOR_THRESHOLD,
+    };
     use crate::storage::connect_storage_for_testing;
     use crate::sui_client::SuiClient;
     use crate::test_env::start_sui_cluster;
+    use std::collections::BTreeMap;
     use sui_types::gas_coin::MIST_PER_OCT;
 
     // TODO: Add more accurate tests.
@@ -375,13 +428,16 @@ mod tests {
         let sponsor_addresses = signer.get_addresses();
         let sponsor = sponsor_addresses[0];
         let storage = connect_storage_for_testing(sponsor_addresses).await;
-        let sui_client = SuiClient::new(&fullnode_url, None).await;
+        let sui_client = SuiClient::new(&fullnode_url, None, Default::default())
+            .await
+            .unwrap();
         let _ = GasPoolInitializer::start(
             sui_client,
             storage.clone(),
             CoinInitConfig {
                 target_init_balance: MIST_PER_OCT,
                 refresh_interval_sec: 200,
+                ..Default::default()
             },
             signer,
         )
@@ -398,13 +454,16 @@ mod tests {
         let sponsor = sponsor_addresses[0];
         let storage = connect_storage_for_testing(sponsor_addresses).await;
         let target_init_balance = 12345 * MIST_PER_OCT;
-        let sui_client = SuiClient::new(&fullnode_url, None).await;
+        let sui_client = SuiClient::new(&fullnode_url, None, Default::default())
+            .await
+            .unwrap();
         let _ = GasPoolInitializer::start(
             sui_client,
             storage.clone(),
             CoinInitConfig {
                 target_init_balance,
                 refresh_interval_sec: 200,
+                ..Default::default()
             },
             signer,
         )
@@ -412,6 +471,70 @@ mod tests {
         assert!(storage.get_available_coin_count(sponsor).await.unwrap() > 800);
     }
 
+    #[test]
+    fn test_balance_threshold() {
+        let target = MIST_PER_OCT;
+        let refresh_threshold = target * NEW_COIN_BALANCE_FACTOR_THRESHOLD;
+        assert_eq!(
+            GasPoolInitializer::balance_threshold(&RunMode::Init, target, None),
+            0
+        );
+        assert_eq!(
+            GasPoolInitializer::balance_threshold(&RunMode::Refresh, target, None),
+            refresh_threshold
+        );
+        assert_eq!(
+            GasPoolInitializer::balance_threshold(&RunMode::Init, target, Some(5 * target)),
+            5 * target
+        );
+        // A minimum below the refresh threshold does not make refreshes pick up pool coins.
+        assert_eq!(
+            GasPoolInitializer::balance_threshold(&RunMode::Refresh, target, Some(5 * target)),
+            refresh_threshold
+        );
+        assert_eq!(
+            GasPoolInitializer::balance_threshold(
+                &RunMode::Refresh,
+                target,
+                Some(2 * refresh_threshold)
+            ),
+            2 * refresh_threshold
+        );
+    }
+
+    #[tokio::test]
+    async fn test_init_min_import_balance() {
+        telemetry_subscribers::init_for_testing();
+        let (cluster, signer) = start_sui_cluster(vec![
+            MIST_PER_OCT,
+            MIST_PER_OCT,
+            3 * MIST_PER_OCT,
+            3 * MIST_PER_OCT,
+            3 * MIST_PER_OCT,
+        ])
+        .await;
+        let fullnode_url = cluster.fullnode_handle.rpc_url;
+        let sponsor_addresses = signer.get_addresses();
+        let sponsor = sponsor_addresses[0];
+        let storage = connect_storage_for_testing(sponsor_addresses).await;
+        let sui_client = SuiClient::new(&fullnode_url, None, Default::default())
+            .await
+            .unwrap();
+        // The target is large enough that none of the coins is split.
+        let _ = GasPoolInitializer::start(
+            sui_client,
+            storage.clone(),
+            CoinInitConfig {
+                target_init_balance: 10 * MIST_PER_OCT,
+                refresh_interval_sec: 200,
+                min_import_balances: BTreeMap::from([(sponsor, 2 * MIST_PER_OCT)]),
+            },
+            signer,
+        )
+        .await;
+        assert_eq!(storage.get_available_coin_count(sponsor).await.unwrap(), 3);
+    }
+
     #[tokio::test]
     async fn test_add_new_funds_to_pool() {
         telemetry_subscribers::init_for_testing();
@@ -420,13 +543,16 @@ mod tests {
         let sponsor = sponsor_addresses[0];
         let fullnode_url = cluster.fullnode_handle.rpc_url.clone();
         let storage = connect_storage_for_testing(sponsor_addresses).await;
-        let sui_client = SuiClient::new(&fullnode_url, None).await;
+        let sui_client = SuiClient::new(&fullnode_url, None, Default::default())
+            .await
+            .unwrap();
         let _init_task = GasPoolInitializer::start(
             sui_client,
             storage.clone(),
             CoinInitConfig {
                 target_init_balance: MIST_PER_OCT,
                 refresh_interval_sec: 1,
+                ..Default::default()
             },
             signer,
         )
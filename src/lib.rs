@@ -1,13 +1,18 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod audit_log;
 pub mod benchmarks;
+pub mod coin_autoscaler;
 pub mod command;
 pub mod config;
 pub mod errors;
+pub mod gas_estimator;
 pub mod gas_pool;
 pub mod gas_pool_initializer;
 pub mod metrics;
+pub mod onchain_reconciler;
+pub mod reservation_watchdog;
 pub mod rpc;
 pub mod storage;
 pub mod sui_client;
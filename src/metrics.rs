@@ -1,10 +1,11 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use mysten_metrics::histogram::Histogram;
+use mysten_metrics::histogram::{Histogram, HistogramVec};
 use prometheus::{
     register_int_counter_vec_with_registry, register_int_counter_with_registry,
-    register_int_gauge_vec_with_registry, IntCounter, IntCounterVec, IntGaugeVec, Registry,
+    register_int_gauge_vec_with_registry, register_int_gauge_with_registry, IntCounter,
+    IntCounterVec, IntGauge, IntGaugeVec, Registry,
 };
 use std::sync::Arc;
 use tracing::error;
@@ -106,6 +107,42 @@ pub struct GasPoolCoreMetrics {
     pub transaction_execution_latency_ms: Histogram,
     pub num_gas_pool_invariant_violations: IntCounter,
     pub daily_gas_usage: IntGaugeVec,
+    pub daily_gas_usage_cap: IntGauge,
+    pub num_rejected_by_estimated_gas_cost: IntCounterVec,
+    pub onchain_coin_count_divergence: IntGaugeVec,
+    pub onchain_total_balance_divergence: IntGaugeVec,
+    pub num_onchain_divergences: IntCounterVec,
+    pub autoscaler_target_coin_count: IntGaugeVec,
+    pub autoscaler_current_coin_count: IntGaugeVec,
+    pub autoscaler_average_coin_balance: IntGaugeVec,
+    pub autoscaler_last_action: IntGaugeVec,
+    pub autoscaler_coins_remaining_to_merge: IntGaugeVec,
+    pub num_autoscaler_actions: IntCounterVec,
+    pub reserve_gas_total: IntCounterVec,
+    pub execute_transaction_total: IntCounterVec,
+    pub num_net_rebate_transactions: IntCounterVec,
+    pub num_released_gas_coins_after_execution: IntCounterVec,
+    pub num_reconciled_reservations: IntCounterVec,
+    pub num_unexpected_gas_objects: IntCounterVec,
+    pub num_refreshed_stale_gas_coins: IntCounterVec,
+    pub num_warm_cache_reservations: IntCounterVec,
+    pub warm_cache_reserve_latency_ms: Histogram,
+    pub sponsor_in_flight_executions: IntGaugeVec,
+    pub coins_vanished_on_expiry: IntCounter,
+    pub storage_operation_latency_ms: HistogramVec,
+    pub num_storage_operation_errors: IntCounterVec,
+    pub oldest_active_reservation_age_ms: IntGaugeVec,
+    pub post_execution_release_wait_ms: Histogram,
+    pub num_post_execution_release_timeouts: IntCounterVec,
+    pub num_onchain_fallback_coins_imported: IntCounterVec,
+    pub num_oversized_gas_coin_splits: IntCounterVec,
+    pub reservation_duration_used_pct: HistogramVec,
+    pub num_signer_timeouts: IntCounterVec,
+    pub cached_available_coin_count: IntGaugeVec,
+    pub coin_count_cache_drift: IntGaugeVec,
+    pub stuck_reservation_count: IntGaugeVec,
+    pub oldest_stuck_reservation_age_sec: IntGaugeVec,
+    pub reservations_frozen: IntGauge,
 }
 
 impl GasPoolCoreMetrics {
@@ -158,6 +195,249 @@ impl GasPoolCoreMetrics {
                 registry,
             )
                 .unwrap(),
+            daily_gas_usage_cap: register_int_gauge_with_registry!(
+                "daily_gas_usage_cap",
+                "Current daily gas usage cap, shared by all sponsors",
+                registry,
+            )
+            .unwrap(),
+            num_rejected_by_estimated_gas_cost: register_int_counter_vec_with_registry!(
+                "num_rejected_by_estimated_gas_cost",
+                "Total number of transactions rejected because their estimated gas cost exceeds the configured maximum",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            onchain_coin_count_divergence: register_int_gauge_vec_with_registry!(
+                "onchain_coin_count_divergence",
+                "Available coin count in storage minus the count recorded in the on-chain anchor object",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            onchain_total_balance_divergence: register_int_gauge_vec_with_registry!(
+                "onchain_total_balance_divergence",
+                "Available coin total balance in storage minus the balance recorded in the on-chain anchor object",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            num_onchain_divergences: register_int_counter_vec_with_registry!(
+                "num_onchain_divergences",
+                "Total number of reconciliation rounds where storage diverged from the on-chain anchor object",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            autoscaler_target_coin_count: register_int_gauge_vec_with_registry!(
+                "autoscaler_target_coin_count",
+                "Number of available coins the autoscaler aims to keep",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            autoscaler_current_coin_count: register_int_gauge_vec_with_registry!(
+                "autoscaler_current_coin_count",
+                "Number of available coins seen by the autoscaler in its last check",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            autoscaler_average_coin_balance: register_int_gauge_vec_with_registry!(
+                "autoscaler_average_coin_balance",
+                "Average balance of the available coins seen by the autoscaler in its last check",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            autoscaler_last_action: register_int_gauge_vec_with_registry!(
+                "autoscaler_last_action",
+                "Action decided by the autoscaler in its last check: 0 for none, 1 for split, 2 for merge",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            autoscaler_coins_remaining_to_merge: register_int_gauge_vec_with_registry!(
+                "autoscaler_coins_remaining_to_merge",
+                "Number of coins the autoscaler still has to merge in its current merge",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            num_autoscaler_actions: register_int_counter_vec_with_registry!(
+                "num_autoscaler_actions",
+                "Total number of successful split or merge actions taken by the autoscaler",
+                &["sponsor", "action"],
+                registry,
+            )
+                .unwrap(),
+            reserve_gas_total: register_int_counter_vec_with_registry!(
+                "reserve_gas_total",
+                "Total number of reserve_gas calls, by outcome",
+                &["sponsor", "outcome"],
+                registry,
+            )
+                .unwrap(),
+            execute_transaction_total: register_int_counter_vec_with_registry!(
+                "execute_transaction_total",
+                "Total number of execute_transaction calls, by outcome",
+                &["sponsor", "outcome"],
+                registry,
+            )
+                .unwrap(),
+            num_net_rebate_transactions: register_int_counter_vec_with_registry!(
+                "num_net_rebate_transactions",
+                "Total number of transactions whose storage rebates exceeded their gas cost, increasing the gas coin balance",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            num_released_gas_coins_after_execution: register_int_counter_vec_with_registry!(
+                "num_released_gas_coins_after_execution",
+                "Total number of gas coins released back to the pool after a transaction execution. Its rate relative to the pool size is the coin turnover rate",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            num_reconciled_reservations: register_int_counter_vec_with_registry!(
+                "num_reconciled_reservations",
+                "Total number of persisted reservations checked at startup, by outcome: kept, refreshed or expired",
+                &["sponsor", "outcome"],
+                registry,
+            )
+                .unwrap(),
+            num_unexpected_gas_objects: register_int_counter_vec_with_registry!(
+                "num_unexpected_gas_objects",
+                "Total number of executed transactions whose effects reported a gas object outside of the payment coins",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            num_refreshed_stale_gas_coins: register_int_counter_vec_with_registry!(
+                "num_refreshed_stale_gas_coins",
+                "Total number of reserved gas coins whose version was stale and got refreshed before being returned, each one an execution failure avoided",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            num_warm_cache_reservations: register_int_counter_vec_with_registry!(
+                "num_warm_cache_reservations",
+                "Total number of reservations that were looked up in the warm cache, by whether they were served from it",
+                &["sponsor", "outcome"],
+                registry,
+            )
+                .unwrap(),
+            warm_cache_reserve_latency_ms: Histogram::new_in_registry(
+                "warm_cache_reserve_latency",
+                "Latency of gas reservations served from the warm cache, in milliseconds, to compare against reserve_gas_latency",
+                registry,
+            ),
+            sponsor_in_flight_executions: register_int_gauge_vec_with_registry!(
+                "sponsor_in_flight_executions",
+                "Number of executions in flight for each sponsor with a concurrency limit",
+                &["sponsor"],
+                registry,
+            )
+                .unwrap(),
+            coins_vanished_on_expiry: register_int_counter_with_registry!(
+                "coins_vanished_on_expiry",
+                "Number of expired coins that no longer existed on chain when they were released",
+                registry,
+            )
+            .unwrap(),
+            storage_operation_latency_ms: HistogramVec::new_in_registry(
+                "storage_operation_latency_ms",
+                "Latency of each storage operation, in milliseconds",
+                &["operation"],
+                registry,
+            ),
+            num_storage_operation_errors: register_int_counter_vec_with_registry!(
+                "num_storage_operation_errors",
+                "Number of storage operations that returned an error",
+                &["operation"],
+                registry,
+            )
+            .unwrap(),
+            oldest_active_reservation_age_ms: register_int_gauge_vec_with_registry!(
+                "oldest_active_reservation_age_ms",
+                "Age of the oldest reservation whose coins are still reserved, in milliseconds, 0 if there is none",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            post_execution_release_wait_ms: Histogram::new_in_registry(
+                "post_execution_release_wait_ms",
+                "Time the gas coin of an executed transaction waited before going back to the pool, in milliseconds",
+                registry,
+            ),
+            num_post_execution_release_timeouts: register_int_counter_vec_with_registry!(
+                "num_post_execution_release_timeouts",
+                "Number of gas coins released before the fullnode served their new version",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            num_onchain_fallback_coins_imported: register_int_counter_vec_with_registry!(
+                "num_onchain_fallback_coins_imported",
+                "Number of coins owned by the sponsor on chain that were imported when the pool ran out of coins",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            num_oversized_gas_coin_splits: register_int_counter_vec_with_registry!(
+                "num_oversized_gas_coin_splits",
+                "Number of gas coins split after an execution because their balance exceeded split-gas-coins-above",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            reservation_duration_used_pct: HistogramVec::new_in_registry(
+                "reservation_duration_used_pct",
+                "Time from reservation to execution, as a percentage of the requested reservation duration",
+                &["sponsor"],
+                registry,
+            ),
+            num_signer_timeouts: register_int_counter_vec_with_registry!(
+                "num_signer_timeouts",
+                "Number of attempts to sign a transaction that timed out waiting for the signer",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            cached_available_coin_count: register_int_gauge_vec_with_registry!(
+                "cached_available_coin_count",
+                "Available coin count of each sponsor as kept track of by the pool, without querying the storage",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            coin_count_cache_drift: register_int_gauge_vec_with_registry!(
+                "coin_count_cache_drift",
+                "Cached available coin count minus the count in storage, as of the last reconciliation",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            stuck_reservation_count: register_int_gauge_vec_with_registry!(
+                "stuck_reservation_count",
+                "Number of reservations whose coins have been in flight for longer than the watchdog allows",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            oldest_stuck_reservation_age_sec: register_int_gauge_vec_with_registry!(
+                "oldest_stuck_reservation_age_sec",
+                "How long the coins of the oldest stuck reservation have been in flight, 0 if there is none",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            reservations_frozen: register_int_gauge_with_registry!(
+                "reservations_frozen",
+                "Whether new reservations are frozen by an operator: 1 if frozen, 0 otherwise",
+                registry,
+            )
+                .unwrap(),
         })
     }
 
@@ -178,6 +458,7 @@ impl GasPoolCoreMetrics {
 pub struct StorageMetrics {
     pub gas_pool_available_gas_coin_count: IntGaugeVec,
     pub gas_pool_available_gas_total_balance: IntGaugeVec,
+    pub gas_pool_max_gas_coin_count: IntGaugeVec,
 
     pub num_reserve_gas_coins_requests: IntCounter,
     pub num_successful_reserve_gas_coins_requests: IntCounter,
@@ -187,6 +468,8 @@ pub struct StorageMetrics {
     pub num_successful_add_new_coins_requests: IntCounter,
     pub num_expire_coins_requests: IntCounter,
     pub num_successful_expire_coins_requests: IntCounter,
+    pub num_unconfirmed_reservation_expirations: IntCounterVec,
+    pub num_coins_over_max_pool_size: IntCounterVec,
 }
 
 impl StorageMetrics {
@@ -206,6 +489,13 @@ impl StorageMetrics {
                 registry,
             )
             .unwrap(),
+            gas_pool_max_gas_coin_count: register_int_gauge_vec_with_registry!(
+                "gas_pool_max_gas_coin_count",
+                "Maximum number of available gas coins the pool holds, only set when capped",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
             num_reserve_gas_coins_requests: register_int_counter_with_registry!(
                 "num_reserve_gas_coins_requests",
                 "Total number of reserve_gas_coins requests received",
@@ -254,6 +544,20 @@ impl StorageMetrics {
                 registry,
             )
             .unwrap(),
+            num_unconfirmed_reservation_expirations: register_int_counter_vec_with_registry!(
+                "num_unconfirmed_reservation_expirations",
+                "Total number of reservations that expired because they were never confirmed",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
+            num_coins_over_max_pool_size: register_int_counter_vec_with_registry!(
+                "num_coins_over_max_pool_size",
+                "Total number of coins left on chain because the pool was at its maximum size",
+                &["sponsor"],
+                registry,
+            )
+            .unwrap(),
         })
     }
 
@@ -0,0 +1,183 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::OnChainAnchorConfig;
+use crate::metrics::GasPoolCoreMetrics;
+use crate::storage::Storage;
+use crate::sui_client::SuiClient;
+use crate::types::OnChainPoolState;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use sui_types::base_types::{ObjectID, SuiAddress};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// The difference between the off-chain storage and the on-chain anchor object of a sponsor.
+/// Positive values mean that the storage has more than what is recorded on chain.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolDivergence {
+    pub sponsor: SuiAddress,
+    pub coin_count_delta: i64,
+    pub total_balance_delta: i64,
+}
+
+impl PoolDivergence {
+    pub fn new(
+        sponsor: SuiAddress,
+        off_chain_coin_count: u64,
+        off_chain_total_balance: u64,
+        on_chain: &OnChainPoolState,
+    ) -> Self {
+        Self {
+            sponsor,
+            coin_count_delta: off_chain_coin_count as i64 - on_chain.available_coin_count as i64,
+            total_balance_delta: off_chain_total_balance as i64
+                - on_chain.available_coin_total_balance as i64,
+        }
+    }
+
+    pub fn is_diverged(&self) -> bool {
+        self.coin_count_delta != 0 || self.total_balance_delta != 0
+    }
+}
+
+/// Periodically compares the off-chain storage against the anchor object of each sponsor,
+/// and reports any divergence through metrics.
+pub struct OnChainReconciler {
+    _task_handle: JoinHandle<()>,
+    // This is always Some. It is None only after the drop method is called.
+    cancel_sender: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl Drop for OnChainReconciler {
+    fn drop(&mut self) {
+        if let Some(cancel_sender) = self.cancel_sender.take() {
+            let _ = cancel_sender.send(());
+        }
+    }
+}
+
+impl OnChainReconciler {
+    pub fn start(
+        sui_client: SuiClient,
+        storage: Arc<dyn Storage>,
+        config: OnChainAnchorConfig,
+        metrics: Arc<GasPoolCoreMetrics>,
+    ) -> Self {
+        let (cancel_sender, cancel_receiver) = tokio::sync::oneshot::channel();
+        let _task_handle = tokio::spawn(Self::run(
+            sui_client,
+            storage,
+            config,
+            metrics,
+            cancel_receiver,
+        ));
+        Self {
+            _task_handle,
+            cancel_sender: Some(cancel_sender),
+        }
+    }
+
+    async fn run(
+        sui_client: SuiClient,
+        storage: Arc<dyn Storage>,
+        config: OnChainAnchorConfig,
+        metrics: Arc<GasPoolCoreMetrics>,
+        mut cancel_receiver: tokio::sync::oneshot::Receiver<()>,
+    ) {
+        loop {
+            if let Err(err) =
+                Self::reconcile_once(&sui_client, &storage, &config.anchor_objects, &metrics).await
+            {
+                error!(
+                    "Failed to reconcile with on-chain anchor objects: {:?}",
+                    err
+                );
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(config.reconcile_interval_sec)) => {}
+                _ = &mut cancel_receiver => {
+                    info!("On-chain reconciler task is cancelled");
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Compares the storage against the anchor object of each sponsor once.
+    /// Returns the divergence of every sponsor whose anchor object could be read.
+    pub async fn reconcile_once(
+        sui_client: &SuiClient,
+        storage: &Arc<dyn Storage>,
+        anchor_objects: &BTreeMap<SuiAddress, ObjectID>,
+        metrics: &GasPoolCoreMetrics,
+    ) -> anyhow::Result<Vec<PoolDivergence>> {
+        let mut results = vec![];
+        for (sponsor, object_id) in anchor_objects {
+            let Some(on_chain) = sui_client.get_onchain_pool_state(*object_id).await else {
+                warn!(
+                    ?sponsor,
+                    "Unable to read pool state from anchor object {:?}", object_id
+                );
+                continue;
+            };
+            if on_chain.owner != *sponsor {
+                warn!(
+                    ?sponsor,
+                    "Anchor object {:?} is owned by {:?} instead of the sponsor",
+                    object_id,
+                    on_chain.owner
+                );
+                continue;
+            }
+            let coin_count = storage.get_available_coin_count(*sponsor).await?;
+            let total_balance = storage.get_available_coin_total_balance(*sponsor).await;
+            let divergence =
+                PoolDivergence::new(*sponsor, coin_count as u64, total_balance, &on_chain);
+            let sponsor_str = sponsor.to_string();
+            metrics
+                .onchain_coin_count_divergence
+                .with_label_values(&[&sponsor_str])
+                .set(divergence.coin_count_delta);
+            metrics
+                .onchain_total_balance_divergence
+                .with_label_values(&[&sponsor_str])
+                .set(divergence.total_balance_delta);
+            if divergence.is_diverged() {
+                warn!(
+                    ?sponsor,
+                    "Storage diverged from on-chain anchor object: {:?}", divergence
+                );
+                metrics
+                    .num_onchain_divergences
+                    .with_label_values(&[&sponsor_str])
+                    .inc();
+            }
+            results.push(divergence);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PoolDivergence;
+    use crate::types::OnChainPoolState;
+    use sui_types::base_types::SuiAddress;
+
+    #[test]
+    fn test_pool_divergence() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let on_chain = OnChainPoolState {
+            owner: sponsor,
+            available_coin_count: 10,
+            available_coin_total_balance: 1000,
+        };
+        assert!(!PoolDivergence::new(sponsor, 10, 1000, &on_chain).is_diverged());
+        let divergence = PoolDivergence::new(sponsor, 8, 1100, &on_chain);
+        assert!(divergence.is_diverged());
+        assert_eq!(divergence.coin_count_delta, -2);
+        assert_eq!(divergence.total_balance_delta, 100);
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::StuckReservationWatchdogConfig;
+use crate::metrics::GasPoolCoreMetrics;
+use crate::storage::Storage;
+use crate::types::StuckReservations;
+use std::sync::Arc;
+use std::time::Duration;
+use sui_types::base_types::SuiAddress;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// Periodically counts the reservations of each sponsor whose coins have been in flight for
+/// longer than a configured age, and reports them through metrics. Such coins are out of the
+/// pool until their in-flight index entries expire, so this is meant for alerting on them.
+pub struct ReservationWatchdog {
+    _task_handle: JoinHandle<()>,
+    // This is always Some. It is None only after the drop method is called.
+    cancel_sender: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl Drop for ReservationWatchdog {
+    fn drop(&mut self) {
+        if let Some(cancel_sender) = self.cancel_sender.take() {
+            let _ = cancel_sender.send(());
+        }
+    }
+}
+
+impl ReservationWatchdog {
+    pub fn start(
+        storage: Arc<dyn Storage>,
+        sponsors: Vec<SuiAddress>,
+        config: StuckReservationWatchdogConfig,
+        metrics: Arc<GasPoolCoreMetrics>,
+    ) -> Self {
+        let (cancel_sender, cancel_receiver) = tokio::sync::oneshot::channel();
+        let _task_handle = tokio::spawn(Self::run(
+            storage,
+            sponsors,
+            config,
+            metrics,
+            cancel_receiver,
+        ));
+        Self {
+            _task_handle,
+            cancel_sender: Some(cancel_sender),
+        }
+    }
+
+    async fn run(
+        storage: Arc<dyn Storage>,
+        sponsors: Vec<SuiAddress>,
+        config: StuckReservationWatchdogConfig,
+        metrics: Arc<GasPoolCoreMetrics>,
+        mut cancel_receiver: tokio::sync::oneshot::Receiver<()>,
+    ) {
+        let min_age = Duration::from_secs(config.min_age_sec);
+        loop {
+            for sponsor in &sponsors {
+                if let Err(err) = Self::check_once(&storage, *sponsor, min_age, &metrics).await {
+                    error!(?sponsor, "Failed to look for stuck reservations: {:?}", err);
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(config.check_interval_sec)) => {}
+                _ = &mut cancel_receiver => {
+                    info!("Reservation watchdog task is cancelled");
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Looks for the stuck reservations of a sponsor once, with a single storage query, and
+    /// updates the metrics with what it finds.
+    pub async fn check_once(
+        storage: &Arc<dyn Storage>,
+        sponsor: SuiAddress,
+        min_age: Duration,
+        metrics: &GasPoolCoreMetrics,
+    ) -> anyhow::Result<StuckReservations> {
+        let stuck = storage.get_stuck_reservations(sponsor, min_age).await?;
+        let oldest_age = stuck.oldest_age().unwrap_or_default();
+        let sponsor_str = sponsor.to_string();
+        metrics
+            .stuck_reservation_count
+            .with_label_values(&[&sponsor_str])
+            .set(stuck.count as i64);
+        metrics
+            .oldest_stuck_reservation_age_sec
+            .with_label_values(&[&sponsor_str])
+            .set(oldest_age.as_secs() as i64);
+        if stuck.count > 0 {
+            warn!(
+                ?sponsor,
+                "{} reservations have been in flight for longer than {:?}, the oldest for {:?}",
+                stuck.count,
+                min_age,
+                oldest_age
+            );
+        }
+        Ok(stuck)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReservationWatchdog;
+    use crate::metrics::GasPoolCoreMetrics;
+    use crate::storage::connect_storage_for_testing;
+    use crate::types::GasCoin;
+    use std::time::Duration;
+    use sui_types::base_types::{random_object_ref, SuiAddress};
+
+    #[tokio::test]
+    async fn test_stuck_reservation_metrics() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = connect_storage_for_testing(vec![sponsor]).await;
+        let coins: Vec<_> = (0..2)
+            .map(|_| GasCoin {
+                owner: sponsor,
+                object_ref: random_object_ref(),
+                balance: 100,
+            })
+            .collect();
+        storage.add_new_coins(coins).await.unwrap();
+        let metrics = GasPoolCoreMetrics::new_for_testing();
+        let sponsor_str = sponsor.to_string();
+        let count = || {
+            metrics
+                .stuck_reservation_count
+                .with_label_values(&[&sponsor_str])
+                .get()
+        };
+
+        let (stale_id, _) = storage.reserve_gas_coins(sponsor, 1, 60_000).await.unwrap();
+        storage
+            .ready_for_execution(sponsor, stale_id)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        let (fresh_id, _) = storage.reserve_gas_coins(sponsor, 1, 60_000).await.unwrap();
+        storage
+            .ready_for_execution(sponsor, fresh_id)
+            .await
+            .unwrap();
+
+        // Only the reservation that has been in flight for longer than the age is stuck.
+        let min_age = Duration::from_secs(1);
+        let stuck = ReservationWatchdog::check_once(&storage, sponsor, min_age, &metrics)
+            .await
+            .unwrap();
+        assert_eq!(stuck.count, 1);
+        assert!(stuck.oldest_age().unwrap() >= min_age);
+        assert_eq!(count(), 1);
+        assert!(
+            metrics
+                .oldest_stuck_reservation_age_sec
+                .with_label_values(&[&sponsor_str])
+                .get()
+                >= 1
+        );
+        let stuck = ReservationWatchdog::check_once(&storage, sponsor, Duration::ZERO, &metrics)
+            .await
+            .unwrap();
+        assert_eq!(stuck.count, 2);
+        assert_eq!(count(), 2);
+    }
+}
@@ -3,16 +3,17 @@
 
 use crate::read_auth_env;
 use crate::rpc::rpc_types::{
-    ExecuteTxRequest, ExecuteTxResponse, ReserveGasRequest, ReserveGasResponse,
-    SupportAddressResponse,
+    ExecuteTxRequest, ExecuteTxResponse, GetExecutionResultRequest, GetExecutionResultResponse,
+    ReserveGasRequest, ReserveGasResponse, SupportAddressResponse,
 };
-use crate::types::ReservationID;
+use crate::types::{ExecutionResultSummary, ReservationID};
 use anyhow::bail;
 use fastcrypto::encoding::Base64;
 use reqwest::header::{HeaderMap, AUTHORIZATION};
 use reqwest::Client;
 use sui_json_rpc_types::SuiTransactionBlockEffects;
 use sui_types::base_types::{ObjectRef, SuiAddress};
+use sui_types::digests::TransactionDigest;
 use sui_types::quorum_driver_types::ExecuteTransactionRequestType;
 use sui_types::signature::GenericSignature;
 use sui_types::transaction::TransactionData;
@@ -59,6 +60,16 @@ impl GasPoolRpcClient {
     }
 
     pub async fn debug_health_check(&self) -> anyhow::Result<()> {
+        self.debug_health_check_impl("debug_health_check").await
+    }
+
+    /// Only checks that the station can sign, without reserving any gas from the pool.
+    pub async fn debug_signer_health_check(&self) -> anyhow::Result<()> {
+        self.debug_health_check_impl("debug_signer_health_check")
+            .await
+    }
+
+    async fn debug_health_check_impl(&self, endpoint: &str) -> anyhow::Result<()> {
         let mut headers = HeaderMap::new();
         headers.insert(
             AUTHORIZATION,
@@ -66,7 +77,7 @@ impl GasPoolRpcClient {
         );
         let response = self
             .client
-            .post(format!("{}/debug_health_check", self.server_address))
+            .post(format!("{}/{}", self.server_address, endpoint))
             .headers(headers)
             .send()
             .await?;
@@ -86,11 +97,13 @@ impl GasPoolRpcClient {
         sponsor_address: SuiAddress,
         gas_budget: u64,
         reserve_duration_secs: u64,
+        max_coins: Option<usize>,
     ) -> anyhow::Result<(SuiAddress, ReservationID, Vec<ObjectRef>)> {
         let request = ReserveGasRequest {
             sponsor_address: Some(sponsor_address),
             gas_budget,
             reserve_duration_secs,
+            max_coins,
         };
         let mut headers = HeaderMap::new();
         headers.insert(
@@ -132,7 +145,7 @@ impl GasPoolRpcClient {
         tx_data: &TransactionData,
         request_type: Option<ExecuteTransactionRequestType>,
         user_sig: &GenericSignature,
-    ) -> anyhow::Result<SuiTransactionBlockEffects> {
+    ) -> anyhow::Result<(TransactionDigest, SuiTransactionBlockEffects)> {
         let mut headers = HeaderMap::new();
         headers.insert(
             AUTHORIZATION,
@@ -153,11 +166,39 @@ impl GasPoolRpcClient {
             .await?
             .json::<ExecuteTxResponse>()
             .await?;
-        response.effects.ok_or_else(|| {
-            anyhow::anyhow!(response
+        match (response.digest, response.effects) {
+            (Some(digest), Some(effects)) => Ok((digest, effects)),
+            _ => Err(anyhow::anyhow!(response
                 .error
-                .unwrap_or_else(|| "Unknown error".to_string()))
-        })
+                .unwrap_or_else(|| "Unknown error".to_string()))),
+        }
+    }
+
+    /// Looks up the result of a previously executed transaction. Returns None if the station
+    /// no longer retains it.
+    pub async fn get_execution_result(
+        &self,
+        digest: TransactionDigest,
+    ) -> anyhow::Result<Option<ExecutionResultSummary>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            format!("Bearer {}", read_auth_env()).parse().unwrap(),
+        );
+        let request = GetExecutionResultRequest { digest };
+        let response = self
+            .client
+            .post(format!("{}/v1/get_execution_result", self.server_address))
+            .headers(headers)
+            .json(&request)
+            .send()
+            .await?
+            .json::<GetExecutionResultResponse>()
+            .await?;
+        if let Some(error) = response.error {
+            bail!(error);
+        }
+        Ok(response.result)
     }
 
     pub async fn support_address(&self) -> anyhow::Result<Vec<SuiAddress>> {
@@ -22,22 +22,31 @@ mod tests {
         let client = server.get_local_client();
         client.health().await.unwrap();
 
-        let (sponsor, reservation_id, gas_coins) =
-            client.reserve_gas(sponsor, MIST_PER_OCT, 10).await.unwrap();
+        let (sponsor, reservation_id, gas_coins) = client
+            .reserve_gas(sponsor, MIST_PER_OCT, 10, None)
+            .await
+            .unwrap();
         assert_eq!(gas_coins.len(), 1);
 
         // We can no longer request all balance given one is loaned out above.
         assert!(client
-            .reserve_gas(sponsor, MIST_PER_OCT * 10, 10)
+            .reserve_gas(sponsor, MIST_PER_OCT * 10, 10, None)
             .await
             .is_err());
 
         let (tx_data, user_sig) = create_test_transaction(&test_cluster, sponsor, gas_coins).await;
-        let effects = client
+        let expected_digest = tx_data.digest();
+        let (digest, effects) = client
             .execute_tx(reservation_id, &tx_data, None, &user_sig)
             .await
             .unwrap();
         assert!(effects.status().is_ok());
+        assert_eq!(digest, expected_digest);
+
+        // The result can be recovered by digest after execution.
+        let result = client.get_execution_result(digest).await.unwrap().unwrap();
+        assert_eq!(result.reservation_id, reservation_id);
+        assert!(result.status.is_ok());
     }
 
     #[tokio::test]
@@ -49,13 +58,18 @@ mod tests {
         let client = server.get_local_client();
         client.health().await.unwrap();
 
-        let (_sponsor, _res_id, gas_coins) =
-            client.reserve_gas(sponsor, MIST_PER_OCT, 10).await.unwrap();
+        let (_sponsor, _res_id, gas_coins) = client
+            .reserve_gas(sponsor, MIST_PER_OCT, 10, None)
+            .await
+            .unwrap();
         assert_eq!(gas_coins.len(), 1);
 
         // Change the auth secret used in the client.
         std::env::set_var(AUTH_ENV_NAME, "b");
-        assert!(client.reserve_gas(sponsor, MIST_PER_OCT, 10).await.is_err());
+        assert!(client
+            .reserve_gas(sponsor, MIST_PER_OCT, 10, None)
+            .await
+            .is_err());
     }
 
     #[tokio::test]
@@ -66,4 +80,27 @@ mod tests {
         let client = server.get_local_client();
         client.debug_health_check().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_debug_signer_health_check() {
+        let (sponsors, _test_cluster, container, server) =
+            start_rpc_server_for_testing(vec![MIST_PER_OCT; 10], MIST_PER_OCT).await;
+
+        let client = server.get_local_client();
+        let available = container
+            .get_gas_pool_arc()
+            .query_pool_available_coin_count(sponsors[0])
+            .await
+            .unwrap();
+        client.debug_signer_health_check().await.unwrap();
+        // No coin was reserved for the check.
+        assert_eq!(
+            container
+                .get_gas_pool_arc()
+                .query_pool_available_coin_count(sponsors[0])
+                .await
+                .unwrap(),
+            available
+        );
+    }
 }
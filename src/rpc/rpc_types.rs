@@ -1,25 +1,30 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::types::ReservationID;
+use crate::errors::GasPoolError;
+use crate::gas_pool::gas_pool_core::MAX_DURATION_S;
+use crate::storage::MAX_GAS_PER_QUERY;
+use crate::types::{ExecutedTransaction, ExecutionResultSummary, ReservationID};
 use fastcrypto::encoding::Base64;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sui_json_rpc_types::{SuiObjectRef, SuiTransactionBlockEffects, SuiTransactionBlockEvents};
 use sui_types::base_types::{ObjectRef, SuiAddress};
+use sui_types::digests::TransactionDigest;
 use sui_types::quorum_driver_types::ExecuteTransactionRequestType;
 
 // 2 SUI.
 pub const MAX_BUDGET: u64 = 2_000_000_000;
 
-// 10 mins.
-pub const MAX_DURATION_S: u64 = 10 * 60;
-
 #[derive(Clone, Debug, JsonSchema, Serialize, Deserialize)]
 pub struct ReserveGasRequest {
     pub sponsor_address: Option<SuiAddress>,
     pub gas_budget: u64,
     pub reserve_duration_secs: u64,
+    /// The maximum number of gas coins the client can take. Defaults to the pool limit.
+    /// With 1, the reserved coin covers the whole budget and survives the transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_coins: Option<usize>,
 }
 
 impl ReserveGasRequest {
@@ -39,6 +44,14 @@ impl ReserveGasRequest {
                 MAX_DURATION_S
             );
         }
+        if let Some(max_coins) = self.max_coins {
+            if max_coins == 0 {
+                anyhow::bail!("Max coins must be positive");
+            }
+            if max_coins > MAX_GAS_PER_QUERY {
+                anyhow::bail!("Max coins must be at most {}", MAX_GAS_PER_QUERY);
+            }
+        }
         Ok(())
     }
 }
@@ -47,13 +60,22 @@ impl ReserveGasRequest {
 pub struct ReserveGasResponse {
     pub result: Option<ReserveGasResult>,
     pub error: Option<String>,
+    /// Set when the reservation failed for a reason that is expected to clear up after this
+    /// many milliseconds, e.g. the pool is empty until another reservation expires.
+    #[serde(default)]
+    pub retry_after_ms: Option<u64>,
 }
 
 #[derive(Debug, JsonSchema, Serialize, Deserialize)]
 pub struct ReserveGasResult {
     pub sponsor_address: SuiAddress,
     pub reservation_id: ReservationID,
+    /// Complete object refs of the reserved coins, ready to be used as gas payment. See
+    /// `GasPool::reserve_gas` for how fresh they are.
     pub gas_coins: Vec<SuiObjectRef>,
+    /// The balance reserved on top of the gas budget to pay for smashing the gas coins.
+    #[serde(default)]
+    pub smash_headroom: u64,
 }
 
 impl ReserveGasResponse {
@@ -61,20 +83,26 @@ impl ReserveGasResponse {
         sponsor_address: SuiAddress,
         reservation_id: ReservationID,
         gas_coins: Vec<ObjectRef>,
+        smash_headroom: u64,
     ) -> Self {
         Self {
             result: Some(ReserveGasResult {
                 sponsor_address,
                 reservation_id,
                 gas_coins: gas_coins.into_iter().map(|c| c.into()).collect(),
+                smash_headroom,
             }),
             error: None,
+            retry_after_ms: None,
         }
     }
 
     pub fn new_err(error: anyhow::Error) -> Self {
         Self {
             result: None,
+            retry_after_ms: GasPoolError::from_anyhow(&error)
+                .and_then(|err| err.retry_after())
+                .map(|retry_after| retry_after.as_millis() as u64),
             error: Some(error.to_string()),
         }
     }
@@ -90,6 +118,7 @@ pub struct ExecuteTxRequest {
 
 #[derive(Debug, JsonSchema, Serialize, Deserialize)]
 pub struct ExecuteTxResponse {
+    pub digest: Option<TransactionDigest>,
     pub timestamp_ms: Option<u64>,
     pub effects: Option<SuiTransactionBlockEffects>,
     pub events: Option<SuiTransactionBlockEvents>,
@@ -97,21 +126,19 @@ pub struct ExecuteTxResponse {
 }
 
 impl ExecuteTxResponse {
-    pub fn new_ok(
-        timestamp_ms: Option<u64>,
-        effects: SuiTransactionBlockEffects,
-        events: Option<SuiTransactionBlockEvents>,
-    ) -> Self {
+    pub fn new_ok(result: ExecutedTransaction) -> Self {
         Self {
-            timestamp_ms,
-            effects: Some(effects),
-            events,
+            digest: Some(result.digest),
+            timestamp_ms: result.timestamp_ms,
+            effects: Some(result.effects),
+            events: result.events,
             error: None,
         }
     }
 
     pub fn new_err(error: anyhow::Error) -> Self {
         Self {
+            digest: None,
             timestamp_ms: None,
             effects: None,
             events: None,
@@ -120,6 +147,34 @@ impl ExecuteTxResponse {
     }
 }
 
+#[derive(Debug, JsonSchema, Serialize, Deserialize)]
+pub struct GetExecutionResultRequest {
+    pub digest: TransactionDigest,
+}
+
+/// Both fields are None if the result was never persisted or has already expired.
+#[derive(Debug, JsonSchema, Serialize, Deserialize)]
+pub struct GetExecutionResultResponse {
+    pub result: Option<ExecutionResultSummary>,
+    pub error: Option<String>,
+}
+
+impl GetExecutionResultResponse {
+    pub fn new_ok(result: Option<ExecutionResultSummary>) -> Self {
+        Self {
+            result,
+            error: None,
+        }
+    }
+
+    pub fn new_err(error: anyhow::Error) -> Self {
+        Self {
+            result: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
 #[derive(Debug, JsonSchema, Serialize, Deserialize)]
 pub struct SupportAddressResponse {
     pub sponsor_addresses: Option<Vec<SuiAddress>>,
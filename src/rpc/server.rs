@@ -1,13 +1,14 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::gas_pool::gas_pool_core::GasPool;
+use crate::errors::ValidationError;
+use crate::gas_pool::gas_pool_core::{GasPool, ReserveGasOptions};
 use crate::metrics::GasPoolRpcMetrics;
 use crate::read_auth_env;
 use crate::rpc::client::GasPoolRpcClient;
 use crate::rpc::rpc_types::{
-    ExecuteTxRequest, ExecuteTxResponse, ReserveGasRequest, ReserveGasResponse,
-    SupportAddressResponse,
+    ExecuteTxRequest, ExecuteTxResponse, GetExecutionResultRequest, GetExecutionResultResponse,
+    ReserveGasRequest, ReserveGasResponse, SupportAddressResponse,
 };
 use axum::headers::authorization::Bearer;
 use axum::headers::Authorization;
@@ -62,8 +63,13 @@ impl GasPoolServer {
             .route("/", get(health))
             .route("/version", get(version))
             .route("/debug_health_check", post(debug_health_check))
+            .route(
+                "/debug_signer_health_check",
+                post(debug_signer_health_check),
+            )
             .route("/v1/reserve_gas", post(reserve_gas))
             .route("/v1/execute_tx", post(execute_tx))
+            .route("/v1/get_execution_result", post(get_execution_result))
             .route("/v1/support_address", get(support_address))
             .layer(Extension(state));
         let address = SocketAddr::new(IpAddr::V4(host_ip), rpc_port);
@@ -124,6 +130,20 @@ async fn debug_health_check(
     "OK".to_string()
 }
 
+async fn debug_signer_health_check(
+    TypedHeader(authorization): TypedHeader<Authorization<Bearer>>,
+    Extension(server): Extension<ServerState>,
+) -> String {
+    info!("Received debug_signer_health_check request");
+    if authorization.token() != server.secret.as_str() {
+        return "Unauthorized".to_string();
+    }
+    if let Err(err) = server.gas_station.debug_check_signer_health().await {
+        return format!("Failed to check signer health: {:?}", err);
+    }
+    "OK".to_string()
+}
+
 async fn reserve_gas(
     TypedHeader(authorization): TypedHeader<Authorization<Bearer>>,
     Extension(server): Extension<ServerState>,
@@ -151,6 +171,7 @@ async fn reserve_gas(
         sponsor_address,
         gas_budget,
         reserve_duration_secs,
+        max_coins,
     } = payload;
     server
         .metrics
@@ -167,6 +188,7 @@ async fn reserve_gas(
         sponsor_address,
         gas_budget,
         reserve_duration_secs,
+        max_coins,
     ))
     .await
     .unwrap_or_else(|err| {
@@ -186,16 +208,21 @@ async fn reserve_gas_impl(
     sponsor_address: Option<SuiAddress>,
     gas_budget: u64,
     reserve_duration_secs: u64,
+    max_coins: Option<usize>,
 ) -> (StatusCode, Json<ReserveGasResponse>) {
     match gas_station
-        .reserve_gas(
+        .reserve_gas_with_headroom(
             sponsor_address,
             gas_budget,
             Duration::from_secs(reserve_duration_secs),
+            ReserveGasOptions {
+                max_coins,
+                ..Default::default()
+            },
         )
         .await
     {
-        Ok((sponsor, reservation_id, gas_coins)) => {
+        Ok((sponsor, reservation_id, gas_coins, smash_headroom)) => {
             info!(
                 ?reservation_id,
                 "Reserved gas coins with sponsor={:?}, budget={:?} and duration={:?}: {:?}",
@@ -205,7 +232,8 @@ async fn reserve_gas_impl(
                 gas_coins
             );
             metrics.num_successful_reserve_gas_requests.inc();
-            let response = ReserveGasResponse::new_ok(sponsor, reservation_id, gas_coins);
+            let response =
+                ReserveGasResponse::new_ok(sponsor, reservation_id, gas_coins, smash_headroom);
             (StatusCode::OK, Json(response))
         }
         Err(err) => {
@@ -282,25 +310,58 @@ async fn execute_tx_impl(
         .execute_transaction(reservation_id, tx_data, request_type, user_sig)
         .await
     {
-        Ok((timestamp_ms, effects, events)) => {
+        Ok(result) => {
             info!(
                 ?reservation_id,
                 "Successfully executed transaction {:?} with status: {:?}",
-                effects.transaction_digest(),
-                effects.status()
+                result.digest,
+                result.effects.status()
             );
             metrics.num_successful_execute_tx_requests.inc();
-            (
-                StatusCode::OK,
-                Json(ExecuteTxResponse::new_ok(timestamp_ms, effects, events)),
-            )
+            (StatusCode::OK, Json(ExecuteTxResponse::new_ok(result)))
         }
         Err(err) => {
             error!("Failed to execute transaction: {:?}", err);
             metrics.num_failed_execute_tx_requests.inc();
+            // Transactions rejected by validation need to be fixed by the client.
+            let status = if ValidationError::from_anyhow(&err).is_some() {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+            (status, Json(ExecuteTxResponse::new_err(err)))
+        }
+    }
+}
+
+async fn get_execution_result(
+    TypedHeader(authorization): TypedHeader<Authorization<Bearer>>,
+    Extension(server): Extension<ServerState>,
+    Json(payload): Json<GetExecutionResultRequest>,
+) -> impl IntoResponse {
+    if authorization.token() != server.secret.as_ref() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(GetExecutionResultResponse::new_err(anyhow::anyhow!(
+                "Invalid authorization token"
+            ))),
+        );
+    }
+    debug!("Received v1 get_execution_result request: {:?}", payload);
+    match server
+        .gas_station
+        .get_execution_result(payload.digest)
+        .await
+    {
+        Ok(result) => (
+            StatusCode::OK,
+            Json(GetExecutionResultResponse::new_ok(result)),
+        ),
+        Err(err) => {
+            error!("Failed to get execution result: {:?}", err);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ExecuteTxResponse::new_err(err)),
+                Json(GetExecutionResultResponse::new_err(err)),
             )
         }
     }
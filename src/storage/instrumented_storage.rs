@@ -0,0 +1,567 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::metrics::GasPoolCoreMetrics;
+use crate::storage::Storage;
+use crate::types::{
+    ActiveReservation, BalancePercentiles, CoinLookup, ExecutionResultSummary, ExpirationTimeMs,
+    GasCoin, ReservationID, ReservationSnapshot, ScheduledExecution, SponsorObject,
+    SponsorSnapshot, StuckReservations,
+};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sui_types::base_types::{ObjectID, ObjectRef, SuiAddress};
+use sui_types::digests::TransactionDigest;
+
+/// Wraps any storage to record the latency and the errors of each of its operations, so that a
+/// slow pool can be traced to the storage or ruled out.
+pub struct InstrumentedStorage {
+    inner: Arc<dyn Storage>,
+    metrics: Arc<GasPoolCoreMetrics>,
+}
+
+impl InstrumentedStorage {
+    pub fn new(inner: Arc<dyn Storage>, metrics: Arc<GasPoolCoreMetrics>) -> Arc<Self> {
+        Arc::new(Self { inner, metrics })
+    }
+
+    async fn observe<T>(
+        &self,
+        operation: &str,
+        future: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        let start = Instant::now();
+        let result = future.await;
+        self.metrics
+            .storage_operation_latency_ms
+            .with_label_values(&[operation])
+            .observe(start.elapsed().as_millis() as u64);
+        if result.is_err() {
+            self.metrics
+                .num_storage_operation_errors
+                .with_label_values(&[operation])
+                .inc();
+        }
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for InstrumentedStorage {
+    async fn reserve_gas_coins_with_max_coins(
+        &self,
+        sponsor: SuiAddress,
+        target_budget: u64,
+        max_coins: usize,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<(ReservationID, Vec<GasCoin>)> {
+        self.observe(
+            "reserve_gas_coins_with_max_coins",
+            self.inner.reserve_gas_coins_with_max_coins(
+                sponsor,
+                target_budget,
+                max_coins,
+                reserved_duration_ms,
+            ),
+        )
+        .await
+    }
+
+    async fn reserve_specific_coins(
+        &self,
+        sponsor: SuiAddress,
+        coin_ids: Vec<ObjectID>,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<(ReservationID, Vec<GasCoin>)> {
+        self.observe(
+            "reserve_specific_coins",
+            self.inner
+                .reserve_specific_coins(sponsor, coin_ids, reserved_duration_ms),
+        )
+        .await
+    }
+
+    async fn hold_coins(
+        &self,
+        sponsor: SuiAddress,
+        coin_ids: Vec<ObjectID>,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<ReservationID> {
+        self.observe(
+            "hold_coins",
+            self.inner
+                .hold_coins(sponsor, coin_ids, reserved_duration_ms),
+        )
+        .await
+    }
+
+    async fn ready_for_execution(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "ready_for_execution",
+            self.inner.ready_for_execution(sponsor, reservation_id),
+        )
+        .await
+    }
+
+    async fn ready_for_execution_with_payment(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        payment: &[ObjectID],
+        transaction_digest: TransactionDigest,
+    ) -> anyhow::Result<Vec<ObjectID>> {
+        self.observe(
+            "ready_for_execution_with_payment",
+            self.inner.ready_for_execution_with_payment(
+                sponsor,
+                reservation_id,
+                payment,
+                transaction_digest,
+            ),
+        )
+        .await
+    }
+
+    async fn hold_reservation_unconfirmed(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        unconfirmed_duration_ms: u64,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "hold_reservation_unconfirmed",
+            self.inner.hold_reservation_unconfirmed(
+                sponsor,
+                reservation_id,
+                unconfirmed_duration_ms,
+            ),
+        )
+        .await
+    }
+
+    async fn confirm_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "confirm_reservation",
+            self.inner.confirm_reservation(sponsor, reservation_id),
+        )
+        .await
+    }
+
+    async fn reset_reservation_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "reset_reservation_expiration",
+            self.inner
+                .reset_reservation_expiration(sponsor, reservation_id, reserved_duration_ms),
+        )
+        .await
+    }
+
+    async fn heartbeat_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        lease_duration_ms: u64,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "heartbeat_reservation",
+            self.inner
+                .heartbeat_reservation(sponsor, reservation_id, lease_duration_ms),
+        )
+        .await
+    }
+
+    async fn tag_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        tag: &str,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "tag_reservation",
+            self.inner.tag_reservation(sponsor, reservation_id, tag),
+        )
+        .await
+    }
+
+    async fn get_reservations_by_tag(
+        &self,
+        sponsor: SuiAddress,
+        tag: &str,
+    ) -> anyhow::Result<Vec<ReservationID>> {
+        self.observe(
+            "get_reservations_by_tag",
+            self.inner.get_reservations_by_tag(sponsor, tag),
+        )
+        .await
+    }
+
+    async fn cancel_reservations_by_tag(
+        &self,
+        sponsor: SuiAddress,
+        tag: &str,
+    ) -> anyhow::Result<Vec<ReservationID>> {
+        self.observe(
+            "cancel_reservations_by_tag",
+            self.inner.cancel_reservations_by_tag(sponsor, tag),
+        )
+        .await
+    }
+
+    async fn release_reservation_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        coin_ids: Vec<ObjectID>,
+        latest_coins: Vec<GasCoin>,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "release_reservation_coins",
+            self.inner
+                .release_reservation_coins(sponsor, reservation_id, coin_ids, latest_coins),
+        )
+        .await
+    }
+
+    async fn add_new_coins(&self, new_coins: Vec<GasCoin>) -> anyhow::Result<Vec<GasCoin>> {
+        self.observe("add_new_coins", self.inner.add_new_coins(new_coins))
+            .await
+    }
+
+    async fn add_dust_coins(&self, dust_coins: Vec<GasCoin>) -> anyhow::Result<()> {
+        self.observe("add_dust_coins", self.inner.add_dust_coins(dust_coins))
+            .await
+    }
+
+    async fn get_dust_coins(&self, sponsor: SuiAddress) -> anyhow::Result<Vec<GasCoin>> {
+        self.observe("get_dust_coins", self.inner.get_dust_coins(sponsor))
+            .await
+    }
+
+    async fn expire_coins(&self) -> anyhow::Result<Vec<ObjectID>> {
+        self.observe("expire_coins", self.inner.expire_coins())
+            .await
+    }
+
+    async fn add_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        objects: Vec<ObjectRef>,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "add_sponsor_objects",
+            self.inner.add_sponsor_objects(sponsor, objects),
+        )
+        .await
+    }
+
+    async fn reserve_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        object_ids: Vec<ObjectID>,
+    ) -> anyhow::Result<Vec<ObjectRef>> {
+        self.observe(
+            "reserve_sponsor_objects",
+            self.inner
+                .reserve_sponsor_objects(sponsor, reservation_id, object_ids),
+        )
+        .await
+    }
+
+    async fn get_in_flight_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Vec<ObjectID>> {
+        self.observe(
+            "get_in_flight_sponsor_objects",
+            self.inner
+                .get_in_flight_sponsor_objects(sponsor, reservation_id),
+        )
+        .await
+    }
+
+    async fn release_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        latest_objects: Vec<ObjectRef>,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "release_sponsor_objects",
+            self.inner
+                .release_sponsor_objects(sponsor, reservation_id, latest_objects),
+        )
+        .await
+    }
+
+    async fn get_sponsor_objects(&self, sponsor: SuiAddress) -> anyhow::Result<Vec<SponsorObject>> {
+        self.observe(
+            "get_sponsor_objects",
+            self.inner.get_sponsor_objects(sponsor),
+        )
+        .await
+    }
+
+    async fn save_execution_result(
+        &self,
+        summary: &ExecutionResultSummary,
+        ttl_sec: u64,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "save_execution_result",
+            self.inner.save_execution_result(summary, ttl_sec),
+        )
+        .await
+    }
+
+    async fn get_execution_result(
+        &self,
+        digest: TransactionDigest,
+    ) -> anyhow::Result<Option<ExecutionResultSummary>> {
+        self.observe(
+            "get_execution_result",
+            self.inner.get_execution_result(digest),
+        )
+        .await
+    }
+
+    async fn set_reservation_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        metadata: &[u8],
+        ttl_ms: u64,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "set_reservation_metadata",
+            self.inner
+                .set_reservation_metadata(sponsor, reservation_id, metadata, ttl_ms),
+        )
+        .await
+    }
+
+    async fn get_reservation_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        self.observe(
+            "get_reservation_metadata",
+            self.inner.get_reservation_metadata(sponsor, reservation_id),
+        )
+        .await
+    }
+
+    async fn get_reservation_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<ExpirationTimeMs>> {
+        self.observe(
+            "get_reservation_expiration",
+            self.inner
+                .get_reservation_expiration(sponsor, reservation_id),
+        )
+        .await
+    }
+
+    async fn get_reservation_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<Vec<ObjectID>>> {
+        self.observe(
+            "get_reservation_coins",
+            self.inner.get_reservation_coins(sponsor, reservation_id),
+        )
+        .await
+    }
+
+    async fn get_oldest_active_reservation(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<ActiveReservation>> {
+        self.observe(
+            "get_oldest_active_reservation",
+            self.inner.get_oldest_active_reservation(sponsor),
+        )
+        .await
+    }
+
+    async fn get_stuck_reservations(
+        &self,
+        sponsor: SuiAddress,
+        min_age: Duration,
+    ) -> anyhow::Result<StuckReservations> {
+        self.observe(
+            "get_stuck_reservations",
+            self.inner.get_stuck_reservations(sponsor, min_age),
+        )
+        .await
+    }
+
+    async fn get_next_reservation_expiry(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<Duration>> {
+        self.observe(
+            "get_next_reservation_expiry",
+            self.inner.get_next_reservation_expiry(sponsor),
+        )
+        .await
+    }
+
+    async fn save_scheduled_execution(&self, execution: &ScheduledExecution) -> anyhow::Result<()> {
+        self.observe(
+            "save_scheduled_execution",
+            self.inner.save_scheduled_execution(execution),
+        )
+        .await
+    }
+
+    async fn get_scheduled_executions(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Vec<ScheduledExecution>> {
+        self.observe(
+            "get_scheduled_executions",
+            self.inner.get_scheduled_executions(sponsor),
+        )
+        .await
+    }
+
+    async fn remove_scheduled_execution(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<bool> {
+        self.observe(
+            "remove_scheduled_execution",
+            self.inner
+                .remove_scheduled_execution(sponsor, reservation_id),
+        )
+        .await
+    }
+
+    async fn get_coin_last_used(
+        &self,
+        sponsor: SuiAddress,
+        object_id: ObjectID,
+    ) -> anyhow::Result<Option<u64>> {
+        self.observe(
+            "get_coin_last_used",
+            self.inner.get_coin_last_used(sponsor, object_id),
+        )
+        .await
+    }
+
+    async fn find_coin(&self, object_id: ObjectID) -> anyhow::Result<Option<CoinLookup>> {
+        self.observe("find_coin", self.inner.find_coin(object_id))
+            .await
+    }
+
+    async fn export_sponsor_snapshot(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<SponsorSnapshot> {
+        self.observe(
+            "export_sponsor_snapshot",
+            self.inner.export_sponsor_snapshot(sponsor),
+        )
+        .await
+    }
+
+    async fn import_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation: &ReservationSnapshot,
+    ) -> anyhow::Result<()> {
+        self.observe(
+            "import_reservation",
+            self.inner.import_reservation(sponsor, reservation),
+        )
+        .await
+    }
+
+    async fn init_coin_stats_at_startup(&self) -> anyhow::Result<Vec<(String, i64, i64)>> {
+        self.observe(
+            "init_coin_stats_at_startup",
+            self.inner.init_coin_stats_at_startup(),
+        )
+        .await
+    }
+
+    async fn is_initialized(&self) -> anyhow::Result<bool> {
+        self.observe("is_initialized", self.inner.is_initialized())
+            .await
+    }
+
+    async fn acquire_init_lock(
+        &self,
+        lock_duration_sec: u64,
+    ) -> anyhow::Result<Vec<(String, bool)>> {
+        self.observe(
+            "acquire_init_lock",
+            self.inner.acquire_init_lock(lock_duration_sec),
+        )
+        .await
+    }
+
+    async fn release_init_lock(&self) -> anyhow::Result<()> {
+        self.observe("release_init_lock", self.inner.release_init_lock())
+            .await
+    }
+
+    async fn check_health(&self) -> anyhow::Result<()> {
+        self.observe("check_health", self.inner.check_health())
+            .await
+    }
+
+    async fn get_available_coin_count(&self, sponsor: SuiAddress) -> anyhow::Result<usize> {
+        self.observe(
+            "get_available_coin_count",
+            self.inner.get_available_coin_count(sponsor),
+        )
+        .await
+    }
+
+    #[cfg(test)]
+    async fn flush_db(&self) {
+        self.inner.flush_db().await
+    }
+
+    async fn get_available_coin_total_balance(&self, sponsor: SuiAddress) -> u64 {
+        self.inner.get_available_coin_total_balance(sponsor).await
+    }
+
+    async fn get_balance_percentiles(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<BalancePercentiles>> {
+        self.observe(
+            "get_balance_percentiles",
+            self.inner.get_balance_percentiles(sponsor),
+        )
+        .await
+    }
+
+    #[cfg(test)]
+    async fn get_reserved_coin_count(&self, sponsor: SuiAddress) -> usize {
+        self.inner.get_reserved_coin_count(sponsor).await
+    }
+}
@@ -3,22 +3,37 @@
 
 use crate::config::GasPoolStorageConfig;
 use crate::metrics::StorageMetrics;
+use crate::retry_with_max_attempts;
 use crate::storage::redis::RedisStorage;
-use crate::types::{GasCoin, ReservationID};
+use crate::types::{
+    ActiveReservation, BalancePercentiles, CoinLookup, ExecutionResultSummary, ExpirationTimeMs,
+    GasCoin, ReservationID, ReservationSnapshot, ScheduledExecution, SponsorObject,
+    SponsorSnapshot, StuckReservations,
+};
 use std::sync::Arc;
-use sui_types::base_types::{ObjectID, SuiAddress};
+use std::time::Duration;
+use sui_types::base_types::{ObjectID, ObjectRef, SuiAddress};
+use sui_types::digests::TransactionDigest;
 
+mod instrumented_storage;
 mod redis;
 
+pub use instrumented_storage::InstrumentedStorage;
+
 pub const MAX_GAS_PER_QUERY: usize = 256;
 
+/// The default number of coins written to the storage at once when adding many coins.
+pub const DEFAULT_ADD_COINS_BATCH_SIZE: usize = 5000;
+const ADD_COINS_BATCH_MAX_ATTEMPTS: usize = 3;
+
 /// Defines the trait for a storage that manages gas coins.
 /// It is expected to support concurrent access and manage atomicity internally.
 /// It supports multiple addresses each with its own gas coin queue.
 #[async_trait::async_trait]
 pub trait Storage: Sync + Send {
     /// Reserve gas coins with total coin balance >= target_budget.
-    /// If there is not enough balance, returns error.
+    /// If there is not enough balance, returns `GasPoolError::InsufficientCoins` with the balance
+    /// that was available.
     /// The implementation is required to guarantee that:
     /// 1. It never returns the same coin to multiple callers.
     /// 2. It keeps a record of the reserved coins with timestamp, so that in the case
@@ -29,18 +44,330 @@ pub trait Storage: Sync + Send {
         sponsor: SuiAddress,
         target_budget: u64,
         reserved_duration_ms: u64,
+    ) -> anyhow::Result<(ReservationID, Vec<GasCoin>)> {
+        self.reserve_gas_coins_with_max_coins(
+            sponsor,
+            target_budget,
+            MAX_GAS_PER_QUERY,
+            reserved_duration_ms,
+        )
+        .await
+    }
+
+    /// Same as `reserve_gas_coins`, but never returns more than `max_coins` coins.
+    /// If the budget cannot be covered within that many coins while the pool has enough balance
+    /// overall, returns `GasPoolError::TooFragmented`.
+    /// With `max_coins` of 1, any coin that covers the budget on its own can be picked.
+    async fn reserve_gas_coins_with_max_coins(
+        &self,
+        sponsor: SuiAddress,
+        target_budget: u64,
+        max_coins: usize,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<(ReservationID, Vec<GasCoin>)>;
+
+    /// Reserve exactly the given gas coins. Either all of them are reserved, or none of them
+    /// if any is already reserved or not in the pool.
+    /// The same guarantees as `reserve_gas_coins` apply.
+    async fn reserve_specific_coins(
+        &self,
+        sponsor: SuiAddress,
+        coin_ids: Vec<ObjectID>,
+        reserved_duration_ms: u64,
     ) -> anyhow::Result<(ReservationID, Vec<GasCoin>)>;
 
+    /// Put gas coins that are not in the pool, such as coins coming back from an execution,
+    /// under a new reservation. Like any other reservation, its coins go back to the pool when
+    /// they are released, or when it expires.
+    async fn hold_coins(
+        &self,
+        sponsor: SuiAddress,
+        coin_ids: Vec<ObjectID>,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<ReservationID>;
+
     async fn ready_for_execution(
         &self,
         sponsor: SuiAddress,
         reservation_id: ReservationID,
     ) -> anyhow::Result<()>;
 
-    async fn add_new_coins(&self, new_coins: Vec<GasCoin>) -> anyhow::Result<()>;
+    /// Same as `ready_for_execution`, but first checks that every payment coin is part of the
+    /// reservation. Otherwise it fails with `GasPoolError::PaymentNotReserved`, and the
+    /// reservation is left as it was. Returns all the coins of the reservation, including those
+    /// the transaction does not pay with.
+    /// Calling it again for the same transaction once the reservation is in flight returns the
+    /// same coins, as long as none of them has been released since, so that a call whose
+    /// response was lost can be retried. Any other transaction is refused.
+    async fn ready_for_execution_with_payment(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        payment: &[ObjectID],
+        transaction_digest: TransactionDigest,
+    ) -> anyhow::Result<Vec<ObjectID>>;
+
+    /// Make a new reservation tentative: it cannot be executed until `confirm_reservation` is
+    /// called, and it expires after `unconfirmed_duration_ms` unless its own expiration time
+    /// comes first.
+    async fn hold_reservation_unconfirmed(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        unconfirmed_duration_ms: u64,
+    ) -> anyhow::Result<()>;
+
+    /// Confirm a tentative reservation, restoring its original expiration time.
+    /// Confirming a reservation that is already confirmed does nothing, but it fails if the
+    /// reservation no longer exists.
+    async fn confirm_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<()>;
+
+    /// Make a reservation that still holds its coins expire `reserved_duration_ms` from now,
+    /// regardless of the duration it was made with.
+    async fn reset_reservation_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<()>;
+
+    /// Keep a confirmed reservation that still holds its coins from expiring in the next
+    /// `lease_duration_ms`. Unlike `reset_reservation_expiration`, this never brings the
+    /// expiration closer.
+    async fn heartbeat_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        lease_duration_ms: u64,
+    ) -> anyhow::Result<()>;
+
+    /// Tag a reservation that still holds its coins, so that it can be found or cancelled along
+    /// with the other reservations of the same tag.
+    async fn tag_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        tag: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Returns the reservations of the tag that still hold their coins, i.e. that have neither
+    /// expired nor started executing.
+    async fn get_reservations_by_tag(
+        &self,
+        sponsor: SuiAddress,
+        tag: &str,
+    ) -> anyhow::Result<Vec<ReservationID>>;
+
+    /// Cancel the reservations of the tag that still hold their coins, and return their IDs.
+    /// Cancelled reservations are expired right away, so their coins are returned by the next
+    /// call to `expire_coins`.
+    async fn cancel_reservations_by_tag(
+        &self,
+        sponsor: SuiAddress,
+        tag: &str,
+    ) -> anyhow::Result<Vec<ReservationID>>;
+
+    /// Take the given coins out of a reservation, leaving the rest of its coins reserved, and
+    /// add `latest_coins` back to the available pool. `latest_coins` is the latest state of the
+    /// released coins, and may omit those that no longer exist.
+    /// Fails without changing anything if any of the coins does not belong to the reservation.
+    async fn release_reservation_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        coin_ids: Vec<ObjectID>,
+        latest_coins: Vec<GasCoin>,
+    ) -> anyhow::Result<()>;
+
+    /// Adds the coins to the available coins of their sponsors. When the storage caps the
+    /// number of coins per sponsor, the coins beyond the cap are left out and returned, so that
+    /// they simply stay on chain.
+    async fn add_new_coins(&self, new_coins: Vec<GasCoin>) -> anyhow::Result<Vec<GasCoin>>;
+
+    /// Adds the coins in batches of at most `batch_size` coins, so that bulk additions never
+    /// turn into one oversized write. Each batch is retried a few times on its own; if a batch
+    /// still fails, the batches before it remain added. Returns the coins left out by the cap.
+    async fn add_new_coins_in_batches(
+        &self,
+        new_coins: Vec<GasCoin>,
+        batch_size: usize,
+    ) -> anyhow::Result<Vec<GasCoin>> {
+        let mut not_added = vec![];
+        for batch in new_coins.chunks(batch_size.max(1)) {
+            not_added.extend(retry_with_max_attempts!(
+                async { self.add_new_coins(batch.to_vec()).await },
+                ADD_COINS_BATCH_MAX_ATTEMPTS
+            )?);
+        }
+        Ok(not_added)
+    }
+
+    /// Puts the coins into the dust bin of their sponsors, out of reach of reservations. Coins
+    /// already in the dust bin are updated.
+    async fn add_dust_coins(&self, dust_coins: Vec<GasCoin>) -> anyhow::Result<()>;
+
+    /// All coins in the dust bin of the sponsor.
+    async fn get_dust_coins(&self, sponsor: SuiAddress) -> anyhow::Result<Vec<GasCoin>>;
 
     async fn expire_coins(&self) -> anyhow::Result<Vec<ObjectID>>;
 
+    /// Add objects owned by the sponsor, other than gas coins, to the pool so that they can be
+    /// reserved as transaction inputs. Objects already in the pool are updated to the given
+    /// version, unless they are currently reserved.
+    async fn add_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        objects: Vec<ObjectRef>,
+    ) -> anyhow::Result<()>;
+
+    /// Reserve sponsor objects as part of an existing gas reservation. Either all of them are
+    /// reserved, or none of them if any is already reserved or not in the pool.
+    /// The objects follow the reservation: they become in flight when its coins are ready for
+    /// execution, and available again when it expires.
+    async fn reserve_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        object_ids: Vec<ObjectID>,
+    ) -> anyhow::Result<Vec<ObjectRef>>;
+
+    /// The sponsor objects that are in flight with the execution of the reservation.
+    async fn get_in_flight_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Vec<ObjectID>>;
+
+    /// Release the in-flight sponsor objects of a reservation after its execution.
+    /// `latest_objects` is the latest state of the objects that are still owned by the sponsor,
+    /// the others are removed from the pool.
+    async fn release_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        latest_objects: Vec<ObjectRef>,
+    ) -> anyhow::Result<()>;
+
+    /// All sponsor objects in the pool, whether available, reserved or in flight.
+    async fn get_sponsor_objects(&self, sponsor: SuiAddress) -> anyhow::Result<Vec<SponsorObject>>;
+
+    /// Persist the summary of an executed transaction, which expires after `ttl_sec` seconds.
+    async fn save_execution_result(
+        &self,
+        summary: &ExecutionResultSummary,
+        ttl_sec: u64,
+    ) -> anyhow::Result<()>;
+
+    /// Look up a previously persisted execution result. Returns None if it was never persisted
+    /// or has already expired.
+    async fn get_execution_result(
+        &self,
+        digest: TransactionDigest,
+    ) -> anyhow::Result<Option<ExecutionResultSummary>>;
+
+    /// Attach opaque metadata to a reservation, which expires after `ttl_ms` milliseconds.
+    async fn set_reservation_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        metadata: &[u8],
+        ttl_ms: u64,
+    ) -> anyhow::Result<()>;
+
+    /// Returns the metadata attached to a reservation, or None if there is none or it has
+    /// already expired.
+    async fn get_reservation_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Returns the expiration time of a reservation, or None if the reservation no longer exists
+    /// because it expired or its coins are already used for execution.
+    async fn get_reservation_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<ExpirationTimeMs>>;
+
+    /// Returns the coins a reservation still holds, or None if it holds none anymore because
+    /// its coins were returned to the pool or are already used for execution.
+    async fn get_reservation_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<Vec<ObjectID>>>;
+
+    /// Returns the active reservation of the sponsor that was made the longest time ago, if any.
+    /// Reservations are indexed by creation time, so this does not scan all of them.
+    async fn get_oldest_active_reservation(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<ActiveReservation>>;
+
+    /// Counts the reservations of the sponsor whose coins have been in flight since at least
+    /// `min_age` ago. This is a single query regardless of how many reservations are in flight.
+    async fn get_stuck_reservations(
+        &self,
+        sponsor: SuiAddress,
+        min_age: Duration,
+    ) -> anyhow::Result<StuckReservations>;
+
+    /// How long until the reservation of the sponsor that expires next gives its coins back, if
+    /// it has any reservation still holding coins.
+    async fn get_next_reservation_expiry(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<Duration>>;
+
+    /// Persist a transaction to be executed later. Scheduling the same reservation again
+    /// replaces the previous transaction.
+    async fn save_scheduled_execution(&self, execution: &ScheduledExecution) -> anyhow::Result<()>;
+
+    /// All scheduled executions of the sponsor that have not been removed yet.
+    async fn get_scheduled_executions(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Vec<ScheduledExecution>>;
+
+    /// Remove a scheduled execution. Returns false if it was already removed, so that when
+    /// several servers share the storage only one of them runs it.
+    async fn remove_scheduled_execution(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<bool>;
+
+    /// When the coin of the sponsor was last reserved, in milliseconds since the epoch. None if
+    /// it was never reserved, or if it left the pool long ago and never came back.
+    async fn get_coin_last_used(
+        &self,
+        sponsor: SuiAddress,
+        object_id: ObjectID,
+    ) -> anyhow::Result<Option<u64>>;
+
+    /// Look up which sponsor a coin belongs to, and whether it is available, reserved or in the
+    /// middle of an execution. Returns None if the pool does not know the coin.
+    async fn find_coin(&self, object_id: ObjectID) -> anyhow::Result<Option<CoinLookup>>;
+
+    /// Read the available coins and active reservations of the sponsor at a single point in time.
+    /// This scans the whole state of the sponsor and should only be used for maintenance.
+    async fn export_sponsor_snapshot(&self, sponsor: SuiAddress)
+        -> anyhow::Result<SponsorSnapshot>;
+
+    /// Recreate a reservation from a snapshot with its original id and expiration time.
+    /// The coins of the reservation are expected to not be in the pool already.
+    /// Fails if a reservation with the same id already exists.
+    async fn import_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation: &ReservationSnapshot,
+    ) -> anyhow::Result<()>;
+
     /// Initialize some of the gas pool statistics at the startup.
     /// Such as the total number of gas coins and the total balance.
     /// This is needed for several reasons:
@@ -74,6 +401,13 @@ pub trait Storage: Sync + Send {
 
     async fn get_available_coin_total_balance(&self, sponsor: SuiAddress) -> u64;
 
+    /// The distribution of the balances of the available coins of the sponsor, or None if it
+    /// has none.
+    async fn get_balance_percentiles(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<BalancePercentiles>>;
+
     #[cfg(test)]
     async fn get_reserved_coin_count(&self, sponsor: SuiAddress) -> usize;
 }
@@ -84,9 +418,22 @@ pub async fn connect_storage(
     metrics: Arc<StorageMetrics>,
 ) -> Arc<dyn Storage> {
     let storage: Arc<dyn Storage> = match config {
-        GasPoolStorageConfig::Redis { redis_url } => {
-            Arc::new(RedisStorage::new(redis_url, sponsor_vec, metrics).await)
-        }
+        GasPoolStorageConfig::Redis {
+            redis_url,
+            coin_selection_strategy,
+            coin_selection_top_k,
+            max_pool_coins_per_sponsor,
+        } => Arc::new(
+            RedisStorage::new(
+                redis_url,
+                sponsor_vec,
+                *coin_selection_strategy,
+                *coin_selection_top_k,
+                *max_pool_coins_per_sponsor,
+                metrics,
+            )
+            .await,
+        ),
     };
     storage
         .check_health()
@@ -129,14 +476,24 @@ pub async fn connect_storage_for_testing(sponsor_address_vec: Vec<SuiAddress>) -
 
 #[cfg(test)]
 mod tests {
-    use crate::storage::{connect_storage_for_testing, Storage, MAX_GAS_PER_QUERY};
-    use crate::types::GasCoin;
+    use crate::config::{CoinSelectionStrategy, GasPoolStorageConfig};
+    use crate::errors::GasPoolError;
+    use crate::metrics::GasPoolCoreMetrics;
+    use crate::storage::{
+        connect_storage_for_testing, connect_storage_for_testing_with_config, InstrumentedStorage,
+        Storage, MAX_GAS_PER_QUERY,
+    };
+    use crate::types::{
+        BalancePercentiles, CoinState, ExecutionResultSummary, GasCoin, StuckReservations,
+    };
+    use chrono::Utc;
     use rand::random;
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeSet, HashSet};
     use std::sync::Arc;
     use std::time::Duration;
+    use sui_json_rpc_types::SuiExecutionStatus;
     use sui_types::base_types::{random_object_ref, ObjectID, SequenceNumber, SuiAddress};
-    use sui_types::digests::ObjectDigest;
+    use sui_types::digests::{ObjectDigest, TransactionDigest};
 
     async fn assert_coin_count(
         storage: &Arc<dyn Storage>,
@@ -152,7 +509,15 @@ mod tests {
     }
 
     async fn setup(sponsor: SuiAddress, init_balances: Vec<u64>) -> Arc<dyn Storage> {
-        let storage = connect_storage_for_testing(vec![sponsor]).await;
+        setup_with_config(&GasPoolStorageConfig::default(), sponsor, init_balances).await
+    }
+
+    async fn setup_with_config(
+        config: &GasPoolStorageConfig,
+        sponsor: SuiAddress,
+        init_balances: Vec<u64>,
+    ) -> Arc<dyn Storage> {
+        let storage = connect_storage_for_testing_with_config(config, vec![sponsor]).await;
         let gas_coins = init_balances
             .into_iter()
             .map(|balance| GasCoin {
@@ -212,104 +577,543 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_max_gas_coin_per_query() {
+    async fn test_prefer_single_coin_selection() {
         let sponsor = SuiAddress::random_for_testing_only();
-        let storage = setup(sponsor, vec![1; MAX_GAS_PER_QUERY + 1]).await;
-        assert!(storage
-            .reserve_gas_coins(sponsor, (MAX_GAS_PER_QUERY + 1) as u64, 1000)
-            .await
-            .is_err());
-        assert_coin_count(&storage, sponsor, MAX_GAS_PER_QUERY + 1, 0).await;
+        let config = GasPoolStorageConfig::Redis {
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            coin_selection_strategy: CoinSelectionStrategy::PreferSingleCoin,
+            coin_selection_top_k: 1,
+            max_pool_coins_per_sponsor: None,
+        };
+        let storage = setup_with_config(&config, sponsor, vec![1, 1, 1, 1, 5, 1, 10]).await;
+        let (_, reserved_gas_coins) = storage.reserve_gas_coins(sponsor, 5, 1000).await.unwrap();
+        assert_eq!(reserved_gas_coins.len(), 1);
+        assert_eq!(reserved_gas_coins[0].balance, 5);
+        let (_, reserved_gas_coins) = storage.reserve_gas_coins(sponsor, 10, 1000).await.unwrap();
+        assert_eq!(reserved_gas_coins.len(), 1);
+        assert_eq!(reserved_gas_coins[0].balance, 10);
+        // No single coin covers the budget anymore, so it falls back to combining coins.
+        let (_, reserved_gas_coins) = storage.reserve_gas_coins(sponsor, 3, 1000).await.unwrap();
+        assert_eq!(reserved_gas_coins.len(), 3);
+        assert_coin_count(&storage, sponsor, 2, 5).await;
+        assert_eq!(storage.get_available_coin_total_balance(sponsor).await, 2);
     }
 
     #[tokio::test]
-    async fn test_insufficient_pool_budget() {
+    async fn test_least_recently_used_coin_selection() {
         let sponsor = SuiAddress::random_for_testing_only();
-        let storage = setup(sponsor, vec![1; 100]).await;
-        assert!(storage.reserve_gas_coins(sponsor, 101, 1000).await.is_err());
-        assert_coin_count(&storage, sponsor, 100, 0).await;
+        let config = GasPoolStorageConfig::Redis {
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            coin_selection_strategy: CoinSelectionStrategy::LeastRecentlyUsed,
+            coin_selection_top_k: 1,
+            max_pool_coins_per_sponsor: None,
+        };
+        let storage = setup_with_config(&config, sponsor, vec![1; 2]).await;
+        let (reservation_id, used_coins) =
+            storage.reserve_gas_coins(sponsor, 2, 1000).await.unwrap();
+        storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .unwrap();
+        let last_used = storage
+            .get_coin_last_used(sponsor, used_coins[0].object_ref.0)
+            .await
+            .unwrap();
+        assert!(last_used.unwrap() <= Utc::now().timestamp_millis() as u64);
+        // The used coins go back to the queue after execution, ahead of coins never used.
+        storage.add_new_coins(used_coins.clone()).await.unwrap();
+        let new_coins: Vec<_> = (0..2)
+            .map(|_| GasCoin {
+                owner: sponsor,
+                object_ref: random_object_ref(),
+                balance: 1,
+            })
+            .collect();
+        storage.add_new_coins(new_coins.clone()).await.unwrap();
+        assert_eq!(
+            storage
+                .get_coin_last_used(sponsor, new_coins[0].object_ref.0)
+                .await
+                .unwrap(),
+            None
+        );
+
+        // The coins that were never used are preferred even though they are further back.
+        let (_, reserved) = storage.reserve_gas_coins(sponsor, 2, 1000).await.unwrap();
+        let reserved_ids: HashSet<_> = reserved.iter().map(|c| c.object_ref.0).collect();
+        let new_ids: HashSet<_> = new_coins.iter().map(|c| c.object_ref.0).collect();
+        let used_ids: HashSet<_> = used_coins.iter().map(|c| c.object_ref.0).collect();
+        assert_eq!(reserved_ids, new_ids);
+        // Then the least recently used ones.
+        let (_, reserved) = storage.reserve_gas_coins(sponsor, 2, 1000).await.unwrap();
+        let reserved_ids: HashSet<_> = reserved.iter().map(|c| c.object_ref.0).collect();
+        assert_eq!(reserved_ids, used_ids);
+        assert_coin_count(&storage, sponsor, 0, 4).await;
     }
 
     #[tokio::test]
-    async fn test_coin_release() {
+    async fn test_least_recently_used_coin_selection_whole_pool() {
         let sponsor = SuiAddress::random_for_testing_only();
-        let storage = setup(sponsor, vec![1; 100]).await;
-        for _ in 0..100 {
-            // Keep reserving and putting them back.
-            // Should be able to repeat this process indefinitely if balance are not changed.
-            let (res_id, reserved_gas_coins) =
-                storage.reserve_gas_coins(sponsor, 99, 1000).await.unwrap();
-            assert_eq!(reserved_gas_coins.len(), 99);
-            assert_coin_count(&storage, sponsor, 1, 99).await;
-            storage.ready_for_execution(sponsor, res_id).await.unwrap();
-            storage.add_new_coins(reserved_gas_coins).await.unwrap();
-            assert_coin_count(&storage, sponsor, 100, 0).await;
+        let config = GasPoolStorageConfig::Redis {
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            coin_selection_strategy: CoinSelectionStrategy::LeastRecentlyUsed,
+            coin_selection_top_k: 1,
+            max_pool_coins_per_sponsor: None,
+        };
+        let storage = setup_with_config(&config, sponsor, vec![1; 1024]).await;
+        // Use every coin once, so that they all have a last reserved time.
+        let mut used_coins = vec![];
+        for _ in 0..4 {
+            let (reservation_id, coins) =
+                storage.reserve_gas_coins(sponsor, 256, 1000).await.unwrap();
+            storage
+                .ready_for_execution(sponsor, reservation_id)
+                .await
+                .unwrap();
+            used_coins.extend(coins);
         }
+        storage.add_new_coins(used_coins).await.unwrap();
+        // A coin never used is picked even though it is at the very back of the queue.
+        let new_coin = GasCoin {
+            owner: sponsor,
+            object_ref: random_object_ref(),
+            balance: 1,
+        };
+        storage.add_new_coins(vec![new_coin.clone()]).await.unwrap();
+        let (_, reserved) = storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        assert_eq!(reserved, vec![new_coin]);
+        assert_coin_count(&storage, sponsor, 1024, 1).await;
     }
 
     #[tokio::test]
-    async fn test_coin_release_with_updated_balance() {
-        let sponsor = SuiAddress::random_for_testing_only();
-        let storage = setup(sponsor, vec![1; 100]).await;
-        for _ in 0..10 {
-            let (res_id, mut reserved_gas_coins) =
-                storage.reserve_gas_coins(sponsor, 10, 1000).await.unwrap();
-            assert_eq!(
-                reserved_gas_coins.iter().map(|c| c.balance).sum::<u64>(),
-                10
-            );
-            for reserved_gas_coin in reserved_gas_coins.iter_mut() {
-                if reserved_gas_coin.balance > 0 {
-                    reserved_gas_coin.balance -= 1;
-                }
+    async fn test_randomized_coin_selection() {
+        for coin_selection_strategy in [
+            CoinSelectionStrategy::Fifo,
+            CoinSelectionStrategy::PreferSingleCoin,
+            CoinSelectionStrategy::LeastRecentlyUsed,
+        ] {
+            let sponsor = SuiAddress::random_for_testing_only();
+            let config = GasPoolStorageConfig::Redis {
+                redis_url: "redis://127.0.0.1:6379".to_string(),
+                coin_selection_strategy,
+                coin_selection_top_k: 10,
+                max_pool_coins_per_sponsor: None,
+            };
+            let storage = setup_with_config(&config, sponsor, vec![1; 100]).await;
+            let mut reserved = BTreeSet::new();
+            for _ in 0..100 {
+                let (_, reserved_gas_coins) =
+                    storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+                assert_eq!(reserved_gas_coins.len(), 1);
+                // Every coin must still be handed out only once.
+                assert!(reserved.insert(reserved_gas_coins[0].object_ref.0));
             }
-            storage.ready_for_execution(sponsor, res_id).await.unwrap();
-            storage.add_new_coins(reserved_gas_coins).await.unwrap();
+            assert_coin_count(&storage, sponsor, 0, 100).await;
+            assert!(storage.reserve_gas_coins(sponsor, 1, 1000).await.is_err());
         }
-        assert_coin_count(&storage, sponsor, 100, 0).await;
-        assert_eq!(storage.get_available_coin_total_balance(sponsor).await, 0);
-        assert!(storage.reserve_gas_coins(sponsor, 1, 1000).await.is_err());
     }
 
     #[tokio::test]
-    async fn test_deleted_objects() {
+    async fn test_reserve_specific_coins() {
         let sponsor = SuiAddress::random_for_testing_only();
-        let storage = setup(sponsor, vec![1; 100]).await;
-        let (res_id, mut reserved_gas_coins) =
-            storage.reserve_gas_coins(sponsor, 100, 1000).await.unwrap();
-        assert_eq!(reserved_gas_coins.len(), 100);
+        let storage = connect_storage_for_testing(vec![sponsor]).await;
+        let gas_coins: Vec<_> = (1..=5)
+            .map(|balance| GasCoin {
+                owner: sponsor,
+                object_ref: random_object_ref(),
+                balance,
+            })
+            .collect();
+        storage.add_new_coins(gas_coins.clone()).await.unwrap();
 
-        storage.ready_for_execution(sponsor, res_id).await.unwrap();
+        let (res_id, reserved_gas_coins) = storage
+            .reserve_specific_coins(
+                sponsor,
+                vec![gas_coins[3].object_ref.0, gas_coins[1].object_ref.0],
+                1000,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            reserved_gas_coins,
+            vec![gas_coins[3].clone(), gas_coins[1].clone()]
+        );
+        assert_coin_count(&storage, sponsor, 3, 2).await;
+        assert_eq!(storage.get_available_coin_total_balance(sponsor).await, 9);
 
-        reserved_gas_coins.drain(0..50);
+        // One of the coins is already reserved, so none of them should be reserved.
+        assert!(storage
+            .reserve_specific_coins(
+                sponsor,
+                vec![gas_coins[0].object_ref.0, gas_coins[1].object_ref.0],
+                1000,
+            )
+            .await
+            .is_err());
+        // Coins that are not in the pool cannot be reserved either.
+        assert!(storage
+            .reserve_specific_coins(sponsor, vec![ObjectID::random()], 1000)
+            .await
+            .is_err());
+        assert!(storage
+            .reserve_specific_coins(
+                sponsor,
+                vec![gas_coins[0].object_ref.0, gas_coins[0].object_ref.0],
+                1000,
+            )
+            .await
+            .is_err());
+        assert_coin_count(&storage, sponsor, 3, 2).await;
+
+        storage.ready_for_execution(sponsor, res_id).await.unwrap();
         storage.add_new_coins(reserved_gas_coins).await.unwrap();
-        assert_coin_count(&storage, sponsor, 50, 0).await;
+        assert_coin_count(&storage, sponsor, 5, 0).await;
     }
 
     #[tokio::test]
-    async fn test_coin_expiration() {
+    async fn test_max_gas_coin_per_query() {
         let sponsor = SuiAddress::random_for_testing_only();
-        let storage = setup(sponsor, vec![1; 100]).await;
-        let (_res_id1, reserved_gas_coins1) =
-            storage.reserve_gas_coins(sponsor, 10, 900).await.unwrap();
-        assert_eq!(reserved_gas_coins1.len(), 10);
-        let (_res_id2, reserved_gas_coins2) =
-            storage.reserve_gas_coins(sponsor, 30, 1900).await.unwrap();
-        assert_eq!(reserved_gas_coins2.len(), 30);
-        // Just to make sure these two reservations will have a different expiration timestamp.
-        tokio::time::sleep(Duration::from_millis(1)).await;
-        let (_res_id3, reserved_gas_coins3) =
-            storage.reserve_gas_coins(sponsor, 50, 1900).await.unwrap();
-        assert_eq!(reserved_gas_coins3.len(), 50);
-        assert_coin_count(&storage, sponsor, 10, 90).await;
+        let storage = setup(sponsor, vec![1; MAX_GAS_PER_QUERY + 1]).await;
+        assert!(storage
+            .reserve_gas_coins(sponsor, (MAX_GAS_PER_QUERY + 1) as u64, 1000)
+            .await
+            .is_err());
+        assert_coin_count(&storage, sponsor, MAX_GAS_PER_QUERY + 1, 0).await;
+    }
 
-        assert!(storage.expire_coins().await.unwrap().is_empty());
-        assert_coin_count(&storage, sponsor, 10, 90).await;
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        let expired1 = storage.expire_coins().await.unwrap();
-        assert_eq!(expired1.len(), 10);
-        assert_eq!(
-            expired1.iter().cloned().collect::<BTreeSet<_>>(),
+    #[tokio::test]
+    async fn test_reserve_with_max_coins() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 10]).await;
+        let (_, reserved_gas_coins) = storage
+            .reserve_gas_coins_with_max_coins(sponsor, 3, 3, 1000)
+            .await
+            .unwrap();
+        assert_eq!(reserved_gas_coins.len(), 3);
+        let err = storage
+            .reserve_gas_coins_with_max_coins(sponsor, 3, 2, 1000)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            GasPoolError::from_anyhow(&err),
+            Some(GasPoolError::TooFragmented)
+        );
+        let err = storage
+            .reserve_gas_coins_with_max_coins(sponsor, 100, 2, 1000)
+            .await
+            .unwrap_err();
+        let Some(GasPoolError::InsufficientCoins {
+            available_balance,
+            available_coin_count,
+            retry_after,
+        }) = GasPoolError::from_anyhow(&err)
+        else {
+            panic!("Unexpected error: {:?}", err);
+        };
+        assert_eq!((available_balance, available_coin_count), (7, 7));
+        // The first reservation gives its coins back within its duration.
+        assert!(retry_after.unwrap() <= Duration::from_millis(1000));
+        assert_coin_count(&storage, sponsor, 7, 3).await;
+    }
+
+    #[tokio::test]
+    async fn test_next_reservation_expiry() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 3]).await;
+        assert_eq!(
+            storage.get_next_reservation_expiry(sponsor).await.unwrap(),
+            None
+        );
+        let (executed_id, _) = storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        storage.reserve_gas_coins(sponsor, 1, 60_000).await.unwrap();
+        let expiry = storage
+            .get_next_reservation_expiry(sponsor)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(expiry <= Duration::from_millis(1000));
+
+        // An executed reservation no longer gives its coins back, so the next one counts.
+        storage
+            .ready_for_execution(sponsor, executed_id)
+            .await
+            .unwrap();
+        let expiry = storage
+            .get_next_reservation_expiry(sponsor)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(expiry > Duration::from_secs(50) && expiry <= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_stuck_reservations() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 3]).await;
+        let (released_id, released_coins) =
+            storage.reserve_gas_coins(sponsor, 1, 60_000).await.unwrap();
+        let (stuck_id, _) = storage.reserve_gas_coins(sponsor, 1, 60_000).await.unwrap();
+        // A reservation that is not in flight yet is never stuck.
+        storage.reserve_gas_coins(sponsor, 1, 60_000).await.unwrap();
+        for reservation_id in [released_id, stuck_id] {
+            storage
+                .ready_for_execution(sponsor, reservation_id)
+                .await
+                .unwrap();
+        }
+        let stuck = storage
+            .get_stuck_reservations(sponsor, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(stuck.count, 2);
+        let oldest_ready_time = stuck.oldest_ready_time.unwrap();
+
+        // Releasing the coins after execution takes the reservation out of flight.
+        storage.add_new_coins(released_coins).await.unwrap();
+        let stuck = storage
+            .get_stuck_reservations(sponsor, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(stuck.count, 1);
+        assert!(stuck.oldest_ready_time.unwrap() >= oldest_ready_time);
+
+        let stuck = storage
+            .get_stuck_reservations(sponsor, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(stuck, StuckReservations::default());
+    }
+
+    #[tokio::test]
+    async fn test_instrumented_storage() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let metrics = GasPoolCoreMetrics::new_for_testing();
+        let storage: Arc<dyn Storage> =
+            InstrumentedStorage::new(setup(sponsor, vec![1; 10]).await, metrics.clone());
+        let (reservation_id, _) = storage.reserve_gas_coins(sponsor, 3, 1000).await.unwrap();
+        storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .unwrap();
+        // The reservation is gone once it is ready for execution.
+        assert!(storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .is_err());
+        let errors = |operation| {
+            metrics
+                .num_storage_operation_errors
+                .with_label_values(&[operation])
+                .get()
+        };
+        assert_eq!(errors("ready_for_execution"), 1);
+        assert_eq!(errors("reserve_gas_coins_with_max_coins"), 0);
+        assert_coin_count(&storage, sponsor, 7, 0).await;
+    }
+
+    #[tokio::test]
+    async fn test_reserve_single_coin() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 1, 5, 1, 10]).await;
+        let (_, reserved_gas_coins) = storage
+            .reserve_gas_coins_with_max_coins(sponsor, 5, 1, 1000)
+            .await
+            .unwrap();
+        assert_eq!(reserved_gas_coins.len(), 1);
+        assert_eq!(reserved_gas_coins[0].balance, 5);
+        let err = storage
+            .reserve_gas_coins_with_max_coins(sponsor, 11, 1, 1000)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            GasPoolError::from_anyhow(&err),
+            Some(GasPoolError::TooFragmented)
+        );
+        assert_coin_count(&storage, sponsor, 4, 1).await;
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_pool_budget() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 100]).await;
+        assert!(storage.reserve_gas_coins(sponsor, 101, 1000).await.is_err());
+        assert_coin_count(&storage, sponsor, 100, 0).await;
+    }
+
+    #[tokio::test]
+    async fn test_add_new_coins_in_batches() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![]).await;
+        let gas_coins: Vec<_> = (0..12345)
+            .map(|_| GasCoin {
+                owner: sponsor,
+                object_ref: random_object_ref(),
+                balance: 1,
+            })
+            .collect();
+        storage
+            .add_new_coins_in_batches(gas_coins, 1000)
+            .await
+            .unwrap();
+        assert_coin_count(&storage, sponsor, 12345, 0).await;
+        assert_eq!(
+            storage.get_available_coin_total_balance(sponsor).await,
+            12345
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_pool_coins_per_sponsor() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let config = GasPoolStorageConfig::Redis {
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            coin_selection_strategy: CoinSelectionStrategy::default(),
+            coin_selection_top_k: 1,
+            max_pool_coins_per_sponsor: Some(10),
+        };
+        let storage = setup_with_config(&config, sponsor, vec![1; 8]).await;
+        let gas_coins: Vec<_> = (0..5)
+            .map(|i| GasCoin {
+                owner: sponsor,
+                object_ref: random_object_ref(),
+                balance: 10 + i,
+            })
+            .collect();
+        let not_added = storage
+            .add_new_coins_in_batches(gas_coins.clone(), 2)
+            .await
+            .unwrap();
+        assert_eq!(not_added, gas_coins[2..]);
+        assert_coin_count(&storage, sponsor, 10, 0).await;
+        assert_eq!(storage.get_available_coin_total_balance(sponsor).await, 29);
+
+        // Once coins are reserved, there is room again.
+        storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        let not_added = storage
+            .add_new_coins(gas_coins[2..].to_vec())
+            .await
+            .unwrap();
+        assert_eq!(not_added, gas_coins[3..]);
+        assert_coin_count(&storage, sponsor, 10, 1).await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_reservations() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 20]).await;
+        // Only 10 of the reservations can be covered.
+        let tasks: Vec<_> = (0..50)
+            .map(|_| {
+                let storage = storage.clone();
+                tokio::spawn(async move { storage.reserve_gas_coins(sponsor, 2, 10000).await })
+            })
+            .collect();
+        let mut reserved_ids = HashSet::new();
+        let mut failures = 0;
+        for task in tasks {
+            match task.await.unwrap() {
+                Ok((_, coins)) => {
+                    assert_eq!(coins.iter().map(|c| c.balance).sum::<u64>(), 2);
+                    for coin in coins {
+                        // No coin is handed out twice.
+                        assert!(reserved_ids.insert(coin.object_ref.0));
+                    }
+                }
+                Err(err) => {
+                    assert!(matches!(
+                        GasPoolError::from_anyhow(&err),
+                        Some(GasPoolError::InsufficientCoins { .. })
+                    ));
+                    failures += 1;
+                }
+            }
+        }
+        assert_eq!(failures, 40);
+        // No coin is lost or left half reserved.
+        assert_eq!(reserved_ids.len(), 20);
+        assert_coin_count(&storage, sponsor, 0, 20).await;
+    }
+
+    #[tokio::test]
+    async fn test_coin_release() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 100]).await;
+        for _ in 0..100 {
+            // Keep reserving and putting them back.
+            // Should be able to repeat this process indefinitely if balance are not changed.
+            let (res_id, reserved_gas_coins) =
+                storage.reserve_gas_coins(sponsor, 99, 1000).await.unwrap();
+            assert_eq!(reserved_gas_coins.len(), 99);
+            assert_coin_count(&storage, sponsor, 1, 99).await;
+            storage.ready_for_execution(sponsor, res_id).await.unwrap();
+            storage.add_new_coins(reserved_gas_coins).await.unwrap();
+            assert_coin_count(&storage, sponsor, 100, 0).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coin_release_with_updated_balance() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 100]).await;
+        for _ in 0..10 {
+            let (res_id, mut reserved_gas_coins) =
+                storage.reserve_gas_coins(sponsor, 10, 1000).await.unwrap();
+            assert_eq!(
+                reserved_gas_coins.iter().map(|c| c.balance).sum::<u64>(),
+                10
+            );
+            for reserved_gas_coin in reserved_gas_coins.iter_mut() {
+                if reserved_gas_coin.balance > 0 {
+                    reserved_gas_coin.balance -= 1;
+                }
+            }
+            storage.ready_for_execution(sponsor, res_id).await.unwrap();
+            storage.add_new_coins(reserved_gas_coins).await.unwrap();
+        }
+        assert_coin_count(&storage, sponsor, 100, 0).await;
+        assert_eq!(storage.get_available_coin_total_balance(sponsor).await, 0);
+        assert!(storage.reserve_gas_coins(sponsor, 1, 1000).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_objects() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 100]).await;
+        let (res_id, mut reserved_gas_coins) =
+            storage.reserve_gas_coins(sponsor, 100, 1000).await.unwrap();
+        assert_eq!(reserved_gas_coins.len(), 100);
+
+        storage.ready_for_execution(sponsor, res_id).await.unwrap();
+
+        reserved_gas_coins.drain(0..50);
+        storage.add_new_coins(reserved_gas_coins).await.unwrap();
+        assert_coin_count(&storage, sponsor, 50, 0).await;
+    }
+
+    #[tokio::test]
+    async fn test_coin_expiration() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 100]).await;
+        let (_res_id1, reserved_gas_coins1) =
+            storage.reserve_gas_coins(sponsor, 10, 900).await.unwrap();
+        assert_eq!(reserved_gas_coins1.len(), 10);
+        let (_res_id2, reserved_gas_coins2) =
+            storage.reserve_gas_coins(sponsor, 30, 1900).await.unwrap();
+        assert_eq!(reserved_gas_coins2.len(), 30);
+        // Just to make sure these two reservations will have a different expiration timestamp.
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        let (_res_id3, reserved_gas_coins3) =
+            storage.reserve_gas_coins(sponsor, 50, 1900).await.unwrap();
+        assert_eq!(reserved_gas_coins3.len(), 50);
+        assert_coin_count(&storage, sponsor, 10, 90).await;
+
+        assert!(storage.expire_coins().await.unwrap().is_empty());
+        assert_coin_count(&storage, sponsor, 10, 90).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let expired1 = storage.expire_coins().await.unwrap();
+        assert_eq!(expired1.len(), 10);
+        assert_eq!(
+            expired1.iter().cloned().collect::<BTreeSet<_>>(),
             reserved_gas_coins1
                 .iter()
                 .map(|coin| coin.object_ref.0)
@@ -334,6 +1138,803 @@ mod tests {
         assert_coin_count(&storage, sponsor, 10, 0).await;
     }
 
+    #[tokio::test]
+    async fn test_hold_coins() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 1]).await;
+        let (reservation_id, gas_coins) =
+            storage.reserve_gas_coins(sponsor, 2, 1000).await.unwrap();
+        storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .unwrap();
+        assert_coin_count(&storage, sponsor, 0, 0).await;
+
+        // The coins come back from the execution under new reservations instead of the pool.
+        let held_id1 = storage
+            .hold_coins(sponsor, vec![gas_coins[0].object_ref.0], 500)
+            .await
+            .unwrap();
+        let held_id2 = storage
+            .hold_coins(sponsor, vec![gas_coins[1].object_ref.0], 500)
+            .await
+            .unwrap();
+        assert!(held_id1 > reservation_id && held_id2 > held_id1);
+        assert_coin_count(&storage, sponsor, 0, 2).await;
+
+        storage
+            .release_reservation_coins(
+                sponsor,
+                held_id2,
+                vec![gas_coins[1].object_ref.0],
+                gas_coins[1..].to_vec(),
+            )
+            .await
+            .unwrap();
+        assert_coin_count(&storage, sponsor, 1, 1).await;
+
+        // The coins still held expire like those of any other reservation.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(
+            storage.expire_coins().await.unwrap(),
+            vec![gas_coins[0].object_ref.0]
+        );
+        assert_coin_count(&storage, sponsor, 1, 0).await;
+    }
+
+    #[tokio::test]
+    async fn test_release_reservation_coins() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 1, 1, 1, 1]).await;
+        let (reservation_id, reserved_gas_coins) =
+            storage.reserve_gas_coins(sponsor, 3, 1000).await.unwrap();
+        assert_eq!(reserved_gas_coins.len(), 3);
+        let (_, other_gas_coins) = storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        assert_coin_count(&storage, sponsor, 1, 4).await;
+
+        // A coin of another reservation cannot be released.
+        assert!(storage
+            .release_reservation_coins(
+                sponsor,
+                reservation_id,
+                vec![other_gas_coins[0].object_ref.0],
+                other_gas_coins.clone(),
+            )
+            .await
+            .is_err());
+        assert_coin_count(&storage, sponsor, 1, 4).await;
+
+        storage
+            .release_reservation_coins(
+                sponsor,
+                reservation_id,
+                vec![
+                    reserved_gas_coins[0].object_ref.0,
+                    reserved_gas_coins[1].object_ref.0,
+                ],
+                reserved_gas_coins[..2].to_vec(),
+            )
+            .await
+            .unwrap();
+        assert_coin_count(&storage, sponsor, 3, 2).await;
+        assert_eq!(storage.get_available_coin_total_balance(sponsor).await, 3);
+        assert_eq!(
+            storage
+                .get_reservation_coins(sponsor, reservation_id)
+                .await
+                .unwrap(),
+            Some(vec![reserved_gas_coins[2].object_ref.0])
+        );
+
+        // Releasing the last coin ends the reservation.
+        storage
+            .release_reservation_coins(
+                sponsor,
+                reservation_id,
+                vec![reserved_gas_coins[2].object_ref.0],
+                reserved_gas_coins[2..].to_vec(),
+            )
+            .await
+            .unwrap();
+        assert_coin_count(&storage, sponsor, 4, 1).await;
+        assert_eq!(
+            storage
+                .get_reservation_coins(sponsor, reservation_id)
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unconfirmed_reservation() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 1, 1, 1]).await;
+        let (confirmed_id, _) = storage.reserve_gas_coins(sponsor, 2, 10000).await.unwrap();
+        let (unconfirmed_id, _) = storage.reserve_gas_coins(sponsor, 2, 10000).await.unwrap();
+        storage
+            .hold_reservation_unconfirmed(sponsor, confirmed_id, 500)
+            .await
+            .unwrap();
+        storage
+            .hold_reservation_unconfirmed(sponsor, unconfirmed_id, 500)
+            .await
+            .unwrap();
+        assert!(storage
+            .ready_for_execution(sponsor, confirmed_id)
+            .await
+            .is_err());
+
+        storage
+            .confirm_reservation(sponsor, confirmed_id)
+            .await
+            .unwrap();
+        // Confirming twice is fine.
+        storage
+            .confirm_reservation(sponsor, confirmed_id)
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(storage.expire_coins().await.unwrap().len(), 2);
+        assert_coin_count(&storage, sponsor, 2, 2).await;
+        assert!(storage
+            .confirm_reservation(sponsor, unconfirmed_id)
+            .await
+            .is_err());
+        storage
+            .ready_for_execution(sponsor, confirmed_id)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reset_reservation_expiration() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 1]).await;
+        let (reservation_id, _) = storage.reserve_gas_coins(sponsor, 1, 600000).await.unwrap();
+        storage
+            .reset_reservation_expiration(sponsor, reservation_id, 500)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(storage.expire_coins().await.unwrap().len(), 1);
+        // The reservation no longer exists.
+        assert!(storage
+            .reset_reservation_expiration(sponsor, reservation_id, 500)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_reservation() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 1, 1]).await;
+        let (leased_id, _) = storage.reserve_gas_coins(sponsor, 1, 500).await.unwrap();
+        let (long_id, _) = storage.reserve_gas_coins(sponsor, 1, 600000).await.unwrap();
+        let (abandoned_id, _) = storage.reserve_gas_coins(sponsor, 1, 500).await.unwrap();
+        for _ in 0..4 {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            storage
+                .heartbeat_reservation(sponsor, leased_id, 500)
+                .await
+                .unwrap();
+            assert!(storage.expire_coins().await.unwrap().len() <= 1);
+        }
+        // Only the reservation without heartbeats expired.
+        assert_coin_count(&storage, sponsor, 1, 2).await;
+        assert!(storage
+            .heartbeat_reservation(sponsor, abandoned_id, 500)
+            .await
+            .is_err());
+
+        // A heartbeat does not shorten a reservation that expires later.
+        storage
+            .heartbeat_reservation(sponsor, long_id, 500)
+            .await
+            .unwrap();
+        let expiration = storage
+            .get_reservation_expiration(sponsor, long_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(expiration > Utc::now().timestamp_millis() as u64 + 500_000);
+
+        // Once the heartbeats stop, the reservation expires.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(storage.expire_coins().await.unwrap().len(), 1);
+        assert_coin_count(&storage, sponsor, 2, 1).await;
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_unconfirmed_reservation() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1]).await;
+        let (reservation_id, _) = storage.reserve_gas_coins(sponsor, 1, 10000).await.unwrap();
+        storage
+            .hold_reservation_unconfirmed(sponsor, reservation_id, 1000)
+            .await
+            .unwrap();
+        assert!(storage
+            .heartbeat_reservation(sponsor, reservation_id, 1000)
+            .await
+            .is_err());
+        storage
+            .confirm_reservation(sponsor, reservation_id)
+            .await
+            .unwrap();
+        storage
+            .heartbeat_reservation(sponsor, reservation_id, 1000)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reservation_tags() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1; 4]).await;
+        let (executed_id, _) = storage.reserve_gas_coins(sponsor, 1, 10000).await.unwrap();
+        let (cancelled_id, _) = storage.reserve_gas_coins(sponsor, 1, 10000).await.unwrap();
+        let (untagged_id, _) = storage.reserve_gas_coins(sponsor, 1, 10000).await.unwrap();
+        for reservation_id in [executed_id, cancelled_id] {
+            storage
+                .tag_reservation(sponsor, reservation_id, "run-1")
+                .await
+                .unwrap();
+        }
+        let mut tagged = storage
+            .get_reservations_by_tag(sponsor, "run-1")
+            .await
+            .unwrap();
+        tagged.sort();
+        assert_eq!(tagged, vec![executed_id, cancelled_id]);
+
+        // Reservations that started executing are no longer listed nor cancelled.
+        storage
+            .ready_for_execution(sponsor, executed_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .get_reservations_by_tag(sponsor, "run-1")
+                .await
+                .unwrap(),
+            vec![cancelled_id]
+        );
+        assert_eq!(
+            storage
+                .cancel_reservations_by_tag(sponsor, "run-1")
+                .await
+                .unwrap(),
+            vec![cancelled_id]
+        );
+        assert!(storage
+            .get_reservations_by_tag(sponsor, "run-1")
+            .await
+            .unwrap()
+            .is_empty());
+
+        // The cancelled reservation expires right away, unlike the untagged one.
+        assert_eq!(storage.expire_coins().await.unwrap().len(), 1);
+        assert_coin_count(&storage, sponsor, 1, 1).await;
+        assert!(storage
+            .get_reservation_expiration(sponsor, untagged_id)
+            .await
+            .unwrap()
+            .is_some());
+        // A reservation that no longer holds its coins cannot be tagged.
+        assert!(storage
+            .tag_reservation(sponsor, cancelled_id, "run-2")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sponsor_object_reservation_and_release() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 1]).await;
+        let objects = vec![random_object_ref(), random_object_ref()];
+        let object_ids: Vec<_> = objects.iter().map(|o| o.0).collect();
+        storage
+            .add_sponsor_objects(sponsor, objects.clone())
+            .await
+            .unwrap();
+
+        // Sponsor objects can only be reserved under an existing gas reservation.
+        assert!(storage
+            .reserve_sponsor_objects(sponsor, 100, object_ids.clone())
+            .await
+            .is_err());
+        let (reservation_id, _) = storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        let reserved = storage
+            .reserve_sponsor_objects(sponsor, reservation_id, vec![object_ids[0]])
+            .await
+            .unwrap();
+        assert_eq!(reserved, vec![objects[0]]);
+
+        // The same object is never reserved twice, and a failed reservation reserves nothing.
+        let (other_reservation_id, _) = storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        assert!(storage
+            .reserve_sponsor_objects(sponsor, other_reservation_id, object_ids.clone())
+            .await
+            .is_err());
+        assert!(storage
+            .reserve_sponsor_objects(sponsor, other_reservation_id, vec![ObjectID::random()])
+            .await
+            .is_err());
+        let sponsor_objects = storage.get_sponsor_objects(sponsor).await.unwrap();
+        assert_eq!(sponsor_objects.len(), 2);
+        for object in sponsor_objects {
+            let expected = (object.object_ref.0 == object_ids[0]).then_some(reservation_id);
+            assert_eq!(object.reservation_id, expected);
+        }
+
+        // Adding an object while it is reserved does not make it available.
+        storage
+            .add_sponsor_objects(sponsor, vec![objects[0]])
+            .await
+            .unwrap();
+        assert!(storage
+            .reserve_sponsor_objects(sponsor, other_reservation_id, vec![object_ids[0]])
+            .await
+            .is_err());
+
+        assert!(storage
+            .get_in_flight_sponsor_objects(sponsor, reservation_id)
+            .await
+            .unwrap()
+            .is_empty());
+        storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .get_in_flight_sponsor_objects(sponsor, reservation_id)
+                .await
+                .unwrap(),
+            vec![object_ids[0]]
+        );
+
+        // The object comes back with its new version after execution.
+        let new_version = (
+            object_ids[0],
+            SequenceNumber::from_u64(objects[0].1.value() + 1),
+            ObjectDigest::random(),
+        );
+        storage
+            .release_sponsor_objects(sponsor, reservation_id, vec![new_version])
+            .await
+            .unwrap();
+        assert!(storage
+            .get_in_flight_sponsor_objects(sponsor, reservation_id)
+            .await
+            .unwrap()
+            .is_empty());
+        let reserved = storage
+            .reserve_sponsor_objects(sponsor, other_reservation_id, object_ids.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            reserved.into_iter().collect::<BTreeSet<_>>(),
+            BTreeSet::from([new_version, objects[1]])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sponsor_object_release_drops_gone_objects() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1]).await;
+        let object = random_object_ref();
+        storage
+            .add_sponsor_objects(sponsor, vec![object])
+            .await
+            .unwrap();
+        let (reservation_id, _) = storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        storage
+            .reserve_sponsor_objects(sponsor, reservation_id, vec![object.0])
+            .await
+            .unwrap();
+        storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .unwrap();
+        // The transaction transferred the object away.
+        storage
+            .release_sponsor_objects(sponsor, reservation_id, vec![])
+            .await
+            .unwrap();
+        assert!(storage
+            .get_sponsor_objects(sponsor)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sponsor_object_expiration() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1]).await;
+        let object = random_object_ref();
+        storage
+            .add_sponsor_objects(sponsor, vec![object])
+            .await
+            .unwrap();
+        let (reservation_id, _) = storage.reserve_gas_coins(sponsor, 1, 500).await.unwrap();
+        storage
+            .reserve_sponsor_objects(sponsor, reservation_id, vec![object.0])
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(storage.expire_coins().await.unwrap().len(), 1);
+        let sponsor_objects = storage.get_sponsor_objects(sponsor).await.unwrap();
+        assert_eq!(sponsor_objects.len(), 1);
+        assert_eq!(sponsor_objects[0].object_ref, object);
+        assert_eq!(sponsor_objects[0].reservation_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_find_coin() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 2]).await;
+        assert!(storage
+            .find_coin(ObjectID::random())
+            .await
+            .unwrap()
+            .is_none());
+
+        let (reservation_id, reserved_gas_coins) =
+            storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        let reserved_id = reserved_gas_coins[0].object_ref.0;
+        let lookup = storage.find_coin(reserved_id).await.unwrap().unwrap();
+        assert_eq!(lookup.sponsor, sponsor);
+        assert_eq!(lookup.state, CoinState::Reserved);
+        assert_eq!(lookup.reservation_id, Some(reservation_id));
+
+        storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .unwrap();
+        let lookup = storage.find_coin(reserved_id).await.unwrap().unwrap();
+        assert_eq!(lookup.state, CoinState::InFlight);
+        assert_eq!(lookup.reservation_id, Some(reservation_id));
+
+        storage.add_new_coins(reserved_gas_coins).await.unwrap();
+        let lookup = storage.find_coin(reserved_id).await.unwrap().unwrap();
+        assert_eq!(lookup.state, CoinState::Available);
+        assert_eq!(lookup.reservation_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_balance_percentiles() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![]).await;
+        assert_eq!(
+            storage.get_balance_percentiles(sponsor).await.unwrap(),
+            None
+        );
+
+        // Balances 1 to 200, added out of order.
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, (1..=200).rev().collect()).await;
+        assert_eq!(
+            storage.get_balance_percentiles(sponsor).await.unwrap(),
+            Some(BalancePercentiles {
+                coin_count: 200,
+                min: 1,
+                p50: 100,
+                p90: 180,
+                p99: 198,
+                max: 200,
+            })
+        );
+
+        // Reserved coins are left out. Only the largest coin covers the budget on its own.
+        storage
+            .reserve_gas_coins_with_max_coins(sponsor, 200, 1, 1000)
+            .await
+            .unwrap();
+        let percentiles = storage
+            .get_balance_percentiles(sponsor)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(percentiles.coin_count, 199);
+        assert_eq!(percentiles.max, 199);
+    }
+
+    #[tokio::test]
+    async fn test_dust_coins() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 2]).await;
+        assert!(storage.get_dust_coins(sponsor).await.unwrap().is_empty());
+
+        let (reservation_id, mut reserved_gas_coins) =
+            storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        storage
+            .ready_for_execution(sponsor, reservation_id)
+            .await
+            .unwrap();
+        reserved_gas_coins[0].balance = 0;
+        storage
+            .add_dust_coins(reserved_gas_coins.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_dust_coins(sponsor).await.unwrap(),
+            reserved_gas_coins
+        );
+        assert_coin_count(&storage, sponsor, 1, 0).await;
+        let lookup = storage
+            .find_coin(reserved_gas_coins[0].object_ref.0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lookup.state, CoinState::Dust);
+        assert_eq!(lookup.reservation_id, None);
+
+        // Adding a coin again updates it instead of adding it twice.
+        storage
+            .add_dust_coins(reserved_gas_coins.clone())
+            .await
+            .unwrap();
+        assert_eq!(storage.get_dust_coins(sponsor).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ready_for_execution_with_payment() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 1, 1]).await;
+        let (reservation_id, reserved_gas_coins) =
+            storage.reserve_gas_coins(sponsor, 2, 1000).await.unwrap();
+        let (_, foreign_gas_coins) = storage.reserve_gas_coins(sponsor, 1, 1000).await.unwrap();
+        let payment: Vec<_> = reserved_gas_coins
+            .iter()
+            .chain(&foreign_gas_coins)
+            .map(|coin| coin.object_ref.0)
+            .collect();
+        let transaction_digest = TransactionDigest::random();
+        let err = storage
+            .ready_for_execution_with_payment(sponsor, reservation_id, &payment, transaction_digest)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            GasPoolError::from_anyhow(&err),
+            Some(GasPoolError::PaymentNotReserved)
+        );
+        assert!(storage
+            .get_reservation_expiration(sponsor, reservation_id)
+            .await
+            .unwrap()
+            .is_some());
+
+        // Paying with only some of the reserved coins is fine, and all of them are returned.
+        let object_ids = storage
+            .ready_for_execution_with_payment(
+                sponsor,
+                reservation_id,
+                &payment[..1],
+                transaction_digest,
+            )
+            .await
+            .unwrap();
+        assert_eq!(object_ids, payment[..reserved_gas_coins.len()]);
+        assert!(storage
+            .get_reservation_expiration(sponsor, reservation_id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // A retry of the same transaction gets all the coins again, but no other transaction.
+        let retried_object_ids = storage
+            .ready_for_execution_with_payment(
+                sponsor,
+                reservation_id,
+                &payment[..1],
+                transaction_digest,
+            )
+            .await
+            .unwrap();
+        assert_eq!(retried_object_ids, object_ids);
+        assert!(storage
+            .ready_for_execution_with_payment(
+                sponsor,
+                reservation_id,
+                &payment[..1],
+                TransactionDigest::random(),
+            )
+            .await
+            .is_err());
+
+        // Once a coin of the reservation is released, the retry is refused as well.
+        storage
+            .add_new_coins(reserved_gas_coins[..1].to_vec())
+            .await
+            .unwrap();
+        assert!(storage
+            .ready_for_execution_with_payment(
+                sponsor,
+                reservation_id,
+                &payment[..1],
+                transaction_digest,
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_oldest_active_reservation() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 2, 3]).await;
+        assert!(storage
+            .get_oldest_active_reservation(sponsor)
+            .await
+            .unwrap()
+            .is_none());
+
+        let (reservation_id1, reserved_gas_coins1) =
+            storage.reserve_gas_coins(sponsor, 1, 10000).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let (reservation_id2, reserved_gas_coins2) =
+            storage.reserve_gas_coins(sponsor, 2, 10000).await.unwrap();
+        let oldest = storage
+            .get_oldest_active_reservation(sponsor)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(oldest.reservation_id, reservation_id1);
+        assert_eq!(oldest.object_ids, vec![reserved_gas_coins1[0].object_ref.0]);
+        assert!(oldest.age() >= Duration::from_millis(10));
+
+        // Once its coins are taken for execution, the reservation is no longer active.
+        storage
+            .ready_for_execution(sponsor, reservation_id1)
+            .await
+            .unwrap();
+        let oldest = storage
+            .get_oldest_active_reservation(sponsor)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(oldest.reservation_id, reservation_id2);
+        assert_eq!(oldest.object_ids, vec![reserved_gas_coins2[0].object_ref.0]);
+
+        storage
+            .ready_for_execution(sponsor, reservation_id2)
+            .await
+            .unwrap();
+        assert!(storage
+            .get_oldest_active_reservation(sponsor)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_snapshot() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = setup(sponsor, vec![1, 2, 3]).await;
+        let (reservation_id, reserved_gas_coins) =
+            storage.reserve_gas_coins(sponsor, 3, 1000).await.unwrap();
+        let snapshot = storage.export_sponsor_snapshot(sponsor).await.unwrap();
+        assert_eq!(snapshot.sponsor, sponsor);
+        assert_eq!(snapshot.available_coins.len() + reserved_gas_coins.len(), 3);
+        assert_eq!(snapshot.reservations.len(), 1);
+        let reservation = &snapshot.reservations[0];
+        assert_eq!(reservation.reservation_id, reservation_id);
+        assert_eq!(
+            reservation.object_ids.iter().collect::<BTreeSet<_>>(),
+            reserved_gas_coins
+                .iter()
+                .map(|c| &c.object_ref.0)
+                .collect::<BTreeSet<_>>()
+        );
+
+        // Import into a fresh sponsor, as if it was a new storage.
+        let new_sponsor = SuiAddress::random_for_testing_only();
+        let new_storage = connect_storage_for_testing(vec![new_sponsor]).await;
+        new_storage
+            .import_reservation(new_sponsor, reservation)
+            .await
+            .unwrap();
+        assert!(new_storage
+            .import_reservation(new_sponsor, reservation)
+            .await
+            .is_err());
+        assert_coin_count(&new_storage, new_sponsor, 0, reserved_gas_coins.len()).await;
+        let lookup = new_storage
+            .find_coin(reservation.object_ids[0])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lookup.sponsor, new_sponsor);
+        assert_eq!(lookup.reservation_id, Some(reservation_id));
+        let new_snapshot = new_storage
+            .export_sponsor_snapshot(new_sponsor)
+            .await
+            .unwrap();
+        assert_eq!(new_snapshot.reservations, snapshot.reservations);
+
+        // New reservations must not reuse the imported reservation id.
+        new_storage
+            .add_new_coins(vec![GasCoin {
+                owner: new_sponsor,
+                object_ref: random_object_ref(),
+                balance: 1,
+            }])
+            .await
+            .unwrap();
+        let (new_reservation_id, _) = new_storage
+            .reserve_gas_coins(new_sponsor, 1, 1000)
+            .await
+            .unwrap();
+        assert!(new_reservation_id > reservation_id);
+    }
+
+    #[tokio::test]
+    async fn test_execution_result_persistence() {
+        let storage =
+            connect_storage_for_testing(vec![SuiAddress::random_for_testing_only()]).await;
+        let summary = ExecutionResultSummary {
+            digest: TransactionDigest::random(),
+            reservation_id: 1,
+            status: SuiExecutionStatus::Success,
+            net_gas_usage: 100,
+        };
+        assert!(storage
+            .get_execution_result(summary.digest)
+            .await
+            .unwrap()
+            .is_none());
+        storage.save_execution_result(&summary, 1).await.unwrap();
+        assert_eq!(
+            storage.get_execution_result(summary.digest).await.unwrap(),
+            Some(summary.clone())
+        );
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(storage
+            .get_execution_result(summary.digest)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reservation_metadata() {
+        let sponsor = SuiAddress::random_for_testing_only();
+        let storage = connect_storage_for_testing(vec![sponsor]).await;
+        assert!(storage
+            .get_reservation_metadata(sponsor, 1)
+            .await
+            .unwrap()
+            .is_none());
+        // Metadata is opaque, so it need not be valid UTF-8.
+        let metadata = vec![0, 159, 146, 150, 255];
+        storage
+            .set_reservation_metadata(sponsor, 1, &metadata, 1000)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_reservation_metadata(sponsor, 1).await.unwrap(),
+            Some(metadata)
+        );
+        assert!(storage
+            .get_reservation_metadata(sponsor, 2)
+            .await
+            .unwrap()
+            .is_none());
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(storage
+            .get_reservation_metadata(sponsor, 1)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     #[tokio::test]
     async fn test_multiple_sponsors() {
         let sponsors = (0..10)
@@ -0,0 +1,22 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to put gas coins too small to be worth reserving into the dust bin of their sponsor.
+-- The first argument is a JSON map from each sponsor's address to its dust coins.
+-- Each coin is a string in the same format as in the available gas coins queue:
+--   balance, object id, object version, object digest.
+-- The dust bin is a hash keyed by object id, so adding a coin that is already there updates it.
+-- Each coin is also indexed under coin:<object id> as dust, for lookups by object id.
+
+local dust_coins = cjson.decode(ARGV[1])
+
+for sponsor_address, coins in pairs(dust_coins) do
+    local t_dust_coins = sponsor_address .. ':dust_coins'
+    for _, coin in ipairs(coins) do
+        local idx1, _ = string.find(coin, ',', 1)
+        local idx2, _ = string.find(coin, ',', idx1 + 1)
+        local object_id = string.sub(coin, idx1 + 1, idx2 - 1)
+        redis.call('HSET', t_dust_coins, object_id, coin)
+        redis.call('SET', 'coin:' .. object_id, sponsor_address .. ',dust')
+    end
+end
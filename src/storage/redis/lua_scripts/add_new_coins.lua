@@ -2,23 +2,36 @@
 -- SPDX-License-Identifier: Apache-2.0
 
 -- This script is used to add new coins to the available gas coins queue.
--- The first argument is the sponsor's address.
--- The second argument is a JSON array of new coins.
+-- The first argument is a JSON array of new coins for each sponsor, keyed by the sponsor's address.
 -- Each coin is just a string, using "," to separate these fields:
 --   balance, object id, object version, object digest.
 -- In this script we don't care about the format, just push each to the queue.
 -- We also set the initialized flag to 1 if we added any coins.
--- Returns a table with the new total balance and new coin count.
+-- Each coin is also indexed under coin:<object id> as available, for lookups by object id, and added to
+-- available_gas_coins_by_last_use, scored by the time it was last reserved as recorded by reserve_gas_coins.lua.
+-- That time is then kept for as long as the coin stays in the pool.
+-- The second argument is the maximum number of available coins per sponsor, 0 meaning no limit.
+-- Coins beyond it are not added, starting from the end of each sponsor's list.
+-- Returns a table with the new total balance, new coin count and number of coins not added.
 
 local new_coins = cjson.decode(ARGV[1])
+local max_coin_count = tonumber(ARGV[2])
 
 local results = {}
 
 for sponsor_address, decoded_new_coins in pairs(new_coins) do
 
     local t_available_gas_coins = sponsor_address .. ':available_gas_coins'
+    local t_coins_by_last_use = sponsor_address .. ':available_gas_coins_by_last_use'
+
+    local t_available_coin_count = sponsor_address .. ':available_coin_count'
+    local cur_coin_count = redis.call('GET', t_available_coin_count)
 
     local count = #decoded_new_coins
+    if max_coin_count > 0 then
+        count = math.max(0, math.min(count, max_coin_count - cur_coin_count))
+    end
+    local rejected_count = #decoded_new_coins - count
 
     local total_balance = 0
     for i = 1, count, 1 do
@@ -28,6 +41,13 @@ for sponsor_address, decoded_new_coins in pairs(new_coins) do
         total_balance = total_balance + tonumber(balance)
 
         redis.call('RPUSH', t_available_gas_coins, coin)
+
+        local idx2, _ = string.find(coin, ',', idx1 + 1)
+        local object_id = string.sub(coin, idx1 + 1, idx2 - 1)
+        redis.call('SET', 'coin:' .. object_id, sponsor_address .. ',available')
+        local t_last_used = sponsor_address .. ':coin_last_used:' .. object_id
+        redis.call('ZADD', t_coins_by_last_use, tonumber(redis.call('GET', t_last_used)) or 0, coin)
+        redis.call('PERSIST', t_last_used)
     end
 
     if count > 0 then
@@ -41,15 +61,14 @@ for sponsor_address, decoded_new_coins in pairs(new_coins) do
     local new_total_balance = cur_coin_total_balance + total_balance
     redis.call('SET', t_available_coin_total_balance, new_total_balance)
 
-    local t_available_coin_count = sponsor_address .. ':available_coin_count'
-    local cur_coin_count = redis.call('GET', t_available_coin_count)
     local new_coin_count = cur_coin_count + count
     redis.call('SET', t_available_coin_count, new_coin_count)
 
     table.insert(results, {
         sponsor_address,
         tonumber(new_total_balance,10),
-        tonumber(new_coin_count,10)
+        tonumber(new_coin_count,10),
+        rejected_count
     })
 end
 
@@ -0,0 +1,27 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to add sponsor-owned objects, other than gas coins, to the pool.
+-- Sponsor objects are stored in a hash keyed by object id, with values in the form of version,digest,reservation_id.
+-- A reservation id of 0 means that the object is available.
+-- Objects that are currently reserved are left untouched.
+-- The first argument is the sponsor's address.
+-- The second argument is a JSON array of objects, each in the form of object_id,version,digest.
+-- Returns the number of objects added or updated.
+
+local sponsor_address = ARGV[1]
+local objects = cjson.decode(ARGV[2])
+
+local t_sponsor_objects = sponsor_address .. ':sponsor_objects'
+
+local count = 0
+for _, object in ipairs(objects) do
+    local object_id, version, digest = string.match(object, '([^,]+),([^,]+),([^,]+)')
+    local entry = redis.call('HGET', t_sponsor_objects, object_id)
+    if not entry or string.match(entry, ',([^,]+)$') == '0' then
+        redis.call('HSET', t_sponsor_objects, object_id, version .. ',' .. digest .. ',0')
+        count = count + 1
+    end
+end
+
+return count
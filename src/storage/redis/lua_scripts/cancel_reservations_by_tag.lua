@@ -0,0 +1,28 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to cancel all reservations of a tag that still hold their coins.
+-- A reservation is cancelled by moving its expiration time to the past, so that the next call to expire_coins.lua
+-- releases it like any other expired reservation. Reservations that started executing are left alone.
+-- The tag index is removed, since none of its reservations can be cancelled again.
+-- The first argument is the sponsor's address.
+-- The second argument is the tag.
+-- Returns the ids of the cancelled reservations.
+
+local sponsor_address = ARGV[1]
+local tag = ARGV[2]
+
+local t_tag = sponsor_address .. ':reservation_tag:' .. tag
+local t_expiration_queue = sponsor_address .. ':expiration_queue'
+local cancelled = {}
+for _, reservation_id in ipairs(redis.call('SMEMBERS', t_tag)) do
+    if redis.call('EXISTS', sponsor_address .. ':' .. reservation_id) == 1 then
+        redis.call('ZADD', t_expiration_queue, 'XX', 0, reservation_id)
+        -- A cancelled reservation does not count as one that was never confirmed.
+        redis.call('DEL', sponsor_address .. ':unconfirmed:' .. reservation_id)
+        table.insert(cancelled, reservation_id)
+    end
+end
+redis.call('DEL', t_tag)
+
+return cancelled
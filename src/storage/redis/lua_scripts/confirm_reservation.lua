@@ -0,0 +1,22 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to confirm a tentative reservation made by hold_reservation_unconfirmed.lua.
+-- It restores the full expiration time of the reservation and makes it eligible for execution.
+-- Confirming a reservation that is already confirmed does nothing.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+
+if redis.call('EXISTS', sponsor_address .. ':' .. reservation_id) == 0 then
+    error('Reservation no longer exist: ' .. reservation_id)
+end
+
+local t_unconfirmed = sponsor_address .. ':unconfirmed:' .. reservation_id
+local expiration_time = redis.call('GET', t_unconfirmed)
+if expiration_time then
+    redis.call('ZADD', sponsor_address .. ':expiration_queue', expiration_time, reservation_id)
+    redis.call('DEL', t_unconfirmed)
+end
@@ -5,11 +5,16 @@
 -- It takes out all gas coins from the expiration_queue that have expired and returns them to the caller.
 -- The first argument is the sponsor's address.
 -- The second argument is the current timestamp.
+-- Sponsor objects reserved under the expired reservations are made available again.
+-- Returns the expired reservations, each as comma separated coin ids, and the sponsor of each expired reservation
+-- that was never confirmed.
 
 local sponsor_addresses = cjson.decode(ARGV[1])
 local current_time = tonumber(ARGV[2])
 
 local expired_reservations = {}
+-- The sponsor of every expired reservation that was never confirmed, for metrics.
+local unconfirmed_expirations = {}
 
 for _, sponsor_address in ipairs(sponsor_addresses) do
     local t_expiration_queue = sponsor_address .. ':expiration_queue'
@@ -21,11 +26,34 @@ for _, sponsor_address in ipairs(sponsor_addresses) do
             local object_ids = redis.call('GET', key)
             if object_ids then
                 redis.call('DEL', key)
+                -- The caller adds the coins that still exist back, which indexes them again.
+                for object_id in string.gmatch(object_ids, '[^,]+') do
+                    redis.call('DEL', 'coin:' .. object_id)
+                end
                 table.insert(expired_reservations, object_ids)
             end
+            local t_unconfirmed = sponsor_address .. ':unconfirmed:' .. reservation_id
+            if redis.call('DEL', t_unconfirmed) == 1 and object_ids then
+                table.insert(unconfirmed_expirations, sponsor_address)
+            end
+            -- Sponsor objects reserved alongside the coins were never used, so they go back to
+            -- the pool as they are.
+            local t_object_reservation = sponsor_address .. ':object_reservation:' .. reservation_id
+            local reserved_objects = redis.call('GET', t_object_reservation)
+            if reserved_objects then
+                redis.call('DEL', t_object_reservation)
+                local t_sponsor_objects = sponsor_address .. ':sponsor_objects'
+                for object_id in string.gmatch(reserved_objects, '[^,]+') do
+                    local entry = redis.call('HGET', t_sponsor_objects, object_id)
+                    if entry then
+                        local version, digest = string.match(entry, '([^,]+),([^,]+),')
+                        redis.call('HSET', t_sponsor_objects, object_id, version .. ',' .. digest .. ',0')
+                    end
+                end
+            end
         end
         redis.call('ZREMRANGEBYSCORE', t_expiration_queue, 0, current_time)
     end
 end
 
-return expired_reservations
+return {expired_reservations, unconfirmed_expirations}
@@ -0,0 +1,29 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to read the whole state of a sponsor address at once, so that it can be exported.
+-- Like get_reserved_coin_count.lua, it scans every reservation and is expensive.
+-- The first argument is the sponsor's address.
+-- Returns a table with the available coins, and a table of reservations.
+-- Each reservation is a table of the reservation id, the expiration time and the concatenated object IDs.
+
+local sponsor_address = ARGV[1]
+
+local t_available_gas_coins = sponsor_address .. ':available_gas_coins'
+local t_expiration_queue = sponsor_address .. ':expiration_queue'
+
+local coins = redis.call('LRANGE', t_available_gas_coins, 0, -1)
+
+local reservations = {}
+local elements = redis.call('ZRANGE', t_expiration_queue, 0, -1, 'WITHSCORES')
+for i = 1, #elements, 2 do
+    local reservation_id = elements[i]
+    local object_ids = redis.call('GET', sponsor_address .. ':' .. reservation_id)
+    if object_ids then
+        -- Scores are returned as strings, which may use the exponent notation.
+        local expiration_time = string.format('%d', tonumber(elements[i + 1]))
+        table.insert(reservations, {reservation_id, expiration_time, object_ids})
+    end
+end
+
+return {coins, reservations}
@@ -0,0 +1,41 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to get the distribution of the balances of the available gas coins of a sponsor.
+-- The first argument is the sponsor's address.
+-- The queue is read in pages and only the balance of each coin is kept, so that only the summary leaves Redis.
+-- Returns a table with the coin count, followed by the minimum, p50, p90, p99 and maximum balance,
+-- or just the coin count of 0 if there are no available coins.
+-- Percentiles use the nearest rank.
+
+local sponsor_address = ARGV[1]
+
+local PAGE_SIZE = 1000
+
+local t_available_gas_coins = sponsor_address .. ':available_gas_coins'
+
+local balances = {}
+local start = 0
+while true do
+    local coins = redis.call('LRANGE', t_available_gas_coins, start, start + PAGE_SIZE - 1)
+    for _, coin in ipairs(coins) do
+        local idx, _ = string.find(coin, ',', 1)
+        table.insert(balances, tonumber(string.sub(coin, 1, idx - 1)))
+    end
+    if #coins < PAGE_SIZE then
+        break
+    end
+    start = start + PAGE_SIZE
+end
+
+local count = #balances
+if count == 0 then
+    return {0}
+end
+table.sort(balances)
+
+local function percentile(p)
+    return balances[math.max(1, math.ceil(count * p / 100))]
+end
+
+return {count, balances[1], percentile(50), percentile(90), percentile(99), balances[count]}
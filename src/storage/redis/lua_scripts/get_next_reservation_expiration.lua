@@ -0,0 +1,28 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to get the expiration time of the reservation of a sponsor that expires next and still holds
+-- its coins. Reservations that were executed stay in the expiration queue until they would have expired, so those
+-- are skipped.
+-- The first argument is the sponsor's address.
+-- Returns the expiration time, or 0 if the sponsor has no such reservation.
+
+local sponsor_address = ARGV[1]
+
+local PAGE_SIZE = 100
+
+local t_expiration_queue = sponsor_address .. ':expiration_queue'
+
+local start = 0
+while true do
+    local entries = redis.call('ZRANGE', t_expiration_queue, start, start + PAGE_SIZE - 1, 'WITHSCORES')
+    if #entries == 0 then
+        return 0
+    end
+    for i = 1, #entries, 2 do
+        if redis.call('EXISTS', sponsor_address .. ':' .. entries[i]) == 1 then
+            return tonumber(entries[i + 1])
+        end
+    end
+    start = start + PAGE_SIZE
+end
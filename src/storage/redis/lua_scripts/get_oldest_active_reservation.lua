@@ -0,0 +1,31 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to find the oldest reservation of a sponsor address whose coins are still reserved.
+-- The reserve scripts index every reservation by creation time in reservation_creation_times. Reservations
+-- are not removed from the index when they end, so ended reservations found at the front of the index
+-- are removed here instead, until an active one is found.
+-- A reservation is active as long as its reservation key exists, i.e. until it expires, is released, or
+-- its coins are taken for execution.
+-- The first argument is the sponsor's address.
+-- Returns a table with the reservation id, the creation time and the concatenated object IDs, or nil
+-- if the sponsor has no active reservation.
+
+local sponsor_address = ARGV[1]
+
+local t_reservation_creation_times = sponsor_address .. ':reservation_creation_times'
+
+while true do
+    local head = redis.call('ZRANGE', t_reservation_creation_times, 0, 0, 'WITHSCORES')
+    if #head == 0 then
+        -- Lua false is converted to a nil reply.
+        return false
+    end
+    local reservation_id = head[1]
+    local object_ids = redis.call('GET', sponsor_address .. ':' .. reservation_id)
+    if object_ids then
+        -- Scores are returned as strings, which may use the exponent notation.
+        return {reservation_id, string.format('%d', tonumber(head[2])), object_ids}
+    end
+    redis.call('ZREM', t_reservation_creation_times, reservation_id)
+end
@@ -0,0 +1,22 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to get the reservations of a tag that still hold their coins, i.e. that have neither expired
+-- nor started executing. Entries of reservations that ended are pruned from the tag index along the way.
+-- The first argument is the sponsor's address.
+-- The second argument is the tag.
+
+local sponsor_address = ARGV[1]
+local tag = ARGV[2]
+
+local t_tag = sponsor_address .. ':reservation_tag:' .. tag
+local reservation_ids = {}
+for _, reservation_id in ipairs(redis.call('SMEMBERS', t_tag)) do
+    if redis.call('EXISTS', sponsor_address .. ':' .. reservation_id) == 1 then
+        table.insert(reservation_ids, reservation_id)
+    else
+        redis.call('SREM', t_tag, reservation_id)
+    end
+end
+
+return reservation_ids
@@ -0,0 +1,47 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to find the reservations of a sponsor address that were made ready for execution before
+-- a given time and whose coins are still in flight.
+-- ready_for_execution.lua indexes every reservation it takes in flight in in_flight_times, by the time it did so.
+-- Reservations are not removed from the index when their coins are released, so a reservation is still in flight
+-- only if each of its coins is still indexed as in flight under it. Released reservations found here are removed
+-- from the index.
+-- The first argument is the sponsor's address.
+-- The second argument is the cutoff time in milliseconds.
+-- Returns a table with the number of reservations still in flight since before the cutoff, and the time the
+-- oldest of them was made ready for execution, 0 if there is none.
+
+local sponsor_address = ARGV[1]
+local cutoff = tonumber(ARGV[2])
+
+local t_in_flight_times = sponsor_address .. ':in_flight_times'
+local entries = redis.call('ZRANGEBYSCORE', t_in_flight_times, '-inf', cutoff, 'WITHSCORES')
+
+local count = 0
+local oldest = 0
+for i = 1, #entries, 2 do
+    local entry = entries[i]
+    local separator = string.find(entry, ':', 1, true)
+    local reservation_id = string.sub(entry, 1, separator - 1)
+    local expected = sponsor_address .. ',in_flight,' .. reservation_id
+    local in_flight = true
+    for object_id in string.gmatch(string.sub(entry, separator + 1), '[^,]+') do
+        if redis.call('GET', 'coin:' .. object_id) ~= expected then
+            in_flight = false
+            break
+        end
+    end
+    if in_flight then
+        count = count + 1
+        if oldest == 0 then
+            -- Entries come in increasing order of time.
+            oldest = tonumber(entries[i + 1])
+        end
+    else
+        redis.call('ZREM', t_in_flight_times, entry)
+    end
+end
+
+-- Scores are returned as strings, which may use the exponent notation.
+return {count, string.format('%d', oldest)}
@@ -0,0 +1,26 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to keep a reservation that still holds its coins alive while its client is, by pushing its
+-- expiration time out to the given one. A reservation that already expires later is left alone, and one that is
+-- still unconfirmed cannot be kept alive this way.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+-- The third argument is the earliest expiration time the reservation gets.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+local expiration_time = tonumber(ARGV[3])
+
+local t_expiration_queue = sponsor_address .. ':expiration_queue'
+local cur_expiration_time = redis.call('ZSCORE', t_expiration_queue, reservation_id)
+if not cur_expiration_time or redis.call('EXISTS', sponsor_address .. ':' .. reservation_id) == 0 then
+    error('Reservation no longer exist: ' .. reservation_id)
+end
+if redis.call('EXISTS', sponsor_address .. ':unconfirmed:' .. reservation_id) == 1 then
+    error('Reservation is not confirmed: ' .. reservation_id)
+end
+
+if tonumber(cur_expiration_time) < expiration_time then
+    redis.call('ZADD', t_expiration_queue, expiration_time, reservation_id)
+end
@@ -0,0 +1,30 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to put coins that are not in the pool, such as coins coming back from an execution, under a
+-- new reservation. They go back to the pool the same way as the coins of any other reservation: once released by
+-- release_reservation_coins.lua, or by the caller of expire_coins.lua when the reservation expires.
+-- The coins are recorded the same way as reserve_gas_coins.lua, but the available coin count and balance are left
+-- untouched since the coins were not available.
+-- The first argument is the sponsor's address.
+-- The second argument is a JSON array of the object IDs to hold.
+-- The third argument is the expiration time.
+-- The fourth argument is the current time, recorded as the creation time of the reservation.
+-- Returns the reservation id.
+
+local sponsor_address = ARGV[1]
+local object_ids = cjson.decode(ARGV[2])
+local expiration_time = tonumber(ARGV[3])
+local creation_time = tonumber(ARGV[4])
+
+local t_next_reservation_id = sponsor_address .. ':next_reservation_id'
+redis.call('INCR', t_next_reservation_id)
+local reservation_id = redis.call('GET', t_next_reservation_id)
+redis.call('SET', sponsor_address .. ':' .. reservation_id, table.concat(object_ids, ','))
+redis.call('ZADD', sponsor_address .. ':expiration_queue', expiration_time, reservation_id)
+redis.call('ZADD', sponsor_address .. ':reservation_creation_times', creation_time, reservation_id)
+for _, object_id in ipairs(object_ids) do
+    redis.call('SET', 'coin:' .. object_id, sponsor_address .. ',reserved,' .. reservation_id)
+end
+
+return reservation_id
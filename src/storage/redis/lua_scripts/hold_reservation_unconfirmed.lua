@@ -0,0 +1,26 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to make a new reservation tentative until it is confirmed by confirm_reservation.lua.
+-- Until then, the reservation cannot be executed, and it expires after the unconfirmed expiration time unless its
+-- own expiration time comes first.
+-- The full expiration time is kept under <sponsor>:unconfirmed:<reservation_id>, to restore it on confirmation.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+-- The third argument is the unconfirmed expiration time.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+local unconfirmed_expiration_time = tonumber(ARGV[3])
+
+local t_expiration_queue = sponsor_address .. ':expiration_queue'
+local expiration_time = redis.call('ZSCORE', t_expiration_queue, reservation_id)
+if not expiration_time or redis.call('EXISTS', sponsor_address .. ':' .. reservation_id) == 0 then
+    error('Reservation no longer exist: ' .. reservation_id)
+end
+expiration_time = tonumber(expiration_time)
+
+redis.call('SET', sponsor_address .. ':unconfirmed:' .. reservation_id, string.format('%d', expiration_time))
+if unconfirmed_expiration_time < expiration_time then
+    redis.call('ZADD', t_expiration_queue, unconfirmed_expiration_time, reservation_id)
+end
@@ -0,0 +1,36 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to recreate a reservation with a given id, when importing a snapshot.
+-- The coins are recorded the same way as reserve_gas_coins.lua, and will be released by expire_coins.lua.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+-- The third argument is the expiration time.
+-- The fourth argument is a JSON array of the reserved object IDs.
+-- The fifth argument is the current time. Snapshots do not carry the creation time of reservations,
+-- so the time of the import is recorded as their creation time instead.
+-- The next reservation id is moved past the imported one so that new reservations never collide with it.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+local expiration_time = tonumber(ARGV[3])
+local object_ids = cjson.decode(ARGV[4])
+local creation_time = tonumber(ARGV[5])
+
+local key = sponsor_address .. ':' .. reservation_id
+if redis.call('EXISTS', key) == 1 then
+    error('Reservation already exists: ' .. reservation_id)
+end
+
+redis.call('SET', key, table.concat(object_ids, ','))
+redis.call('ZADD', sponsor_address .. ':expiration_queue', expiration_time, reservation_id)
+redis.call('ZADD', sponsor_address .. ':reservation_creation_times', creation_time, reservation_id)
+for _, object_id in ipairs(object_ids) do
+    redis.call('SET', 'coin:' .. object_id, sponsor_address .. ',reserved,' .. reservation_id)
+end
+
+local t_next_reservation_id = sponsor_address .. ':next_reservation_id'
+local next_reservation_id = tonumber(redis.call('GET', t_next_reservation_id) or '0')
+if next_reservation_id < tonumber(reservation_id) then
+    redis.call('SET', t_next_reservation_id, reservation_id)
+end
@@ -7,14 +7,83 @@
 -- right before the transaction is executed.
 -- The first argument is the sponsor's address.
 -- The second argument is the reservation id.
+-- The third argument is the current time in milliseconds, used to index the reservation in in_flight_times
+-- so that reservations stuck in flight can be found by get_stuck_reservations.lua.
+-- The optional fourth argument is a JSON array of the object IDs the transaction pays with. When given,
+-- each of them must be part of the reservation; otherwise the reservation is left untouched and the IDs
+-- that are not part of it are returned.
+-- The fifth argument, given along with the payment, is the digest of the transaction. Calling the script again
+-- for the same transaction once the reservation is in flight returns its coins again, as long as all of them are
+-- still in flight under the reservation, so that a call whose response was lost can be retried.
+-- Returns a table with the object IDs that are not part of the reservation, empty once the reservation is ready
+-- for execution, and the comma separated object IDs of all the coins of the reservation.
+-- Reservations that are still waiting for confirmation cannot be executed.
+-- Sponsor objects reserved under the reservation are moved to in flight as well, until they are released
+-- by release_sponsor_objects.lua after execution.
 
 local sponsor_address = ARGV[1]
 local reservation_id = ARGV[2]
+local now = tonumber(ARGV[3])
+local payment = ARGV[4] and cjson.decode(ARGV[4]) or {}
+local transaction_digest = ARGV[5]
+
+-- Coins that get smashed during execution are never added back, so their in-flight index entries
+-- expire on their own after a while.
+local IN_FLIGHT_INDEX_TTL_SEC = 3600
+
+if redis.call('EXISTS', sponsor_address .. ':unconfirmed:' .. reservation_id) == 1 then
+    error('Reservation is not confirmed: ' .. reservation_id)
+end
 
 local key = sponsor_address .. ':' .. reservation_id
-local exists = redis.call('EXISTS', key)
-if exists == 1 then
+local t_in_flight = sponsor_address .. ':in_flight:' .. reservation_id
+local object_ids = redis.call('GET', key)
+if object_ids then
+    local reserved = {}
+    for object_id in string.gmatch(object_ids, '[^,]+') do
+        reserved[object_id] = true
+    end
+    local not_reserved = {}
+    for _, object_id in ipairs(payment) do
+        if not reserved[object_id] then
+            table.insert(not_reserved, object_id)
+        end
+    end
+    if #not_reserved > 0 then
+        return {not_reserved, ''}
+    end
+
     redis.call('DEL', key)
+    for object_id in string.gmatch(object_ids, '[^,]+') do
+        redis.call('SET', 'coin:' .. object_id, sponsor_address .. ',in_flight,' .. reservation_id,
+            'EX', IN_FLIGHT_INDEX_TTL_SEC)
+    end
+    -- Entries are only useful as long as the in-flight index entries of their coins, so the ones past that
+    -- are trimmed here to keep the index bounded.
+    local t_in_flight_times = sponsor_address .. ':in_flight_times'
+    redis.call('ZREMRANGEBYSCORE', t_in_flight_times, '-inf', '(' .. (now - IN_FLIGHT_INDEX_TTL_SEC * 1000))
+    redis.call('ZADD', t_in_flight_times, now, reservation_id .. ':' .. object_ids)
+    if transaction_digest then
+        redis.call('HSET', t_in_flight, 'transaction_digest', transaction_digest, 'object_ids', object_ids)
+        redis.call('EXPIRE', t_in_flight, IN_FLIGHT_INDEX_TTL_SEC)
+    end
 else
+    local in_flight = redis.call('HMGET', t_in_flight, 'transaction_digest', 'object_ids')
+    if transaction_digest and in_flight[1] == transaction_digest then
+        local expected = sponsor_address .. ',in_flight,' .. reservation_id
+        for object_id in string.gmatch(in_flight[2], '[^,]+') do
+            if redis.call('GET', 'coin:' .. object_id) ~= expected then
+                error('Reservation no longer exist: ' .. reservation_id)
+            end
+        end
+        return {{}, in_flight[2]}
+    end
     error('Reservation no longer exist: ' .. reservation_id)
-end
\ No newline at end of file
+end
+
+local t_object_reservation = sponsor_address .. ':object_reservation:' .. reservation_id
+if redis.call('EXISTS', t_object_reservation) == 1 then
+    redis.call('RENAME', t_object_reservation, sponsor_address .. ':object_in_flight:' .. reservation_id)
+end
+
+return {{}, object_ids}
@@ -0,0 +1,79 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to release some of the coins of a reservation back to the available pool,
+-- while keeping the rest of the coins reserved under the same reservation id.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+-- The third argument is a JSON array of the object IDs to take out of the reservation.
+-- The fourth argument is a JSON array of the latest state of these coins, in the same format as add_new_coins.lua.
+-- Coins that no longer exist on chain are omitted from it, and are simply dropped from the reservation.
+-- The released coins are added back the same way as add_new_coins.lua.
+-- Returns a table with the new total balance and new coin count.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+local released_object_ids = cjson.decode(ARGV[3])
+local released_coins = cjson.decode(ARGV[4])
+
+local key = sponsor_address .. ':' .. reservation_id
+local object_ids = redis.call('GET', key)
+if not object_ids then
+    error('Reservation no longer exist: ' .. reservation_id)
+end
+
+local released = {}
+for _, object_id in ipairs(released_object_ids) do
+    released[object_id] = true
+end
+local remaining = {}
+local found = 0
+for object_id in string.gmatch(object_ids, '[^,]+') do
+    if released[object_id] then
+        found = found + 1
+    else
+        table.insert(remaining, object_id)
+    end
+end
+if found ~= #released_object_ids then
+    error('Some of the coins do not belong to reservation ' .. reservation_id)
+end
+
+if #remaining == 0 then
+    redis.call('DEL', key)
+    redis.call('ZREM', sponsor_address .. ':expiration_queue', reservation_id)
+    redis.call('DEL', sponsor_address .. ':unconfirmed:' .. reservation_id)
+else
+    redis.call('SET', key, table.concat(remaining, ','))
+end
+for _, object_id in ipairs(released_object_ids) do
+    redis.call('DEL', 'coin:' .. object_id)
+end
+
+local t_available_gas_coins = sponsor_address .. ':available_gas_coins'
+local t_coins_by_last_use = sponsor_address .. ':available_gas_coins_by_last_use'
+local total_balance = 0
+for _, coin in ipairs(released_coins) do
+    local idx1, _ = string.find(coin, ',', 1)
+    total_balance = total_balance + tonumber(string.sub(coin, 1, idx1 - 1))
+    redis.call('RPUSH', t_available_gas_coins, coin)
+
+    local idx2, _ = string.find(coin, ',', idx1 + 1)
+    local object_id = string.sub(coin, idx1 + 1, idx2 - 1)
+    redis.call('SET', 'coin:' .. object_id, sponsor_address .. ',available')
+    local t_last_used = sponsor_address .. ':coin_last_used:' .. object_id
+    redis.call('ZADD', t_coins_by_last_use, tonumber(redis.call('GET', t_last_used)) or 0, coin)
+    redis.call('PERSIST', t_last_used)
+end
+
+local t_available_coin_total_balance = sponsor_address .. ':available_coin_total_balance'
+local cur_coin_total_balance = redis.call('GET', t_available_coin_total_balance)
+local new_total_balance = cur_coin_total_balance + total_balance
+redis.call('SET', t_available_coin_total_balance, new_total_balance)
+
+local t_available_coin_count = sponsor_address .. ':available_coin_count'
+local cur_coin_count = redis.call('GET', t_available_coin_count)
+local new_coin_count = cur_coin_count + #released_coins
+redis.call('SET', t_available_coin_count, new_coin_count)
+
+return {new_total_balance, new_coin_count}
@@ -0,0 +1,30 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to release the sponsor objects of a reservation after execution.
+-- Objects that still belong to the sponsor are made available again with their latest version,
+-- while the others are removed from the pool.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+-- The third argument is a JSON array of the latest state of the objects that still belong to the sponsor,
+-- each in the form of object_id,version,digest.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+local latest_objects = cjson.decode(ARGV[3])
+
+local t_object_in_flight = sponsor_address .. ':object_in_flight:' .. reservation_id
+local object_ids = redis.call('GET', t_object_in_flight)
+if not object_ids then
+    return
+end
+
+local t_sponsor_objects = sponsor_address .. ':sponsor_objects'
+for object_id in string.gmatch(object_ids, '[^,]+') do
+    redis.call('HDEL', t_sponsor_objects, object_id)
+end
+for _, object in ipairs(latest_objects) do
+    local object_id, version, digest = string.match(object, '([^,]+),([^,]+),([^,]+)')
+    redis.call('HSET', t_sponsor_objects, object_id, version .. ',' .. digest .. ',0')
+end
+redis.call('DEL', t_object_in_flight)
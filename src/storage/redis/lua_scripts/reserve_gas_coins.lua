@@ -9,23 +9,123 @@
 -- The first argument is the sponsor's address.
 -- The second argument is the target budget.
 -- The third argument is the expiration time.
+-- The fourth argument is whether to prefer a single coin that covers the whole budget ("1") over
+-- combining multiple coins from the front of the queue ("0").
+-- The fifth argument is the number of candidates to skip, chosen randomly by the caller to spread
+-- concurrent reservations across different coins. With 0 the selection is deterministic.
+-- The sixth argument is the maximum number of coins to reserve, at most MAX_GAS_PER_QUERY.
+-- The seventh argument is the current time, recorded as the creation time of the reservation.
+-- The optional eighth argument is whether to prefer the coins that were reserved the longest time ago ("1"). It only
+-- applies when no single coin was picked.
+-- The available coins are also kept in available_gas_coins_by_last_use, scored by the time they were last reserved,
+-- 0 for coins that were never reserved. It is rebuilt from the queue whenever the two are out of sync, which only
+-- happens to coins added before it existed.
+-- The coins are indexed under coin:<object id> as reserved, and the time they were last reserved is recorded under
+-- coin_last_used:<object id> until they are added back to the pool. Coins that never come back, because they were
+-- smashed or no longer exist, have their entry expire on its own.
 -- Returns a table with the reservation id, reserved coins, new total balance, and new coin count.
+-- If the coins are not enough, returns a reservation id of 0 with no coins, along with the current
+-- total balance and coin count.
 
 local sponsor_address = ARGV[1]
 local target_budget = tonumber(ARGV[2])
 local expiration_time = tonumber(ARGV[3])
+local prefer_single_coin = ARGV[4] == '1'
+local candidate_skip = tonumber(ARGV[5])
 
 local MAX_GAS_PER_QUERY = 256
+local max_coins = math.min(tonumber(ARGV[6]), MAX_GAS_PER_QUERY)
+local creation_time = tonumber(ARGV[7])
+local prefer_least_recently_used = ARGV[8] == '1'
+-- How many coins from the front of the queue we look at when searching for a single coin.
+local SINGLE_COIN_SCAN_LIMIT = 1000
+-- How long the last reserved time of a coin is kept once it leaves the pool, long enough to outlive any
+-- reservation and execution of the coin.
+local LAST_USED_TTL_SEC = 7 * 24 * 3600
 
 local t_available_gas_coins = sponsor_address .. ':available_gas_coins'
 local t_expiration_queue = sponsor_address .. ':expiration_queue'
 local t_next_reservation_id = sponsor_address .. ':next_reservation_id'
 
+local t_available_coin_total_balance = sponsor_address .. ':available_coin_total_balance'
+local t_available_coin_count = sponsor_address .. ':available_coin_count'
+local t_coins_by_last_use = sponsor_address .. ':available_gas_coins_by_last_use'
+
 local total_balance = 0
 local coins = {}
 local object_ids = {}
 
-while total_balance < target_budget and #coins < MAX_GAS_PER_QUERY do
+if prefer_single_coin then
+    -- Pick the (candidate_skip + 1)-th coin covering the budget, or the last one found if there are fewer.
+    local picked = nil
+    local matches = 0
+    local candidates = redis.call('LRANGE', t_available_gas_coins, 0, SINGLE_COIN_SCAN_LIMIT - 1)
+    for _, coin in ipairs(candidates) do
+        local idx1, _ = string.find(coin, ',', 1)
+        local balance = tonumber(string.sub(coin, 1, idx1 - 1))
+        if balance >= target_budget then
+            picked = coin
+            matches = matches + 1
+            if matches > candidate_skip then break end
+        end
+    end
+    if picked then
+        redis.call('LREM', t_available_gas_coins, 1, picked)
+        local idx1, _ = string.find(picked, ',', 1)
+        total_balance = tonumber(string.sub(picked, 1, idx1 - 1))
+
+        local idx2, _ = string.find(picked, ',', idx1 + 1)
+        table.insert(coins, picked)
+        table.insert(object_ids, string.sub(picked, idx1 + 1, idx2 - 1))
+    end
+end
+
+if #coins == 0 and prefer_least_recently_used then
+    if redis.call('ZCARD', t_coins_by_last_use) ~= redis.call('LLEN', t_available_gas_coins) then
+        redis.call('DEL', t_coins_by_last_use)
+        for _, coin in ipairs(redis.call('LRANGE', t_available_gas_coins, 0, -1)) do
+            local idx1, _ = string.find(coin, ',', 1)
+            local idx2, _ = string.find(coin, ',', idx1 + 1)
+            local object_id = string.sub(coin, idx1 + 1, idx2 - 1)
+            local last_used = redis.call('GET', sponsor_address .. ':coin_last_used:' .. object_id)
+            redis.call('ZADD', t_coins_by_last_use, tonumber(last_used) or 0, coin)
+        end
+    end
+    local candidates = redis.call('ZRANGE', t_coins_by_last_use, 0, max_coins - 1)
+    local picked_balance = 0
+    local picked = 0
+    for i, coin in ipairs(candidates) do
+        if picked_balance >= target_budget then break end
+        local idx1, _ = string.find(coin, ',', 1)
+        picked_balance = picked_balance + tonumber(string.sub(coin, 1, idx1 - 1))
+        picked = i
+    end
+    -- Otherwise fall back to combining coins from the front of the queue.
+    if picked_balance >= target_budget then
+        for i = 1, picked do
+            local coin = candidates[i]
+            redis.call('LREM', t_available_gas_coins, 1, coin)
+            local idx1, _ = string.find(coin, ',', 1)
+            local idx2, _ = string.find(coin, ',', idx1 + 1)
+            total_balance = total_balance + tonumber(string.sub(coin, 1, idx1 - 1))
+            table.insert(coins, coin)
+            table.insert(object_ids, string.sub(coin, idx1 + 1, idx2 - 1))
+        end
+    end
+end
+
+if #coins == 0 then
+    -- Rotate the skipped coins to the back of the queue, so that we start combining from a
+    -- different coin than concurrent reservations.
+    for _ = 1, candidate_skip do
+        local coin = redis.call('LPOP', t_available_gas_coins)
+        if not coin then break end
+        redis.call('RPUSH', t_available_gas_coins, coin)
+    end
+end
+
+-- If no single coin was picked above, combine coins from the front of the queue.
+while total_balance < target_budget and #coins < max_coins do
     local coin = redis.call('LPOP', t_available_gas_coins)
     if not coin then break end
 
@@ -45,16 +145,16 @@ if total_balance < target_budget then
     for i = #coins, 1, -1 do
         redis.call('LPUSH', t_available_gas_coins, coins[i])
     end
-    return {0, {}, 0, 0}
+    local cur_coin_total_balance = tonumber(redis.call('GET', t_available_coin_total_balance)) or 0
+    local cur_coin_count = tonumber(redis.call('GET', t_available_coin_count)) or 0
+    return {0, {}, cur_coin_total_balance, cur_coin_count}
 end
 
-local t_available_coin_total_balance = sponsor_address .. ':available_coin_total_balance'
 -- TODO: For some reason DECRBY is not working, so we have to do this in two steps.
 local cur_coin_total_balance = redis.call('GET', t_available_coin_total_balance)
 local new_total_balance = cur_coin_total_balance - total_balance
 redis.call('SET', t_available_coin_total_balance, new_total_balance)
 
-local t_available_coin_count = sponsor_address .. ':available_coin_count'
 local cur_coin_count = redis.call('GET', t_available_coin_count)
 local new_coin_count = cur_coin_count - #coins
 redis.call('SET', t_available_coin_count, new_coin_count)
@@ -65,5 +165,11 @@ local concated_object_ids = table.concat(object_ids, ',')
 local key = sponsor_address .. ':' .. reservation_id
 redis.call('SET', key, concated_object_ids)
 redis.call('ZADD', t_expiration_queue, expiration_time, reservation_id)
+redis.call('ZADD', sponsor_address .. ':reservation_creation_times', creation_time, reservation_id)
+for i, object_id in ipairs(object_ids) do
+    redis.call('SET', 'coin:' .. object_id, sponsor_address .. ',reserved,' .. reservation_id)
+    redis.call('ZREM', t_coins_by_last_use, coins[i])
+    redis.call('SET', sponsor_address .. ':coin_last_used:' .. object_id, creation_time, 'EX', LAST_USED_TTL_SEC)
+end
 
 return {reservation_id, coins, new_total_balance, new_coin_count}
@@ -0,0 +1,85 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to reserve a specific set of gas coins for a sponsor address.
+-- It either reserves all of the requested coins, or none of them if any is not available.
+-- Since the available coins are stored in a list, this requires scanning the whole list, which makes it
+-- much more expensive than reserve_gas_coins.lua.
+-- The reservation is recorded in the same way as reserve_gas_coins.lua.
+-- The first argument is the sponsor's address.
+-- The second argument is a JSON array of the requested object IDs.
+-- The third argument is the expiration time.
+-- The fourth argument is the current time, recorded as the creation time of the reservation.
+-- The coins are indexed under coin:<object id> as reserved, and the time they were last reserved is recorded under
+-- coin_last_used:<object id>, the same way as reserve_gas_coins.lua.
+-- Returns a table with the reservation id, reserved coins, new total balance, and new coin count.
+
+local sponsor_address = ARGV[1]
+local requested_object_ids = cjson.decode(ARGV[2])
+local expiration_time = tonumber(ARGV[3])
+local creation_time = tonumber(ARGV[4])
+
+local t_available_gas_coins = sponsor_address .. ':available_gas_coins'
+local t_expiration_queue = sponsor_address .. ':expiration_queue'
+local t_next_reservation_id = sponsor_address .. ':next_reservation_id'
+local t_coins_by_last_use = sponsor_address .. ':available_gas_coins_by_last_use'
+-- Same as in reserve_gas_coins.lua.
+local LAST_USED_TTL_SEC = 7 * 24 * 3600
+
+local requested = {}
+for _, object_id in ipairs(requested_object_ids) do
+    requested[object_id] = true
+end
+
+local found = {}
+local elements = redis.call('LRANGE', t_available_gas_coins, 0, -1)
+for _, coin in ipairs(elements) do
+    local idx1, _ = string.find(coin, ',', 1)
+    local idx2, _ = string.find(coin, ',', idx1 + 1)
+    local object_id = string.sub(coin, idx1 + 1, idx2 - 1)
+    if requested[object_id] then
+        found[object_id] = coin
+    end
+end
+
+local total_balance = 0
+local coins = {}
+for _, object_id in ipairs(requested_object_ids) do
+    local coin = found[object_id]
+    if not coin then
+        -- At least one of the requested coins is not available, do not reserve any of them.
+        return {0, {}, 0, 0}
+    end
+    local idx1, _ = string.find(coin, ',', 1)
+    total_balance = total_balance + tonumber(string.sub(coin, 1, idx1 - 1))
+    table.insert(coins, coin)
+end
+
+for _, coin in ipairs(coins) do
+    redis.call('LREM', t_available_gas_coins, 1, coin)
+    redis.call('ZREM', t_coins_by_last_use, coin)
+end
+
+local t_available_coin_total_balance = sponsor_address .. ':available_coin_total_balance'
+local cur_coin_total_balance = redis.call('GET', t_available_coin_total_balance)
+local new_total_balance = cur_coin_total_balance - total_balance
+redis.call('SET', t_available_coin_total_balance, new_total_balance)
+
+local t_available_coin_count = sponsor_address .. ':available_coin_count'
+local cur_coin_count = redis.call('GET', t_available_coin_count)
+local new_coin_count = cur_coin_count - #coins
+redis.call('SET', t_available_coin_count, new_coin_count)
+
+redis.call('INCR', t_next_reservation_id)
+local reservation_id = redis.call('GET', t_next_reservation_id)
+local concated_object_ids = table.concat(requested_object_ids, ',')
+local key = sponsor_address .. ':' .. reservation_id
+redis.call('SET', key, concated_object_ids)
+redis.call('ZADD', t_expiration_queue, expiration_time, reservation_id)
+redis.call('ZADD', sponsor_address .. ':reservation_creation_times', creation_time, reservation_id)
+for _, object_id in ipairs(requested_object_ids) do
+    redis.call('SET', 'coin:' .. object_id, sponsor_address .. ',reserved,' .. reservation_id)
+    redis.call('SET', sponsor_address .. ':coin_last_used:' .. object_id, creation_time, 'EX', LAST_USED_TTL_SEC)
+end
+
+return {reservation_id, coins, new_total_balance, new_coin_count}
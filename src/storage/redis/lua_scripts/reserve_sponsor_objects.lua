@@ -0,0 +1,45 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to reserve sponsor objects alongside the gas coins of an existing reservation.
+-- Either all of the requested objects are reserved, or none of them.
+-- The objects are released together with the gas coins when the reservation expires.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+-- The third argument is a JSON array of the requested object IDs.
+-- Returns the reserved objects, each in the form of object_id,version,digest.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+local object_ids = cjson.decode(ARGV[3])
+
+if redis.call('EXISTS', sponsor_address .. ':' .. reservation_id) == 0 then
+    error('Reservation no longer exist: ' .. reservation_id)
+end
+local t_object_reservation = sponsor_address .. ':object_reservation:' .. reservation_id
+if redis.call('EXISTS', t_object_reservation) == 1 then
+    error('Reservation already has sponsor objects: ' .. reservation_id)
+end
+
+local t_sponsor_objects = sponsor_address .. ':sponsor_objects'
+local objects = {}
+for _, object_id in ipairs(object_ids) do
+    local entry = redis.call('HGET', t_sponsor_objects, object_id)
+    if not entry then
+        error('Object is not in the pool: ' .. object_id)
+    end
+    local version, digest, current = string.match(entry, '([^,]+),([^,]+),([^,]+)')
+    if current ~= '0' then
+        error('Object is already reserved: ' .. object_id)
+    end
+    table.insert(objects, {object_id, version, digest})
+end
+
+local reserved = {}
+for _, object in ipairs(objects) do
+    redis.call('HSET', t_sponsor_objects, object[1], object[2] .. ',' .. object[3] .. ',' .. reservation_id)
+    table.insert(reserved, object[1] .. ',' .. object[2] .. ',' .. object[3])
+end
+redis.call('SET', t_object_reservation, table.concat(object_ids, ','))
+
+return reserved
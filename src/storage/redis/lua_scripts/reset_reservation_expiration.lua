@@ -0,0 +1,20 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to change when a reservation that still holds its coins expires, e.g. when a reservation made
+-- ahead of time is handed out with the duration its caller asked for.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+-- The third argument is the new expiration time.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+local expiration_time = tonumber(ARGV[3])
+
+local t_expiration_queue = sponsor_address .. ':expiration_queue'
+if redis.call('EXISTS', sponsor_address .. ':' .. reservation_id) == 0
+    or not redis.call('ZSCORE', t_expiration_queue, reservation_id) then
+    error('Reservation no longer exist: ' .. reservation_id)
+end
+
+redis.call('ZADD', t_expiration_queue, expiration_time, reservation_id)
@@ -0,0 +1,20 @@
+-- Copyright (c) Mysten Labs, Inc.
+-- SPDX-License-Identifier: Apache-2.0
+
+-- This script is used to tag a reservation, so that it can later be looked up or cancelled along with the other
+-- reservations of the same tag.
+-- The tag index is a set of reservation ids under <sponsor>:reservation_tag:<tag>. Entries are not removed when the
+-- reservation ends; get_reservations_by_tag.lua and cancel_reservations_by_tag.lua prune them instead.
+-- The first argument is the sponsor's address.
+-- The second argument is the reservation id.
+-- The third argument is the tag.
+
+local sponsor_address = ARGV[1]
+local reservation_id = ARGV[2]
+local tag = ARGV[3]
+
+if redis.call('EXISTS', sponsor_address .. ':' .. reservation_id) == 0 then
+    error('Reservation no longer exist: ' .. reservation_id)
+end
+
+redis.call('SADD', sponsor_address .. ':reservation_tag:' .. tag, reservation_id)
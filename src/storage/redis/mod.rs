@@ -3,24 +3,39 @@
 
 mod script_manager;
 
+use crate::config::CoinSelectionStrategy;
+use crate::errors::GasPoolError;
 use crate::metrics::StorageMetrics;
 use crate::storage::redis::script_manager::ScriptManager;
-use crate::storage::Storage;
-use crate::types::{GasCoin, ReservationID};
+use crate::storage::{Storage, MAX_GAS_PER_QUERY};
+use crate::types::{
+    ActiveReservation, BalancePercentiles, CoinLookup, CoinState, ExecutionResultSummary,
+    ExpirationTimeMs, GasCoin, ReservationID, ReservationSnapshot, ScheduledExecution,
+    SponsorObject, SponsorSnapshot, StuckReservations,
+};
+use anyhow::bail;
 use chrono::Utc;
+use rand::Rng;
 use redis::aio::ConnectionManager;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Add;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
-use sui_types::base_types::{ObjectDigest, ObjectID, SequenceNumber, SuiAddress};
+use sui_types::base_types::{ObjectDigest, ObjectID, ObjectRef, SequenceNumber, SuiAddress};
+use sui_types::digests::TransactionDigest;
 use tracing::{debug, info};
 
+/// Tracing target of the storage layer.
+const LOG_TARGET: &str = "gas_pool::storage";
+
 pub struct RedisStorage {
     conn_manager: ConnectionManager,
     // String format of the sponsor address to avoid converting it to string multiple times.
     sponsor_vec: Vec<String>,
+    coin_selection_strategy: CoinSelectionStrategy,
+    coin_selection_top_k: u64,
+    max_pool_coins_per_sponsor: Option<u64>,
     metrics: Arc<StorageMetrics>,
 }
 
@@ -28,24 +43,83 @@ impl RedisStorage {
     pub async fn new(
         redis_url: &str,
         sponsor_vec: Vec<SuiAddress>,
+        coin_selection_strategy: CoinSelectionStrategy,
+        coin_selection_top_k: u64,
+        max_pool_coins_per_sponsor: Option<u64>,
         metrics: Arc<StorageMetrics>,
     ) -> Self {
         let client = redis::Client::open(redis_url).unwrap();
         let conn_manager = ConnectionManager::new(client).await.unwrap();
+        let sponsor_vec: Vec<String> = sponsor_vec.into_iter().map(|s| s.to_string()).collect();
+        if let Some(max_coins) = max_pool_coins_per_sponsor {
+            for sponsor in &sponsor_vec {
+                metrics
+                    .gas_pool_max_gas_coin_count
+                    .with_label_values(&[sponsor])
+                    .set(max_coins as i64);
+            }
+        }
         Self {
             conn_manager,
-            sponsor_vec: sponsor_vec.into_iter().map(|s| s.to_string()).collect(),
+            sponsor_vec,
+            coin_selection_strategy,
+            coin_selection_top_k,
+            max_pool_coins_per_sponsor,
             metrics,
         }
     }
 }
 
+impl RedisStorage {
+    async fn ready_for_execution_impl(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        payment: Option<(&[ObjectID], TransactionDigest)>,
+    ) -> anyhow::Result<Vec<ObjectID>> {
+        self.metrics.num_ready_for_execution_requests.inc();
+
+        let mut conn = self.conn_manager.clone();
+        let mut invocation = ScriptManager::ready_for_execution_script().prepare_invoke();
+        invocation
+            .arg(sponsor.to_string())
+            .arg(reservation_id)
+            .arg(Utc::now().timestamp_millis() as u64);
+        if let Some((payment, transaction_digest)) = payment {
+            let payment: Vec<_> = payment.iter().map(|id| id.to_string()).collect();
+            invocation
+                .arg(serde_json::to_string(&payment)?)
+                .arg(transaction_digest.to_string());
+        }
+        let (not_reserved, object_ids): (Vec<String>, String) =
+            invocation.invoke_async(&mut conn).await?;
+        if !not_reserved.is_empty() {
+            info!(
+                target: LOG_TARGET,
+                ?reservation_id,
+                "Payment coins {:?} are not part of the reservation",
+                not_reserved
+            );
+            return Err(GasPoolError::PaymentNotReserved.into());
+        }
+
+        self.metrics
+            .num_successful_ready_for_execution_requests
+            .inc();
+        Ok(object_ids
+            .split(',')
+            .map(ObjectID::from_str)
+            .collect::<Result<_, _>>()?)
+    }
+}
+
 #[async_trait::async_trait]
 impl Storage for RedisStorage {
-    async fn reserve_gas_coins(
+    async fn reserve_gas_coins_with_max_coins(
         &self,
         sponsor: SuiAddress,
         target_budget: u64,
+        max_coins: usize,
         reserved_duration_ms: u64,
     ) -> anyhow::Result<(ReservationID, Vec<GasCoin>)> {
         self.metrics.num_reserve_gas_coins_requests.inc();
@@ -54,6 +128,13 @@ impl Storage for RedisStorage {
         let expiration_time = Utc::now()
             .add(Duration::from_millis(reserved_duration_ms))
             .timestamp_millis() as u64;
+        // The randomness is drawn here rather than in the script, so that the script stays
+        // deterministic as Redis requires.
+        let candidate_skip = if self.coin_selection_top_k > 1 {
+            rand::thread_rng().gen_range(0..self.coin_selection_top_k)
+        } else {
+            0
+        };
         let mut conn = self.conn_manager.clone();
         let (reservation_id, coins, new_total_balance, new_coin_count): (
             ReservationID,
@@ -64,32 +145,39 @@ impl Storage for RedisStorage {
             .arg(&sponsor_str)
             .arg(target_budget)
             .arg(expiration_time)
+            // A single coin can only cover the budget if it covers it on its own, so look for
+            // such a coin whatever the strategy, rather than only trying the first one.
+            .arg(
+                matches!(
+                    self.coin_selection_strategy,
+                    CoinSelectionStrategy::PreferSingleCoin
+                ) || max_coins == 1,
+            )
+            .arg(candidate_skip)
+            .arg(max_coins)
+            .arg(Utc::now().timestamp_millis() as u64)
+            .arg(matches!(
+                self.coin_selection_strategy,
+                CoinSelectionStrategy::LeastRecentlyUsed
+            ))
             .invoke_async(&mut conn)
             .await?;
-        // The script returns (0, []) if it is unable to find enough coins to reserve.
-        // We choose to handle the error here instead of inside the script so that we could
-        // provide a more readable error message.
+        // The script returns (0, []) along with the current total balance and coin count if it
+        // is unable to find enough coins to reserve. We choose to handle the error here instead
+        // of inside the script so that we could provide a more readable error message.
         if coins.is_empty() {
-            return Err(anyhow::anyhow!(
-                "Unable to reserve gas coins for the given budget."
-            ));
+            // Only a limit requested by the caller can leave enough balance out of reach.
+            if max_coins < MAX_GAS_PER_QUERY && new_total_balance as u64 >= target_budget {
+                return Err(GasPoolError::TooFragmented.into());
+            }
+            return Err(GasPoolError::InsufficientCoins {
+                available_balance: new_total_balance as u64,
+                available_coin_count: new_coin_count as u64,
+                retry_after: self.get_next_reservation_expiry(sponsor).await?,
+            }
+            .into());
         }
-        let gas_coins: Vec<_> = coins
-            .into_iter()
-            .map(|s| {
-                // Each coin is in the form of: balance,object_id,version,digest
-                let mut splits = s.split(',');
-                let balance = splits.next().unwrap().parse::<u64>().unwrap();
-                let object_id = ObjectID::from_str(splits.next().unwrap()).unwrap();
-                let version = SequenceNumber::from(splits.next().unwrap().parse::<u64>().unwrap());
-                let digest = ObjectDigest::from_str(splits.next().unwrap()).unwrap();
-                GasCoin {
-                    owner: sponsor,
-                    balance,
-                    object_ref: (object_id, version, digest),
-                }
-            })
-            .collect();
+        let gas_coins = parse_coins(sponsor, coins);
 
         self.metrics
             .gas_pool_available_gas_coin_count
@@ -103,54 +191,296 @@ impl Storage for RedisStorage {
         Ok((reservation_id, gas_coins))
     }
 
+    async fn reserve_specific_coins(
+        &self,
+        sponsor: SuiAddress,
+        coin_ids: Vec<ObjectID>,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<(ReservationID, Vec<GasCoin>)> {
+        if coin_ids.is_empty() {
+            bail!("No gas coins requested");
+        }
+        if coin_ids.len() > MAX_GAS_PER_QUERY {
+            bail!(
+                "Cannot reserve more than {} gas coins at a time",
+                MAX_GAS_PER_QUERY
+            );
+        }
+        if coin_ids.iter().collect::<HashSet<_>>().len() != coin_ids.len() {
+            bail!("Requested gas coins contain duplicate ids");
+        }
+        self.metrics.num_reserve_gas_coins_requests.inc();
+        let sponsor_str = sponsor.to_string();
+
+        let expiration_time = Utc::now()
+            .add(Duration::from_millis(reserved_duration_ms))
+            .timestamp_millis() as u64;
+        let coin_id_strings: Vec<_> = coin_ids.iter().map(|id| id.to_string()).collect();
+        let mut conn = self.conn_manager.clone();
+        let (reservation_id, coins, new_total_balance, new_coin_count): (
+            ReservationID,
+            Vec<String>,
+            i64,
+            i64,
+        ) = ScriptManager::reserve_specific_coins_script()
+            .arg(&sponsor_str)
+            .arg(serde_json::to_string(&coin_id_strings)?)
+            .arg(expiration_time)
+            .arg(Utc::now().timestamp_millis() as u64)
+            .invoke_async(&mut conn)
+            .await?;
+        if coins.is_empty() {
+            bail!("Some of the requested gas coins are not available for reservation");
+        }
+        let gas_coins = parse_coins(sponsor, coins);
+
+        self.metrics
+            .gas_pool_available_gas_coin_count
+            .with_label_values(&[&sponsor_str])
+            .set(new_coin_count);
+        self.metrics
+            .gas_pool_available_gas_total_balance
+            .with_label_values(&[&sponsor_str])
+            .set(new_total_balance);
+        self.metrics.num_successful_reserve_gas_coins_requests.inc();
+        Ok((reservation_id, gas_coins))
+    }
+
+    async fn hold_coins(
+        &self,
+        sponsor: SuiAddress,
+        coin_ids: Vec<ObjectID>,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<ReservationID> {
+        if coin_ids.is_empty() {
+            bail!("No gas coins to hold");
+        }
+        let expiration_time = Utc::now()
+            .add(Duration::from_millis(reserved_duration_ms))
+            .timestamp_millis() as u64;
+        let coin_id_strings: Vec<_> = coin_ids.iter().map(|id| id.to_string()).collect();
+        let mut conn = self.conn_manager.clone();
+        let reservation_id: ReservationID = ScriptManager::hold_coins_script()
+            .arg(sponsor.to_string())
+            .arg(serde_json::to_string(&coin_id_strings)?)
+            .arg(expiration_time)
+            .arg(Utc::now().timestamp_millis() as u64)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(reservation_id)
+    }
+
     async fn ready_for_execution(
         &self,
         sponsor: SuiAddress,
         reservation_id: ReservationID,
     ) -> anyhow::Result<()> {
-        self.metrics.num_ready_for_execution_requests.inc();
+        self.ready_for_execution_impl(sponsor, reservation_id, None)
+            .await?;
+        Ok(())
+    }
 
+    async fn ready_for_execution_with_payment(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        payment: &[ObjectID],
+        transaction_digest: TransactionDigest,
+    ) -> anyhow::Result<Vec<ObjectID>> {
+        self.ready_for_execution_impl(sponsor, reservation_id, Some((payment, transaction_digest)))
+            .await
+    }
+
+    async fn hold_reservation_unconfirmed(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        unconfirmed_duration_ms: u64,
+    ) -> anyhow::Result<()> {
+        let unconfirmed_expiration_time = Utc::now()
+            .add(Duration::from_millis(unconfirmed_duration_ms))
+            .timestamp_millis() as u64;
         let mut conn = self.conn_manager.clone();
-        ScriptManager::ready_for_execution_script()
+        ScriptManager::hold_reservation_unconfirmed_script()
             .arg(sponsor.to_string())
             .arg(reservation_id)
+            .arg(unconfirmed_expiration_time)
             .invoke_async::<_, ()>(&mut conn)
             .await?;
+        Ok(())
+    }
 
+    async fn confirm_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn_manager.clone();
+        ScriptManager::confirm_reservation_script()
+            .arg(sponsor.to_string())
+            .arg(reservation_id)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn reset_reservation_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        reserved_duration_ms: u64,
+    ) -> anyhow::Result<()> {
+        let expiration_time = Utc::now()
+            .add(Duration::from_millis(reserved_duration_ms))
+            .timestamp_millis() as u64;
+        let mut conn = self.conn_manager.clone();
+        ScriptManager::reset_reservation_expiration_script()
+            .arg(sponsor.to_string())
+            .arg(reservation_id)
+            .arg(expiration_time)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn heartbeat_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        lease_duration_ms: u64,
+    ) -> anyhow::Result<()> {
+        let expiration_time = Utc::now()
+            .add(Duration::from_millis(lease_duration_ms))
+            .timestamp_millis() as u64;
+        let mut conn = self.conn_manager.clone();
+        ScriptManager::heartbeat_reservation_script()
+            .arg(sponsor.to_string())
+            .arg(reservation_id)
+            .arg(expiration_time)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn tag_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        tag: &str,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn_manager.clone();
+        ScriptManager::tag_reservation_script()
+            .arg(sponsor.to_string())
+            .arg(reservation_id)
+            .arg(tag)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_reservations_by_tag(
+        &self,
+        sponsor: SuiAddress,
+        tag: &str,
+    ) -> anyhow::Result<Vec<ReservationID>> {
+        let mut conn = self.conn_manager.clone();
+        let reservation_ids = ScriptManager::get_reservations_by_tag_script()
+            .arg(sponsor.to_string())
+            .arg(tag)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(reservation_ids)
+    }
+
+    async fn cancel_reservations_by_tag(
+        &self,
+        sponsor: SuiAddress,
+        tag: &str,
+    ) -> anyhow::Result<Vec<ReservationID>> {
+        let mut conn = self.conn_manager.clone();
+        let reservation_ids = ScriptManager::cancel_reservations_by_tag_script()
+            .arg(sponsor.to_string())
+            .arg(tag)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(reservation_ids)
+    }
+
+    async fn release_reservation_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        coin_ids: Vec<ObjectID>,
+        latest_coins: Vec<GasCoin>,
+    ) -> anyhow::Result<()> {
+        if coin_ids.is_empty() {
+            return Ok(());
+        }
+        let sponsor_str = sponsor.to_string();
+        let coin_id_strings: Vec<_> = coin_ids.iter().map(|id| id.to_string()).collect();
+        let mut conn = self.conn_manager.clone();
+        let (new_total_balance, new_coin_count): (i64, i64) =
+            ScriptManager::release_reservation_coins_script()
+                .arg(&sponsor_str)
+                .arg(reservation_id)
+                .arg(serde_json::to_string(&coin_id_strings)?)
+                .arg(serde_json::to_string(&format_coins(&latest_coins))?)
+                .invoke_async(&mut conn)
+                .await?;
         self.metrics
-            .num_successful_ready_for_execution_requests
-            .inc();
+            .gas_pool_available_gas_coin_count
+            .with_label_values(&[&sponsor_str])
+            .set(new_coin_count);
+        self.metrics
+            .gas_pool_available_gas_total_balance
+            .with_label_values(&[&sponsor_str])
+            .set(new_total_balance);
         Ok(())
     }
 
-    async fn add_new_coins(&self, new_coins: Vec<GasCoin>) -> anyhow::Result<()> {
+    async fn add_new_coins(&self, new_coins: Vec<GasCoin>) -> anyhow::Result<Vec<GasCoin>> {
         if new_coins.is_empty() {
-            return Ok(());
+            return Ok(vec![]);
         }
         self.metrics.num_add_new_coins_requests.inc();
-        let mut formatted_coin_maps = HashMap::new();
+        let mut coins_by_sponsor: HashMap<SuiAddress, Vec<GasCoin>> = HashMap::new();
         for c in new_coins {
-            formatted_coin_maps
-                .entry(c.owner)
-                .or_insert_with(Vec::new)
-                .push(format!(
-                    "{},{},{},{}",
-                    c.balance,
-                    c.object_ref.0,
-                    c.object_ref.1.value(),
-                    c.object_ref.2
-                ))
+            coins_by_sponsor.entry(c.owner).or_default().push(c);
         }
+        let formatted_coin_maps: HashMap<_, _> = coins_by_sponsor
+            .iter()
+            .map(|(sponsor, coins)| (sponsor, format_coins(coins)))
+            .collect();
         let mut conn = self.conn_manager.clone();
         let results: String = ScriptManager::add_new_coins_script()
             .arg(serde_json::to_string(&formatted_coin_maps)?)
+            .arg(self.max_pool_coins_per_sponsor.unwrap_or(0))
             .invoke_async(&mut conn)
             .await?;
 
-        let results = serde_json::from_str::<Vec<(String, i64, i64)>>(&results)?;
+        let results = serde_json::from_str::<Vec<(String, i64, i64, usize)>>(&results)?;
 
-        for (sponsor, new_total_balance, new_coin_count) in results {
+        let mut not_added = vec![];
+        for (sponsor, new_total_balance, new_coin_count, rejected_count) in results {
+            if rejected_count > 0 {
+                info!(
+                    target: LOG_TARGET,
+                    "Sponsor {} is at its maximum pool size, leaving {} coins on chain",
+                    sponsor,
+                    rejected_count
+                );
+                self.metrics
+                    .num_coins_over_max_pool_size
+                    .with_label_values(&[&sponsor])
+                    .inc_by(rejected_count as u64);
+                // The script adds the coins in order, so the ones left out are at the end.
+                let mut coins = coins_by_sponsor
+                    .remove(&SuiAddress::from_str(&sponsor)?)
+                    .unwrap_or_default();
+                not_added.extend(coins.split_off(coins.len().saturating_sub(rejected_count)));
+            }
             debug!(
+                target: LOG_TARGET,
                 "After add_new_coins. New total balance: {}, new coin count: {}",
                 new_total_balance, new_coin_count
             );
@@ -164,19 +494,54 @@ impl Storage for RedisStorage {
                 .set(new_total_balance as i64);
             self.metrics.num_successful_add_new_coins_requests.inc();
         }
+        Ok(not_added)
+    }
+
+    async fn add_dust_coins(&self, dust_coins: Vec<GasCoin>) -> anyhow::Result<()> {
+        if dust_coins.is_empty() {
+            return Ok(());
+        }
+        let mut coins_by_sponsor: HashMap<SuiAddress, Vec<String>> = HashMap::new();
+        for c in &dust_coins {
+            coins_by_sponsor
+                .entry(c.owner)
+                .or_default()
+                .push(format_coin(c));
+        }
+        let mut conn = self.conn_manager.clone();
+        ScriptManager::add_dust_coins_script()
+            .arg(serde_json::to_string(&coins_by_sponsor)?)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
         Ok(())
     }
 
+    async fn get_dust_coins(&self, sponsor: SuiAddress) -> anyhow::Result<Vec<GasCoin>> {
+        let mut conn = self.conn_manager.clone();
+        let coins: Vec<String> = redis::cmd("HVALS")
+            .arg(format!("{}:dust_coins", sponsor))
+            .query_async(&mut conn)
+            .await?;
+        Ok(parse_coins(sponsor, coins))
+    }
+
     async fn expire_coins(&self) -> anyhow::Result<Vec<ObjectID>> {
         self.metrics.num_expire_coins_requests.inc();
 
         let now = Utc::now().timestamp_millis() as u64;
         let mut conn = self.conn_manager.clone();
-        let expired_coin_strings: Vec<String> = ScriptManager::expire_coins_script()
-            .arg(serde_json::to_string(&self.sponsor_vec)?)
-            .arg(now)
-            .invoke_async(&mut conn)
-            .await?;
+        let (expired_coin_strings, unconfirmed_sponsors): (Vec<String>, Vec<String>) =
+            ScriptManager::expire_coins_script()
+                .arg(serde_json::to_string(&self.sponsor_vec)?)
+                .arg(now)
+                .invoke_async(&mut conn)
+                .await?;
+        for sponsor in unconfirmed_sponsors {
+            self.metrics
+                .num_unconfirmed_reservation_expirations
+                .with_label_values(&[&sponsor])
+                .inc();
+        }
         // The script returns a list of comma separated coin ids.
         let expired_coin_ids = expired_coin_strings
             .iter()
@@ -187,6 +552,383 @@ impl Storage for RedisStorage {
         Ok(expired_coin_ids)
     }
 
+    async fn add_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        objects: Vec<ObjectRef>,
+    ) -> anyhow::Result<()> {
+        if objects.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.conn_manager.clone();
+        let count: u64 = ScriptManager::add_sponsor_objects_script()
+            .arg(sponsor.to_string())
+            .arg(serde_json::to_string(&format_object_refs(&objects))?)
+            .invoke_async(&mut conn)
+            .await?;
+        debug!(
+            target: LOG_TARGET,
+            "Added {} of {} sponsor objects for {}",
+            count,
+            objects.len(),
+            sponsor
+        );
+        Ok(())
+    }
+
+    async fn reserve_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        object_ids: Vec<ObjectID>,
+    ) -> anyhow::Result<Vec<ObjectRef>> {
+        if object_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        if object_ids.iter().collect::<HashSet<_>>().len() != object_ids.len() {
+            bail!("Requested sponsor objects contain duplicate ids");
+        }
+        let object_id_strings: Vec<_> = object_ids.iter().map(|id| id.to_string()).collect();
+        let mut conn = self.conn_manager.clone();
+        let objects: Vec<String> = ScriptManager::reserve_sponsor_objects_script()
+            .arg(sponsor.to_string())
+            .arg(reservation_id)
+            .arg(serde_json::to_string(&object_id_strings)?)
+            .invoke_async(&mut conn)
+            .await?;
+        objects.iter().map(|s| parse_object_ref(s)).collect()
+    }
+
+    async fn get_in_flight_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Vec<ObjectID>> {
+        let mut conn = self.conn_manager.clone();
+        let object_ids: Option<String> = redis::cmd("GET")
+            .arg(format!("{}:object_in_flight:{}", sponsor, reservation_id))
+            .query_async(&mut conn)
+            .await?;
+        let Some(object_ids) = object_ids else {
+            return Ok(vec![]);
+        };
+        Ok(object_ids
+            .split(',')
+            .map(ObjectID::from_str)
+            .collect::<Result<_, _>>()?)
+    }
+
+    async fn release_sponsor_objects(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        latest_objects: Vec<ObjectRef>,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn_manager.clone();
+        ScriptManager::release_sponsor_objects_script()
+            .arg(sponsor.to_string())
+            .arg(reservation_id)
+            .arg(serde_json::to_string(&format_object_refs(&latest_objects))?)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_sponsor_objects(&self, sponsor: SuiAddress) -> anyhow::Result<Vec<SponsorObject>> {
+        let mut conn = self.conn_manager.clone();
+        let entries: HashMap<String, String> = redis::cmd("HGETALL")
+            .arg(format!("{}:sponsor_objects", sponsor))
+            .query_async(&mut conn)
+            .await?;
+        entries
+            .into_iter()
+            .map(|(object_id, entry)| {
+                // The entry is in the form of: version,digest,reservation_id
+                let (object_ref, reservation_id) = entry
+                    .rsplit_once(',')
+                    .ok_or_else(|| anyhow::anyhow!("Malformed sponsor object entry: {}", entry))?;
+                let reservation_id: ReservationID = reservation_id.parse()?;
+                Ok(SponsorObject {
+                    object_ref: parse_object_ref(&format!("{},{}", object_id, object_ref))?,
+                    reservation_id: (reservation_id != 0).then_some(reservation_id),
+                })
+            })
+            .collect()
+    }
+
+    async fn save_execution_result(
+        &self,
+        summary: &ExecutionResultSummary,
+        ttl_sec: u64,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn_manager.clone();
+        redis::cmd("SET")
+            .arg(execution_result_key(&summary.digest))
+            .arg(serde_json::to_string(summary)?)
+            .arg("EX")
+            .arg(ttl_sec)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_execution_result(
+        &self,
+        digest: TransactionDigest,
+    ) -> anyhow::Result<Option<ExecutionResultSummary>> {
+        let mut conn = self.conn_manager.clone();
+        let result: Option<String> = redis::cmd("GET")
+            .arg(execution_result_key(&digest))
+            .query_async(&mut conn)
+            .await?;
+        Ok(result.map(|s| serde_json::from_str(&s)).transpose()?)
+    }
+
+    async fn set_reservation_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+        metadata: &[u8],
+        ttl_ms: u64,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn_manager.clone();
+        redis::cmd("SET")
+            .arg(reservation_metadata_key(&sponsor, reservation_id))
+            .arg(metadata)
+            .arg("PX")
+            .arg(ttl_ms)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_reservation_metadata(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut conn = self.conn_manager.clone();
+        let metadata = redis::cmd("GET")
+            .arg(reservation_metadata_key(&sponsor, reservation_id))
+            .query_async(&mut conn)
+            .await?;
+        Ok(metadata)
+    }
+
+    async fn get_reservation_expiration(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<ExpirationTimeMs>> {
+        let mut conn = self.conn_manager.clone();
+        // The expiration queue keeps the reservation until it expires even after its coins are
+        // taken for execution, so the reservation key needs to be checked as well.
+        let (exists, expiration_time): (bool, Option<f64>) = redis::pipe()
+            .cmd("EXISTS")
+            .arg(format!("{}:{}", sponsor, reservation_id))
+            .cmd("ZSCORE")
+            .arg(format!("{}:expiration_queue", sponsor))
+            .arg(reservation_id)
+            .query_async(&mut conn)
+            .await?;
+        Ok(expiration_time
+            .filter(|_| exists)
+            .map(|expiration_time| expiration_time as ExpirationTimeMs))
+    }
+
+    async fn get_reservation_coins(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<Option<Vec<ObjectID>>> {
+        let mut conn = self.conn_manager.clone();
+        let object_ids: Option<String> = redis::cmd("GET")
+            .arg(format!("{}:{}", sponsor, reservation_id))
+            .query_async(&mut conn)
+            .await?;
+        object_ids
+            .map(|object_ids| {
+                object_ids
+                    .split(',')
+                    .map(ObjectID::from_str)
+                    .collect::<Result<_, _>>()
+            })
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    async fn get_oldest_active_reservation(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<ActiveReservation>> {
+        let mut conn = self.conn_manager.clone();
+        let result: Option<(ReservationID, u64, String)> =
+            ScriptManager::get_oldest_active_reservation_script()
+                .arg(sponsor.to_string())
+                .invoke_async(&mut conn)
+                .await?;
+        result
+            .map(|(reservation_id, creation_time, object_ids)| {
+                Ok(ActiveReservation {
+                    reservation_id,
+                    creation_time,
+                    object_ids: object_ids
+                        .split(',')
+                        .map(ObjectID::from_str)
+                        .collect::<Result<_, _>>()?,
+                })
+            })
+            .transpose()
+    }
+
+    async fn get_stuck_reservations(
+        &self,
+        sponsor: SuiAddress,
+        min_age: Duration,
+    ) -> anyhow::Result<StuckReservations> {
+        let cutoff =
+            (Utc::now().timestamp_millis() as u64).saturating_sub(min_age.as_millis() as u64);
+        let mut conn = self.conn_manager.clone();
+        let (count, oldest_ready_time): (u64, u64) = ScriptManager::get_stuck_reservations_script()
+            .arg(sponsor.to_string())
+            .arg(cutoff)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(StuckReservations {
+            count,
+            oldest_ready_time: (oldest_ready_time > 0).then_some(oldest_ready_time),
+        })
+    }
+
+    async fn get_next_reservation_expiry(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<Duration>> {
+        let mut conn = self.conn_manager.clone();
+        let expiration_time: ExpirationTimeMs =
+            ScriptManager::get_next_reservation_expiration_script()
+                .arg(sponsor.to_string())
+                .invoke_async(&mut conn)
+                .await?;
+        let now = Utc::now().timestamp_millis() as u64;
+        Ok((expiration_time > 0)
+            .then(|| Duration::from_millis(expiration_time.saturating_sub(now))))
+    }
+
+    async fn save_scheduled_execution(&self, execution: &ScheduledExecution) -> anyhow::Result<()> {
+        let mut conn = self.conn_manager.clone();
+        redis::cmd("HSET")
+            .arg(scheduled_executions_key(&execution.sponsor))
+            .arg(execution.reservation_id)
+            .arg(serde_json::to_string(execution)?)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_scheduled_executions(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Vec<ScheduledExecution>> {
+        let mut conn = self.conn_manager.clone();
+        let executions: Vec<String> = redis::cmd("HVALS")
+            .arg(scheduled_executions_key(&sponsor))
+            .query_async(&mut conn)
+            .await?;
+        executions
+            .iter()
+            .map(|s| Ok(serde_json::from_str(s)?))
+            .collect()
+    }
+
+    async fn remove_scheduled_execution(
+        &self,
+        sponsor: SuiAddress,
+        reservation_id: ReservationID,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.conn_manager.clone();
+        let removed: u64 = redis::cmd("HDEL")
+            .arg(scheduled_executions_key(&sponsor))
+            .arg(reservation_id)
+            .query_async(&mut conn)
+            .await?;
+        Ok(removed == 1)
+    }
+
+    async fn get_coin_last_used(
+        &self,
+        sponsor: SuiAddress,
+        object_id: ObjectID,
+    ) -> anyhow::Result<Option<u64>> {
+        let mut conn = self.conn_manager.clone();
+        let last_used: Option<u64> = redis::cmd("GET")
+            .arg(format!("{}:coin_last_used:{}", sponsor, object_id))
+            .query_async(&mut conn)
+            .await?;
+        Ok(last_used)
+    }
+
+    async fn find_coin(&self, object_id: ObjectID) -> anyhow::Result<Option<CoinLookup>> {
+        let mut conn = self.conn_manager.clone();
+        let result: Option<String> = redis::cmd("GET")
+            .arg(format!("coin:{}", object_id))
+            .query_async(&mut conn)
+            .await?;
+        result.map(|s| parse_coin_lookup(&s)).transpose()
+    }
+
+    async fn export_sponsor_snapshot(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<SponsorSnapshot> {
+        let mut conn = self.conn_manager.clone();
+        let (coins, reservations): (Vec<String>, Vec<(ReservationID, u64, String)>) =
+            ScriptManager::export_sponsor_snapshot_script()
+                .arg(sponsor.to_string())
+                .invoke_async(&mut conn)
+                .await?;
+        let reservations = reservations
+            .into_iter()
+            .map(|(reservation_id, expiration_time, object_ids)| {
+                Ok(ReservationSnapshot {
+                    reservation_id,
+                    expiration_time,
+                    object_ids: object_ids
+                        .split(',')
+                        .map(ObjectID::from_str)
+                        .collect::<Result<_, _>>()?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(SponsorSnapshot {
+            sponsor,
+            available_coins: parse_coins(sponsor, coins),
+            reservations,
+        })
+    }
+
+    async fn import_reservation(
+        &self,
+        sponsor: SuiAddress,
+        reservation: &ReservationSnapshot,
+    ) -> anyhow::Result<()> {
+        let object_ids: Vec<_> = reservation
+            .object_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        let mut conn = self.conn_manager.clone();
+        ScriptManager::import_reservation_script()
+            .arg(sponsor.to_string())
+            .arg(reservation.reservation_id)
+            .arg(reservation.expiration_time)
+            .arg(serde_json::to_string(&object_ids)?)
+            .arg(Utc::now().timestamp_millis() as u64)
+            .invoke_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
     async fn init_coin_stats_at_startup(&self) -> anyhow::Result<Vec<(String, i64, i64)>> {
         let mut conn = self.conn_manager.clone();
         let results: String = ScriptManager::init_coin_stats_at_startup_script()
@@ -198,6 +940,7 @@ impl Storage for RedisStorage {
 
         for (sponsor, available_coin_count, available_coin_total_balance) in results.clone() {
             info!(
+                target: LOG_TARGET,
                 sponsor_address=?sponsor,
                 "Number of available gas coins in the pool: {}, total balance: {}",
                 available_coin_count,
@@ -231,6 +974,7 @@ impl Storage for RedisStorage {
         let mut conn = self.conn_manager.clone();
         let cur_timestamp = Utc::now().timestamp() as u64;
         debug!(
+            target: LOG_TARGET,
             "Acquiring init lock at {} for {} seconds",
             cur_timestamp, lock_duration_sec
         );
@@ -247,7 +991,7 @@ impl Storage for RedisStorage {
     }
 
     async fn release_init_lock(&self) -> anyhow::Result<()> {
-        debug!("Releasing the init lock.");
+        debug!(target: LOG_TARGET, "Releasing the init lock.");
         let mut conn = self.conn_manager.clone();
         ScriptManager::release_init_lock_script()
             .arg(serde_json::to_string(&self.sponsor_vec)?)
@@ -289,6 +1033,28 @@ impl Storage for RedisStorage {
             .unwrap()
     }
 
+    async fn get_balance_percentiles(
+        &self,
+        sponsor: SuiAddress,
+    ) -> anyhow::Result<Option<BalancePercentiles>> {
+        let mut conn = self.conn_manager.clone();
+        let result: Vec<u64> = ScriptManager::get_balance_percentiles_script()
+            .arg(sponsor.to_string())
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(match result[..] {
+            [coin_count, min, p50, p90, p99, max] => Some(BalancePercentiles {
+                coin_count,
+                min,
+                p50,
+                p90,
+                p99,
+                max,
+            }),
+            _ => None,
+        })
+    }
+
     #[cfg(test)]
     async fn get_reserved_coin_count(&self, sponsor: SuiAddress) -> usize {
         let mut conn = self.conn_manager.clone();
@@ -300,11 +1066,103 @@ impl Storage for RedisStorage {
     }
 }
 
+// Execution results are looked up by digest alone, so unlike the other keys they are not
+// prefixed by the sponsor address.
+fn execution_result_key(digest: &TransactionDigest) -> String {
+    format!("execution_result:{}", digest)
+}
+
+fn reservation_metadata_key(sponsor: &SuiAddress, reservation_id: ReservationID) -> String {
+    format!("{}:reservation_metadata:{}", sponsor, reservation_id)
+}
+
+fn scheduled_executions_key(sponsor: &SuiAddress) -> String {
+    format!("{}:scheduled_executions", sponsor)
+}
+
+/// Parses a coin index entry, which is in the form of: sponsor,state[,reservation_id]
+fn parse_coin_lookup(entry: &str) -> anyhow::Result<CoinLookup> {
+    let mut splits = entry.split(',');
+    let sponsor = SuiAddress::from_str(splits.next().unwrap_or_default())?;
+    let state = match splits.next() {
+        Some("available") => CoinState::Available,
+        Some("reserved") => CoinState::Reserved,
+        Some("in_flight") => CoinState::InFlight,
+        Some("dust") => CoinState::Dust,
+        other => bail!("Unknown coin state in index entry: {:?}", other),
+    };
+    let reservation_id = splits.next().map(|id| id.parse()).transpose()?;
+    Ok(CoinLookup {
+        sponsor,
+        state,
+        reservation_id,
+    })
+}
+
+/// Formats a coin the way it is stored in the available coin queue:
+/// balance,object_id,version,digest
+fn format_coin(coin: &GasCoin) -> String {
+    format!(
+        "{},{},{},{}",
+        coin.balance,
+        coin.object_ref.0,
+        coin.object_ref.1.value(),
+        coin.object_ref.2
+    )
+}
+
+fn format_coins(coins: &[GasCoin]) -> Vec<String> {
+    coins.iter().map(format_coin).collect()
+}
+
+/// Formats objects the way sponsor objects are passed to the scripts:
+/// object_id,version,digest
+fn format_object_refs(objects: &[ObjectRef]) -> Vec<String> {
+    objects
+        .iter()
+        .map(|(id, version, digest)| format!("{},{},{}", id, version.value(), digest))
+        .collect()
+}
+
+fn parse_object_ref(s: &str) -> anyhow::Result<ObjectRef> {
+    let mut splits = s.split(',');
+    let (Some(id), Some(version), Some(digest)) = (splits.next(), splits.next(), splits.next())
+    else {
+        bail!("Malformed object reference: {}", s);
+    };
+    Ok((
+        ObjectID::from_str(id)?,
+        SequenceNumber::from(version.parse::<u64>()?),
+        ObjectDigest::from_str(digest)?,
+    ))
+}
+
+/// Parses coins returned by the reservation scripts.
+/// Each coin is in the form of: balance,object_id,version,digest
+fn parse_coins(sponsor: SuiAddress, coins: Vec<String>) -> Vec<GasCoin> {
+    coins
+        .into_iter()
+        .map(|s| {
+            let mut splits = s.split(',');
+            let balance = splits.next().unwrap().parse::<u64>().unwrap();
+            let object_id = ObjectID::from_str(splits.next().unwrap()).unwrap();
+            let version = SequenceNumber::from(splits.next().unwrap().parse::<u64>().unwrap());
+            let digest = ObjectDigest::from_str(splits.next().unwrap()).unwrap();
+            GasCoin {
+                owner: sponsor,
+                balance,
+                object_ref: (object_id, version, digest),
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use sui_types::base_types::{random_object_ref, SuiAddress};
 
     use crate::{
+        config::CoinSelectionStrategy,
         metrics::StorageMetrics,
         storage::{redis::RedisStorage, Storage},
         types::GasCoin,
@@ -382,6 +1240,9 @@ mod tests {
         let storage = RedisStorage::new(
             "redis://127.0.0.1:6379",
             vec![SuiAddress::ZERO],
+            CoinSelectionStrategy::default(),
+            1,
+            None,
             StorageMetrics::new_for_testing(),
         )
         .await;
@@ -2,8 +2,39 @@ use once_cell::sync::Lazy;
 use redis::Script;
 
 const RESERVE_GAS_COINS_SCRIPT: &str = include_str!("lua_scripts/reserve_gas_coins.lua");
+const RESERVE_SPECIFIC_COINS_SCRIPT: &str = include_str!("lua_scripts/reserve_specific_coins.lua");
+const HOLD_COINS_SCRIPT: &str = include_str!("lua_scripts/hold_coins.lua");
 const ADD_NEW_COINS_SCRIPT: &str = include_str!("lua_scripts/add_new_coins.lua");
+const ADD_DUST_COINS_SCRIPT: &str = include_str!("lua_scripts/add_dust_coins.lua");
+const GET_BALANCE_PERCENTILES_SCRIPT: &str =
+    include_str!("lua_scripts/get_balance_percentiles.lua");
+const RELEASE_RESERVATION_COINS_SCRIPT: &str =
+    include_str!("lua_scripts/release_reservation_coins.lua");
 const READY_FOR_EXECUTION_SCRIPT: &str = include_str!("lua_scripts/ready_for_execution.lua");
+const HOLD_RESERVATION_UNCONFIRMED_SCRIPT: &str =
+    include_str!("lua_scripts/hold_reservation_unconfirmed.lua");
+const CONFIRM_RESERVATION_SCRIPT: &str = include_str!("lua_scripts/confirm_reservation.lua");
+const RESET_RESERVATION_EXPIRATION_SCRIPT: &str =
+    include_str!("lua_scripts/reset_reservation_expiration.lua");
+const HEARTBEAT_RESERVATION_SCRIPT: &str = include_str!("lua_scripts/heartbeat_reservation.lua");
+const TAG_RESERVATION_SCRIPT: &str = include_str!("lua_scripts/tag_reservation.lua");
+const GET_RESERVATIONS_BY_TAG_SCRIPT: &str =
+    include_str!("lua_scripts/get_reservations_by_tag.lua");
+const CANCEL_RESERVATIONS_BY_TAG_SCRIPT: &str =
+    include_str!("lua_scripts/cancel_reservations_by_tag.lua");
+const GET_OLDEST_ACTIVE_RESERVATION_SCRIPT: &str =
+    include_str!("lua_scripts/get_oldest_active_reservation.lua");
+const GET_NEXT_RESERVATION_EXPIRATION_SCRIPT: &str =
+    include_str!("lua_scripts/get_next_reservation_expiration.lua");
+const GET_STUCK_RESERVATIONS_SCRIPT: &str = include_str!("lua_scripts/get_stuck_reservations.lua");
+const EXPORT_SPONSOR_SNAPSHOT_SCRIPT: &str =
+    include_str!("lua_scripts/export_sponsor_snapshot.lua");
+const IMPORT_RESERVATION_SCRIPT: &str = include_str!("lua_scripts/import_reservation.lua");
+const ADD_SPONSOR_OBJECTS_SCRIPT: &str = include_str!("lua_scripts/add_sponsor_objects.lua");
+const RESERVE_SPONSOR_OBJECTS_SCRIPT: &str =
+    include_str!("lua_scripts/reserve_sponsor_objects.lua");
+const RELEASE_SPONSOR_OBJECTS_SCRIPT: &str =
+    include_str!("lua_scripts/release_sponsor_objects.lua");
 const EXPIRE_COINS_SCRIPT: &str = include_str!("lua_scripts/expire_coins.lua");
 const INIT_COIN_STATS_AT_STARTUP_SCRIPT: &str =
     include_str!("lua_scripts/init_coin_stats_at_startup.lua");
@@ -27,16 +58,115 @@ impl ScriptManager {
         Lazy::force(&SCRIPT)
     }
 
+    pub fn reserve_specific_coins_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(RESERVE_SPECIFIC_COINS_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn hold_coins_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(HOLD_COINS_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
     pub fn add_new_coins_script() -> &'static Script {
         static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(ADD_NEW_COINS_SCRIPT));
         Lazy::force(&SCRIPT)
     }
 
+    pub fn add_dust_coins_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(ADD_DUST_COINS_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn release_reservation_coins_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(RELEASE_RESERVATION_COINS_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
     pub fn ready_for_execution_script() -> &'static Script {
         static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(READY_FOR_EXECUTION_SCRIPT));
         Lazy::force(&SCRIPT)
     }
 
+    pub fn hold_reservation_unconfirmed_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> =
+            Lazy::new(|| Script::new(HOLD_RESERVATION_UNCONFIRMED_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn confirm_reservation_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(CONFIRM_RESERVATION_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn reset_reservation_expiration_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> =
+            Lazy::new(|| Script::new(RESET_RESERVATION_EXPIRATION_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn heartbeat_reservation_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(HEARTBEAT_RESERVATION_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn tag_reservation_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(TAG_RESERVATION_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn get_reservations_by_tag_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(GET_RESERVATIONS_BY_TAG_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn cancel_reservations_by_tag_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(CANCEL_RESERVATIONS_BY_TAG_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn get_oldest_active_reservation_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> =
+            Lazy::new(|| Script::new(GET_OLDEST_ACTIVE_RESERVATION_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn get_next_reservation_expiration_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> =
+            Lazy::new(|| Script::new(GET_NEXT_RESERVATION_EXPIRATION_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn get_stuck_reservations_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(GET_STUCK_RESERVATIONS_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn export_sponsor_snapshot_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(EXPORT_SPONSOR_SNAPSHOT_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn import_reservation_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(IMPORT_RESERVATION_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn add_sponsor_objects_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(ADD_SPONSOR_OBJECTS_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn reserve_sponsor_objects_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(RESERVE_SPONSOR_OBJECTS_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
+    pub fn release_sponsor_objects_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(RELEASE_SPONSOR_OBJECTS_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
     pub fn expire_coins_script() -> &'static Script {
         static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(EXPIRE_COINS_SCRIPT));
         Lazy::force(&SCRIPT)
@@ -75,6 +205,11 @@ impl ScriptManager {
 
     // This needs to be test only because it's really expensive to call in production.
     #[cfg(test)]
+    pub fn get_balance_percentiles_script() -> &'static Script {
+        static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(GET_BALANCE_PERCENTILES_SCRIPT));
+        Lazy::force(&SCRIPT)
+    }
+
     pub fn get_reserved_coin_count_script() -> &'static Script {
         static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(GET_RESERVED_COIN_COUNT_SCRIPT));
         Lazy::force(&SCRIPT)
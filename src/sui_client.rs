@@ -1,110 +1,271 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::types::GasCoin;
+use crate::config::{
+    ReadConsistency, DEFAULT_FULLNODE_MAX_CONCURRENT_OBJECT_FETCHES,
+    DEFAULT_FULLNODE_MAX_OBJECT_FETCH_ATTEMPTS,
+};
+use crate::types::{ExecutedTransaction, GasCoin, OnChainPoolState};
 use crate::{retry_forever, retry_with_max_attempts};
-use futures_util::stream::FuturesUnordered;
-use futures_util::StreamExt;
+use anyhow::bail;
+use futures_util::{Stream, StreamExt};
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
+use sui_json_rpc_types::SuiTransactionBlockEffectsAPI;
 use sui_json_rpc_types::{
-    SuiData, SuiObjectDataOptions, SuiObjectResponse, SuiTransactionBlockEffects,
+    SuiData, SuiObjectDataOptions, SuiObjectResponse, SuiParsedData, SuiTransactionBlockResponse,
     SuiTransactionBlockResponseOptions,
 };
-use sui_json_rpc_types::{SuiTransactionBlockEffectsAPI, SuiTransactionBlockEvents};
 use sui_sdk::SuiClientBuilder;
 use sui_types::base_types::{ObjectID, ObjectRef, SuiAddress};
-use sui_types::coin::{PAY_MODULE_NAME, PAY_SPLIT_N_FUNC_NAME};
+use sui_types::coin::{Coin, PAY_MODULE_NAME, PAY_SPLIT_N_FUNC_NAME};
+use sui_types::digests::TransactionDigest;
+use sui_types::error::SuiObjectResponseError;
 use sui_types::gas_coin::GAS;
 use sui_types::programmable_transaction_builder::ProgrammableTransactionBuilder;
 use sui_types::quorum_driver_types::ExecuteTransactionRequestType;
 use sui_types::transaction::{
-    Argument, ObjectArg, ProgrammableTransaction, Transaction, TransactionKind,
+    Argument, Command, ObjectArg, ProgrammableTransaction, Transaction, TransactionData,
+    TransactionDataAPI, TransactionKind,
 };
-use sui_types::SUI_FRAMEWORK_PACKAGE_ID;
+use sui_types::{TypeTag, SUI_FRAMEWORK_PACKAGE_ID};
 use tap::TapFallible;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// Tracing target for all fullnode interactions. These tend to be noisy at debug level.
+const LOG_TARGET: &str = "gas_pool::sui_client";
+/// How long a strongly consistent read waits for the fullnode to catch up.
+const STRONG_READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many times the reference gas price is read before falling back to the last known one.
+const REFERENCE_GAS_PRICE_MAX_ATTEMPTS: usize = 3;
+/// The headers the SDK sets on every fullnode request, which custom headers cannot override.
+pub const RESERVED_FULLNODE_HEADERS: [&str; 4] = [
+    "client-sdk-type",
+    "client-sdk-version",
+    "client-target-api-version",
+    "content-type",
+];
+
+/// What `execute_transaction` asks the fullnode for unless the caller says otherwise: the
+/// effects and events of the transaction.
+pub fn default_execute_response_options() -> SuiTransactionBlockResponseOptions {
+    SuiTransactionBlockResponseOptions::new()
+        .with_effects()
+        .with_events()
+}
 
 #[derive(Clone)]
 pub struct SuiClient {
     sui_client: sui_sdk::SuiClient,
+    max_concurrent_object_fetches: usize,
+    max_object_fetch_attempts: usize,
+    gas_coin_type: TypeTag,
+    /// The last reference gas price read successfully, shared by all clones. 0 until the first
+    /// successful read.
+    last_reference_gas_price: Arc<AtomicU64>,
 }
 
 impl SuiClient {
-    pub async fn new(fullnode_url: &str, basic_auth: Option<(String, String)>) -> Self {
+    /// Connects to the fullnode, failing if it cannot be reached. `headers` are sent with every
+    /// request, e.g. for providers that take an API key or a client identifier in a header.
+    /// They cannot override `RESERVED_FULLNODE_HEADERS`, nor `authorization` when `basic_auth`
+    /// is set.
+    pub async fn new(
+        fullnode_url: &str,
+        basic_auth: Option<(String, String)>,
+        headers: BTreeMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        check_fullnode_headers(&headers, basic_auth.is_some())?;
         let mut sui_client_builder = SuiClientBuilder::default().max_concurrent_requests(100000);
         if let Some((username, password)) = basic_auth {
             sui_client_builder = sui_client_builder.basic_auth(username, password);
         }
-        let sui_client = sui_client_builder.build(fullnode_url).await.unwrap();
-        Self { sui_client }
+        if !headers.is_empty() {
+            sui_client_builder = sui_client_builder.custom_headers(headers.into_iter().collect());
+        }
+        let sui_client = sui_client_builder.build(fullnode_url).await?;
+        Ok(Self {
+            sui_client,
+            max_concurrent_object_fetches: DEFAULT_FULLNODE_MAX_CONCURRENT_OBJECT_FETCHES,
+            max_object_fetch_attempts: DEFAULT_FULLNODE_MAX_OBJECT_FETCH_ATTEMPTS,
+            gas_coin_type: GAS::type_tag(),
+            last_reference_gas_price: Default::default(),
+        })
+    }
+
+    /// Like `new`, but keeps trying with an exponential backoff for up to `timeout` while the
+    /// fullnode cannot be reached, e.g. because it is still starting up.
+    pub async fn new_with_retries(
+        fullnode_url: &str,
+        basic_auth: Option<(String, String)>,
+        headers: BTreeMap<String, String>,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        retry_with_backoff(timeout, || {
+            Self::new(fullnode_url, basic_auth.clone(), headers.clone())
+        })
+        .await
+    }
+
+    /// Bounds how many chunks of objects `get_latest_gas_objects` fetches at the same time.
+    pub fn with_max_concurrent_object_fetches(
+        mut self,
+        max_concurrent_object_fetches: usize,
+    ) -> Self {
+        self.max_concurrent_object_fetches = max_concurrent_object_fetches.max(1);
+        self
     }
 
+    /// Bounds how many times `get_latest_gas_objects` asks again for a chunk of objects when the
+    /// fullnode returns fewer objects than requested. Objects still missing after that are
+    /// treated as gone instead of retrying forever.
+    pub fn with_max_object_fetch_attempts(mut self, max_object_fetch_attempts: usize) -> Self {
+        self.max_object_fetch_attempts = max_object_fetch_attempts.max(1);
+        self
+    }
+
+    /// Sets the type of the coins the pool is made of, e.g. on forks where gas is payable in a
+    /// coin other than the native one. Only objects of type `Coin<gas_coin_type>` are treated as
+    /// gas coins.
+    pub fn with_gas_coin_type(mut self, gas_coin_type: TypeTag) -> Self {
+        self.gas_coin_type = gas_coin_type;
+        self
+    }
+
+    pub fn gas_coin_type(&self) -> &TypeTag {
+        &self.gas_coin_type
+    }
+
+    /// Collects all coins owned by the address with at least `balance_threshold` balance.
+    /// For addresses that own a very large number of coins, prefer
+    /// `stream_owned_sui_coins_above_balance_threshold` to avoid holding all of them in memory.
     pub async fn get_all_owned_sui_coins_above_balance_threshold(
         &self,
         address: SuiAddress,
         balance_threshold: u64,
     ) -> Vec<GasCoin> {
         info!(
+            target: LOG_TARGET,
             "Querying all gas coins owned by sponsor address {} that has at least {} balance",
             address, balance_threshold
         );
-        let mut cursor = None;
-        let mut coins = Vec::new();
-        loop {
+        self.stream_owned_sui_coins_above_balance_threshold(address, balance_threshold)
+            .concat()
+            .await
+    }
+
+    /// Yields the coins owned by the address with at least `balance_threshold` balance,
+    /// one page of the fullnode response at a time.
+    /// Pages are only fetched as the stream is polled, so memory usage stays bounded by the
+    /// page size no matter how many coins the address owns.
+    pub fn stream_owned_sui_coins_above_balance_threshold(
+        &self,
+        address: SuiAddress,
+        balance_threshold: u64,
+    ) -> impl Stream<Item = Vec<GasCoin>> + '_ {
+        // The state is None once the last page has been yielded.
+        futures_util::stream::unfold(Some(None), move |cursor| async move {
+            let cursor = cursor?;
             let page = retry_forever!(async {
                 self.sui_client
                     .coin_read_api()
-                    .get_coins(address, None, cursor, None)
+                    .get_coins(
+                        address,
+                        Some(self.gas_coin_type.to_string()),
+                        cursor,
+                        None,
+                    )
                     .await
-                    .tap_err(|err| debug!("Failed to get owned gas coins: {:?}", err))
+                    .tap_err(|err| {
+                        debug!(target: LOG_TARGET, "Failed to get owned gas coins: {:?}", err)
+                    })
             })
             .unwrap();
-            for coin in page.data {
-                if coin.balance >= balance_threshold {
-                    coins.push(GasCoin {
-                        owner: address,
-                        object_ref: coin.object_ref(),
-                        balance: coin.balance,
-                    });
+            let coins = page
+                .data
+                .into_iter()
+                .filter(|coin| coin.balance >= balance_threshold)
+                .map(|coin| GasCoin {
+                    owner: address,
+                    object_ref: coin.object_ref(),
+                    balance: coin.balance,
+                })
+                .collect();
+            let next_cursor = page.has_next_page.then_some(page.next_cursor);
+            Some((coins, next_cursor))
+        })
+    }
+
+    pub async fn get_reference_gas_price(&self) -> u64 {
+        retry_forever!(self.read_reference_gas_price()).unwrap()
+    }
+
+    /// Reads the reference gas price with a bounded number of attempts. When the fullnode keeps
+    /// failing, the last reference gas price read successfully is returned instead, so this only
+    /// fails if it was never read before.
+    pub async fn get_reference_gas_price_or_cached(&self) -> anyhow::Result<u64> {
+        match retry_with_max_attempts!(
+            self.read_reference_gas_price(),
+            REFERENCE_GAS_PRICE_MAX_ATTEMPTS
+        ) {
+            Ok(reference_gas_price) => Ok(reference_gas_price),
+            Err(err) => match self.last_reference_gas_price.load(Ordering::Relaxed) {
+                0 => Err(anyhow::anyhow!(
+                    "Failed to get reference gas price: {:?}",
+                    err
+                )),
+                cached => {
+                    warn!(
+                        target: LOG_TARGET,
+                        "Failed to get reference gas price, using the last known one {}: {:?}",
+                        cached,
+                        err
+                    );
+                    Ok(cached)
                 }
-            }
-            if page.has_next_page {
-                cursor = page.next_cursor;
-            } else {
-                break;
-            }
+            },
         }
-        coins
     }
 
-    pub async fn get_reference_gas_price(&self) -> u64 {
-        retry_forever!(async {
-            self.sui_client
-                .governance_api()
-                .get_reference_gas_price()
-                .await
-                .tap_err(|err| debug!("Failed to get reference gas price: {:?}", err))
-        })
-        .unwrap()
+    async fn read_reference_gas_price(&self) -> anyhow::Result<u64> {
+        let reference_gas_price = self
+            .sui_client
+            .governance_api()
+            .get_reference_gas_price()
+            .await
+            .tap_err(
+                |err| debug!(target: LOG_TARGET, "Failed to get reference gas price: {:?}", err),
+            )?;
+        self.last_reference_gas_price
+            .store(reference_gas_price, Ordering::Relaxed);
+        Ok(reference_gas_price)
     }
 
     pub async fn get_latest_gas_objects(
         &self,
         object_ids: impl IntoIterator<Item = ObjectID>,
     ) -> HashMap<ObjectID, Option<GasCoin>> {
-        let tasks: FuturesUnordered<_> = object_ids
+        // Chunks are collected upfront since the itertools chunk iterator cannot be held across
+        // an await point.
+        let chunks: Vec<Vec<_>> = object_ids
             .into_iter()
             .chunks(50)
             .into_iter()
-            .map(|chunk| {
-                let chunk: Vec<_> = chunk.collect();
-                let sui_client = self.sui_client.clone();
-                tokio::spawn(async move {
-                    retry_forever!(async {
-                        let chunk = chunk.clone();
-                        let result = sui_client
+            .map(|chunk| chunk.collect())
+            .collect();
+        let max_attempts = self.max_object_fetch_attempts;
+        let tasks = chunks.into_iter().map(|chunk| {
+            let sui_client = self.sui_client.clone();
+            async move {
+                let mut attempt = 0;
+                loop {
+                    attempt += 1;
+                    let result = retry_forever!(async {
+                        sui_client
                             .clone()
                             .read_api()
                             .multi_get_object_with_options(
@@ -112,32 +273,49 @@ impl SuiClient {
                                 SuiObjectDataOptions::default().with_bcs().with_owner(),
                             )
                             .await
-                            .map_err(anyhow::Error::from)?;
-                        if result.len() != chunk.len() {
-                            anyhow::bail!(
-                                "Unable to get all gas coins, got {} out of {}",
-                                result.len(),
-                                chunk.len()
-                            );
-                        }
-                        Ok(chunk.into_iter().zip(result).collect::<Vec<_>>())
+                            .map_err(anyhow::Error::from)
                     })
-                    .unwrap()
-                })
-            })
-            .collect();
-        let objects: Vec<_> = tasks.collect().await;
-        let objects: Vec<_> = objects.into_iter().flat_map(|r| r.unwrap()).collect();
+                    .unwrap();
+                    if result.len() == chunk.len() {
+                        break chunk
+                            .into_iter()
+                            .zip(result.into_iter().map(Some))
+                            .collect();
+                    }
+                    if attempt >= max_attempts {
+                        warn!(
+                            target: LOG_TARGET,
+                            "Got {} out of {} objects after {} attempts, treating the rest as gone",
+                            result.len(),
+                            chunk.len(),
+                            attempt
+                        );
+                        break match_object_responses(chunk, result);
+                    }
+                    debug!(
+                        target: LOG_TARGET,
+                        "Unable to get all gas coins, got {} out of {}",
+                        result.len(),
+                        chunk.len()
+                    );
+                }
+            }
+        });
+        let objects = spawn_bounded(tasks, self.max_concurrent_object_fetches).await;
         objects
             .into_iter()
-            .map(|(id, response)| {
-                let object = match Self::try_get_sui_coin_balance(&response) {
+            .flatten()
+            .map(|(id, response): (ObjectID, Option<SuiObjectResponse>)| {
+                let object = match response
+                    .as_ref()
+                    .and_then(|response| self.try_get_gas_coin_balance(response))
+                {
                     Some(coin) => {
-                        debug!("Got updated gas coin info: {:?}", coin);
+                        debug!(target: LOG_TARGET, "Got updated gas coin info: {:?}", coin);
                         Some(coin)
                     }
                     None => {
-                        debug!("Object no longer exists: {:?}", id);
+                        debug!(target: LOG_TARGET, "Object no longer exists: {:?}", id);
                         None
                     }
                 };
@@ -146,7 +324,108 @@ impl SuiClient {
             .collect()
     }
 
+    /// The total balance of the gas coins owned by the address, whether or not they are in the
+    /// pool.
+    pub async fn get_total_balance(&self, address: SuiAddress) -> anyhow::Result<u64> {
+        let balance = self
+            .sui_client
+            .coin_read_api()
+            .get_balance(address, Some(self.gas_coin_type.to_string()))
+            .await?;
+        Ok(u64::try_from(balance.total_balance)?)
+    }
+
+    /// Whether the object still exists on chain. Unlike `get_latest_gas_objects`, a failed read
+    /// is returned as an error instead of being treated as a missing object.
+    pub async fn object_exists(&self, object_id: ObjectID) -> anyhow::Result<bool> {
+        let response = self
+            .sui_client
+            .read_api()
+            .get_object_with_options(object_id, SuiObjectDataOptions::default())
+            .await?;
+        if response.data.is_some() {
+            return Ok(true);
+        }
+        match response.error {
+            Some(SuiObjectResponseError::NotExists { .. })
+            | Some(SuiObjectResponseError::Deleted { .. }) => Ok(false),
+            error => Err(anyhow::anyhow!(
+                "Unexpected response for object {:?}: {:?}",
+                object_id,
+                error
+            )),
+        }
+    }
+
+    /// Returns the latest references of the given objects that are currently owned by `owner`.
+    /// Gas coins are left out, since they are tracked by the gas pool itself.
+    pub async fn get_latest_owned_object_refs(
+        &self,
+        owner: SuiAddress,
+        object_ids: Vec<ObjectID>,
+    ) -> Vec<ObjectRef> {
+        let mut object_refs = vec![];
+        for chunk in object_ids.chunks(50) {
+            let responses = retry_forever!(async {
+                self.sui_client
+                    .read_api()
+                    .multi_get_object_with_options(
+                        chunk.to_vec(),
+                        SuiObjectDataOptions::default().with_bcs().with_owner(),
+                    )
+                    .await
+                    .tap_err(|err| debug!(target: LOG_TARGET, "Failed to get objects: {:?}", err))
+            })
+            .unwrap();
+            object_refs.extend(responses.iter().filter_map(|response| {
+                let data = response.data.as_ref()?;
+                let object_owner = data.owner.clone()?.get_address_owner_address().ok()?;
+                (object_owner == owner && self.try_get_gas_coin_balance(response).is_none())
+                    .then(|| data.object_ref())
+            }));
+        }
+        object_refs
+    }
+
+    /// Reads the pool state recorded in a sponsor-owned anchor object.
+    /// The Move object is expected to have `available_coin_count` and
+    /// `available_coin_total_balance` u64 fields. Returns None if the object does not exist,
+    /// is not owned by an address, or does not have these fields.
+    pub async fn get_onchain_pool_state(&self, object_id: ObjectID) -> Option<OnChainPoolState> {
+        let response = retry_forever!(async {
+            self.sui_client
+                .read_api()
+                .get_object_with_options(
+                    object_id,
+                    SuiObjectDataOptions::default().with_content().with_owner(),
+                )
+                .await
+                .tap_err(|err| debug!(target: LOG_TARGET, "Failed to get anchor object: {:?}", err))
+        })
+        .unwrap();
+        let data = response.data?;
+        let owner = data.owner?.get_address_owner_address().ok()?;
+        let Some(SuiParsedData::MoveObject(move_obj)) = data.content else {
+            return None;
+        };
+        let fields = move_obj.fields.to_json_value();
+        // Move u64 values are encoded as strings in JSON.
+        let read_u64 = |name: &str| -> Option<u64> {
+            match fields.get(name)? {
+                serde_json::Value::String(value) => value.parse().ok(),
+                serde_json::Value::Number(value) => value.as_u64(),
+                _ => None,
+            }
+        };
+        Some(OnChainPoolState {
+            owner,
+            available_coin_count: read_u64("available_coin_count")?,
+            available_coin_total_balance: read_u64("available_coin_total_balance")?,
+        })
+    }
+
     pub fn construct_coin_split_pt(
+        &self,
         gas_coin: Argument,
         split_count: u64,
     ) -> ProgrammableTransaction {
@@ -156,12 +435,38 @@ impl SuiClient {
             SUI_FRAMEWORK_PACKAGE_ID,
             PAY_MODULE_NAME.into(),
             PAY_SPLIT_N_FUNC_NAME.into(),
-            vec![GAS::type_tag()],
+            vec![self.gas_coin_type.clone()],
             vec![gas_coin, pure_arg],
         );
         pt_builder.finish()
     }
 
+    /// Splits one coin of `amount` off the gas coin for each entry of `amounts`, and sends them
+    /// to `recipient`. Unlike `construct_coin_split_pt`, the sizes of the new coins do not depend
+    /// on the balance of the gas coin, which keeps whatever is left.
+    pub fn construct_coin_split_amounts_pt(
+        &self,
+        gas_coin: Argument,
+        amounts: &[u64],
+        recipient: SuiAddress,
+    ) -> ProgrammableTransaction {
+        let mut pt_builder = ProgrammableTransactionBuilder::new();
+        let amount_args = amounts
+            .iter()
+            .map(|amount| pt_builder.pure(*amount).unwrap())
+            .collect();
+        let Argument::Result(split) =
+            pt_builder.command(Command::SplitCoins(gas_coin, amount_args))
+        else {
+            unreachable!("A command always produces a result argument");
+        };
+        let new_coins = (0..amounts.len())
+            .map(|i| Argument::NestedResult(split, i as u16))
+            .collect();
+        pt_builder.transfer_args(recipient, new_coins);
+        pt_builder.finish()
+    }
+
     pub async fn calibrate_gas_cost_per_object(
         &self,
         sponsor_address: SuiAddress,
@@ -177,7 +482,7 @@ impl SuiClient {
             SUI_FRAMEWORK_PACKAGE_ID,
             PAY_MODULE_NAME.into(),
             PAY_SPLIT_N_FUNC_NAME.into(),
-            vec![GAS::type_tag()],
+            vec![self.gas_coin_type.clone()],
             vec![object_arg, pure_arg],
         );
         let pt = pt_builder.finish();
@@ -199,39 +504,203 @@ impl SuiClient {
         gas_used / SPLIT_COUNT * 2
     }
 
+    /// Estimates the gas a transaction would use (computation plus storage cost, without rebate)
+    /// by dev-inspecting it at the transaction's gas price.
+    pub async fn estimate_gas_used(&self, tx_data: &TransactionData) -> anyhow::Result<u64> {
+        let response = retry_with_max_attempts!(
+            async {
+                self.sui_client
+                    .read_api()
+                    .dev_inspect_transaction_block(
+                        tx_data.sender(),
+                        tx_data.kind().clone(),
+                        Some(tx_data.gas_price().into()),
+                        None,
+                        None,
+                    )
+                    .await
+                    .tap_err(|err| {
+                        debug!(target: LOG_TARGET, "Failed to dev-inspect transaction: {:?}", err)
+                    })
+            },
+            3
+        )?;
+        Ok(response.effects.gas_cost_summary().gas_used())
+    }
+
+    /// Executes a signed transaction, retrying up to `max_attempts` times. `response_options`
+    /// picks what the fullnode returns; the effects are always requested, since the pool needs
+    /// them to update its coins.
     pub async fn execute_transaction(
         &self,
         tx: Transaction,
         request_type: Option<ExecuteTransactionRequestType>,
+        response_options: SuiTransactionBlockResponseOptions,
         max_attempts: usize,
-    ) -> anyhow::Result<(
-        Option<u64>,
-        SuiTransactionBlockEffects,
-        Option<SuiTransactionBlockEvents>,
-    )> {
+    ) -> anyhow::Result<ExecutedTransaction> {
         let digest = *tx.digest();
+        let response_options = response_options.with_effects();
         let request_type = request_type.or(Some(ExecuteTransactionRequestType::WaitForEffectsCert));
-        debug!(?digest, "Executing transaction: {:?}", tx);
+        debug!(target: LOG_TARGET, ?digest, "Executing transaction: {:?}", tx);
         let response = retry_with_max_attempts!(
             async {
-                self.sui_client
+                let result = self
+                    .sui_client
                     .quorum_driver_api()
                     .execute_transaction_block(
                         tx.clone(),
-                        SuiTransactionBlockResponseOptions::new()
-                            .with_effects()
-                            .with_events(),
+                        response_options.clone(),
                         request_type.clone(),
                     )
                     .await
-                    .tap_err(|err| debug!(?digest, "execute_transaction error: {:?}", err))
-                    .map_err(anyhow::Error::from)
+                    .tap_err(|err| {
+                        debug!(target: LOG_TARGET, ?digest, "execute_transaction error: {:?}", err)
+                    });
+                match result {
+                    Ok(response) => Ok(response),
+                    // An earlier attempt may have executed the transaction even though its
+                    // response was lost, in which case this attempt fails because the inputs are
+                    // already used. The effects of that execution are the result then.
+                    Err(err) => match self
+                        .get_transaction_block(digest, response_options.clone())
+                        .await
+                    {
+                        Ok(response) => {
+                            info!(
+                                target: LOG_TARGET,
+                                ?digest,
+                                "Transaction was already executed, using its existing effects"
+                            );
+                            Ok(response)
+                        }
+                        Err(_) => Err(anyhow::Error::from(err)),
+                    },
+                }
             },
             max_attempts
         )?;
+        Self::to_executed_transaction(digest, response)
+    }
+
+    /// Returns the effects and events of a transaction that was already executed.
+    pub async fn get_transaction_effects(
+        &self,
+        digest: TransactionDigest,
+    ) -> anyhow::Result<ExecutedTransaction> {
+        let response = self
+            .get_transaction_block(digest, default_execute_response_options())
+            .await?;
+        Self::to_executed_transaction(digest, response)
+    }
+
+    async fn get_transaction_block(
+        &self,
+        digest: TransactionDigest,
+        response_options: SuiTransactionBlockResponseOptions,
+    ) -> anyhow::Result<SuiTransactionBlockResponse> {
+        let response = self
+            .sui_client
+            .read_api()
+            .get_transaction_with_options(digest, response_options)
+            .await?;
+        Ok(response)
+    }
+
+    fn to_executed_transaction(
+        digest: TransactionDigest,
+        response: SuiTransactionBlockResponse,
+    ) -> anyhow::Result<ExecutedTransaction> {
         let effects = response.effects.ok_or(anyhow::anyhow!("No effects"))?;
-        debug!(?digest, "Transaction execution effects: {:?}", effects);
-        Ok((response.timestamp_ms, effects, response.events))
+        debug!(target: LOG_TARGET, ?digest, "Transaction execution effects: {:?}", effects);
+        Ok(ExecutedTransaction {
+            digest,
+            timestamp_ms: response.timestamp_ms,
+            effects,
+            events: response.events,
+            transaction: response.transaction,
+            object_changes: response.object_changes,
+            balance_changes: response.balance_changes,
+            raw_effects: response.raw_effects,
+        })
+    }
+
+    /// Same as `get_latest_gas_objects`, but with `ReadConsistency::Strong` it first waits for
+    /// the fullnode to return at least the given version of each object, up to a timeout after
+    /// which it falls back to whatever the fullnode returns.
+    pub async fn get_latest_gas_objects_with_consistency(
+        &self,
+        object_refs: Vec<ObjectRef>,
+        consistency: ReadConsistency,
+    ) -> HashMap<ObjectID, Option<GasCoin>> {
+        if consistency == ReadConsistency::Strong {
+            let (_, timed_out) = self
+                .wait_for_objects(object_refs.clone(), STRONG_READ_TIMEOUT)
+                .await;
+            for obj_ref in timed_out {
+                info!(
+                    target: LOG_TARGET,
+                    "Timed out waiting for version {:?} of object {:?}", obj_ref.1, obj_ref.0
+                );
+            }
+        }
+        self.get_latest_gas_objects(object_refs.into_iter().map(|obj_ref| obj_ref.0))
+            .await
+    }
+
+    /// Waits until the fullnode returns at least the given version of the object.
+    pub async fn wait_for_object_version(&self, obj_ref: ObjectRef) {
+        loop {
+            let response = self
+                .sui_client
+                .read_api()
+                .get_object_with_options(obj_ref.0, SuiObjectDataOptions::default())
+                .await;
+            if let Ok(SuiObjectResponse {
+                data: Some(data), ..
+            }) = response
+            {
+                if data.version >= obj_ref.1 {
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    /// Waits until the fullnode returns at least the given version of each object, for at most
+    /// `timeout` in total. The objects are polled concurrently, bounded the same way as
+    /// `get_latest_gas_objects`. Returns the objects that reached their version and the ones
+    /// that did not in time, each in the order given.
+    pub async fn wait_for_objects(
+        &self,
+        object_refs: Vec<ObjectRef>,
+        timeout: Duration,
+    ) -> (Vec<ObjectRef>, Vec<ObjectRef>) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let waits = object_refs.into_iter().enumerate().map(|(i, obj_ref)| {
+            let sui_client = self.clone();
+            async move {
+                let wait = sui_client.wait_for_object_version(obj_ref);
+                let confirmed = tokio::time::timeout_at(deadline, wait).await.is_ok();
+                (i, obj_ref, confirmed)
+            }
+        });
+        let (confirmed, timed_out): (Vec<_>, Vec<_>) =
+            spawn_bounded(waits, self.max_concurrent_object_fetches)
+                .await
+                .into_iter()
+                .sorted_by_key(|(i, _, _)| *i)
+                .partition(|(_, _, confirmed)| *confirmed);
+        (
+            confirmed
+                .into_iter()
+                .map(|(_, obj_ref, _)| obj_ref)
+                .collect(),
+            timed_out
+                .into_iter()
+                .map(|(_, obj_ref, _)| obj_ref)
+                .collect(),
+        )
     }
 
     /// Wait for a known valid object version to be available on the fullnode.
@@ -254,19 +723,453 @@ impl SuiClient {
         }
     }
 
-    fn try_get_sui_coin_balance(object: &SuiObjectResponse) -> Option<GasCoin> {
+    /// The object as a gas coin, if it is a coin of the gas coin type of the pool.
+    fn try_get_gas_coin_balance(&self, object: &SuiObjectResponse) -> Option<GasCoin> {
+        Self::try_get_coin_balance(&self.gas_coin_type, object)
+    }
+
+    fn try_get_coin_balance(coin_type: &TypeTag, object: &SuiObjectResponse) -> Option<GasCoin> {
         let data = object.data.as_ref()?;
         let owner = data.owner.clone()?.get_address_owner_address().ok()?;
         let object_ref = data.object_ref();
         let move_obj = data.bcs.as_ref()?.try_as_move()?;
-        if move_obj.type_ != sui_types::gas_coin::GasCoin::type_() {
+        if move_obj.type_ != Coin::type_(coin_type.clone()) {
             return None;
         }
-        let gas_coin: sui_types::gas_coin::GasCoin = bcs::from_bytes(&move_obj.bcs_bytes).ok()?;
+        let coin: Coin = bcs::from_bytes(&move_obj.bcs_bytes).ok()?;
         Some(GasCoin {
             owner,
             object_ref,
-            balance: gas_coin.value(),
+            balance: coin.value(),
+        })
+    }
+}
+
+/// Fails if a custom header would override one the SDK sets. Header names are case insensitive.
+fn check_fullnode_headers(
+    headers: &BTreeMap<String, String>,
+    basic_auth: bool,
+) -> anyhow::Result<()> {
+    for name in headers.keys() {
+        let name = name.to_ascii_lowercase();
+        if RESERVED_FULLNODE_HEADERS.contains(&name.as_str()) {
+            bail!("Fullnode header {} is set by the SDK", name);
+        }
+        if basic_auth && name == "authorization" {
+            bail!("Fullnode header authorization is set by the basic auth");
+        }
+    }
+    Ok(())
+}
+
+/// Calls `attempt` until it succeeds, waiting exponentially longer between failures, up to a
+/// second. Gives up with the last error once `timeout` has passed.
+async fn retry_with_backoff<T, F, Fut>(timeout: Duration, mut attempt: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut delay = Duration::from_millis(50);
+    loop {
+        match attempt().await {
+            Ok(result) => return Ok(result),
+            Err(err) => {
+                let now = tokio::time::Instant::now();
+                if now >= deadline {
+                    return Err(err);
+                }
+                warn!(
+                    target: LOG_TARGET,
+                    "Attempt failed, retrying in {:?}: {:?}",
+                    delay.min(deadline - now),
+                    err
+                );
+                tokio::time::sleep(delay.min(deadline - now)).await;
+                delay = (delay * 2).min(Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+/// Pairs each requested object with its response, for responses that do not line up with the
+/// request. Responses are matched by object ID, and requested objects without one are None.
+fn match_object_responses(
+    object_ids: Vec<ObjectID>,
+    responses: Vec<SuiObjectResponse>,
+) -> Vec<(ObjectID, Option<SuiObjectResponse>)> {
+    let mut responses: HashMap<_, _> = responses
+        .into_iter()
+        .filter_map(|response| Some((response.object_id().ok()?, response)))
+        .collect();
+    object_ids
+        .into_iter()
+        .map(|id| (id, responses.remove(&id)))
+        .collect()
+}
+
+/// Runs each future on its own task, with at most `limit` of them running at the same time.
+/// Tasks are only spawned as earlier ones complete, so a long list of futures never floods the
+/// runtime or the fullnode.
+async fn spawn_bounded<F>(futures: impl IntoIterator<Item = F>, limit: usize) -> Vec<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    futures_util::stream::iter(futures)
+        .map(tokio::spawn)
+        .buffer_unordered(limit)
+        .map(|result| result.unwrap())
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_fullnode_headers, match_object_responses, retry_with_backoff, spawn_bounded,
+        SuiClient,
+    };
+    use crate::test_env::start_sui_cluster;
+    use futures_util::StreamExt;
+    use std::collections::{BTreeMap, HashSet};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use sui_json_rpc_types::{
+        SuiObjectResponse, SuiTransactionBlockEffectsAPI, SuiTransactionBlockResponseOptions,
+    };
+    use sui_types::base_types::{random_object_ref, ObjectID, SequenceNumber, SuiAddress};
+    use sui_types::error::SuiObjectResponseError;
+    use sui_types::gas_coin::{GAS, MIST_PER_OCT};
+    use sui_types::transaction::{Argument, Transaction, TransactionData};
+
+    #[tokio::test]
+    async fn test_execute_transaction_after_lost_response() {
+        let (test_cluster, _signer) = start_sui_cluster(vec![MIST_PER_OCT]).await;
+        let sui_client = SuiClient::new(
+            &test_cluster.fullnode_handle.rpc_url,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let sender = test_cluster.get_address_0();
+        let object = test_cluster
+            .wallet
+            .get_one_gas_object_owned_by_address(sender)
+            .await
+            .unwrap()
+            .unwrap();
+        let tx_data = test_cluster
+            .test_transaction_builder()
+            .await
+            .transfer(object, sender)
+            .build();
+        let tx = test_cluster.sign_transaction(&tx_data);
+        let digest = *tx.digest();
+        // The first attempt goes through, but its response never reaches the caller.
+        test_cluster.execute_transaction(tx.clone()).await;
+
+        let result = sui_client
+            .execute_transaction(
+                tx,
+                None,
+                SuiTransactionBlockResponseOptions::new().with_balance_changes(),
+                3,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.digest, digest);
+        assert!(result.effects.status().is_ok());
+        // Only what was asked for comes back, besides the effects.
+        assert!(result.events.is_none());
+        assert!(result.object_changes.is_none());
+        assert!(result.balance_changes.is_some());
+        let executed = sui_client.get_transaction_effects(digest).await.unwrap();
+        assert_eq!(executed.effects.transaction_digest(), &digest);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_objects() {
+        let (test_cluster, signer) = start_sui_cluster(vec![MIST_PER_OCT; 5]).await;
+        let sui_client = SuiClient::new(
+            &test_cluster.fullnode_handle.rpc_url,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let objects = sui_client
+            .get_all_owned_sui_coins_above_balance_threshold(signer.get_addresses()[0], 1)
+            .await;
+        assert_eq!(objects.len(), 5);
+        let mut object_refs: Vec<_> = objects.iter().map(|coin| coin.object_ref).collect();
+        let (confirmed, timed_out) = sui_client
+            .wait_for_objects(object_refs.clone(), Duration::from_secs(10))
+            .await;
+        assert_eq!(confirmed, object_refs);
+        assert!(timed_out.is_empty());
+
+        // A version the object never reaches and an object that does not exist both time out.
+        let mut ahead = object_refs[0];
+        ahead.1 = SequenceNumber::from_u64(ahead.1.value() + 1000);
+        let missing = random_object_ref();
+        object_refs.push(ahead);
+        object_refs.push(missing);
+        let (confirmed, timed_out) = sui_client
+            .wait_for_objects(object_refs.clone(), Duration::from_secs(1))
+            .await;
+        assert_eq!(confirmed, object_refs[..object_refs.len() - 2]);
+        assert_eq!(timed_out, vec![ahead, missing]);
+    }
+
+    #[tokio::test]
+    async fn test_get_total_balance() {
+        let (test_cluster, signer) = start_sui_cluster(vec![MIST_PER_OCT; 3]).await;
+        let sui_client = SuiClient::new(
+            &test_cluster.fullnode_handle.rpc_url,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let balance = sui_client
+            .get_total_balance(signer.get_addresses()[0])
+            .await
+            .unwrap();
+        assert_eq!(balance, MIST_PER_OCT * 3);
+        let balance = sui_client
+            .get_total_balance(SuiAddress::random_for_testing_only())
+            .await
+            .unwrap();
+        assert_eq!(balance, 0);
+    }
+
+    #[tokio::test]
+    async fn test_construct_coin_split_amounts_pt() {
+        let (test_cluster, signer) = start_sui_cluster(vec![MIST_PER_OCT]).await;
+        let sui_client = SuiClient::new(
+            &test_cluster.fullnode_handle.rpc_url,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let sponsor = signer.get_addresses()[0];
+        let coin = sui_client
+            .get_all_owned_sui_coins_above_balance_threshold(sponsor, 1)
+            .await
+            .remove(0);
+        let amount = MIST_PER_OCT / 10;
+        let pt =
+            sui_client.construct_coin_split_amounts_pt(Argument::GasCoin, &[amount; 3], sponsor);
+        let tx_data = TransactionData::new_programmable(
+            sponsor,
+            vec![coin.object_ref],
+            pt,
+            MIST_PER_OCT / 100,
+            sui_client.get_reference_gas_price().await,
+        );
+        let sig = signer.sign_transaction(&tx_data).await.unwrap();
+        let result = sui_client
+            .execute_transaction(
+                Transaction::from_generic_sig_data(tx_data, vec![sig]),
+                None,
+                SuiTransactionBlockResponseOptions::new().with_effects(),
+                3,
+            )
+            .await
+            .unwrap();
+        assert!(result.effects.status().is_ok());
+        let created: Vec<_> = result
+            .effects
+            .created()
+            .iter()
+            .map(|created| created.reference.0)
+            .collect();
+        assert_eq!(created.len(), 3);
+        let coins = sui_client.get_latest_gas_objects(created).await;
+        for coin in coins.into_values() {
+            assert_eq!(coin.unwrap().balance, amount);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_owned_sui_coins_across_pages() {
+        let (test_cluster, signer) = start_sui_cluster(vec![MIST_PER_OCT * 10]).await;
+        let sui_client = SuiClient::new(
+            &test_cluster.fullnode_handle.rpc_url,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let sponsor = signer.get_addresses()[0];
+        let coin = sui_client
+            .get_all_owned_sui_coins_above_balance_threshold(sponsor, 1)
+            .await
+            .remove(0);
+        // More coins than the fullnode returns in one page.
+        let amounts = [1000; 120];
+        let tx_data = TransactionData::new_programmable(
+            sponsor,
+            vec![coin.object_ref],
+            sui_client.construct_coin_split_amounts_pt(Argument::GasCoin, &amounts, sponsor),
+            MIST_PER_OCT,
+            sui_client.get_reference_gas_price().await,
+        );
+        let sig = signer.sign_transaction(&tx_data).await.unwrap();
+        let result = sui_client
+            .execute_transaction(
+                Transaction::from_generic_sig_data(tx_data, vec![sig]),
+                None,
+                SuiTransactionBlockResponseOptions::new().with_effects(),
+                3,
+            )
+            .await
+            .unwrap();
+        assert!(result.effects.status().is_ok());
+
+        let pages: Vec<_> = sui_client
+            .stream_owned_sui_coins_above_balance_threshold(sponsor, 1)
+            .collect()
+            .await;
+        assert!(pages.len() > 1);
+        let coin_ids: HashSet<_> = pages
+            .iter()
+            .flatten()
+            .map(|coin| coin.object_ref.0)
+            .collect();
+        assert_eq!(coin_ids.len(), amounts.len() + 1);
+        assert_eq!(pages.iter().map(Vec::len).sum::<usize>(), coin_ids.len());
+
+        // The balance threshold applies to every page, leaving only the gas coin.
+        let pages: Vec<_> = sui_client
+            .stream_owned_sui_coins_above_balance_threshold(sponsor, 1001)
+            .collect()
+            .await;
+        assert!(pages.len() > 1);
+        assert_eq!(pages.concat().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_custom_fullnode_headers() {
+        let (test_cluster, _signer) = start_sui_cluster(vec![MIST_PER_OCT]).await;
+        let url = &test_cluster.fullnode_handle.rpc_url;
+        let headers = BTreeMap::from([("x-api-key".to_string(), "secret".to_string())]);
+        let sui_client = SuiClient::new(url, Some(("user".into(), "pass".into())), headers)
+            .await
+            .unwrap();
+        assert!(sui_client.get_reference_gas_price().await > 0);
+
+        // Headers set by the SDK or the basic auth cannot be overridden.
+        let headers = BTreeMap::from([("Client-SDK-Type".to_string(), "other".to_string())]);
+        assert!(SuiClient::new(url, None, headers).await.is_err());
+        let headers = BTreeMap::from([("Authorization".to_string(), "Bearer x".to_string())]);
+        assert!(check_fullnode_headers(&headers, false).is_ok());
+        assert!(check_fullnode_headers(&headers, true).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_object_exists() {
+        let (test_cluster, _signer) = start_sui_cluster(vec![MIST_PER_OCT]).await;
+        let sui_client = SuiClient::new(
+            &test_cluster.fullnode_handle.rpc_url,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let object = test_cluster
+            .wallet
+            .get_one_gas_object_owned_by_address(test_cluster.get_address_0())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(sui_client.object_exists(object.0).await.unwrap());
+        assert!(!sui_client.object_exists(ObjectID::random()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_backoff() {
+        // The first attempts fail as if the fullnode were not up yet, then it comes up.
+        let attempts = AtomicUsize::new(0);
+        let result = retry_with_backoff(Duration::from_secs(10), || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                anyhow::bail!("Connection refused");
+            }
+            Ok(42)
+        })
+        .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Past the timeout, the last error is returned instead of waiting any longer.
+        let attempts = AtomicUsize::new(0);
+        let result: anyhow::Result<()> = retry_with_backoff(Duration::from_millis(300), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            anyhow::bail!("Connection refused")
         })
+        .await;
+        assert!(result.is_err());
+        assert!(attempts.load(Ordering::SeqCst) > 1);
+    }
+
+    #[tokio::test]
+    async fn test_new_with_unreachable_fullnode() {
+        // Nothing listens on this port, so connecting fails instead of panicking.
+        let result = SuiClient::new_with_retries(
+            "http://127.0.0.1:1",
+            None,
+            Default::default(),
+            Duration::from_millis(200),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spawn_bounded_respects_limit() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let futures: Vec<_> = (0..100)
+            .map(|i| {
+                let running = running.clone();
+                let max_running = max_running.clone();
+                async move {
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now_running, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                }
+            })
+            .collect();
+        let mut results = spawn_bounded(futures, 7).await;
+        results.sort();
+        assert_eq!(results, (0..100).collect::<Vec<_>>());
+        assert_eq!(max_running.load(Ordering::SeqCst), 7);
+    }
+
+    #[test]
+    fn test_match_object_responses_with_missing_object() {
+        let ids: Vec<_> = (0..3).map(|_| ObjectID::random()).collect();
+        let not_exists = |object_id| {
+            SuiObjectResponse::new_with_error(SuiObjectResponseError::NotExists { object_id })
+        };
+        // The fullnode dropped the second object from its response.
+        let responses = vec![not_exists(ids[2]), not_exists(ids[0])];
+        let matched = match_object_responses(ids.clone(), responses);
+        assert_eq!(matched.len(), 3);
+        assert_eq!(matched[0].0, ids[0]);
+        assert_eq!(matched[0].1, Some(not_exists(ids[0])));
+        assert_eq!(matched[1], (ids[1], None));
+        assert_eq!(matched[2].1, Some(not_exists(ids[2])));
+        // None of them is a gas coin, so they all end up as gone.
+        assert!(matched.iter().all(|(_, response)| response
+            .as_ref()
+            .and_then(|response| SuiClient::try_get_coin_balance(&GAS::type_tag(), response))
+            .is_none()));
     }
 }
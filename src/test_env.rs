@@ -1,12 +1,12 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::config::{CoinInitConfig, DEFAULT_DAILY_GAS_USAGE_CAP};
+use crate::config::{CoinInitConfig, GasPoolCoreConfig, DEFAULT_DAILY_GAS_USAGE_CAP};
 use crate::gas_pool::gas_pool_core::GasPoolContainer;
 use crate::gas_pool_initializer::GasPoolInitializer;
 use crate::metrics::{GasPoolCoreMetrics, GasPoolRpcMetrics};
 use crate::rpc::GasPoolServer;
-use crate::storage::connect_storage_for_testing;
+use crate::storage::{connect_storage_for_testing, Storage};
 use crate::sui_client::SuiClient;
 use crate::tx_signer::{TestTxSigner, TxSigner};
 use crate::AUTH_ENV_NAME;
@@ -43,6 +43,36 @@ pub async fn start_sui_cluster(init_gas_amounts: Vec<u64>) -> (TestCluster, Arc<
 pub async fn start_gas_station(
     init_gas_amounts: Vec<u64>,
     target_init_coin_balance: u64,
+) -> (Vec<SuiAddress>, TestCluster, GasPoolContainer) {
+    start_gas_station_with_config(init_gas_amounts, target_init_coin_balance, |_| {
+        GasPoolCoreConfig::default()
+    })
+    .await
+}
+
+/// Same as `start_gas_station`, but allows tests to customize the core config, given the
+/// sponsor addresses of the station.
+pub async fn start_gas_station_with_config(
+    init_gas_amounts: Vec<u64>,
+    target_init_coin_balance: u64,
+    config_fn: impl FnOnce(&[SuiAddress]) -> GasPoolCoreConfig,
+) -> (Vec<SuiAddress>, TestCluster, GasPoolContainer) {
+    start_gas_station_with_storage(
+        init_gas_amounts,
+        target_init_coin_balance,
+        config_fn,
+        |storage| storage,
+    )
+    .await
+}
+
+/// Same as `start_gas_station_with_config`, but the pool runs on the storage returned by
+/// `storage_fn`, given the storage its coins were added to, e.g. to wrap it in a fake.
+pub async fn start_gas_station_with_storage(
+    init_gas_amounts: Vec<u64>,
+    target_init_coin_balance: u64,
+    config_fn: impl FnOnce(&[SuiAddress]) -> GasPoolCoreConfig,
+    storage_fn: impl FnOnce(Arc<dyn Storage>) -> Arc<dyn Storage>,
 ) -> (Vec<SuiAddress>, TestCluster, GasPoolContainer) {
     debug!("Starting Sui cluster..");
     let (test_cluster, signer) = start_sui_cluster(init_gas_amounts).await;
@@ -50,7 +80,9 @@ pub async fn start_gas_station(
     let sponsor_addresses = signer.get_addresses();
     debug!("Starting storage. Sponsor address: {:?}", sponsor_addresses);
     let storage = connect_storage_for_testing(sponsor_addresses.clone()).await;
-    let sui_client = SuiClient::new(&fullnode_url, None).await;
+    let sui_client = SuiClient::new(&fullnode_url, None, Default::default())
+        .await
+        .unwrap();
     GasPoolInitializer::start(
         sui_client.clone(),
         storage.clone(),
@@ -63,9 +95,10 @@ pub async fn start_gas_station(
     .await;
     let station = GasPoolContainer::new(
         signer,
-        storage,
+        storage_fn(storage),
         sui_client,
         DEFAULT_DAILY_GAS_USAGE_CAP,
+        config_fn(&sponsor_addresses),
         GasPoolCoreMetrics::new_for_testing(),
     )
     .await;
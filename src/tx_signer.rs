@@ -3,6 +3,7 @@
 
 use anyhow::anyhow;
 use fastcrypto::encoding::{Base64, Encoding};
+use futures_util::future::join_all;
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
@@ -10,15 +11,30 @@ use shared_crypto::intent::{Intent, IntentMessage};
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use sui_types::base_types::SuiAddress;
-use sui_types::crypto::{Signature, SuiKeyPair};
+use sui_types::crypto::{PublicKey, Signature, SuiKeyPair};
+use sui_types::multisig::{MultiSig, MultiSigPublicKey, ThresholdUnit, WeightUnit};
 use sui_types::signature::GenericSignature;
 use sui_types::transaction::{TransactionData, TransactionDataAPI};
+use tracing::warn;
 
 #[async_trait::async_trait]
 pub trait TxSigner: Send + Sync {
-    async fn sign_transaction(&self, tx_data: &TransactionData)
-        -> anyhow::Result<GenericSignature>;
+    /// Sign the transaction with the default transaction intent.
+    async fn sign_transaction(
+        &self,
+        tx_data: &TransactionData,
+    ) -> anyhow::Result<GenericSignature> {
+        self.sign_transaction_with_intent(Intent::sui_transaction(), tx_data)
+            .await
+    }
+    /// Sign the transaction with an explicitly given intent, e.g. a non-default intent version.
+    async fn sign_transaction_with_intent(
+        &self,
+        intent: Intent,
+        tx_data: &TransactionData,
+    ) -> anyhow::Result<GenericSignature>;
     fn get_addresses(&self) -> Vec<SuiAddress>;
     fn is_valid_address(&self, address: &SuiAddress) -> bool {
         self.get_addresses()
@@ -69,10 +85,15 @@ impl SidecarTxSigner {
 
 #[async_trait::async_trait]
 impl TxSigner for SidecarTxSigner {
-    async fn sign_transaction(
+    async fn sign_transaction_with_intent(
         &self,
+        intent: Intent,
         tx_data: &TransactionData,
     ) -> anyhow::Result<GenericSignature> {
+        // The sidecar always wraps the transaction bytes with the default transaction intent.
+        if intent != Intent::sui_transaction() {
+            anyhow::bail!("Sidecar signer only supports the default transaction intent");
+        }
         let bytes = Base64::encode(bcs::to_bytes(&tx_data)?);
         let resp = self
             .client
@@ -109,8 +130,9 @@ impl TestTxSigner {
 
 #[async_trait::async_trait]
 impl TxSigner for TestTxSigner {
-    async fn sign_transaction(
+    async fn sign_transaction_with_intent(
         &self,
+        intent: Intent,
         tx_data: &TransactionData,
     ) -> anyhow::Result<GenericSignature> {
         let gas_owner = tx_data.gas_owner();
@@ -118,7 +140,7 @@ impl TxSigner for TestTxSigner {
             .keypair_map
             .get(&gas_owner)
             .ok_or(anyhow!("Not found ${} keypair", gas_owner))?;
-        let intent_msg = IntentMessage::new(Intent::sui_transaction(), tx_data);
+        let intent_msg = IntentMessage::new(intent, tx_data);
         let sponsor_sig = Signature::new_secure(&intent_msg, keypair).into();
         Ok(sponsor_sig)
     }
@@ -127,3 +149,230 @@ impl TxSigner for TestTxSigner {
         self.keypair_map.keys().cloned().collect()
     }
 }
+
+/// Signs with a key held in memory as a member of a multisig sponsor. The multisig address is not
+/// derived from the key, so unlike `TestTxSigner` it signs whatever the gas owner.
+pub struct MultiSigKeypairSigner {
+    keypair: SuiKeyPair,
+}
+
+impl MultiSigKeypairSigner {
+    pub fn new(keypair: SuiKeyPair) -> Arc<Self> {
+        Arc::new(Self { keypair })
+    }
+}
+
+#[async_trait::async_trait]
+impl TxSigner for MultiSigKeypairSigner {
+    async fn sign_transaction_with_intent(
+        &self,
+        intent: Intent,
+        tx_data: &TransactionData,
+    ) -> anyhow::Result<GenericSignature> {
+        let intent_msg = IntentMessage::new(intent, tx_data);
+        Ok(Signature::new_secure(&intent_msg, &self.keypair).into())
+    }
+
+    fn get_addresses(&self) -> Vec<SuiAddress> {
+        vec![(&self.keypair.public()).into()]
+    }
+}
+
+/// One of the signers of a multisig sponsor, along with its public key and weight in the multisig.
+pub struct MultiSigMember {
+    pub signer: Arc<dyn TxSigner>,
+    pub public_key: PublicKey,
+    pub weight: WeightUnit,
+}
+
+/// Signs as a multisig sponsor address. Every member is asked to sign in parallel, and the
+/// signatures are combined as long as the members that succeeded within the timeout carry
+/// enough weight to reach the threshold.
+pub struct MultiSigSigner {
+    members: Vec<MultiSigMember>,
+    multisig_pk: MultiSigPublicKey,
+    threshold: ThresholdUnit,
+    sui_address: SuiAddress,
+    sign_timeout: Duration,
+}
+
+impl MultiSigSigner {
+    pub fn new(
+        members: Vec<MultiSigMember>,
+        threshold: ThresholdUnit,
+        sign_timeout: Duration,
+    ) -> anyhow::Result<Arc<Self>> {
+        let multisig_pk = MultiSigPublicKey::new(
+            members.iter().map(|m| m.public_key.clone()).collect(),
+            members.iter().map(|m| m.weight).collect(),
+            threshold,
+        )
+        .map_err(|err| anyhow!("Invalid multisig configuration: {}", err))?;
+        let sui_address = SuiAddress::from(&multisig_pk);
+        Ok(Arc::new(Self {
+            members,
+            multisig_pk,
+            threshold,
+            sui_address,
+            sign_timeout,
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl TxSigner for MultiSigSigner {
+    async fn sign_transaction_with_intent(
+        &self,
+        intent: Intent,
+        tx_data: &TransactionData,
+    ) -> anyhow::Result<GenericSignature> {
+        let results = join_all(self.members.iter().map(|member| {
+            let intent = intent.clone();
+            async move {
+                let result = tokio::time::timeout(
+                    self.sign_timeout,
+                    member.signer.sign_transaction_with_intent(intent, tx_data),
+                )
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("Timed out")));
+                (member, result)
+            }
+        }))
+        .await;
+        let mut weight: ThresholdUnit = 0;
+        let mut signatures = vec![];
+        for (member, result) in results {
+            match result {
+                Ok(sig) => {
+                    weight += member.weight as ThresholdUnit;
+                    signatures.push(sig);
+                }
+                Err(err) => warn!(
+                    "Multisig member {:?} failed to sign: {:?}",
+                    SuiAddress::from(&member.public_key),
+                    err
+                ),
+            }
+        }
+        if weight < self.threshold {
+            anyhow::bail!(
+                "Collected signatures with a total weight of {}, below the threshold of {}",
+                weight,
+                self.threshold
+            );
+        }
+        let multisig = MultiSig::combine(signatures, self.multisig_pk.clone())
+            .map_err(|err| anyhow!("Failed to combine multisig: {}", err))?;
+        Ok(GenericSignature::MultiSig(multisig))
+    }
+
+    fn get_addresses(&self) -> Vec<SuiAddress> {
+        vec![self.sui_address]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tx_signer::{
+        MultiSigKeypairSigner, MultiSigMember, MultiSigSigner, TestTxSigner, TxSigner,
+    };
+    use shared_crypto::intent::{Intent, IntentScope};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use sui_types::base_types::{random_object_ref, SuiAddress};
+    use sui_types::crypto::{get_account_key_pair, SuiKeyPair};
+    use sui_types::multisig::MultiSigPublicKey;
+    use sui_types::signature::GenericSignature;
+    use sui_types::transaction::TransactionData;
+
+    /// A member that never answers, to exercise the signing timeout.
+    struct UnresponsiveTxSigner;
+
+    #[async_trait::async_trait]
+    impl TxSigner for UnresponsiveTxSigner {
+        async fn sign_transaction_with_intent(
+            &self,
+            _intent: Intent,
+            _tx_data: &TransactionData,
+        ) -> anyhow::Result<GenericSignature> {
+            futures_util::future::pending().await
+        }
+
+        fn get_addresses(&self) -> Vec<SuiAddress> {
+            vec![]
+        }
+    }
+
+    fn multisig_member(signer: Arc<dyn TxSigner>, keypair: &SuiKeyPair) -> MultiSigMember {
+        MultiSigMember {
+            signer,
+            public_key: keypair.public(),
+            weight: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_with_intent() {
+        let (sponsor, keypair) = get_account_key_pair();
+        let signer = TestTxSigner::new(vec![keypair.into()]);
+        let tx_data = TransactionData::new_transfer_sui(
+            SuiAddress::ZERO,
+            sponsor,
+            None,
+            random_object_ref(),
+            1_000_000,
+            1000,
+        );
+        let default_sig = signer.sign_transaction(&tx_data).await.unwrap();
+        let explicit_sig = signer
+            .sign_transaction_with_intent(Intent::sui_transaction(), &tx_data)
+            .await
+            .unwrap();
+        assert_eq!(default_sig, explicit_sig);
+        let other_sig = signer
+            .sign_transaction_with_intent(Intent::sui_app(IntentScope::PersonalMessage), &tx_data)
+            .await
+            .unwrap();
+        assert_ne!(default_sig, other_sig);
+    }
+
+    #[tokio::test]
+    async fn test_multisig_signer() {
+        let keypairs: Vec<SuiKeyPair> = (0..3).map(|_| get_account_key_pair().1.into()).collect();
+        let sponsor = SuiAddress::from(
+            &MultiSigPublicKey::new(keypairs.iter().map(|k| k.public()).collect(), vec![1; 3], 2)
+                .unwrap(),
+        );
+        let signer_of = |keypair: &SuiKeyPair| -> Arc<dyn TxSigner> {
+            MultiSigKeypairSigner::new(keypair.copy())
+        };
+        let members = vec![
+            multisig_member(signer_of(&keypairs[0]), &keypairs[0]),
+            multisig_member(signer_of(&keypairs[1]), &keypairs[1]),
+            multisig_member(Arc::new(UnresponsiveTxSigner), &keypairs[2]),
+        ];
+        let signer = MultiSigSigner::new(members, 2, Duration::from_millis(100)).unwrap();
+        assert_eq!(signer.get_addresses(), vec![sponsor]);
+        let tx_data = TransactionData::new_transfer_sui(
+            SuiAddress::ZERO,
+            sponsor,
+            None,
+            random_object_ref(),
+            1_000_000,
+            1000,
+        );
+        // Two of the three members sign, which reaches the threshold.
+        let sig = signer.sign_transaction(&tx_data).await.unwrap();
+        assert!(matches!(sig, GenericSignature::MultiSig(_)));
+
+        let members = vec![
+            multisig_member(signer_of(&keypairs[0]), &keypairs[0]),
+            multisig_member(Arc::new(UnresponsiveTxSigner), &keypairs[1]),
+            multisig_member(Arc::new(UnresponsiveTxSigner), &keypairs[2]),
+        ];
+        let signer = MultiSigSigner::new(members, 2, Duration::from_millis(100)).unwrap();
+        // The members are the same, so is the multisig address.
+        assert_eq!(signer.get_addresses(), vec![sponsor]);
+        assert!(signer.sign_transaction(&tx_data).await.is_err());
+    }
+}
@@ -2,11 +2,19 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::bail;
+use chrono::Utc;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
-use sui_json_rpc_types::SuiObjectRef;
+use std::time::Duration;
+use sui_json_rpc_types::{
+    BalanceChange, ObjectChange, SuiExecutionStatus, SuiObjectRef, SuiTransactionBlock,
+    SuiTransactionBlockEffects, SuiTransactionBlockEffectsAPI, SuiTransactionBlockEvents,
+};
 use sui_types::base_types::{ObjectID, ObjectRef, SuiAddress};
+use sui_types::digests::TransactionDigest;
+use sui_types::signature::GenericSignature;
+use sui_types::transaction::TransactionData;
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct GasCoin {
@@ -42,8 +50,215 @@ impl From<SuiGasCoin> for GasCoin {
     }
 }
 
+/// The outcome of executing a sponsored transaction. The digest is returned explicitly so that
+/// callers can correlate the result without digging it out of the effects.
+/// Everything besides the effects is only there if the execution asked the fullnode for it.
+#[derive(Clone, Debug)]
+pub struct ExecutedTransaction {
+    pub digest: TransactionDigest,
+    pub timestamp_ms: Option<u64>,
+    pub effects: SuiTransactionBlockEffects,
+    pub events: Option<SuiTransactionBlockEvents>,
+    pub transaction: Option<SuiTransactionBlock>,
+    pub object_changes: Option<Vec<ObjectChange>>,
+    pub balance_changes: Option<Vec<BalanceChange>>,
+    /// The BCS serialized effects, empty unless requested.
+    pub raw_effects: Vec<u8>,
+}
+
+/// A small summary of an executed transaction. It is persisted for a while after execution so
+/// that clients that lost their connection can still recover the result.
+#[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
+pub struct ExecutionResultSummary {
+    pub digest: TransactionDigest,
+    pub reservation_id: ReservationID,
+    pub status: SuiExecutionStatus,
+    pub net_gas_usage: i64,
+}
+
+impl ExecutionResultSummary {
+    pub fn new(reservation_id: ReservationID, result: &ExecutedTransaction) -> Self {
+        Self {
+            digest: result.digest,
+            reservation_id,
+            status: result.effects.status().clone(),
+            net_gas_usage: result.effects.gas_cost_summary().net_gas_usage(),
+        }
+    }
+}
+
+/// The pool state of a sponsor as recorded in its on-chain anchor object.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OnChainPoolState {
+    pub owner: SuiAddress,
+    pub available_coin_count: u64,
+    pub available_coin_total_balance: u64,
+}
+
+/// How much gas has been used in a usage cap window, against the cap of that window.
+#[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
+pub struct GasUsageWindowStatus {
+    /// The name of the window, e.g. "daily".
+    pub window: String,
+    /// The net gas usage in the window so far, in MIST. It can be negative when storage rebates
+    /// exceed the gas spent.
+    pub usage: i64,
+    pub cap: u64,
+    /// How long until the window resets and its usage goes back to 0.
+    pub resets_in_ms: u64,
+}
+
 pub type ReservationID = u64;
 pub type ExpirationTimeMs = u64;
+
+/// Formats a reservation ID in its canonical string form, for APIs that carry it as a string:
+/// the decimal number without sign or leading zeros, e.g. "42".
+pub fn reservation_id_to_string(reservation_id: ReservationID) -> String {
+    reservation_id.to_string()
+}
+
+/// Parses a reservation ID from its canonical string form. Unlike parsing a u64, this rejects
+/// signs, leading zeros and surrounding whitespace, so that every reservation ID has exactly one
+/// string form. 0 is rejected too, since reservation IDs start from 1.
+pub fn parse_reservation_id(s: &str) -> anyhow::Result<ReservationID> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) || s.starts_with('0') {
+        bail!("Invalid reservation ID: {:?}", s);
+    }
+    Ok(s.parse()?)
+}
+
+/// Serializes a reservation ID as its canonical string, for use with `#[serde(with = ...)]`
+/// in APIs where clients cannot represent every u64 as a number, e.g. JavaScript.
+pub mod reservation_id_as_string {
+    use super::{parse_reservation_id, reservation_id_to_string, ReservationID};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        reservation_id: &ReservationID,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&reservation_id_to_string(*reservation_id))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ReservationID, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse_reservation_id(&s).map_err(D::Error::custom)
+    }
+}
+
+/// Where a coin currently is in the lifecycle of the pool.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoinState {
+    /// The coin can be picked by new reservations.
+    Available,
+    /// The coin is held by a reservation that has not been executed yet.
+    Reserved,
+    /// The coin is being used by a transaction that is executing.
+    InFlight,
+    /// The coin was released with too little balance left and sits in the dust bin.
+    Dust,
+}
+
+/// What the pool knows about a coin, as returned by a lookup by object ID.
+#[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
+pub struct CoinLookup {
+    pub sponsor: SuiAddress,
+    pub state: CoinState,
+    /// Set when the coin is reserved or in flight.
+    pub reservation_id: Option<ReservationID>,
+}
+
+/// A reservation whose coins are still reserved, i.e. that has neither expired, been released
+/// nor been executed yet.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ActiveReservation {
+    pub reservation_id: ReservationID,
+    /// When the reservation was made, in milliseconds since the epoch. Reservations imported from
+    /// a snapshot count from the time of the import.
+    pub creation_time: u64,
+    pub object_ids: Vec<ObjectID>,
+}
+
+impl ActiveReservation {
+    pub fn age(&self) -> Duration {
+        let now = Utc::now().timestamp_millis() as u64;
+        Duration::from_millis(now.saturating_sub(self.creation_time))
+    }
+}
+
+/// The reservations of a sponsor whose coins have been in flight for longer than some age, i.e.
+/// that were made ready for execution but never had their coins released.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StuckReservations {
+    pub count: u64,
+    /// When the oldest of them was made ready for execution, in milliseconds since the epoch.
+    /// None if there is none.
+    pub oldest_ready_time: Option<u64>,
+}
+
+impl StuckReservations {
+    pub fn oldest_age(&self) -> Option<Duration> {
+        let now = Utc::now().timestamp_millis() as u64;
+        self.oldest_ready_time
+            .map(|ready_time| Duration::from_millis(now.saturating_sub(ready_time)))
+    }
+}
+
+/// A point-in-time dump of the pool state, used to move a pool to a new storage.
+/// Coins that are in the middle of an execution are not part of a snapshot.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PoolSnapshot {
+    pub sponsors: Vec<SponsorSnapshot>,
+}
+
+/// How the balances of the available coins of a sponsor are distributed, in MIST. A long tail
+/// of small coins shows a fragmented pool.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
+pub struct BalancePercentiles {
+    pub coin_count: u64,
+    pub min: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SponsorSnapshot {
+    pub sponsor: SuiAddress,
+    pub available_coins: Vec<GasCoin>,
+    pub reservations: Vec<ReservationSnapshot>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ReservationSnapshot {
+    pub reservation_id: ReservationID,
+    pub expiration_time: ExpirationTimeMs,
+    pub object_ids: Vec<ObjectID>,
+}
+
+/// A signed transaction that the pool executes with its reservation at a later time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledExecution {
+    pub sponsor: SuiAddress,
+    pub reservation_id: ReservationID,
+    pub tx_data: TransactionData,
+    pub user_sig: GenericSignature,
+    /// Unix timestamp in milliseconds.
+    pub execute_at_ms: u64,
+}
+
+/// An object owned by a sponsor, other than a gas coin, that transactions can use as input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SponsorObject {
+    pub object_ref: ObjectRef,
+    /// Set when the object is reserved or in flight.
+    pub reservation_id: Option<ReservationID>,
+}
+
 pub type GasGroupKey = ObjectID;
 
 #[derive(Clone, Default, Debug)]
@@ -88,3 +303,46 @@ impl ReservedGasGroup {
         *self.objects.iter().next().unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_reservation_id, reservation_id_as_string, reservation_id_to_string};
+    use crate::types::ReservationID;
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn test_reservation_id_string_round_trip() {
+        for reservation_id in [1, 42, u64::MAX] {
+            let s = reservation_id_to_string(reservation_id);
+            assert_eq!(parse_reservation_id(&s).unwrap(), reservation_id);
+        }
+        for invalid in [
+            "",
+            "0",
+            "007",
+            "+7",
+            "-7",
+            " 7",
+            "7 ",
+            "0x7",
+            "18446744073709551616",
+        ] {
+            assert!(parse_reservation_id(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_reservation_id_as_string_serde() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Request {
+            #[serde(with = "reservation_id_as_string")]
+            reservation_id: ReservationID,
+        }
+        let request = Request { reservation_id: 42 };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"reservation_id":"42"}"#);
+        assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+        assert!(serde_json::from_str::<Request>(r#"{"reservation_id":"042"}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"reservation_id":42}"#).is_err());
+    }
+}